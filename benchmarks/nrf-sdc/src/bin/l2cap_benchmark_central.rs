@@ -103,6 +103,7 @@ async fn main(spawner: Spawner) {
                 initial_credits: Some(8),
                 mtu: Some(PAYLOAD_LEN as u16),
                 mps: Some(L2CAP_MTU as u16 - 4),
+                ..Default::default()
             };
             let mut ch1 = unwrap!(L2capChannel::create(&stack, &conn, 0x2349, &config).await);
             info!("sending l2cap data");
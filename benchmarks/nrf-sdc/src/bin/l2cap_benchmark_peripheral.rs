@@ -115,6 +115,7 @@ async fn main(spawner: Spawner) {
                 initial_credits: Some(8),
                 mtu: Some(PAYLOAD_LEN as u16),
                 mps: Some(L2CAP_MTU as u16 - 4),
+                ..Default::default()
             };
             let mut ch1 = unwrap!(L2capChannel::accept(&stack, &conn, &[0x2349], &config).await);
 
@@ -38,11 +38,34 @@ impl Key for StoredAddr {
 struct StoredBondInformation {
     ltk: LongTermKey,
     security_level: SecurityLevel,
+    identity_kind: AddrKind,
+    ediv: u16,
+    rand: [u8; 8],
+    irk: Option<IdentityResolvingKey>,
+    local_csrk: Option<SigningKey>,
+    peer_csrk: Option<SigningKey>,
+}
+
+/// Size of a serialized [`StoredBondInformation`].
+const STORED_BOND_SIZE: usize = 16 + 1 + 1 + 2 + 8 + 17 + 2 * 21;
+
+fn serialize_csrk(key: &Option<SigningKey>, buffer: &mut [u8]) {
+    buffer[0] = key.is_some() as u8;
+    let key = key.unwrap_or(SigningKey::new(ConnectionSignatureResolvingKey::new(0)));
+    buffer[1..17].copy_from_slice(key.csrk.to_le_bytes().as_slice());
+    buffer[17..21].copy_from_slice(&key.counter.to_le_bytes());
+}
+
+fn deserialize_csrk(buffer: &[u8]) -> Option<SigningKey> {
+    (buffer[0] != 0).then(|| SigningKey {
+        csrk: ConnectionSignatureResolvingKey::from_le_bytes(buffer[1..17].try_into().unwrap()),
+        counter: u32::from_le_bytes(buffer[17..21].try_into().unwrap()),
+    })
 }
 
 impl<'a> Value<'a> for StoredBondInformation {
     fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, SerializationError> {
-        if buffer.len() < 17 {
+        if buffer.len() < STORED_BOND_SIZE {
             return Err(SerializationError::BufferTooSmall);
         }
         buffer[0..16].copy_from_slice(self.ltk.to_le_bytes().as_slice());
@@ -51,25 +74,45 @@ impl<'a> Value<'a> for StoredBondInformation {
             SecurityLevel::Encrypted => 1,
             SecurityLevel::EncryptedAuthenticated => 2,
         };
-        Ok(17)
+        buffer[17] = self.identity_kind.as_raw();
+        buffer[18..20].copy_from_slice(&self.ediv.to_le_bytes());
+        buffer[20..28].copy_from_slice(&self.rand);
+        buffer[28] = self.irk.is_some() as u8;
+        buffer[29..45].copy_from_slice(&self.irk.map(|irk| irk.0).unwrap_or(0).to_le_bytes());
+        serialize_csrk(&self.local_csrk, &mut buffer[45..66]);
+        serialize_csrk(&self.peer_csrk, &mut buffer[66..87]);
+        Ok(STORED_BOND_SIZE)
     }
 
     fn deserialize_from(buffer: &'a [u8]) -> Result<Self, SerializationError>
     where
-        Self: Sized
+        Self: Sized,
     {
-        if buffer.len() < 17 {
+        if buffer.len() < STORED_BOND_SIZE {
             Err(SerializationError::BufferTooSmall)
-        }
-        else {
+        } else {
             let ltk = LongTermKey::from_le_bytes(buffer[0..16].try_into().unwrap());
             let security_level = match buffer[16] {
                 0 => SecurityLevel::NoEncryption,
                 1 => SecurityLevel::Encrypted,
                 2 => SecurityLevel::EncryptedAuthenticated,
-                _ => return Err(SerializationError::InvalidData)
+                _ => return Err(SerializationError::InvalidData),
             };
-            Ok(StoredBondInformation { ltk, security_level })
+            let identity_kind = match AddrKind::new(buffer[17]) {
+                kind @ (AddrKind::PUBLIC | AddrKind::RANDOM) => kind,
+                _ => return Err(SerializationError::InvalidData),
+            };
+            Ok(StoredBondInformation {
+                ltk,
+                security_level,
+                identity_kind,
+                ediv: u16::from_le_bytes(buffer[18..20].try_into().unwrap()),
+                rand: buffer[20..28].try_into().unwrap(),
+                irk: (buffer[28] != 0)
+                    .then(|| IdentityResolvingKey::from_le_bytes(buffer[29..45].try_into().unwrap())),
+                local_csrk: deserialize_csrk(&buffer[45..66]),
+                peer_csrk: deserialize_csrk(&buffer[66..87]),
+            })
         }
     }
 }
@@ -81,27 +124,41 @@ fn flash_range<S: NorFlash>() -> Range<u32> {
 async fn store_bonding_info<S: NorFlash>(storage: &mut S, info: &BondInformation) -> Result<(), sequential_storage::Error<S::Error>> {
     // Assumes that S::ERASE_SIZE is large enough
     sequential_storage::erase_all(storage, 0..S::ERASE_SIZE as u32).await?;
-    let mut buffer = [0;32];
+    let mut buffer = [0; 128];
     let key = StoredAddr(info.identity.bd_addr);
-    let value = StoredBondInformation { ltk: info.ltk, security_level: info.security_level };
+    let value = StoredBondInformation {
+        ltk: info.ltk,
+        security_level: info.security_level,
+        identity_kind: info.identity_kind,
+        ediv: info.ediv,
+        rand: info.rand,
+        irk: info.identity.irk,
+        local_csrk: info.local_csrk,
+        peer_csrk: info.peer_csrk,
+    };
     sequential_storage::map::store_item(storage, flash_range::<S>(), &mut NoCache::new(), &mut buffer, &key, &value).await?;
     Ok(())
 }
 
 async fn load_bonding_info<S: NorFlash>(storage: &mut S) -> Option<BondInformation>
 {
-    let mut buffer = [0;32];
+    let mut buffer = [0; 128];
     let mut cache = NoCache::new();
     let mut iter = sequential_storage::map::fetch_all_items::<StoredAddr, _, _>(storage, flash_range::<S>(), &mut cache, &mut buffer).await.ok()?;
     while let Some((key, value)) = iter.next::<StoredBondInformation>(&mut buffer).await.ok()? {
         return Some(BondInformation {
             identity: Identity {
                 bd_addr: key.0,
-                irk: None,
+                irk: value.irk,
             },
+            identity_kind: value.identity_kind,
             security_level: value.security_level,
             is_bonded: true,
-            ltk: value.ltk
+            ltk: value.ltk,
+            ediv: value.ediv,
+            rand: value.rand,
+            local_csrk: value.local_csrk,
+            peer_csrk: value.peer_csrk,
         });
     }
     None
@@ -46,6 +46,53 @@ pub struct AccessArgs {
     pub notify: bool,
     /// If true, the characteristic can send indications.
     pub indicate: bool,
+    /// Security required to read the characteristic.
+    pub read_security: SecurityArgs,
+    /// Security required to write the characteristic, with or without a response.
+    pub write_security: SecurityArgs,
+}
+
+/// Security requirements for accessing a characteristic or descriptor.
+///
+/// Parsed from the optional list following an access property, i.e. `read(encrypted)` or `write(authenticated, authorized)`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SecurityArgs {
+    /// The link must be encrypted.
+    pub encrypted: bool,
+    /// The link must be encrypted with an authenticated key.
+    pub authenticated: bool,
+    /// The client must be authorized by the application.
+    pub authorized: bool,
+    /// The link must be encrypted with a 128-bit key.
+    pub key_size_16: bool,
+}
+
+impl SecurityArgs {
+    /// Parse the security requirements following an access property, if any.
+    fn parse(&mut self, meta: &ParseNestedMeta<'_>) -> Result<()> {
+        if !meta.input.peek(syn::token::Paren) {
+            return Ok(());
+        }
+        meta.parse_nested_meta(|meta| {
+            match meta.path.get_ident().ok_or(meta.error("no ident"))?.to_string().as_str() {
+                "encrypted" => self.encrypted = true,
+                "authenticated" => self.authenticated = true,
+                "authorized" => self.authorized = true,
+                "key_size_16" => self.key_size_16 = true,
+                other => {
+                    return Err(meta.error(format!(
+                        "Unsupported security requirement: '{other}'.\nSupported requirements are: encrypted, authenticated, authorized, key_size_16"
+                    )))
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns true if any security requirement is set.
+    pub fn is_set(&self) -> bool {
+        self.encrypted || self.authenticated || self.authorized || self.key_size_16
+    }
 }
 
 /// Descriptor attribute arguments.
@@ -79,6 +126,28 @@ pub struct CharacteristicArgs {
     /// Any '///' comments on each field, parsed in super::check_for_characteristic.
     pub doc_string: String,
     pub access: AccessArgs,
+    /// Extended properties, added as a Characteristic Extended Properties descriptor if any is set.
+    pub extended: ExtendedArgs,
+    /// Text of the Characteristic User Description descriptor.
+    pub description: Option<syn::Expr>,
+    /// Value of the Characteristic Presentation Format descriptor.
+    pub format: Option<syn::Expr>,
+}
+
+/// Characteristic extended properties.
+#[derive(Debug, Default)]
+pub struct ExtendedArgs {
+    /// The characteristic value can be written with reliable writes.
+    pub reliable_write: bool,
+    /// The User Description descriptor can be written.
+    pub writable_auxiliaries: bool,
+}
+
+impl ExtendedArgs {
+    /// Returns true if any extended property is set.
+    pub fn is_set(&self) -> bool {
+        self.reliable_write || self.writable_auxiliaries
+    }
 }
 
 /// Check if this bool type has been specified more than once.
@@ -125,26 +194,55 @@ impl CharacteristicArgs {
         let mut indicate: Option<bool> = None;
         let mut default_value: Option<syn::Expr> = None;
         let mut write_without_response: Option<bool> = None;
+        let mut reliable_write: Option<bool> = None;
+        let mut writable_auxiliaries: Option<bool> = None;
+        let mut description: Option<syn::Expr> = None;
+        let mut format: Option<syn::Expr> = None;
+        let mut read_security = SecurityArgs::default();
+        let mut write_security = SecurityArgs::default();
         attribute.parse_nested_meta(|meta| {
             match meta.path.get_ident().ok_or(meta.error("no ident"))?.to_string().as_str() {
                 "uuid" => check_multi(&mut uuid, "uuid", &meta, parse_uuid(&meta)?)?,
-                "read" => check_multi(&mut read, "read", &meta, true)?,
-                "write" => check_multi(&mut write, "write", &meta, true)?,
+                "read" => {
+                    check_multi(&mut read, "read", &meta, true)?;
+                    read_security.parse(&meta)?
+                }
+                "write" => {
+                    check_multi(&mut write, "write", &meta, true)?;
+                    write_security.parse(&meta)?
+                }
                 "notify" => check_multi(&mut notify, "notify", &meta, true)?,
                 "indicate" => check_multi(&mut indicate, "indicate", &meta, true)?,
-                "write_without_response" => check_multi(&mut write_without_response, "write_without_response", &meta, true)?,
+                "write_without_response" => {
+                    check_multi(&mut write_without_response, "write_without_response", &meta, true)?;
+                    write_security.parse(&meta)?
+                }
+                "reliable_write" => check_multi(&mut reliable_write, "reliable_write", &meta, true)?,
+                "writable_auxiliaries" => check_multi(&mut writable_auxiliaries, "writable_auxiliaries", &meta, true)?,
                 "value" => {
                     let value = meta
                         .value()
                         .map_err(|_| meta.error("'value' must be followed by '= [data]'.  i.e. value = \"42\""))?;
                     check_multi(&mut default_value, "value", &meta, value.parse()?)?
                 }
+                "description" => {
+                    let value = meta.value().map_err(|_| {
+                        meta.error("'description' must be followed by '= [text]'.  i.e. description = \"Battery Level\"")
+                    })?;
+                    check_multi(&mut description, "description", &meta, value.parse()?)?
+                }
+                "format" => {
+                    let value = meta.value().map_err(|_| {
+                        meta.error("'format' must be followed by '= [format]'.  i.e. format = PresentationFormat::new(PresentationFormat::UINT8, 0, units::PERCENTAGE)")
+                    })?;
+                    check_multi(&mut format, "format", &meta, value.parse()?)?
+                }
                 "default_value" => return Err(meta.error("Use 'value' for default value")),
                 "descriptor" => return Err(meta.error("Descriptors are added as separate tags i.e. #[descriptor(uuid = \"1234\", value = 42, read, write, notify, indicate)]")),
                 other => return Err(
                     meta.error(
                         format!(
-                            "Unsupported characteristic property: '{other}'.\nSupported properties are:\nuuid, read, write, write_without_response, notify, indicate, value\n"
+                            "Unsupported characteristic property: '{other}'.\nSupported properties are:\nuuid, read, write, write_without_response, notify, indicate, reliable_write, writable_auxiliaries, value, description, format\n"
                         ))),
             };
             Ok(())
@@ -160,7 +258,15 @@ impl CharacteristicArgs {
                 notify: notify.unwrap_or_default(),
                 write: write.unwrap_or_default(),
                 read: read.unwrap_or_default(),
+                read_security,
+                write_security,
+            },
+            extended: ExtendedArgs {
+                reliable_write: reliable_write.unwrap_or_default(),
+                writable_auxiliaries: writable_auxiliaries.unwrap_or_default(),
             },
+            description,
+            format,
         })
     }
 }
@@ -170,10 +276,11 @@ impl DescriptorArgs {
         let mut uuid: Option<_> = None;
         let mut name: Option<LitStr> = None;
         let mut read: Option<bool> = None;
-        // let mut write: Option<bool> = None;
-        // let mut capacity: Option<syn::Expr> = None;
+        let mut read_security = SecurityArgs::default();
+        let mut write: Option<bool> = None;
+        let mut write_security = SecurityArgs::default();
+        let mut capacity: Option<syn::Expr> = None;
         let mut default_value: Option<syn::Expr> = None;
-        // let mut write_without_response: Option<bool> = None;
         attribute.parse_nested_meta(|meta| {
             match meta
                 .path
@@ -189,23 +296,30 @@ impl DescriptorArgs {
                         .map_err(|_| meta.error("'name' must be followed by '= [name]'. i.e. name = \"units\""))?;
                     check_multi(&mut name, "name", &meta, value.parse()?)?
                 }
-                "read" => check_multi(&mut read, "read", &meta, true)?,
-                // "write" => check_multi(&mut write, "write", &meta, true)?,
-                // "write_without_response" => check_multi(&mut write_without_response, "write_without_response", &meta, true)?,
+                "read" => {
+                    check_multi(&mut read, "read", &meta, true)?;
+                    read_security.parse(&meta)?
+                }
+                "write" => {
+                    check_multi(&mut write, "write", &meta, true)?;
+                    write_security.parse(&meta)?
+                }
                 "value" => {
                     let value = meta.value().map_err(|_| {
                         meta.error("'value' must be followed by '= [data]'.  i.e. value = \"Hello World\"")
                     })?;
                     check_multi(&mut default_value, "value", &meta, value.parse()?)?
                 }
-                // "capacity" => {
-                //     let value = meta.value().map_err(|_| meta.error("'capacity' must be followed by '= [data]'.  i.e. value = 100"))?;
-                //     check_multi(&mut capacity, "capacity", &meta, value.parse()?)?
-                //     }
+                "capacity" => {
+                    let value = meta
+                        .value()
+                        .map_err(|_| meta.error("'capacity' must be followed by '= [size]'.  i.e. capacity = 100"))?;
+                    check_multi(&mut capacity, "capacity", &meta, value.parse()?)?
+                }
                 "default_value" => return Err(meta.error("use 'value' for default value")),
                 other => {
                     return Err(meta.error(format!(
-                        "Unsupported descriptor property: '{other}'.\nSupported properties are: uuid, name, read, value"
+                        "Unsupported descriptor property: '{other}'.\nSupported properties are: uuid, name, read, write, value, capacity"
                     )));
                 }
             };
@@ -216,13 +330,15 @@ impl DescriptorArgs {
             uuid: uuid.ok_or(Error::custom("Descriptor must have a UUID"))?,
             name,
             default_value,
-            capacity: None,
+            capacity,
             access: AccessArgs {
                 indicate: false, // not possible for descriptor
                 notify: false,   // not possible for descriptor
                 read: read.unwrap_or_default(),
                 write_without_response: false,
-                write: false,
+                write: write.unwrap_or_default(),
+                read_security,
+                write_security,
             },
         })
     }
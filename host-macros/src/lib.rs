@@ -21,7 +21,7 @@ use syn::{parse_macro_input, Error};
 
 /// Gatt Service attribute macro.
 ///
-///
+/// A service field can include previously declared services with `#[include(...)]`.
 ///
 /// # Example
 /// ```rust no_run
@@ -31,6 +31,8 @@ use syn::{parse_macro_input, Error};
 /// struct MyGattServer {
 ///     hrs: HeartRateService,
 ///     bas: BatteryService,
+///     #[include(bas)]
+///     hid: HidService,
 /// }
 ///
 /// ```
@@ -56,6 +58,8 @@ pub fn gatt_server(args: TokenStream, item: TokenStream) -> TokenStream {
 
 /// Gatt Service attribute macro.
 ///
+/// Add `secondary` to the arguments to declare a secondary service, meant to be included by other services.
+///
 /// # Example
 ///
 /// ```rust no_run
@@ -74,10 +78,16 @@ pub fn gatt_server(args: TokenStream, item: TokenStream) -> TokenStream {
 ///    #[characteristic(uuid = "2a28", read, write, notify, value = 42.0)]
 ///    /// Can be in any order
 ///    location: f32,
-///    #[characteristic(uuid = "2a39", write)]
+///    /// Security requirements can follow the access properties
+///    #[characteristic(uuid = "2a39", write(encrypted))]
 ///    control: u8,
 ///    #[characteristic(uuid = "2a63", read, notify)]
 ///    energy_expended: u16,
+///    /// Standard descriptors are generated from the characteristic arguments, and descriptors can be writable
+///    #[descriptor(uuid = descriptors::VALID_RANGE, read, write, capacity = 4, value = [0u8, 200])]
+///    #[characteristic(uuid = "2a6e", read, writable_auxiliaries, description = "Temperature",
+///        format = PresentationFormat::new(PresentationFormat::SINT16, -2, units::CELSIUS_TEMPERATURE_DEGREE_CELSIUS))]
+///    temperature: i16,
 /// }
 /// ```
 #[proc_macro_attribute]
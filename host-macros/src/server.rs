@@ -102,6 +102,8 @@ impl ServerBuilder {
         let mut code_server_populate = TokenStream2::new();
         let mut code_attribute_summation = TokenStream2::new();
         let mut code_cccd_summation = TokenStream2::new();
+        let mut code_errors = TokenStream2::new();
+        let mut service_names: Vec<&syn::Ident> = Vec::new();
         for service in &self.properties.fields {
             let vis = &service.vis;
             let service_span = service.span();
@@ -112,9 +114,28 @@ impl ServerBuilder {
                 #vis #service_name: #service_type,
             });
 
-            code_service_init.extend(quote_spanned! {service_span=>
-                let #service_name = #service_type::new(&mut table);
-            });
+            let includes = match parse_includes(service, &service_names) {
+                Ok(includes) => includes,
+                Err(e) => {
+                    code_errors.extend(e.to_compile_error());
+                    Vec::new()
+                }
+            };
+            service_names.push(service_name);
+
+            if includes.is_empty() {
+                code_service_init.extend(quote_spanned! {service_span=>
+                    let #service_name = #service_type::new(&mut table);
+                });
+            } else {
+                let include_count = includes.len();
+                code_service_init.extend(quote_spanned! {service_span=>
+                    let #service_name = #service_type::new_with_includes(&mut table, &[#(#includes.handle()),*]);
+                });
+                code_attribute_summation.extend(quote_spanned! {service_span=>
+                   + #include_count
+                });
+            }
 
             code_server_populate.extend(quote_spanned! {service_span=>
                 #service_name,
@@ -138,7 +159,7 @@ impl ServerBuilder {
         let cccd_table_size = if let Some(value) = self.arguments.cccd_table_size {
             value
         } else {
-            parse_quote!(0 #code_cccd_summation)
+            parse_quote!(trouble_host::gap::GAP_SERVICE_CCCD_COUNT #code_cccd_summation)
         };
 
         let connections_max = if let Some(value) = self.arguments.connections_max {
@@ -148,12 +169,17 @@ impl ServerBuilder {
         };
 
         quote! {
+            #code_errors
+
             const _ATTRIBUTE_TABLE_SIZE: usize = #attribute_table_size;
             // This pattern causes the assertion to happen at compile time
             const _: () = {
                 core::assert!(_ATTRIBUTE_TABLE_SIZE >= trouble_host::gap::GAP_SERVICE_ATTRIBUTE_COUNT #code_attribute_summation, "Specified attribute table size is insufficient. Please increase attribute_table_size or remove the argument entirely to allow automatic sizing of the attribute table.");
             };
             const _CCCD_TABLE_SIZE: usize = #cccd_table_size;
+            const _: () = {
+                core::assert!(_CCCD_TABLE_SIZE >= trouble_host::gap::GAP_SERVICE_CCCD_COUNT #code_cccd_summation, "Specified CCCD table size is insufficient. Please increase cccd_table_size or remove the argument entirely to allow automatic sizing of the CCCD table.");
+            };
             const _CONNECTIONS_MAX: usize = #connections_max;
 
             #visibility struct #name<'values>
@@ -241,3 +267,25 @@ impl ServerBuilder {
         }
     }
 }
+
+/// Parse the `#[include(...)]` attributes of a service field, naming the services it includes.
+///
+/// Included services must be declared before the service that includes them, so they are already in the table.
+fn parse_includes<'a>(service: &syn::Field, declared: &[&'a syn::Ident]) -> Result<Vec<&'a syn::Ident>> {
+    let mut includes = Vec::new();
+    for attr in service.attrs.iter().filter(|attr| attr.path().is_ident("include")) {
+        attr.parse_nested_meta(|meta| {
+            let ident = meta.path.get_ident().ok_or(meta.error("expected the name of a service field"))?;
+            match declared.iter().find(|declared| **declared == ident) {
+                Some(declared) => {
+                    includes.push(*declared);
+                    Ok(())
+                }
+                None => Err(meta.error(format!(
+                    "Unknown service '{ident}', included services must be declared before the service that includes them"
+                ))),
+            }
+        })?;
+    }
+    Ok(includes)
+}
@@ -12,17 +12,20 @@ use syn::parse::Result;
 use syn::spanned::Spanned;
 use syn::{Meta, Token};
 
-use crate::characteristic::{AccessArgs, Characteristic};
+use crate::characteristic::{AccessArgs, Characteristic, SecurityArgs};
 use crate::uuid::parse_arg_uuid;
 
 #[derive(Debug)]
 pub(crate) struct ServiceArgs {
     pub uuid: TokenStream2,
+    /// The service is declared as a secondary service.
+    pub secondary: bool,
 }
 
 impl syn::parse::Parse for ServiceArgs {
     fn parse(input: syn::parse::ParseStream) -> Result<Self> {
         let mut uuid: Option<_> = None;
+        let mut secondary = false;
 
         while !input.is_empty() {
             let meta = input.parse()?;
@@ -46,13 +49,14 @@ impl syn::parse::Parse for ServiceArgs {
                         }
                         other => {
                             return Err(Error::unknown_field(&format!(
-                                "Unsupported service property: '{other}'.\nSupported properties are: uuid"
+                                "Unsupported service property: '{other}'.\nSupported properties are: uuid, secondary"
                             ))
                             .with_span(&name_value.span())
                             .into())
                         }
                     }
                 }
+                Meta::Path(path) if path.is_ident("secondary") => secondary = true,
                 _ => return Err(Error::custom("Unexpected argument").with_span(&meta.span()).into()),
             }
             let _ = input.parse::<Token![,]>();
@@ -62,6 +66,7 @@ impl syn::parse::Parse for ServiceArgs {
             uuid: uuid.ok_or(Error::custom(
                 "Service must have a UUID (i.e. `#[gatt_service(uuid = '1234')]` or `#[gatt_service(uuid = service::BATTERY)]`)",
             ))?,
+            secondary,
         })
     }
 }
@@ -117,6 +122,11 @@ impl ServiceBuilder {
         let fields = self.code_fields;
         let code_build_chars = self.code_build_chars;
         let uuid = self.args.uuid;
+        let add_service = if self.args.secondary {
+            quote!(add_secondary_service)
+        } else {
+            quote!(add_service)
+        };
         let attribute_count = self.attribute_count;
         let cccd_count = self.cccd_count;
         quote! {
@@ -130,11 +140,26 @@ impl ServiceBuilder {
                 #visibility const ATTRIBUTE_COUNT: usize = #attribute_count;
                 #visibility const CCCD_COUNT: usize = #cccd_count;
 
-                #visibility fn new<M, const MAX_ATTRIBUTES: usize>(table: &mut trouble_host::attribute::AttributeTable<'_, M, MAX_ATTRIBUTES>) -> Self
+                #visibility fn new<M, const MAX_ATTRIBUTES: usize>(table: &trouble_host::attribute::AttributeTable<'_, M, MAX_ATTRIBUTES>) -> Self
+                where
+                    M: embassy_sync::blocking_mutex::raw::RawMutex,
+                {
+                    Self::new_with_includes(table, &[])
+                }
+
+                /// Add the service to the table, including the services declared at the given handles.
+                ///
+                /// Panics if one of the handles is not a service already added to the table.
+                #visibility fn new_with_includes<M, const MAX_ATTRIBUTES: usize>(table: &trouble_host::attribute::AttributeTable<'_, M, MAX_ATTRIBUTES>, includes: &[u16]) -> Self
                 where
                     M: embassy_sync::blocking_mutex::raw::RawMutex,
                 {
-                    let mut service = table.add_service(trouble_host::attribute::Service::new(#uuid));
+                    let mut service = table.#add_service(trouble_host::attribute::Service::new(#uuid));
+                    for include in includes {
+                        service
+                            .add_included_service(*include)
+                            .expect("included services must be added to the table first");
+                    }
                     #code_build_chars
 
                     Self {
@@ -142,6 +167,11 @@ impl ServiceBuilder {
                         #code_struct_init
                     }
                 }
+
+                /// Handle of the service declaration.
+                #visibility fn handle(&self) -> u16 {
+                    self.handle
+                }
                 #code_impl
             }
         }
@@ -149,12 +179,14 @@ impl ServiceBuilder {
 
     /// Construct instructions for adding a characteristic to the service, with static storage.
     fn construct_characteristic_static(&mut self, characteristic: Characteristic) {
+        let code_standard_descriptors = self.build_standard_descriptors(&characteristic);
         let (code_descriptors, named_descriptors) = self.build_descriptors(&characteristic);
         let name_screaming = format_ident!("{}", characteristic.name.as_str().to_case(Case::Constant));
         let char_name = format_ident!("{}", characteristic.name);
         let ty = characteristic.ty;
         let access = &characteristic.args.access;
         let properties = set_access_properties(access);
+        let permissions = set_permissions(access).map(|permissions| quote!(builder.set_permissions(#permissions);));
         let uuid = characteristic.args.uuid;
         let default_value = match characteristic.args.default_value {
             Some(val) => quote!(#val),                                       // if set by user
@@ -167,6 +199,8 @@ impl ServiceBuilder {
                 let store = #name_screaming.init([0; <#ty as trouble_host::types::gatt_traits::AsGatt>::MAX_SIZE]);
                 let mut builder = service
                     .add_characteristic(#uuid, &[#(#properties),*], #default_value, store);
+                #permissions
+                #code_standard_descriptors
                 #code_descriptors
 
                 (builder.build(), #(#named_descriptors),*)
@@ -238,6 +272,48 @@ impl ServiceBuilder {
         self
     }
 
+    /// Generate token stream for the descriptors defined by the characteristic arguments.
+    ///
+    /// These are the Characteristic Extended Properties, User Description and Presentation Format descriptors.
+    fn build_standard_descriptors(&mut self, characteristic: &Characteristic) -> TokenStream2 {
+        let mut code = TokenStream2::new();
+        let args = &characteristic.args;
+        if args.extended.is_set() {
+            let reliable_write = args.extended.reliable_write;
+            let writable_auxiliaries = args.extended.writable_auxiliaries;
+            code.extend(quote_spanned! {characteristic.span=>
+                builder.add_extended_properties(#reliable_write, #writable_auxiliaries);
+            });
+            self.attribute_count += 1;
+        }
+        if let Some(description) = &args.description {
+            if args.extended.writable_auxiliaries {
+                // Clients can write descriptions up to the length of the initial one
+                let name_screaming =
+                    format_ident!("{}_DESCRIPTION", characteristic.name.as_str().to_case(Case::Constant));
+                code.extend(quote_spanned! {characteristic.span=>
+                    static #name_screaming: static_cell::StaticCell<[u8; #description.len()]> = static_cell::StaticCell::new();
+                    let store = #name_screaming.init([0; #description.len()]);
+                    builder.add_user_description_writable(#description, store);
+                });
+            } else {
+                code.extend(quote_spanned! {characteristic.span=>
+                    builder.add_user_description(#description);
+                });
+            }
+            self.attribute_count += 1;
+        }
+        if let Some(format) = &args.format {
+            let name_screaming = format_ident!("{}_FORMAT", characteristic.name.as_str().to_case(Case::Constant));
+            code.extend(quote_spanned! {characteristic.span=>
+                static #name_screaming: trouble_host::attribute::PresentationFormat = #format;
+                builder.add_presentation_format(&#name_screaming);
+            });
+            self.attribute_count += 1;
+        }
+        code
+    }
+
     /// Generate token stream for any descriptors tagged against this characteristic.
     fn build_descriptors(&mut self, characteristic: &Characteristic) -> (TokenStream2, Vec<TokenStream2>) {
         let mut named_descriptors = Vec::<TokenStream2>::new();
@@ -252,6 +328,8 @@ impl ServiceBuilder {
                     let identifier = args.name.as_ref().map(|name| format_ident!("{}_{}_descriptor", characteristic.name.as_str(), name.value()));
                     let access = &args.access;
                     let properties = set_access_properties(access);
+                    let permissions = set_permissions(access)
+                        .map(|permissions| quote!(builder.set_descriptor_permissions(&descriptor, #permissions);));
                     let uuid = &args.uuid;
                     let default_value = match &args.default_value {
                         Some(val) => quote!(#val), // if set by user
@@ -281,13 +359,14 @@ impl ServiceBuilder {
                             let value = #default_value;
                             static #name_screaming: static_cell::StaticCell<[u8; #capacity]> = static_cell::StaticCell::new();
                             let store = #name_screaming.init([0; #capacity]);
-                            let value = trouble_host::types::gatt_traits::AsGatt::as_gatt(&value);
-                            store[..value.len()].copy_from_slice(value);
-                            builder.add_descriptor::<&[u8], _>(
+                            let descriptor = builder.add_descriptor_value::<&[u8], _>(
                                 #uuid,
                                 &[#(#properties),*],
+                                trouble_host::types::gatt_traits::AsGatt::as_gatt(&value),
                                 store,
-                            )
+                            );
+                            #permissions
+                            descriptor
                         };
                     }
                 })
@@ -302,6 +381,38 @@ fn parse_property_into_list(property: bool, variant: TokenStream2, properties: &
     }
 }
 
+/// Generate the security requirements of a characteristic or descriptor, if any are set.
+fn set_permissions(args: &AccessArgs) -> Option<TokenStream2> {
+    if !args.read_security.is_set() && !args.write_security.is_set() {
+        return None;
+    }
+    let read = security_tokens(&args.read_security);
+    let write = security_tokens(&args.write_security);
+    Some(quote! {
+        trouble_host::attribute::AttributePermissions {
+            read: #read,
+            write: #write,
+        }
+    })
+}
+
+fn security_tokens(args: &SecurityArgs) -> TokenStream2 {
+    let SecurityArgs {
+        encrypted,
+        authenticated,
+        authorized,
+        key_size_16,
+    } = args;
+    quote! {
+        trouble_host::attribute::AttributeSecurity {
+            encrypted: #encrypted,
+            authenticated: #authenticated,
+            authorized: #authorized,
+            key_size_16: #key_size_16,
+        }
+    }
+}
+
 /// Parse the properties of a characteristic and return a list of properties
 fn set_access_properties(args: &AccessArgs) -> Vec<TokenStream2> {
     let mut properties = Vec::new();
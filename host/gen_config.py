@@ -58,6 +58,12 @@ feature("gatt_client_notification_max_subscribers",
 feature("gatt_client_notification_queue_size",
         "When using the GATT client, this controls how many notifications can be queued for each subscriber.",
         default=1, min=1, max=512, pow2=True)
+feature("gatt_server_prepare_write_queue_size",
+        "When using the GATT server, this controls how many bytes of prepared writes can be queued for each connection.",
+        default=512, min=64, max=4096, pow2=True)
+feature("gatt_client_eatt_bearers_max",
+        "When using the GATT client, this controls how many EATT bearers can be opened for each client.",
+        default=2, min=1, max=5)
 
 # ========= Update Cargo.toml
 
@@ -3,21 +3,28 @@ use core::cell::RefCell;
 use core::fmt;
 use core::marker::PhantomData;
 
-use bt_hci::uuid::declarations::{CHARACTERISTIC, PRIMARY_SERVICE};
-use bt_hci::uuid::descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION;
+use bt_hci::uuid::declarations::{CHARACTERISTIC, INCLUDE, PRIMARY_SERVICE, SECONDARY_SERVICE};
+use bt_hci::uuid::descriptors::{
+    CHARACTERISTIC_EXTENDED_PROPERTIES, CHARACTERISTIC_PRESENTATION_FORMAT, CHARACTERISTIC_USER_DESCRIPTION,
+    CLIENT_CHARACTERISTIC_CONFIGURATION,
+};
+use bt_hci::uuid::BluetoothUuid16;
 use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::with_timeout;
 use heapless::Vec;
 
 use crate::att::{AttErrorCode, AttUns};
-use crate::gatt;
-
 use crate::attribute_server::AttributeServer;
+use crate::connection::SecurityLevel;
+use crate::connection_manager::ATT_TRANSACTION_TIMEOUT;
 use crate::cursor::{ReadCursor, WriteCursor};
 use crate::prelude::{AsGatt, FixedGattValue, FromGatt, GattConnection};
+#[cfg(feature = "security")]
+use crate::security_manager::AesCmac;
 use crate::types::gatt_traits::FromGattError;
 pub use crate::types::uuid::Uuid;
-use crate::{Error, PacketPool, MAX_INVALID_DATA_LEN};
+use crate::{gatt, Error, PacketPool, MAX_INVALID_DATA_LEN};
 
 /// Characteristic properties
 #[derive(Debug, Clone, Copy)]
@@ -41,12 +48,60 @@ pub enum CharacteristicProp {
     Extended = 0x80,
 }
 
+/// Security requirements for accessing an attribute.
+///
+/// The requirements are checked against the security level of the connection before the
+/// attribute is read or written, and the ATT error returned on failure prompts the client to
+/// pair or re-encrypt the link.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeSecurity {
+    /// The link must be encrypted.
+    pub encrypted: bool,
+    /// The link must be encrypted with an authenticated (MITM protected) key.
+    pub authenticated: bool,
+    /// The client must have been authorized by the application, see [`AttributeServer::set_authorized`].
+    pub authorized: bool,
+    /// The link must be encrypted with a 128-bit key.
+    pub key_size_16: bool,
+}
+
+impl AttributeSecurity {
+    /// Check the requirements against the security level of a link.
+    pub(crate) fn check(&self, level: SecurityLevel, authorized: bool) -> Result<(), AttErrorCode> {
+        // The security manager only accepts 128-bit keys, so any encrypted link satisfies `key_size_16`.
+        if (self.encrypted || self.key_size_16) && !level.encrypted() {
+            return Err(AttErrorCode::INSUFFICIENT_ENCRYPTION);
+        }
+        if self.authenticated && !level.authenticated() {
+            return Err(AttErrorCode::INSUFFICIENT_AUTHENTICATION);
+        }
+        if self.authorized && !authorized {
+            return Err(AttErrorCode::INSUFFICIENT_AUTHORISATION);
+        }
+        Ok(())
+    }
+}
+
+/// Security requirements for reading and writing an attribute.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributePermissions {
+    /// Requirements for reading the attribute.
+    pub read: AttributeSecurity,
+    /// Requirements for writing the attribute.
+    pub write: AttributeSecurity,
+}
+
 /// Attribute metadata.
 pub struct Attribute<'a> {
     pub(crate) uuid: Uuid,
     pub(crate) handle: u16,
     pub(crate) last_handle_in_group: u16,
     pub(crate) data: AttributeData<'a>,
+    pub(crate) permissions: AttributePermissions,
+    /// Disabled attributes are hidden from clients but keep their handle.
+    pub(crate) enabled: bool,
 }
 
 impl<'a> Attribute<'a> {
@@ -72,6 +127,12 @@ pub(crate) enum AttributeData<'d> {
     Service {
         uuid: Uuid,
     },
+    /// Include declaration referring to another service in the table.
+    IncludedService {
+        handle: u16,
+        last_handle_in_group: u16,
+        uuid: Uuid,
+    },
     ReadOnlyData {
         props: CharacteristicProps,
         value: &'d [u8],
@@ -87,6 +148,10 @@ pub(crate) enum AttributeData<'d> {
         handle: u16,
         uuid: Uuid,
     },
+    /// Value owned by the application, which answers reads and handles writes through GATT events.
+    Deferred {
+        props: CharacteristicProps,
+    },
     Cccd {
         notifications: bool,
         indications: bool,
@@ -96,14 +161,14 @@ pub(crate) enum AttributeData<'d> {
 impl AttributeData<'_> {
     pub(crate) fn readable(&self) -> bool {
         match self {
-            Self::Data { props, .. } => props.0 & (CharacteristicProp::Read as u8) != 0,
+            Self::Data { props, .. } | Self::Deferred { props } => props.0 & (CharacteristicProp::Read as u8) != 0,
             _ => true,
         }
     }
 
     pub(crate) fn writable(&self) -> bool {
         match self {
-            Self::Data { props, .. } => {
+            Self::Data { props, .. } | Self::Deferred { props } => {
                 props.0
                     & (CharacteristicProp::Write as u8
                         | CharacteristicProp::WriteWithoutResponse as u8
@@ -118,6 +183,35 @@ impl AttributeData<'_> {
         }
     }
 
+    /// Current length of the attribute value.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Service { uuid } => uuid.as_raw().len(),
+            Self::IncludedService { uuid, .. } => 4 + Self::included_uuid(uuid).len(),
+            Self::ReadOnlyData { value, .. } => value.len(),
+            Self::Data { len, .. } => *len as usize,
+            Self::Declaration { uuid, .. } => 3 + uuid.as_raw().len(),
+            Self::Deferred { .. } => 0,
+            Self::Cccd { .. } => 2,
+        }
+    }
+
+    /// Maximum length the attribute value can be written to.
+    pub(crate) fn capacity(&self) -> usize {
+        match self {
+            Self::Data { value, .. } => value.len(),
+            _ => self.len(),
+        }
+    }
+
+    /// The service UUID is only part of an include declaration if it is a 16-bit UUID ([Vol 3] Part G, Section 3.2).
+    fn included_uuid(uuid: &Uuid) -> &[u8] {
+        match uuid.as_raw() {
+            raw @ [_, _] => raw,
+            _ => &[],
+        }
+    }
+
     fn read(&self, offset: usize, data: &mut [u8]) -> Result<usize, AttErrorCode> {
         if !self.readable() {
             return Err(AttErrorCode::READ_NOT_PERMITTED);
@@ -160,6 +254,26 @@ impl AttributeData<'_> {
                 }
                 Ok(len)
             }
+            Self::IncludedService {
+                handle,
+                last_handle_in_group,
+                uuid,
+            } => {
+                let mut val = [0u8; 6];
+                val[..2].copy_from_slice(&handle.to_le_bytes());
+                val[2..4].copy_from_slice(&last_handle_in_group.to_le_bytes());
+                let uuid = Self::included_uuid(uuid);
+                val[4..4 + uuid.len()].copy_from_slice(uuid);
+                let val = &val[..4 + uuid.len()];
+                if offset > val.len() {
+                    return Ok(0);
+                }
+                let len = data.len().min(val.len() - offset);
+                if len > 0 {
+                    data[..len].copy_from_slice(&val[offset..offset + len]);
+                }
+                Ok(len)
+            }
             Self::Cccd {
                 notifications,
                 indications,
@@ -203,6 +317,8 @@ impl AttributeData<'_> {
                 }
                 Ok(w.len())
             }
+            // Only the application can provide the value, see `ReadEvent::respond`.
+            Self::Deferred { .. } => Err(AttErrorCode::REQUEST_NOT_SUPPORTED),
         }
     }
 
@@ -244,6 +360,8 @@ impl AttributeData<'_> {
                 *indications = data[0] & 0x02 != 0;
                 Ok(())
             }
+            // The written value is handed to the application in the write event.
+            Self::Deferred { .. } if writable => Ok(()),
             _ => Err(AttErrorCode::WRITE_NOT_PERMITTED),
         }
     }
@@ -284,6 +402,8 @@ impl<'a> Attribute<'a> {
             handle: 0,
             data,
             last_handle_in_group: 0xffff,
+            permissions: AttributePermissions::default(),
+            enabled: true,
         }
     }
 }
@@ -291,16 +411,33 @@ impl<'a> Attribute<'a> {
 /// A table of attributes.
 pub struct AttributeTable<'d, M: RawMutex, const MAX: usize> {
     inner: Mutex<M, RefCell<InnerTable<'d, MAX>>>,
-    handle: u16,
 }
 
 pub(crate) struct InnerTable<'d, const MAX: usize> {
     attributes: Vec<Attribute<'d>, MAX>,
+    /// Handle assigned to the next attribute. Handles are never reused, so that removing a service
+    /// does not move the handles of the services added after it. Past `0xffff` once the handle space is exhausted.
+    next_handle: u32,
+    /// Whether an attribute did not fit while services are added with [`AttributeTable::try_add`], `None` outside
+    /// of it, where an attribute that does not fit panics.
+    overflow: Option<bool>,
 }
 
 impl<'d, const MAX: usize> InnerTable<'d, MAX> {
-    fn push(&mut self, attribute: Attribute<'d>) {
-        self.attributes.push(attribute).unwrap();
+    fn push(&mut self, mut attribute: Attribute<'d>) -> u16 {
+        let handle = self.next_handle as u16;
+        if self.attributes.is_full() || self.next_handle > u32::from(u16::MAX) {
+            match self.overflow.as_mut() {
+                Some(overflow) => *overflow = true,
+                None => panic!("attribute table is full"),
+            }
+        } else {
+            attribute.handle = handle;
+            // There is room, it was checked above.
+            let _ = self.attributes.push(attribute);
+        }
+        self.next_handle += 1;
+        handle
     }
 }
 
@@ -314,8 +451,11 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
     /// Create a new GATT table.
     pub fn new() -> Self {
         Self {
-            handle: 1,
-            inner: Mutex::new(RefCell::new(InnerTable { attributes: Vec::new() })),
+            inner: Mutex::new(RefCell::new(InnerTable {
+                attributes: Vec::new(),
+                next_handle: 1,
+                overflow: None,
+            })),
         }
     }
 
@@ -326,37 +466,132 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         })
     }
 
-    pub(crate) fn iterate<F: FnMut(AttributeIterator<'_, 'd>) -> R, R>(&self, mut f: F) -> R {
+    /// Iterate over the attributes visible to clients, skipping those of disabled services.
+    pub(crate) fn iterate<F: FnMut(AttributeIterator<'_, 'd>) -> R, R>(&self, f: F) -> R {
+        self.iterate_inner(false, f)
+    }
+
+    /// Iterate over all attributes, including those of disabled services.
+    pub(crate) fn iterate_all<F: FnMut(AttributeIterator<'_, 'd>) -> R, R>(&self, f: F) -> R {
+        self.iterate_inner(true, f)
+    }
+
+    fn iterate_inner<F: FnMut(AttributeIterator<'_, 'd>) -> R, R>(&self, include_disabled: bool, mut f: F) -> R {
         self.inner.lock(|inner| {
             let mut table = inner.borrow_mut();
             let it = AttributeIterator {
                 attributes: &mut table.attributes[..],
                 pos: 0,
+                include_disabled,
             };
             f(it)
         })
     }
 
-    fn push(&mut self, mut attribute: Attribute<'d>) -> u16 {
-        let handle = self.handle;
-        attribute.handle = handle;
+    /// Index range of the attributes belonging to the service declared at `handle`.
+    fn service_range(attributes: &[Attribute<'d>], handle: u16) -> Result<core::ops::Range<usize>, Error> {
+        let start = attributes
+            .iter()
+            .position(|att| att.handle == handle && matches!(att.data, AttributeData::Service { .. }))
+            .ok_or(Error::NotFound)?;
+        let end = attributes[start + 1..]
+            .iter()
+            .position(|att| matches!(att.data, AttributeData::Service { .. }))
+            .map(|pos| start + 1 + pos)
+            .unwrap_or(attributes.len());
+        Ok(start..end)
+    }
+
+    /// Remove the service declared at `handle` together with its characteristics and descriptors.
+    ///
+    /// The handles of the removed attributes are not reused.
+    pub(crate) fn remove_service(&self, handle: u16) -> Result<(), Error> {
         self.inner.lock(|inner| {
             let mut inner = inner.borrow_mut();
-            inner.push(attribute);
+            let range = Self::service_range(&inner.attributes, handle)?;
+            let (first, last) = (
+                inner.attributes[range.start].handle,
+                inner.attributes[range.end - 1].handle,
+            );
+            inner.attributes.retain(|att| att.handle < first || att.handle > last);
+            Ok(())
+        })
+    }
+
+    /// Enable or disable the service declared at `handle`.
+    ///
+    /// The attributes of a disabled service are hidden from clients but keep their handles and values.
+    pub(crate) fn set_service_enabled(&self, handle: u16, enabled: bool) -> Result<(), Error> {
+        self.inner.lock(|inner| {
+            let mut inner = inner.borrow_mut();
+            let range = Self::service_range(&inner.attributes, handle)?;
+            for att in inner.attributes[range].iter_mut() {
+                att.enabled = enabled;
+            }
+            Ok(())
+        })
+    }
+
+    fn push(&self, attribute: Attribute<'d>) -> u16 {
+        self.inner.lock(|inner| inner.borrow_mut().push(attribute))
+    }
+
+    /// Handle that will be assigned to the next attribute.
+    fn next_handle(&self) -> u16 {
+        self.inner.lock(|inner| inner.borrow().next_handle as u16)
+    }
+
+    /// Add attributes with `f`, failing instead of panicking if they do not fit.
+    ///
+    /// Everything added by `f` is removed again if an attribute did not fit in the table or the handle space, in which
+    /// case [`Error::InsufficientSpace`] is returned, or if `f` fails.
+    pub(crate) fn try_add<R>(&self, f: impl FnOnce(&Self) -> Result<R, Error>) -> Result<R, Error> {
+        let (len, next_handle) = self.inner.lock(|inner| {
+            let mut inner = inner.borrow_mut();
+            inner.overflow = Some(false);
+            (inner.attributes.len(), inner.next_handle)
         });
-        self.handle += 1;
-        handle
+        let res = f(self);
+        self.inner.lock(|inner| {
+            let mut inner = inner.borrow_mut();
+            let res = match inner.overflow.take() {
+                Some(true) => Err(Error::InsufficientSpace),
+                _ => res,
+            };
+            if res.is_err() {
+                inner.attributes.truncate(len);
+                inner.next_handle = next_handle;
+            }
+            res
+        })
     }
 
     /// Add a service to the attribute table (group of characteristics)
-    pub fn add_service(&mut self, service: Service) -> ServiceBuilder<'_, 'd, M, MAX> {
+    ///
+    /// Services can also be added to the table of a running [`AttributeServer`](crate::attribute_server::AttributeServer)
+    /// with [`AttributeServer::add_services`](crate::attribute_server::AttributeServer::add_services).
+    pub fn add_service(&self, service: Service) -> ServiceBuilder<'_, 'd, M, MAX> {
+        self.add_service_internal(PRIMARY_SERVICE.into(), service)
+    }
+
+    /// Add a secondary service to the attribute table.
+    ///
+    /// A secondary service is not discovered as a primary service by clients, it is only meant to be included by other
+    /// services with [`ServiceBuilder::add_included_service`].
+    pub fn add_secondary_service(&self, service: Service) -> ServiceBuilder<'_, 'd, M, MAX> {
+        self.add_service_internal(SECONDARY_SERVICE.into(), service)
+    }
+
+    fn add_service_internal(&self, declaration: Uuid, service: Service) -> ServiceBuilder<'_, 'd, M, MAX> {
         let len = self.inner.lock(|i| i.borrow().attributes.len());
-        let handle = self.handle;
+        let handle = self.next_handle();
         self.push(Attribute {
-            uuid: PRIMARY_SERVICE.into(),
+            uuid: declaration,
             handle: 0,
             last_handle_in_group: 0,
             data: AttributeData::Service { uuid: service.uuid },
+            permissions: AttributePermissions::default(),
+            enabled: true,
         });
         ServiceBuilder {
             handle,
@@ -365,10 +600,22 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         }
     }
 
+    fn set_permissions(&self, attribute: u16, permissions: AttributePermissions) {
+        self.with_inner(|inner| {
+            if let Some(att) = inner.attributes.iter_mut().find(|att| att.handle == attribute) {
+                att.permissions = permissions;
+            }
+        });
+    }
+
     pub(crate) fn set_raw(&self, attribute: u16, input: &[u8]) -> Result<(), Error> {
-        self.iterate(|mut it| {
+        self.iterate_all(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == attribute {
+                    // The application owns deferred values, there is nothing to store.
+                    if let AttributeData::Deferred { .. } = att.data {
+                        return Ok(());
+                    }
                     if let AttributeData::Data {
                         props: _,
                         value,
@@ -417,7 +664,7 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
     ///
     /// If the characteristic for the handle cannot be found, an error is returned.
     pub fn get<T: AttributeHandle<Value = V>, V: FromGatt>(&self, attribute_handle: &T) -> Result<T::Value, Error> {
-        self.iterate(|mut it| {
+        self.iterate_all(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == attribute_handle.handle() {
                     if let AttributeData::Data {
@@ -446,11 +693,52 @@ impl<'d, M: RawMutex, const MAX: usize> AttributeTable<'d, M, MAX> {
         })
     }
 
+    /// Calculate the database hash of the table ([Vol 3] Part G, Section 7.3.1).
+    ///
+    /// The hash covers the handles and types of all service, characteristic and descriptor declarations,
+    /// so it changes whenever the layout of the table changes.
+    #[cfg(feature = "security")]
+    pub fn hash(&self) -> u128 {
+        use bt_hci::uuid::{declarations, descriptors};
+
+        let with_value: [Uuid; 5] = [
+            declarations::PRIMARY_SERVICE.into(),
+            declarations::SECONDARY_SERVICE.into(),
+            declarations::INCLUDE.into(),
+            declarations::CHARACTERISTIC.into(),
+            descriptors::CHARACTERISTIC_EXTENDED_PROPERTIES.into(),
+        ];
+        let without_value: [Uuid; 5] = [
+            descriptors::CHARACTERISTIC_USER_DESCRIPTION.into(),
+            descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION.into(),
+            descriptors::SERVER_CHARACTERISTIC_CONFIGURATION.into(),
+            descriptors::CHARACTERISTIC_PRESENTATION_FORMAT.into(),
+            descriptors::CHARACTERISTIC_AGGREGATE_FORMAT.into(),
+        ];
+
+        let mut mac = AesCmac::db_hash();
+        self.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if with_value.contains(&att.uuid) {
+                    // Large enough for a characteristic declaration with a 128-bit UUID
+                    let mut value = [0u8; 19];
+                    let len = att.data.read(0, &mut value).unwrap_or(0);
+                    mac.update(att.handle.to_le_bytes())
+                        .update(att.uuid.as_raw())
+                        .update(&value[..len]);
+                } else if without_value.contains(&att.uuid) {
+                    mac.update(att.handle.to_le_bytes()).update(att.uuid.as_raw());
+                }
+            }
+        });
+        mac.finalize()
+    }
+
     /// Return the characteristic which corresponds to the supplied value handle
     ///
     /// If no characteristic corresponding to the given value handle was found, returns an error
     pub fn find_characteristic_by_value_handle<T: AsGatt>(&self, handle: u16) -> Result<Characteristic<T>, Error> {
-        self.iterate(|mut it| {
+        self.iterate_all(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == handle {
                     // If next is CCCD
@@ -507,10 +795,39 @@ impl<T: AsGatt> AttributeHandle for Characteristic<T> {
 pub struct ServiceBuilder<'r, 'd, M: RawMutex, const MAX: usize> {
     handle: u16,
     start: usize,
-    table: &'r mut AttributeTable<'d, M, MAX>,
+    table: &'r AttributeTable<'d, M, MAX>,
 }
 
 impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
+    /// Include the service declared at `service` in this service, returning the handle of the include declaration.
+    ///
+    /// The included service must already be built, and includes must be added before any characteristic of the
+    /// service.
+    pub fn add_included_service(&mut self, service: u16) -> Result<u16, Error> {
+        let data = self.table.iterate_all(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == service {
+                    if let AttributeData::Service { uuid } = &att.data {
+                        return Ok(AttributeData::IncludedService {
+                            handle: service,
+                            last_handle_in_group: att.last_handle_in_group,
+                            uuid: uuid.clone(),
+                        });
+                    }
+                }
+            }
+            Err(Error::NotFound)
+        })?;
+        Ok(self.table.push(Attribute {
+            uuid: INCLUDE.into(),
+            handle: 0,
+            last_handle_in_group: 0,
+            data,
+            permissions: AttributePermissions::default(),
+            enabled: true,
+        }))
+    }
+
     fn add_characteristic_internal<T: AsGatt>(
         &mut self,
         uuid: Uuid,
@@ -518,8 +835,8 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
         data: AttributeData<'d>,
     ) -> CharacteristicBuilder<'_, 'd, T, M, MAX> {
         // First the characteristic declaration
-        let next = self.table.handle + 1;
-        let cccd = self.table.handle + 2;
+        let next = self.table.next_handle().wrapping_add(1);
+        let cccd = self.table.next_handle().wrapping_add(2);
         self.table.push(Attribute {
             uuid: CHARACTERISTIC.into(),
             handle: 0,
@@ -529,6 +846,8 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
                 handle: next,
                 uuid: uuid.clone(),
             },
+            permissions: AttributePermissions::default(),
+            enabled: true,
         });
 
         // Then the value declaration
@@ -537,6 +856,8 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
             handle: 0,
             last_handle_in_group: 0,
             data,
+            permissions: AttributePermissions::default(),
+            enabled: true,
         });
 
         // Add optional CCCD handle
@@ -549,6 +870,8 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
                     notifications: false,
                     indications: false,
                 },
+                permissions: AttributePermissions::default(),
+                enabled: true,
             });
             Some(cccd)
         } else {
@@ -607,6 +930,21 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
         )
     }
 
+    /// Add a characteristic to this service whose value is owned by the application.
+    ///
+    /// No value is stored in the attribute table. Read and Read Blob requests must be answered with
+    /// [`ReadEvent::respond`](crate::gatt::ReadEvent::respond), and written values, including the parts of long
+    /// writes, are only available from the [`WriteEvent`](crate::gatt::WriteEvent). Other requests reading the value,
+    /// such as Read By Type and Read Multiple, fail with [`AttErrorCode::REQUEST_NOT_SUPPORTED`].
+    pub fn add_characteristic_deferred<T: AsGatt, U: Into<Uuid>>(
+        &mut self,
+        uuid: U,
+        props: &[CharacteristicProp],
+    ) -> CharacteristicBuilder<'_, 'd, T, M, MAX> {
+        let props = props.into();
+        self.add_characteristic_internal(uuid.into(), props, AttributeData::Deferred { props })
+    }
+
     /// Finish construction of the service and return a handle.
     pub fn build(self) -> u16 {
         self.handle
@@ -615,15 +953,15 @@ impl<'d, M: RawMutex, const MAX: usize> ServiceBuilder<'_, 'd, M, MAX> {
 
 impl<M: RawMutex, const MAX: usize> Drop for ServiceBuilder<'_, '_, M, MAX> {
     fn drop(&mut self) {
-        let last_handle = self.table.handle;
         self.table.with_inner(|inner| {
+            let last_handle = u16::try_from(inner.next_handle).unwrap_or(u16::MAX);
             for item in inner.attributes[self.start..].iter_mut() {
                 item.last_handle_in_group = last_handle;
             }
-        });
 
-        // Jump to next 16-aligned
-        self.table.handle = self.table.handle + (0x10 - (self.table.handle % 0x10));
+            // Jump to next 16-aligned, handles past 0xffff are rejected when attributes are added
+            inner.next_handle += 0x10 - (inner.next_handle % 0x10);
+        });
     }
 }
 
@@ -671,8 +1009,11 @@ impl<T: FromGatt> Characteristic<T> {
     ///
     /// If the characteristic does not support indications, an error is returned.
     ///
-    /// This function does not block for the confirmation to the indication message, if the client sends a confirmation
-    /// this will be seen on the [GattConnection] as a [crate::att::AttClient::Confirmation] event.
+    /// Only one indication can be outstanding on a connection. If the confirmation of another indication has not been
+    /// received yet, [`Error::Busy`] is returned.
+    ///
+    /// This function waits for the client to confirm the indication. If the confirmation is not received within the
+    /// ATT transaction timeout of 30 seconds, [`Error::Timeout`] is returned.
     pub async fn indicate<P: PacketPool>(
         &self,
         connection: &GattConnection<'_, '_, P>,
@@ -689,13 +1030,26 @@ impl<T: FromGatt> Characteristic<T> {
             return Ok(());
         }
 
+        connection.start_indication()?;
         let uns = AttUns::Indicate {
             handle: self.handle,
             data: value,
         };
-        let pdu = gatt::assemble(connection, crate::att::AttServer::Unsolicited(uns))?;
+        let pdu = match gatt::assemble(connection, crate::att::AttServer::Unsolicited(uns)) {
+            Ok(pdu) => pdu,
+            Err(e) => {
+                connection.end_indication();
+                return Err(e);
+            }
+        };
         connection.send(pdu).await;
-        Ok(())
+        match with_timeout(ATT_TRANSACTION_TIMEOUT, connection.wait_confirmation()).await {
+            Ok(result) => result,
+            Err(_) => {
+                connection.indication_timed_out();
+                Err(Error::Timeout)
+            }
+        }
     }
 
     /// Set the value of the characteristic in the provided attribute server.
@@ -740,7 +1094,7 @@ impl AttributeHandle for CharacteristicPropertiesHandle {
 /// Builder for characteristics.
 pub struct CharacteristicBuilder<'r, 'd, T: AsGatt, M: RawMutex, const MAX: usize> {
     handle: Characteristic<T>,
-    table: &'r mut AttributeTable<'d, M, MAX>,
+    table: &'r AttributeTable<'d, M, MAX>,
 }
 
 impl<'d, T: AsGatt, M: RawMutex, const MAX: usize> CharacteristicBuilder<'_, 'd, T, M, MAX> {
@@ -750,12 +1104,14 @@ impl<'d, T: AsGatt, M: RawMutex, const MAX: usize> CharacteristicBuilder<'_, 'd,
         props: CharacteristicProps,
         data: AttributeData<'d>,
     ) -> Descriptor<DT> {
-        let handle = self.table.handle;
+        let handle = self.table.next_handle();
         self.table.push(Attribute {
             uuid,
             handle: 0,
             last_handle_in_group: 0,
             data,
+            permissions: AttributePermissions::default(),
+            enabled: true,
         });
 
         Descriptor {
@@ -785,12 +1141,110 @@ impl<'d, T: AsGatt, M: RawMutex, const MAX: usize> CharacteristicBuilder<'_, 'd,
         )
     }
 
+    /// Add a characteristic descriptor with a variable length value for this characteristic.
+    ///
+    /// The initial value is copied into `store`, whose length limits the values clients can write.
+    pub fn add_descriptor_value<DT: AsGatt, U: Into<Uuid>>(
+        &mut self,
+        uuid: U,
+        props: &[CharacteristicProp],
+        value: &[u8],
+        store: &'d mut [u8],
+    ) -> Descriptor<DT> {
+        let props = props.into();
+        store[..value.len()].copy_from_slice(value);
+        self.add_descriptor_internal(
+            uuid.into(),
+            props,
+            AttributeData::Data {
+                props,
+                value: store,
+                variable_len: true,
+                len: value.len() as u16,
+            },
+        )
+    }
+
+    /// Add a Characteristic Extended Properties descriptor, and set the extended properties bit in the
+    /// characteristic declaration.
+    ///
+    /// `reliable_write` allows the value to be written with reliable writes, `writable_auxiliaries` tells clients
+    /// that the User Description descriptor can be written.
+    pub fn add_extended_properties(&mut self, reliable_write: bool, writable_auxiliaries: bool) -> Descriptor<u16> {
+        static VALUES: [[u8; 2]; 4] = [[0x00, 0x00], [0x01, 0x00], [0x02, 0x00], [0x03, 0x00]];
+        let declaration = self.handle.handle - 1;
+        self.table.with_inner(|inner| {
+            if let Some(att) = inner.attributes.iter_mut().find(|att| att.handle == declaration) {
+                if let AttributeData::Declaration { props, .. } = &mut att.data {
+                    props.0 |= CharacteristicProp::Extended as u8;
+                }
+            }
+        });
+        let value = &VALUES[reliable_write as usize | (writable_auxiliaries as usize) << 1];
+        self.add_descriptor_ro(CHARACTERISTIC_EXTENDED_PROPERTIES, value)
+    }
+
+    /// Add a read only Characteristic User Description descriptor.
+    ///
+    /// Use [`Self::add_user_description_writable`] if the extended properties allow writing auxiliaries.
+    pub fn add_user_description(&mut self, description: &'d str) -> Descriptor<&'static str> {
+        self.add_descriptor_ro(CHARACTERISTIC_USER_DESCRIPTION, description.as_bytes())
+    }
+
+    /// Add a Characteristic User Description descriptor that clients can write, for characteristics whose
+    /// extended properties have `writable_auxiliaries` set.
+    ///
+    /// The initial description is copied into `store`, whose length limits the descriptions clients can write.
+    pub fn add_user_description_writable(
+        &mut self,
+        description: &str,
+        store: &'d mut [u8],
+    ) -> Descriptor<&'static str> {
+        self.add_descriptor_value(
+            CHARACTERISTIC_USER_DESCRIPTION,
+            &[CharacteristicProp::Read, CharacteristicProp::Write],
+            description.as_bytes(),
+            store,
+        )
+    }
+
+    /// Add a Characteristic Presentation Format descriptor.
+    pub fn add_presentation_format(&mut self, format: &'d PresentationFormat) -> Descriptor<PresentationFormat> {
+        self.add_descriptor_ro(CHARACTERISTIC_PRESENTATION_FORMAT, &format.0)
+    }
+
     /// Add a read only characteristic descriptor for this characteristic.
     pub fn add_descriptor_ro<DT: AsGatt, U: Into<Uuid>>(&mut self, uuid: U, data: &'d [u8]) -> Descriptor<DT> {
         let props = [CharacteristicProp::Read].into();
         self.add_descriptor_internal(uuid.into(), props, AttributeData::ReadOnlyData { props, value: data })
     }
 
+    /// Add a characteristic descriptor whose value is owned by the application.
+    ///
+    /// See [`ServiceBuilder::add_characteristic_deferred`] for how deferred values are accessed.
+    pub fn add_descriptor_deferred<DT: AsGatt, U: Into<Uuid>>(
+        &mut self,
+        uuid: U,
+        props: &[CharacteristicProp],
+    ) -> Descriptor<DT> {
+        let props = props.into();
+        self.add_descriptor_internal(uuid.into(), props, AttributeData::Deferred { props })
+    }
+
+    /// Set the security requirements for reading and writing the characteristic value.
+    pub fn set_permissions(&mut self, permissions: AttributePermissions) {
+        self.table.set_permissions(self.handle.handle, permissions);
+    }
+
+    /// Set the security requirements for reading and writing a descriptor of this characteristic.
+    pub fn set_descriptor_permissions<DT: AsGatt>(
+        &mut self,
+        descriptor: &Descriptor<DT>,
+        permissions: AttributePermissions,
+    ) {
+        self.table.set_permissions(descriptor.handle, permissions);
+    }
+
     /// Return the built characteristic.
     pub fn build(self) -> Characteristic<T> {
         self.handle
@@ -813,22 +1267,101 @@ impl<T: AsGatt> AttributeHandle for Descriptor<T> {
     }
 }
 
+/// Value of a Characteristic Presentation Format descriptor ([Vol 3] Part G, Section 3.3.3.5).
+///
+/// Describes how generic clients should display the characteristic value.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PresentationFormat([u8; 7]);
+
+impl PresentationFormat {
+    /// Boolean format.
+    pub const BOOLEAN: u8 = 0x01;
+    /// Unsigned 8-bit integer format.
+    pub const UINT8: u8 = 0x04;
+    /// Unsigned 16-bit integer format.
+    pub const UINT16: u8 = 0x06;
+    /// Unsigned 32-bit integer format.
+    pub const UINT32: u8 = 0x08;
+    /// Signed 8-bit integer format.
+    pub const SINT8: u8 = 0x0C;
+    /// Signed 16-bit integer format.
+    pub const SINT16: u8 = 0x0E;
+    /// Signed 32-bit integer format.
+    pub const SINT32: u8 = 0x10;
+    /// IEEE-754 32-bit floating point format.
+    pub const FLOAT32: u8 = 0x14;
+    /// IEEE-754 64-bit floating point format.
+    pub const FLOAT64: u8 = 0x15;
+    /// UTF-8 string format.
+    pub const UTF8S: u8 = 0x19;
+    /// Opaque structure format.
+    pub const STRUCT: u8 = 0x1B;
+    /// Bluetooth SIG namespace for descriptions.
+    pub const NAMESPACE_BLUETOOTH_SIG: u8 = 0x01;
+
+    /// Create a presentation format from the format type, the base 10 exponent applied to the value and the unit.
+    pub const fn new(format: u8, exponent: i8, unit: BluetoothUuid16) -> Self {
+        let unit = unit.to_le_bytes();
+        Self([format, exponent as u8, unit[0], unit[1], 0, 0, 0])
+    }
+
+    /// Set the description of the value, such as its position when a characteristic is present more than once.
+    pub const fn with_description(self, namespace: u8, description: u16) -> Self {
+        let mut value = self.0;
+        let description = description.to_le_bytes();
+        value[4] = namespace;
+        value[5] = description[0];
+        value[6] = description[1];
+        Self(value)
+    }
+
+    /// Format type of the value.
+    pub const fn format(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Base 10 exponent applied to the value.
+    pub const fn exponent(&self) -> i8 {
+        self.0[1] as i8
+    }
+
+    /// Unit of the value.
+    pub const fn unit(&self) -> BluetoothUuid16 {
+        BluetoothUuid16::from_le_bytes([self.0[2], self.0[3]])
+    }
+}
+
+impl FixedGattValue for PresentationFormat {
+    const SIZE: usize = 7;
+
+    fn from_gatt(data: &[u8]) -> Result<Self, FromGattError> {
+        data.try_into().map(Self).map_err(|_| FromGattError::InvalidLength)
+    }
+
+    fn as_gatt(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 /// Iterator over attributes.
 pub struct AttributeIterator<'a, 'd> {
     attributes: &'a mut [Attribute<'d>],
     pos: usize,
+    include_disabled: bool,
 }
 
 impl<'d> AttributeIterator<'_, 'd> {
     /// Return next attribute in iterator.
     pub fn next<'m>(&'m mut self) -> Option<&'m mut Attribute<'d>> {
-        if self.pos < self.attributes.len() {
-            let i = &mut self.attributes[self.pos];
+        while self.pos < self.attributes.len() {
+            let pos = self.pos;
             self.pos += 1;
-            Some(i)
-        } else {
-            None
+            if self.include_disabled || self.attributes[pos].enabled {
+                return Some(&mut self.attributes[pos]);
+            }
         }
+        None
     }
 }
 
@@ -846,7 +1379,8 @@ impl Service {
 }
 
 /// Properties of a characteristic.
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CharacteristicProps(u8);
 
 impl<'a> From<&'a [CharacteristicProp]> for CharacteristicProps {
@@ -1,26 +1,79 @@
+#[cfg(feature = "security")]
+use core::cell::Cell;
 use core::cell::RefCell;
 use core::marker::PhantomData;
+use core::task::{Context, Poll};
 
+use bt_hci::param::ConnHandle;
+use bt_hci::uuid::characteristic::{CLIENT_SUPPORTED_FEATURES, DATABASE_HASH, SERVICE_CHANGED};
 use embassy_sync::blocking_mutex::raw::RawMutex;
 use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::MultiWakerRegistration;
+use heapless::Vec;
 
-use crate::att::{self, AttClient, AttCmd, AttErrorCode, AttReq};
-use crate::attribute::{Attribute, AttributeData, AttributeTable, CCCD};
+use crate::att::{self, AttClient, AttCmd, AttErrorCode, AttReq, AttUns};
+use crate::attribute::{Attribute, AttributeData, AttributeSecurity, AttributeTable, Characteristic, CCCD};
+use crate::connection::SecurityLevel;
+use crate::connection_manager::ConnectionManager;
 use crate::cursor::WriteCursor;
 use crate::prelude::Connection;
+use crate::types::gatt_traits::AsGatt;
 use crate::types::uuid::Uuid;
-use crate::{codec, Error, Identity, PacketPool};
+use crate::{codec, config, gatt, Error, Identity, PacketPool, Stack};
+
+/// Robust Caching bit of the Client Supported Features characteristic.
+const ROBUST_CACHING: u8 = 0x01;
+/// Enhanced ATT bearer bit of the Client Supported Features characteristic.
+pub(crate) const EATT_SUPPORTED: u8 = 0x02;
+/// Multiple Handle Value Notifications bit of the Client Supported Features characteristic.
+pub(crate) const MULTIPLE_HANDLE_VALUE_NOTIFICATIONS: u8 = 0x04;
 
 #[derive(Default)]
 struct Client {
     identity: Identity,
     is_connected: bool,
+    /// Handle of the connection to the client, while connected.
+    handle: Option<ConnHandle>,
+    /// Value of the Client Supported Features characteristic written by the client.
+    supported_features: u8,
+    /// Database hash known to the client, if it is change-unaware.
+    stale_database_hash: Option<u128>,
+    /// A Service Changed indication was sent to the change-unaware client.
+    service_changed_sent: bool,
+    /// The Service Changed indication is the indication awaiting confirmation from the client.
+    service_changed_pending: bool,
+    /// A Database Out Of Sync error was sent to the change-unaware client.
+    out_of_sync_sent: bool,
+    /// The application has authorized the client to access attributes requiring authorization.
+    authorized: bool,
 }
 
 impl Client {
     fn set_identity(&mut self, identity: Identity) {
         self.identity = identity;
     }
+
+    fn set_change_aware(&mut self) {
+        self.stale_database_hash = None;
+        self.service_changed_sent = false;
+        self.service_changed_pending = false;
+        self.out_of_sync_sent = false;
+    }
+}
+
+/// GATT caching state of a client ([Vol 3] Part G, Section 2.5.2).
+///
+/// Store it alongside the bond information of a client and restore it with
+/// [`AttributeServer::set_client_cache_state`] when the client reconnects. If the attribute table has changed
+/// in the meantime, the client is sent a Service Changed indication.
+#[cfg(feature = "security")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientCacheState {
+    /// Database hash known to the client.
+    pub database_hash: u128,
+    /// Value of the Client Supported Features characteristic written by the client.
+    pub supported_features: u8,
 }
 
 /// A table of CCCD values.
@@ -58,6 +111,17 @@ impl<const ENTRIES: usize> CccdTable<ENTRIES> {
         }
     }
 
+    /// Replace the CCCD handles, keeping the values of the handles that are still present.
+    fn update_handles(&mut self, base: &CccdTable<ENTRIES>) {
+        let mut table = base.clone();
+        for (handle, value) in table.inner.iter_mut() {
+            if let Some((_, previous)) = self.inner.iter().find(|(h, _)| *h != 0 && h == handle) {
+                *value = *previous;
+            }
+        }
+        *self = table;
+    }
+
     fn disable_all(&mut self) {
         for (_, value) in self.inner.iter_mut() {
             value.disable();
@@ -120,14 +184,7 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
     fn new<const ATT_MAX: usize>(att_table: &AttributeTable<'_, M, ATT_MAX>) -> Self {
         let mut values: [(Client, CccdTable<CCCD_MAX>); CONN_MAX] =
             core::array::from_fn(|_| (Client::default(), CccdTable::default()));
-        let mut base_cccd_table = CccdTable::default();
-        att_table.iterate(|mut at| {
-            while let Some(att) = at.next() {
-                if let AttributeData::Cccd { .. } = att.data {
-                    base_cccd_table.add_handle(att.handle);
-                }
-            }
-        });
+        let base_cccd_table = Self::base_table(att_table);
         // add the base CCCD table for each potential connected client
         for (_, table) in values.iter_mut() {
             *table = base_cccd_table.clone();
@@ -137,6 +194,71 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
         }
     }
 
+    /// CCCD table with the handles of all CCCDs in the attribute table, including those of disabled services.
+    fn base_table<const ATT_MAX: usize>(att_table: &AttributeTable<'_, M, ATT_MAX>) -> CccdTable<CCCD_MAX> {
+        let mut base_cccd_table = CccdTable::default();
+        att_table.iterate_all(|mut at| {
+            while let Some(att) = at.next() {
+                if let AttributeData::Cccd { .. } = att.data {
+                    base_cccd_table.add_handle(att.handle);
+                }
+            }
+        });
+        base_cccd_table
+    }
+
+    /// Number of CCCDs in the attribute table, including those of disabled services.
+    fn count<const ATT_MAX: usize>(att_table: &AttributeTable<'_, M, ATT_MAX>) -> usize {
+        att_table.iterate_all(|mut at| {
+            let mut count = 0;
+            while let Some(att) = at.next() {
+                if let AttributeData::Cccd { .. } = att.data {
+                    count += 1;
+                }
+            }
+            count
+        })
+    }
+
+    /// Update the CCCD tables of all clients after the layout of the attribute table has changed.
+    fn update_handles<const ATT_MAX: usize>(&self, att_table: &AttributeTable<'_, M, ATT_MAX>) {
+        let base_cccd_table = Self::base_table(att_table);
+        self.state.lock(|n| {
+            let mut n = n.borrow_mut();
+            for (_, table) in n.iter_mut() {
+                table.update_handles(&base_cccd_table);
+            }
+        })
+    }
+
+    /// Connection handles of the connected clients for which `f` returns true.
+    fn connected_handles<const N: usize>(&self, f: impl Fn(&CccdTable<CCCD_MAX>) -> bool) -> Vec<ConnHandle, N> {
+        self.state.lock(|n| {
+            let n = n.borrow();
+            n.iter()
+                .filter(|(client, table)| client.is_connected && f(table))
+                .filter_map(|(client, _)| client.handle)
+                .take(N)
+                .collect()
+        })
+    }
+
+    /// Consider all known clients change-unaware of a table whose previous database hash was `database_hash`.
+    fn set_change_unaware(&self, database_hash: u128) {
+        self.state.lock(|n| {
+            let mut n = n.borrow_mut();
+            let empty_slot = Identity::default();
+            for (client, _) in n.iter_mut() {
+                if client.identity != empty_slot {
+                    client.stale_database_hash.get_or_insert(database_hash);
+                    client.service_changed_sent = false;
+                    client.service_changed_pending = false;
+                    client.out_of_sync_sent = false;
+                }
+            }
+        })
+    }
+
     fn connect(&self, peer_identity: &Identity) -> Result<(), Error> {
         self.state.lock(|n| {
             trace!("[server] searching for peer {:?}", peer_identity);
@@ -159,9 +281,12 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
             for (client, table) in n.iter_mut() {
                 if !client.is_connected {
                     trace!("[server] booting disconnected peer {:?}", client.identity);
-                    client.is_connected = true;
-                    client.set_identity(*peer_identity);
                     // erase the previous client's config
+                    *client = Client {
+                        identity: *peer_identity,
+                        is_connected: true,
+                        ..Default::default()
+                    };
                     table.disable_all();
                     return Ok(());
                 }
@@ -179,6 +304,13 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
             for (client, _) in n.iter_mut() {
                 if client.identity.match_identity(peer_identity) {
                     client.is_connected = false;
+                    client.handle = None;
+                    client.authorized = false;
+                    // An unconfirmed Service Changed indication is sent again when the client reconnects.
+                    if client.service_changed_pending {
+                        client.service_changed_sent = false;
+                        client.service_changed_pending = false;
+                    }
                     break;
                 }
             }
@@ -270,6 +402,22 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
         })
     }
 
+    fn with_client<R>(
+        &self,
+        peer_identity: &Identity,
+        f: impl FnOnce(&mut Client, &CccdTable<CCCD_MAX>) -> R,
+    ) -> Option<R> {
+        self.state.lock(|n| {
+            let mut n = n.borrow_mut();
+            for (client, table) in n.iter_mut() {
+                if client.identity.match_identity(peer_identity) {
+                    return Some(f(client, table));
+                }
+            }
+            None
+        })
+    }
+
     fn update_identity(&self, identity: Identity) -> Result<(), Error> {
         self.state.lock(|n| {
             let mut n = n.borrow_mut();
@@ -284,6 +432,116 @@ impl<M: RawMutex, const CCCD_MAX: usize, const CONN_MAX: usize> CccdTables<M, CC
     }
 }
 
+const PREPARE_WRITE_QUEUE_SIZE: usize = config::GATT_SERVER_PREPARE_WRITE_QUEUE_SIZE;
+/// Size of the handle, offset and length header stored in front of each queued value.
+const PREPARED_WRITE_HEADER_LEN: usize = 6;
+
+/// Prepared writes of a single connection, waiting for an Execute Write Request.
+///
+/// Each fragment is stored as handle, offset and length followed by the value bytes.
+#[derive(Default)]
+struct PrepareWriteQueue {
+    conn: Option<ConnHandle>,
+    data: Vec<u8, PREPARE_WRITE_QUEUE_SIZE>,
+}
+
+impl PrepareWriteQueue {
+    fn push(&mut self, handle: u16, offset: u16, value: &[u8]) -> Result<(), AttErrorCode> {
+        if self.data.capacity() - self.data.len() < PREPARED_WRITE_HEADER_LEN + value.len() {
+            return Err(AttErrorCode::PREPARE_QUEUE_FULL);
+        }
+        // Capacity was checked above, so none of these can fail.
+        let _ = self.data.extend_from_slice(&handle.to_le_bytes());
+        let _ = self.data.extend_from_slice(&offset.to_le_bytes());
+        let _ = self.data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        let _ = self.data.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn iter(&self) -> PreparedWriteIter<'_> {
+        PreparedWriteIter { data: &self.data[..] }
+    }
+
+    fn clear(&mut self) {
+        self.data.clear();
+    }
+}
+
+/// A single queued fragment: attribute handle, value offset and value.
+struct PreparedWrite<'d> {
+    handle: u16,
+    offset: u16,
+    value: &'d [u8],
+}
+
+struct PreparedWriteIter<'d> {
+    data: &'d [u8],
+}
+
+impl<'d> Iterator for PreparedWriteIter<'d> {
+    type Item = PreparedWrite<'d>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < PREPARED_WRITE_HEADER_LEN {
+            return None;
+        }
+        let handle = u16::from_le_bytes([self.data[0], self.data[1]]);
+        let offset = u16::from_le_bytes([self.data[2], self.data[3]]);
+        let len = u16::from_le_bytes([self.data[4], self.data[5]]) as usize;
+        let (value, rest) = self.data[PREPARED_WRITE_HEADER_LEN..].split_at(len);
+        self.data = rest;
+        Some(PreparedWrite { handle, offset, value })
+    }
+}
+
+/// Prepare write queues for each connected client.
+struct PrepareWriteQueues<M: RawMutex, const CONN_MAX: usize> {
+    state: Mutex<M, RefCell<[PrepareWriteQueue; CONN_MAX]>>,
+}
+
+impl<M: RawMutex, const CONN_MAX: usize> PrepareWriteQueues<M, CONN_MAX> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(core::array::from_fn(|_| PrepareWriteQueue::default()))),
+        }
+    }
+
+    /// Run `f` with the queue of the given connection, claiming a free queue if it has none yet.
+    fn with_queue<R>(
+        &self,
+        conn: ConnHandle,
+        f: impl FnOnce(&mut PrepareWriteQueue) -> Result<R, AttErrorCode>,
+    ) -> Result<R, AttErrorCode> {
+        self.state.lock(|n| {
+            let mut n = n.borrow_mut();
+            let index = match n.iter().position(|q| q.conn == Some(conn)) {
+                Some(index) => index,
+                None => {
+                    let index = n
+                        .iter()
+                        .position(|q| q.conn.is_none())
+                        .ok_or(AttErrorCode::INSUFFICIENT_RESOURCES)?;
+                    n[index].conn = Some(conn);
+                    index
+                }
+            };
+            f(&mut n[index])
+        })
+    }
+
+    fn release(&self, conn: ConnHandle) {
+        self.state.lock(|n| {
+            let mut n = n.borrow_mut();
+            for queue in n.iter_mut() {
+                if queue.conn == Some(conn) {
+                    queue.conn = None;
+                    queue.clear();
+                }
+            }
+        })
+    }
+}
+
 /// A GATT server capable of processing the GATT protocol using the provided table of attributes.
 pub struct AttributeServer<
     'values,
@@ -295,6 +553,13 @@ pub struct AttributeServer<
 > {
     att_table: AttributeTable<'values, M, ATT_MAX>,
     cccd_tables: CccdTables<M, CCCD_MAX, CONN_MAX>,
+    prepare_queues: PrepareWriteQueues<M, CONN_MAX>,
+    /// Value and CCCD handles of the Service Changed characteristic, if present in the table.
+    service_changed: Option<(u16, u16)>,
+    /// Wakers of the connections waiting for the attribute table to change.
+    changed: Mutex<M, RefCell<MultiWakerRegistration<CONN_MAX>>>,
+    #[cfg(feature = "security")]
+    database_hash: Mutex<M, Cell<u128>>,
     _p: PhantomData<P>,
 }
 
@@ -312,8 +577,19 @@ pub(crate) mod sealed {
         ) -> Result<Option<usize>, Error>;
         fn should_notify(&self, connection: &Connection<'_, P>, cccd_handle: u16) -> bool;
         fn should_indicate(&self, connection: &Connection<'_, P>, cccd_handle: u16) -> bool;
+        fn supports_multiple_notifications(&self, connection: &Connection<'_, P>) -> bool;
+        fn supports_eatt(&self, connection: &Connection<'_, P>) -> bool;
+        fn respond(
+            &self,
+            connection: &Connection<'_, P>,
+            packet: &AttClient,
+            value: &[u8],
+            rx: &mut [u8],
+        ) -> Result<Option<usize>, Error>;
         fn set(&self, characteristic: u16, input: &[u8]) -> Result<(), Error>;
         fn update_identity(&self, identity: Identity) -> Result<(), Error>;
+        fn poll_service_changed(&self, connection: &Connection<'_, P>, cx: &mut Context<'_>) -> Poll<u16>;
+        fn retry_service_changed(&self, connection: &Connection<'_, P>);
     }
 }
 
@@ -333,6 +609,7 @@ impl<M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX: usize, co
 
     fn disconnect(&self, connection: &Connection<'_, P>) {
         self.cccd_tables.disconnect(&connection.peer_identity());
+        self.prepare_queues.release(connection.handle());
     }
 
     fn process(
@@ -351,6 +628,23 @@ impl<M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX: usize, co
     fn should_indicate(&self, connection: &Connection<'_, P>, cccd_handle: u16) -> bool {
         AttributeServer::should_indicate(self, connection, cccd_handle)
     }
+    fn supports_multiple_notifications(&self, connection: &Connection<'_, P>) -> bool {
+        AttributeServer::supports_multiple_notifications(self, connection)
+    }
+    fn supports_eatt(&self, connection: &Connection<'_, P>) -> bool {
+        AttributeServer::supports_eatt(self, connection)
+    }
+
+    fn respond(
+        &self,
+        connection: &Connection<'_, P>,
+        packet: &AttClient,
+        value: &[u8],
+        rx: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        let res = AttributeServer::respond(self, connection, packet, value, rx)?;
+        Ok(res)
+    }
 
     fn set(&self, characteristic: u16, input: &[u8]) -> Result<(), Error> {
         self.att_table.set_raw(characteristic, input)
@@ -359,6 +653,14 @@ impl<M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX: usize, co
     fn update_identity(&self, identity: Identity) -> Result<(), Error> {
         self.cccd_tables.update_identity(identity)
     }
+
+    fn poll_service_changed(&self, connection: &Connection<'_, P>, cx: &mut Context<'_>) -> Poll<u16> {
+        AttributeServer::poll_service_changed(self, connection, cx)
+    }
+
+    fn retry_service_changed(&self, connection: &Connection<'_, P>) {
+        AttributeServer::retry_service_changed(self, connection)
+    }
 }
 
 impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX: usize, const CONN_MAX: usize>
@@ -369,15 +671,208 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         att_table: AttributeTable<'values, M, ATT_MAX>,
     ) -> AttributeServer<'values, M, P, ATT_MAX, CCCD_MAX, CONN_MAX> {
         let cccd_tables = CccdTables::new(&att_table);
+
+        let service_changed_uuid: Uuid = SERVICE_CHANGED.into();
+        let service_changed = att_table.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.uuid == service_changed_uuid {
+                    let handle = att.handle;
+                    return match it.next() {
+                        Some(next) if matches!(next.data, AttributeData::Cccd { .. }) => Some((handle, next.handle)),
+                        _ => None,
+                    };
+                }
+            }
+            None
+        });
+
+        #[cfg(feature = "security")]
+        let database_hash = Self::update_database_hash(&att_table);
+
         AttributeServer {
             att_table,
             cccd_tables,
+            prepare_queues: PrepareWriteQueues::new(),
+            service_changed,
+            changed: Mutex::new(RefCell::new(MultiWakerRegistration::new())),
+            #[cfg(feature = "security")]
+            database_hash: Mutex::new(Cell::new(database_hash)),
             _p: PhantomData,
         }
     }
 
+    /// Calculate the database hash of the table and store it in the Database Hash characteristic, if present.
+    #[cfg(feature = "security")]
+    fn update_database_hash(att_table: &AttributeTable<'values, M, ATT_MAX>) -> u128 {
+        let hash = att_table.hash();
+        let database_hash_uuid: Uuid = DATABASE_HASH.into();
+        let handle = att_table.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.uuid == database_hash_uuid {
+                    return Some(att.handle);
+                }
+            }
+            None
+        });
+        if let Some(handle) = handle {
+            let _ = att_table.set_raw(handle, &hash.to_le_bytes());
+        }
+        hash
+    }
+
+    /// Add services to the attribute table while the server is running.
+    ///
+    /// The services are added after the existing ones, so the handles of existing attributes do not change.
+    /// Connected clients are considered change-unaware and are sent a Service Changed indication.
+    ///
+    /// Returns [`Error::InsufficientSpace`] if the added attributes do not fit in the attribute table or the handle
+    /// space, or their CCCDs in the CCCD table. Nothing is added in that case.
+    pub fn add_services<R>(&self, f: impl FnOnce(&AttributeTable<'values, M, ATT_MAX>) -> R) -> Result<R, Error> {
+        let res = self.att_table.try_add(|table| {
+            let res = f(table);
+            match CccdTables::<M, CCCD_MAX, CONN_MAX>::count(table) {
+                count if count > CCCD_MAX => Err(Error::InsufficientSpace),
+                _ => Ok(res),
+            }
+        })?;
+        self.table_changed();
+        Ok(res)
+    }
+
+    /// Remove the service declared at `handle`, together with its characteristics and descriptors.
+    ///
+    /// The handles of the removed attributes are not reused. Connected clients are considered change-unaware
+    /// and are sent a Service Changed indication.
+    pub fn remove_service(&self, handle: u16) -> Result<(), Error> {
+        self.att_table.remove_service(handle)?;
+        self.table_changed();
+        Ok(())
+    }
+
+    /// Enable or disable the service declared at `handle`.
+    ///
+    /// A disabled service is hidden from clients but keeps its handles and values, so it can be enabled again
+    /// later. Connected clients are considered change-unaware and are sent a Service Changed indication.
+    pub fn set_service_enabled(&self, handle: u16, enabled: bool) -> Result<(), Error> {
+        self.att_table.set_service_enabled(handle, enabled)?;
+        self.table_changed();
+        Ok(())
+    }
+
+    /// Update the server state after the layout of the attribute table has changed.
+    fn table_changed(&self) {
+        self.cccd_tables.update_handles(&self.att_table);
+
+        #[cfg(feature = "security")]
+        let previous = {
+            let hash = Self::update_database_hash(&self.att_table);
+            let previous = self.database_hash.lock(|h| h.replace(hash));
+            if previous == hash {
+                return;
+            }
+            previous
+        };
+        #[cfg(not(feature = "security"))]
+        let previous = 0;
+
+        self.cccd_tables.set_change_unaware(previous);
+        self.changed.lock(|w| w.borrow_mut().wake());
+    }
+
     pub(crate) fn connect(&self, connection: &Connection<'_, P>) -> Result<(), Error> {
-        self.cccd_tables.connect(&connection.peer_identity())
+        let identity = connection.peer_identity();
+        self.cccd_tables.connect(&identity)?;
+        self.cccd_tables.with_client(&identity, |client, _| {
+            client.handle = Some(connection.handle());
+        });
+        Ok(())
+    }
+
+    /// Write a value to a characteristic, and notify all connected clients subscribed to it.
+    ///
+    /// Returns the connections that could not be notified, along with the error. If the value cannot be written or
+    /// the characteristic does not support notifications, an error is returned instead.
+    pub async fn notify_all<'stack, C, T: AsGatt>(
+        &self,
+        stack: &'stack Stack<'stack, C, P>,
+        characteristic: &Characteristic<T>,
+        value: &T,
+    ) -> Result<Vec<(ConnHandle, Error), CONN_MAX>, Error> {
+        self.broadcast(&stack.host.connections, characteristic, value, false)
+            .await
+    }
+
+    /// Write a value to a characteristic, and indicate all connected clients subscribed to it.
+    ///
+    /// Returns the connections that could not be sent an indication, along with the error. If the value cannot be
+    /// written or the characteristic does not support indications, an error is returned instead.
+    ///
+    /// This function does not wait for the confirmations of the indications. Connections on which the confirmation of
+    /// a previous indication is still outstanding are reported with [`Error::Busy`]. If that confirmation is not received
+    /// within the ATT transaction timeout of 30 seconds, no more indications are sent on the connection and it is
+    /// reported with [`Error::Timeout`].
+    pub async fn indicate_all<'stack, C, T: AsGatt>(
+        &self,
+        stack: &'stack Stack<'stack, C, P>,
+        characteristic: &Characteristic<T>,
+        value: &T,
+    ) -> Result<Vec<(ConnHandle, Error), CONN_MAX>, Error> {
+        self.broadcast(&stack.host.connections, characteristic, value, true)
+            .await
+    }
+
+    async fn broadcast<'stack, T: AsGatt>(
+        &self,
+        connections: &'stack ConnectionManager<'stack, P>,
+        characteristic: &Characteristic<T>,
+        value: &T,
+        indicate: bool,
+    ) -> Result<Vec<(ConnHandle, Error), CONN_MAX>, Error> {
+        let value = value.as_gatt();
+        self.att_table.set_raw(characteristic.handle, value)?;
+        let cccd_handle = characteristic.cccd_handle.ok_or(Error::NotFound)?;
+
+        let subscribers: Vec<ConnHandle, CONN_MAX> = self.cccd_tables.connected_handles(|table| {
+            if indicate {
+                table.should_indicate(cccd_handle)
+            } else {
+                table.should_notify(cccd_handle)
+            }
+        });
+        let mut failures = Vec::new();
+        for handle in subscribers {
+            let Some(connection) = connections.get_connected_handle(handle) else {
+                let _ = failures.push((handle, Error::Disconnected));
+                continue;
+            };
+            let uns = if indicate {
+                AttUns::Indicate {
+                    handle: characteristic.handle,
+                    data: value,
+                }
+            } else {
+                AttUns::Notify {
+                    handle: characteristic.handle,
+                    data: value,
+                }
+            };
+            if indicate {
+                if let Err(e) = connection.start_indication() {
+                    let _ = failures.push((handle, e));
+                    continue;
+                }
+            }
+            match gatt::assemble(&connection, att::AttServer::Unsolicited(uns)) {
+                Ok(pdu) => connection.send(pdu).await,
+                Err(e) => {
+                    if indicate {
+                        connection.end_indication();
+                    }
+                    let _ = failures.push((handle, e));
+                }
+            }
+        }
+        Ok(failures)
     }
 
     pub(crate) fn should_notify(&self, connection: &Connection<'_, P>, cccd_handle: u16) -> bool {
@@ -389,6 +884,162 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
             .should_indicate(&connection.peer_identity(), cccd_handle)
     }
 
+    /// Whether the client enabled Multiple Handle Value Notifications in its Client Supported Features.
+    pub(crate) fn supports_multiple_notifications(&self, connection: &Connection<'_, P>) -> bool {
+        self.cccd_tables
+            .with_client(&connection.peer_identity(), |client, _| {
+                client.supported_features & MULTIPLE_HANDLE_VALUE_NOTIFICATIONS != 0
+            })
+            .unwrap_or(false)
+    }
+
+    /// Whether the client enabled Enhanced ATT bearers in its Client Supported Features.
+    pub(crate) fn supports_eatt(&self, connection: &Connection<'_, P>) -> bool {
+        self.cccd_tables
+            .with_client(&connection.peer_identity(), |client, _| {
+                client.supported_features & EATT_SUPPORTED != 0
+            })
+            .unwrap_or(false)
+    }
+
+    /// Returns the Service Changed value handle if a change-unaware client should be sent an indication,
+    /// which is then considered sent.
+    pub(crate) fn take_service_changed(&self, connection: &Connection<'_, P>) -> Option<u16> {
+        self.service_changed_due(connection, true)
+    }
+
+    fn service_changed_due(&self, connection: &Connection<'_, P>, take: bool) -> Option<u16> {
+        let (handle, cccd_handle) = self.service_changed?;
+        self.cccd_tables
+            .with_client(&connection.peer_identity(), |client, table| {
+                if client.stale_database_hash.is_some()
+                    && !client.service_changed_sent
+                    && table.should_indicate(cccd_handle)
+                {
+                    client.service_changed_sent |= take;
+                    Some(handle)
+                } else {
+                    None
+                }
+            })
+            .flatten()
+    }
+
+    /// Record whether the Service Changed indication is the indication awaiting confirmation from the client.
+    pub(crate) fn set_service_changed_pending(&self, connection: &Connection<'_, P>, pending: bool) {
+        self.cccd_tables.with_client(&connection.peer_identity(), |client, _| {
+            client.service_changed_pending = pending && client.service_changed_sent;
+        });
+    }
+
+    /// The Service Changed indication could not be sent, send it again.
+    pub(crate) fn retry_service_changed(&self, connection: &Connection<'_, P>) {
+        self.cccd_tables.with_client(&connection.peer_identity(), |client, _| {
+            client.service_changed_sent = false;
+            client.service_changed_pending = false;
+        });
+    }
+
+    /// Poll for a Service Changed indication to send to the client, see [`Self::take_service_changed`].
+    ///
+    /// The indication is only considered sent once the unenhanced ATT bearer has been claimed for it.
+    pub(crate) fn poll_service_changed(&self, connection: &Connection<'_, P>, cx: &mut Context<'_>) -> Poll<u16> {
+        self.changed.lock(|w| w.borrow_mut().register(cx.waker()));
+        if self.service_changed_due(connection, false).is_none() {
+            return Poll::Pending;
+        }
+        // Wait for the confirmation of the outstanding indication. The bearer stays claimed for good once an
+        // indication timed out.
+        if !matches!(connection.poll_start_indication(cx), Poll::Ready(Ok(()))) {
+            return Poll::Pending;
+        }
+        match self.take_service_changed(connection) {
+            Some(handle) => {
+                self.set_service_changed_pending(connection, true);
+                Poll::Ready(handle)
+            }
+            None => {
+                connection.end_indication();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Apply the rules for change-unaware clients ([Vol 3] Part G, Section 2.5.2.1).
+    ///
+    /// Returns the response to send instead of processing the PDU, if any.
+    fn check_change_aware(
+        &self,
+        connection: &Connection<'_, P>,
+        packet: &AttClient,
+        buf: &mut [u8],
+    ) -> Option<Result<Option<usize>, codec::Error>> {
+        let database_hash: Uuid = DATABASE_HASH.into();
+        let opcode = self
+            .cccd_tables
+            .with_client(&connection.peer_identity(), |client, _| {
+                client.stale_database_hash?;
+                let robust_caching = client.supported_features & ROBUST_CACHING != 0;
+                match packet {
+                    // Only the confirmation of the Service Changed indication makes the client change-aware.
+                    AttClient::Confirmation(_) => {
+                        if client.service_changed_pending {
+                            client.set_change_aware();
+                        }
+                        None
+                    }
+                    _ if !robust_caching => None,
+                    // Reading the database hash is how a client finds out whether its cache is still valid.
+                    AttClient::Request(AttReq::ReadByType { attribute_type, .. })
+                        if *attribute_type == database_hash =>
+                    {
+                        client.set_change_aware();
+                        None
+                    }
+                    AttClient::Request(_) if client.out_of_sync_sent => {
+                        client.set_change_aware();
+                        None
+                    }
+                    AttClient::Request(req) => {
+                        client.out_of_sync_sent = true;
+                        Some(Some(req.opcode()))
+                    }
+                    // Commands from change-unaware clients are ignored.
+                    AttClient::Command(_) => Some(None),
+                }
+            })
+            .flatten()?;
+
+        Some(match opcode {
+            Some(opcode) => {
+                Self::error_response(WriteCursor::new(buf), opcode, 0, AttErrorCode::DATABASE_OUT_OF_SYNC).map(Some)
+            }
+            None => Ok(None),
+        })
+    }
+
+    /// Check the security requirements of an attribute against the link to the client.
+    fn check_security(&self, connection: &Connection<'_, P>, security: &AttributeSecurity) -> Result<(), AttErrorCode> {
+        if *security == AttributeSecurity::default() {
+            return Ok(());
+        }
+        let level = connection.security_level().unwrap_or(SecurityLevel::NoEncryption);
+        self.check_security_level(connection, level, security)
+    }
+
+    fn check_security_level(
+        &self,
+        connection: &Connection<'_, P>,
+        level: SecurityLevel,
+        security: &AttributeSecurity,
+    ) -> Result<(), AttErrorCode> {
+        let authorized = self
+            .cccd_tables
+            .with_client(&connection.peer_identity(), |client, _| client.authorized)
+            .unwrap_or(false);
+        security.check(level, authorized)
+    }
+
     fn read_attribute_data(
         &self,
         connection: &Connection<'_, P>,
@@ -396,6 +1047,7 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         att: &mut Attribute<'values>,
         data: &mut [u8],
     ) -> Result<usize, AttErrorCode> {
+        self.check_security(connection, &att.permissions.read)?;
         if let AttributeData::Cccd { .. } = att.data {
             // CCCD values for each connected client are held in the CCCD tables:
             // the value is written back into att.data so att.read() has the final
@@ -403,6 +1055,14 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
             if let Some(value) = self.cccd_tables.get_value(&connection.peer_identity(), att.handle) {
                 let _ = att.write(0, value.as_slice());
             }
+        } else if att.uuid == CLIENT_SUPPORTED_FEATURES.into() {
+            // Likewise, the supported features of each client are held in the CCCD tables.
+            if let Some(features) = self
+                .cccd_tables
+                .with_client(&connection.peer_identity(), |client, _| client.supported_features)
+            {
+                let _ = att.write(0, &[features]);
+            }
         }
         att.read(offset, data)
     }
@@ -414,6 +1074,36 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         att: &mut Attribute<'values>,
         data: &[u8],
     ) -> Result<(), AttErrorCode> {
+        self.check_security(connection, &att.permissions.write)?;
+        self.write_attribute_value(connection, offset, att, data)
+    }
+
+    fn write_attribute_value(
+        &self,
+        connection: &Connection<'_, P>,
+        offset: usize,
+        att: &mut Attribute<'values>,
+        data: &[u8],
+    ) -> Result<(), AttErrorCode> {
+        if att.uuid == CLIENT_SUPPORTED_FEATURES.into() {
+            if offset > 0 {
+                return Err(AttErrorCode::INVALID_OFFSET);
+            }
+            let [features] = data else {
+                return Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH);
+            };
+            self.cccd_tables
+                .with_client(&connection.peer_identity(), |client, _| {
+                    // A client may not disable features it has enabled ([Vol 3] Part G, Section 7.2).
+                    if client.supported_features & !features != 0 {
+                        return Err(AttErrorCode::VALUE_NOT_ALLOWED);
+                    }
+                    client.supported_features = *features;
+                    Ok(())
+                })
+                .unwrap_or(Err(AttErrorCode::UNLIKELY_ERROR))?;
+        }
+
         let err = att.write(offset, data);
         if err.is_ok() {
             if let AttributeData::Cccd {
@@ -636,19 +1326,62 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         Ok(0)
     }
 
-    fn handle_write_req(
+    #[cfg(feature = "security")]
+    fn handle_signed_write_cmd(
         &self,
         connection: &Connection<'_, P>,
         buf: &mut [u8],
         handle: u16,
         data: &[u8],
+        signature: &[u8; 12],
     ) -> Result<usize, codec::Error> {
-        let err = self.att_table.iterate(|mut it| {
-            let mut err = Err(AttErrorCode::ATTRIBUTE_NOT_FOUND);
+        // Signed writes with an invalid signature are ignored, otherwise the data has the security level of the bond
+        // the signing key was distributed with ([Vol 3] Part C, Section 10.4.2).
+        let signed_data = [&[att::ATT_SIGNED_WRITE_CMD][..], &handle.to_le_bytes(), data];
+        let Ok(signing_level) = connection.verify_signature(&signed_data, signature) else {
+            return Ok(0);
+        };
+        let level = connection
+            .security_level()
+            .unwrap_or(SecurityLevel::NoEncryption)
+            .max(signing_level);
+        self.att_table.iterate(|mut it| {
             while let Some(att) = it.next() {
                 if att.handle == handle {
-                    err = self.write_attribute_data(connection, 0, att, data);
-                    break;
+                    let signed = match att.data {
+                        AttributeData::Data { props, .. } | AttributeData::Deferred { props } => {
+                            props.any(&[crate::attribute::CharacteristicProp::AuthenticatedWrite])
+                        }
+                        _ => false,
+                    };
+                    // Signed write commands can't respond with an error either.
+                    if signed
+                        && self
+                            .check_security_level(connection, level, &att.permissions.write)
+                            .is_ok()
+                    {
+                        let _ = self.write_attribute_value(connection, 0, att, data);
+                    }
+                    break;
+                }
+            }
+        });
+        Ok(0)
+    }
+
+    fn handle_write_req(
+        &self,
+        connection: &Connection<'_, P>,
+        buf: &mut [u8],
+        handle: u16,
+        data: &[u8],
+    ) -> Result<usize, codec::Error> {
+        let err = self.att_table.iterate(|mut it| {
+            let mut err = Err(AttErrorCode::ATTRIBUTE_NOT_FOUND);
+            while let Some(att) = it.next() {
+                if att.handle == handle {
+                    err = self.write_attribute_data(connection, 0, att, data);
+                    break;
                 }
             }
             err
@@ -767,28 +1500,113 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         w.write(handle)?;
         w.write(offset)?;
 
+        // Permissions are checked when the value is queued, the value itself is only
+        // validated and written once the client executes the queue.
         let err = self.att_table.iterate(|mut it| {
-            let mut err = Err(AttErrorCode::ATTRIBUTE_NOT_FOUND);
             while let Some(att) = it.next() {
                 if att.handle == handle {
-                    err = self.write_attribute_data(connection, offset as usize, att, value);
-                    w.append(value)?;
-                    break;
+                    if !att.data.writable() {
+                        return Err(AttErrorCode::WRITE_NOT_PERMITTED);
+                    }
+                    self.check_security(connection, &att.permissions.write)?;
+                    // The application got the value of deferred attributes in the write event.
+                    if let AttributeData::Deferred { .. } = att.data {
+                        return Ok(());
+                    }
+                    return self
+                        .prepare_queues
+                        .with_queue(connection.handle(), |queue| queue.push(handle, offset, value));
                 }
             }
-            err
+            Err(AttErrorCode::INVALID_HANDLE)
         });
 
         match err {
-            Ok(()) => Ok(w.len()),
+            Ok(()) => {
+                w.append(value)?;
+                Ok(w.len())
+            }
             Err(e) => Ok(Self::error_response(w, att::ATT_PREPARE_WRITE_REQ, handle, e)?),
         }
     }
 
-    fn handle_execute_write(&self, buf: &mut [u8], _flags: u8) -> Result<usize, codec::Error> {
+    /// Check that every queued value can be written, then write all of them.
+    ///
+    /// Nothing is written if any of the queued values is rejected, the handle of the
+    /// offending attribute is returned together with the error code in that case.
+    fn execute_prepared_writes(
+        &self,
+        connection: &Connection<'_, P>,
+        queue: &PrepareWriteQueue,
+    ) -> Result<(), (u16, AttErrorCode)> {
+        for (i, prepared) in queue.iter().enumerate() {
+            // The value length after all earlier fragments for the same attribute have been applied.
+            let mut current = None;
+            for earlier in queue.iter().take(i) {
+                if earlier.handle == prepared.handle {
+                    current = Some(earlier.offset as usize + earlier.value.len());
+                }
+            }
+            self.att_table.iterate(|mut it| {
+                while let Some(att) = it.next() {
+                    if att.handle == prepared.handle {
+                        let current = current.unwrap_or(att.data.len());
+                        let offset = prepared.offset as usize;
+                        if offset > current {
+                            return Err((prepared.handle, AttErrorCode::INVALID_OFFSET));
+                        }
+                        if offset + prepared.value.len() > att.data.capacity() {
+                            return Err((prepared.handle, AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH));
+                        }
+                        return Ok(());
+                    }
+                }
+                Err((prepared.handle, AttErrorCode::INVALID_HANDLE))
+            })?;
+        }
+
+        for prepared in queue.iter() {
+            self.att_table.iterate(|mut it| {
+                while let Some(att) = it.next() {
+                    if att.handle == prepared.handle {
+                        return self
+                            .write_attribute_data(connection, prepared.offset as usize, att, prepared.value)
+                            .map_err(|e| (prepared.handle, e));
+                    }
+                }
+                Err((prepared.handle, AttErrorCode::INVALID_HANDLE))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn handle_execute_write(
+        &self,
+        connection: &Connection<'_, P>,
+        buf: &mut [u8],
+        flags: u8,
+    ) -> Result<usize, codec::Error> {
+        let err = self.prepare_queues.with_queue(connection.handle(), |queue| {
+            let result = match flags {
+                // Cancel all prepared writes
+                0x00 => Ok(()),
+                // Immediately write all pending prepared values
+                0x01 => self.execute_prepared_writes(connection, queue),
+                _ => Err((0, AttErrorCode::INVALID_PDU)),
+            };
+            queue.clear();
+            Ok(result)
+        });
+
         let mut w = WriteCursor::new(buf);
-        w.write(att::ATT_EXECUTE_WRITE_RSP)?;
-        Ok(w.len())
+        match err {
+            Ok(Ok(())) => {
+                w.write(att::ATT_EXECUTE_WRITE_RSP)?;
+                Ok(w.len())
+            }
+            Ok(Err((handle, e))) => Ok(Self::error_response(w, att::ATT_EXECUTE_WRITE_REQ, handle, e)?),
+            Err(e) => Ok(Self::error_response(w, att::ATT_EXECUTE_WRITE_REQ, 0, e)?),
+        }
     }
 
     fn handle_read_blob(
@@ -821,14 +1639,67 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         }
     }
 
-    fn handle_read_multiple(&self, buf: &mut [u8], handles: &[u8]) -> Result<usize, codec::Error> {
-        let w = WriteCursor::new(buf);
-        Self::error_response(
-            w,
-            att::ATT_READ_MULTIPLE_REQ,
-            u16::from_le_bytes([handles[0], handles[1]]),
-            AttErrorCode::ATTRIBUTE_NOT_FOUND,
-        )
+    /// Read the values of a set of handles, either concatenated (Read Multiple) or each prefixed
+    /// with its length (Read Multiple Variable Length).
+    fn handle_read_multiple(
+        &self,
+        connection: &Connection<'_, P>,
+        buf: &mut [u8],
+        handles: &[u8],
+        variable: bool,
+    ) -> Result<usize, codec::Error> {
+        let (request, response) = if variable {
+            (att::ATT_READ_MULTIPLE_VARIABLE_REQ, att::ATT_READ_MULTIPLE_VARIABLE_RSP)
+        } else {
+            (att::ATT_READ_MULTIPLE_REQ, att::ATT_READ_MULTIPLE_RSP)
+        };
+        let mut w = WriteCursor::new(buf);
+        if handles.len() < 4 || !handles.len().is_multiple_of(2) {
+            return Self::error_response(w, request, 0, AttErrorCode::INVALID_PDU);
+        }
+
+        w.write(response)?;
+        let mut err = Ok(());
+        for handle in handles.chunks_exact(2) {
+            let handle = u16::from_le_bytes([handle[0], handle[1]]);
+            // Whether the variable length response is full, the remaining handles are then left out
+            let res = self.att_table.iterate(|mut it| {
+                while let Some(att) = it.next() {
+                    if att.handle == handle {
+                        if !variable {
+                            let len = self.read_attribute_data(connection, 0, att, w.write_buf())?;
+                            w.commit(len)?;
+                            return Ok(false);
+                        }
+                        // Each value is prefixed with its full length, the tuple list as a whole is truncated
+                        // to the ATT MTU when the response is assembled, and here to the response buffer
+                        // ([Vol 3] Part F, Section 3.4.4.12).
+                        let Some(value) = w.write_buf().get_mut(2..) else {
+                            return Ok(true);
+                        };
+                        let len = self.read_attribute_data(connection, 0, att, value)?;
+                        let full_len = att.data.len().max(len);
+                        w.write(full_len as u16)?;
+                        w.commit(len)?;
+                        return Ok(len < full_len || w.available() == 0);
+                    }
+                }
+                Err(AttErrorCode::INVALID_HANDLE)
+            });
+            match res {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => {
+                    err = Err((handle, e));
+                    break;
+                }
+            }
+        }
+
+        match err {
+            Ok(()) => Ok(w.len()),
+            Err((handle, e)) => Self::error_response(w, request, handle, e),
+        }
     }
 
     /// Process an event and produce a response if necessary
@@ -838,6 +1709,10 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         packet: &AttClient,
         rx: &mut [u8],
     ) -> Result<Option<usize>, codec::Error> {
+        if let Some(res) = self.check_change_aware(connection, packet, rx) {
+            return res;
+        }
+
         let len = match packet {
             AttClient::Request(AttReq::ReadByType {
                 start,
@@ -860,6 +1735,18 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
                 0
             }
 
+            #[cfg(feature = "security")]
+            AttClient::Command(AttCmd::SignedWrite {
+                handle,
+                data,
+                signature,
+            }) => {
+                self.handle_signed_write_cmd(connection, rx, *handle, data, signature)?;
+                0
+            }
+            #[cfg(not(feature = "security"))]
+            AttClient::Command(AttCmd::SignedWrite { .. }) => 0,
+
             AttClient::Request(AttReq::Write { handle, data }) => {
                 self.handle_write_req(connection, rx, *handle, data)?
             }
@@ -877,13 +1764,19 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
                 self.handle_prepare_write(connection, rx, *handle, *offset, value)?
             }
 
-            AttClient::Request(AttReq::ExecuteWrite { flags }) => self.handle_execute_write(rx, *flags)?,
+            AttClient::Request(AttReq::ExecuteWrite { flags }) => self.handle_execute_write(connection, rx, *flags)?,
 
             AttClient::Request(AttReq::ReadBlob { handle, offset }) => {
                 self.handle_read_blob(connection, rx, *handle, *offset)?
             }
 
-            AttClient::Request(AttReq::ReadMultiple { handles }) => self.handle_read_multiple(rx, handles)?,
+            AttClient::Request(AttReq::ReadMultiple { handles }) => {
+                self.handle_read_multiple(connection, rx, handles, false)?
+            }
+
+            AttClient::Request(AttReq::ReadMultipleVariable { handles }) => {
+                self.handle_read_multiple(connection, rx, handles, true)?
+            }
 
             AttClient::Confirmation(_) => 0,
         };
@@ -894,6 +1787,57 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
         }
     }
 
+    /// Process a Read or Read Blob request of a deferred attribute, answering it with a value provided by
+    /// the application.
+    ///
+    /// `value` holds the attribute value starting at the requested offset, it is truncated to fit the response.
+    /// Any other request is processed as by [`Self::process`].
+    pub fn respond(
+        &self,
+        connection: &Connection<'_, P>,
+        packet: &AttClient,
+        value: &[u8],
+        rx: &mut [u8],
+    ) -> Result<Option<usize>, codec::Error> {
+        let (handle, request, response) = match packet {
+            AttClient::Request(AttReq::Read { handle }) => (*handle, att::ATT_READ_REQ, att::ATT_READ_RSP),
+            AttClient::Request(AttReq::ReadBlob { handle, .. }) => {
+                (*handle, att::ATT_READ_BLOB_REQ, att::ATT_READ_BLOB_RSP)
+            }
+            _ => return self.process(connection, packet, rx),
+        };
+        if let Some(res) = self.check_change_aware(connection, packet, rx) {
+            return res;
+        }
+
+        let mut w = WriteCursor::new(rx);
+        w.write(response)?;
+        let err = self.att_table.iterate(|mut it| {
+            while let Some(att) = it.next() {
+                if att.handle == handle {
+                    let AttributeData::Deferred { .. } = att.data else {
+                        return Err(AttErrorCode::UNLIKELY_ERROR);
+                    };
+                    if !att.data.readable() {
+                        return Err(AttErrorCode::READ_NOT_PERMITTED);
+                    }
+                    self.check_security(connection, &att.permissions.read)?;
+                    return Ok(());
+                }
+            }
+            Err(AttErrorCode::ATTRIBUTE_NOT_FOUND)
+        });
+
+        match err {
+            Ok(()) => {
+                let len = value.len().min(w.available());
+                w.append(&value[..len])?;
+                Ok(Some(w.len()))
+            }
+            Err(e) => Ok(Some(Self::error_response(w, request, handle, e)?)),
+        }
+    }
+
     /// Get a reference to the attribute table
     pub fn table(&self) -> &AttributeTable<'values, M, ATT_MAX> {
         &self.att_table
@@ -908,17 +1852,59 @@ impl<'values, M: RawMutex, P: PacketPool, const ATT_MAX: usize, const CCCD_MAX:
     pub fn set_cccd_table(&self, connection: &Connection<'_, P>, table: CccdTable<CCCD_MAX>) {
         self.cccd_tables.set_cccd_table(&connection.peer_identity(), table);
     }
+
+    /// Set whether a client is authorized to access attributes that require authorization.
+    ///
+    /// Authorization is revoked when the client disconnects.
+    pub fn set_authorized(&self, connection: &Connection<'_, P>, authorized: bool) {
+        self.cccd_tables.with_client(&connection.peer_identity(), |client, _| {
+            client.authorized = authorized;
+        });
+    }
+
+    /// Get the database hash of the attribute table
+    #[cfg(feature = "security")]
+    pub fn database_hash(&self) -> u128 {
+        self.database_hash.lock(|h| h.get())
+    }
+
+    /// Get the GATT caching state for a connection
+    #[cfg(feature = "security")]
+    pub fn get_client_cache_state(&self, connection: &Connection<'_, P>) -> Option<ClientCacheState> {
+        self.cccd_tables
+            .with_client(&connection.peer_identity(), |client, _| ClientCacheState {
+                database_hash: client.stale_database_hash.unwrap_or(self.database_hash()),
+                supported_features: client.supported_features,
+            })
+    }
+
+    /// Set the GATT caching state for a connection
+    ///
+    /// If the database hash differs from the current one, the client is considered change-unaware.
+    #[cfg(feature = "security")]
+    pub fn set_client_cache_state(&self, connection: &Connection<'_, P>, state: ClientCacheState) {
+        self.cccd_tables.with_client(&connection.peer_identity(), |client, _| {
+            client.supported_features = state.supported_features;
+            client.set_change_aware();
+            if state.database_hash != self.database_hash() {
+                client.stale_database_hash = Some(state.database_hash);
+            }
+        });
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use core::task::Poll;
+    extern crate std;
 
-    use bt_hci::param::{AddrKind, BdAddr, ConnHandle, LeConnRole};
+    use core::task::{Context, Poll, Waker};
+
+    use bt_hci::param::{AddrKind, BdAddr, ConnHandle, LeConnRole, Status};
+    use embassy_futures::{block_on, poll_once};
     use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 
     use super::*;
-    use crate::connection_manager::tests::{setup, ADDR_1};
+    use crate::connection_manager::tests::{setup, ADDR_1, ADDR_2};
     use crate::prelude::*;
 
     #[test]
@@ -959,7 +1945,7 @@ mod tests {
             debug!("Testing with interior handle count of {}", interior_handle_count);
 
             // Create a new table.
-            let mut table: AttributeTable<'_, NoopRawMutex, { MAX_ATTRIBUTES }> = AttributeTable::new();
+            let table: AttributeTable<'_, NoopRawMutex, { MAX_ATTRIBUTES }> = AttributeTable::new();
 
             // Add a first service, contents don't really matter, but the issue doesn't manifest without this.
             {
@@ -1049,4 +2035,1178 @@ mod tests {
             };
         }
     }
+
+    fn accept_connection() -> Connection<'static, DefaultPacketPool> {
+        let mgr = setup();
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+        unwrap!(mgr.connect(
+            ConnHandle::new(0),
+            AddrKind::RANDOM,
+            BdAddr::new(ADDR_1),
+            LeConnRole::Peripheral
+        ));
+        let Poll::Ready(connection) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        connection
+    }
+
+    fn error_code(rsp: &[u8]) -> Option<AttErrorCode> {
+        match att::Att::decode(rsp) {
+            Ok(att::Att::Server(att::AttServer::Response(att::AttRsp::Error { code, .. }))) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_attribute_server_prepare_write_queue() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 8;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let mut store = [0u8; 8];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let characteristic = {
+            let mut svc = table.add_service(Service {
+                uuid: Uuid::new_long([1; 16]),
+            });
+            svc.add_characteristic(
+                Uuid::new_long([2; 16]),
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                heapless::Vec::<u8, 8>::from_slice(&[1, 2, 3, 4]).unwrap(),
+                &mut store,
+            )
+            .build()
+        };
+        let handle = characteristic.handle;
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+
+        let connection = accept_connection();
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttReq<'_>| -> std::vec::Vec<u8> {
+            let len = server
+                .process(&connection, &AttClient::Request(req), &mut buffer)
+                .unwrap()
+                .unwrap();
+            buffer[..len].to_vec()
+        };
+        let value = |server: &AttributeServer<
+            '_,
+            NoopRawMutex,
+            DefaultPacketPool,
+            MAX_ATTRIBUTES,
+            CCCD_MAX,
+            CONNECTIONS_MAX,
+        >| { server.table().get(&characteristic).unwrap() };
+
+        // Fragments are echoed but not written until executed
+        let rsp = request(AttReq::PrepareWrite {
+            handle,
+            offset: 0,
+            value: &[5, 6, 7],
+        });
+        assert!(matches!(
+            att::Att::decode(&rsp),
+            Ok(att::Att::Server(att::AttServer::Response(att::AttRsp::PrepareWrite {
+                handle: h,
+                offset: 0,
+                value: &[5, 6, 7],
+            }))) if h == handle
+        ));
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 3,
+            value: &[8, 9],
+        });
+        assert_eq!(&value(&server)[..], &[1, 2, 3, 4]);
+
+        // Cancelling drops the queued fragments
+        let rsp = request(AttReq::ExecuteWrite { flags: 0x00 });
+        assert_eq!(rsp, [att::ATT_EXECUTE_WRITE_RSP]);
+        assert_eq!(&value(&server)[..], &[1, 2, 3, 4]);
+
+        // Executing applies all fragments
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 0,
+            value: &[5, 6, 7],
+        });
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 3,
+            value: &[8, 9],
+        });
+        let rsp = request(AttReq::ExecuteWrite { flags: 0x01 });
+        assert_eq!(rsp, [att::ATT_EXECUTE_WRITE_RSP]);
+        assert_eq!(&value(&server)[..], &[5, 6, 7, 8, 9]);
+
+        // An offset past the end of the value rejects the whole queue
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 0,
+            value: &[1],
+        });
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 7,
+            value: &[1],
+        });
+        let rsp = request(AttReq::ExecuteWrite { flags: 0x01 });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INVALID_OFFSET));
+        assert_eq!(&value(&server)[..], &[5, 6, 7, 8, 9]);
+
+        // A value longer than the attribute storage is rejected
+        request(AttReq::PrepareWrite {
+            handle,
+            offset: 4,
+            value: &[1, 2, 3, 4, 5],
+        });
+        let rsp = request(AttReq::ExecuteWrite { flags: 0x01 });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH));
+        assert_eq!(&value(&server)[..], &[5, 6, 7, 8, 9]);
+
+        // Unknown handles are rejected when queued
+        let rsp = request(AttReq::PrepareWrite {
+            handle: 0x100,
+            offset: 0,
+            value: &[1],
+        });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INVALID_HANDLE));
+
+        // Fill the queue
+        let chunk = [0u8; 32];
+        let mut full = false;
+        for _ in 0..config::GATT_SERVER_PREPARE_WRITE_QUEUE_SIZE {
+            let rsp = request(AttReq::PrepareWrite {
+                handle,
+                offset: 0,
+                value: &chunk,
+            });
+            if let Some(code) = error_code(&rsp) {
+                assert_eq!(code, AttErrorCode::PREPARE_QUEUE_FULL);
+                full = true;
+                break;
+            }
+        }
+        assert!(full);
+        request(AttReq::ExecuteWrite { flags: 0x00 });
+    }
+
+    #[test]
+    fn test_attribute_server_read_multiple() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let mut store = [0u8; 4];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let (first, second, hidden) = {
+            let mut svc = table.add_service(Service {
+                uuid: Uuid::new_long([1; 16]),
+            });
+            let first = svc
+                .add_characteristic_ro::<[u8; 2], _>(Uuid::new_long([2; 16]), &[1, 2])
+                .build();
+            let second = svc
+                .add_characteristic_ro::<[u8; 3], _>(Uuid::new_long([3; 16]), &[3, 4, 5])
+                .build();
+            let hidden = svc
+                .add_characteristic(
+                    Uuid::new_long([4; 16]),
+                    &[CharacteristicProp::Write],
+                    [0u8; 4],
+                    &mut store,
+                )
+                .build();
+            (first, second, hidden)
+        };
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+
+        let mut handles = [0u8; 4];
+        handles[..2].copy_from_slice(&first.handle.to_le_bytes());
+        handles[2..].copy_from_slice(&second.handle.to_le_bytes());
+
+        let mut buffer = [0u8; 64];
+        let len = server
+            .process(
+                &connection,
+                &AttClient::Request(AttReq::ReadMultiple { handles: &handles }),
+                &mut buffer,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buffer[..len], &[att::ATT_READ_MULTIPLE_RSP, 1, 2, 3, 4, 5]);
+
+        let len = server
+            .process(
+                &connection,
+                &AttClient::Request(AttReq::ReadMultipleVariable { handles: &handles }),
+                &mut buffer,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            &buffer[..len],
+            &[att::ATT_READ_MULTIPLE_VARIABLE_RSP, 2, 0, 1, 2, 3, 0, 3, 4, 5]
+        );
+        let Ok(att::Att::Server(att::AttServer::Response(att::AttRsp::ReadMultipleVariable { mut it }))) =
+            att::Att::decode(&buffer[..len])
+        else {
+            panic!("unexpected response");
+        };
+        assert_eq!(it.next().unwrap().unwrap(), (2, &[1, 2][..]));
+        assert_eq!(it.next().unwrap().unwrap(), (3, &[3, 4, 5][..]));
+        assert!(it.next().is_none());
+
+        // Values that don't fit are truncated rather than failing the request
+        let mut short = [0u8; 8];
+        let len = server
+            .process(
+                &connection,
+                &AttClient::Request(AttReq::ReadMultipleVariable { handles: &handles }),
+                &mut short,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            &short[..len],
+            &[att::ATT_READ_MULTIPLE_VARIABLE_RSP, 2, 0, 1, 2, 3, 0, 3]
+        );
+
+        // Permissions are checked for every handle
+        handles[2..].copy_from_slice(&hidden.handle.to_le_bytes());
+        let len = server
+            .process(
+                &connection,
+                &AttClient::Request(AttReq::ReadMultiple { handles: &handles }),
+                &mut buffer,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(error_code(&buffer[..len]), Some(AttErrorCode::READ_NOT_PERMITTED));
+        assert_eq!(u16::from_le_bytes([buffer[2], buffer[3]]), hidden.handle);
+
+        // At least two handles must be requested
+        let len = server
+            .process(
+                &connection,
+                &AttClient::Request(AttReq::ReadMultiple { handles: &handles[..2] }),
+                &mut buffer,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(error_code(&buffer[..len]), Some(AttErrorCode::INVALID_PDU));
+    }
+
+    #[test]
+    fn test_attribute_server_deferred_value() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 8;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let characteristic = {
+            let mut svc = table.add_service(Service {
+                uuid: Uuid::new_long([1; 16]),
+            });
+            svc.add_characteristic_deferred::<[u8; 64], _>(
+                Uuid::new_long([2; 16]),
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+            )
+            .build()
+        };
+        let handle = characteristic.handle;
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        let mut buffer = [0u8; 64];
+
+        // The value is provided by the application from the requested offset
+        let value: [u8; 64] = core::array::from_fn(|i| i as u8);
+        let read = AttClient::Request(AttReq::ReadBlob { handle, offset: 22 });
+        let len = server
+            .respond(&connection, &read, &value[22..], &mut buffer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer[0], att::ATT_READ_BLOB_RSP);
+        assert_eq!(&buffer[1..len], &value[22..]);
+        let read = AttClient::Request(AttReq::ReadBlob { handle, offset: 64 });
+        let len = server.respond(&connection, &read, &[], &mut buffer).unwrap().unwrap();
+        assert_eq!(&buffer[..len], &[att::ATT_READ_BLOB_RSP]);
+
+        // The table holds no value to fall back on, so other reads aren't supported
+        let mut request = |req: AttReq<'_>| -> std::vec::Vec<u8> {
+            let len = server
+                .process(&connection, &AttClient::Request(req), &mut buffer)
+                .unwrap()
+                .unwrap();
+            buffer[..len].to_vec()
+        };
+        let rsp = request(AttReq::Read { handle });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::REQUEST_NOT_SUPPORTED));
+        let rsp = request(AttReq::ReadByType {
+            start: 1,
+            end: 0xffff,
+            attribute_type: Uuid::new_long([2; 16]),
+        });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::REQUEST_NOT_SUPPORTED));
+        let handles = [handle.to_le_bytes(), (handle - 1).to_le_bytes()].concat();
+        let rsp = request(AttReq::ReadMultiple { handles: &handles });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::REQUEST_NOT_SUPPORTED));
+
+        // Writes, including the parts of long writes, are accepted without being stored
+        let rsp = request(AttReq::Write { handle, data: &[1, 2] });
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        let rsp = request(AttReq::PrepareWrite {
+            handle,
+            offset: 100,
+            value: &[1],
+        });
+        assert_eq!(
+            rsp,
+            [&[att::ATT_PREPARE_WRITE_RSP][..], &handle.to_le_bytes(), &[100, 0, 1]].concat()
+        );
+        let rsp = request(AttReq::ExecuteWrite { flags: 1 });
+        assert_eq!(rsp, [att::ATT_EXECUTE_WRITE_RSP]);
+        assert!(server.table().set(&characteristic, &[0; 64]).is_ok());
+    }
+
+    #[test]
+    fn test_attribute_server_permissions() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 8;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let mut store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let characteristic = {
+            let mut svc = table.add_service(Service {
+                uuid: Uuid::new_long([1; 16]),
+            });
+            let mut builder = svc.add_characteristic(
+                Uuid::new_long([2; 16]),
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                0u8,
+                &mut store,
+            );
+            builder.set_permissions(AttributePermissions {
+                read: AttributeSecurity {
+                    encrypted: true,
+                    ..Default::default()
+                },
+                write: AttributeSecurity {
+                    authorized: true,
+                    ..Default::default()
+                },
+            });
+            builder.build()
+        };
+        let handle = characteristic.handle;
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        server.connect(&connection).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttReq<'_>| -> std::vec::Vec<u8> {
+            let len = server
+                .process(&connection, &AttClient::Request(req), &mut buffer)
+                .unwrap()
+                .unwrap();
+            buffer[..len].to_vec()
+        };
+
+        // Reading requires an encrypted link
+        let rsp = request(AttReq::Read { handle });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INSUFFICIENT_ENCRYPTION));
+
+        // Writing requires authorization by the application
+        let rsp = request(AttReq::Write { handle, data: &[1] });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INSUFFICIENT_AUTHORISATION));
+        let rsp = request(AttReq::PrepareWrite {
+            handle,
+            offset: 0,
+            value: &[1],
+        });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INSUFFICIENT_AUTHORISATION));
+
+        server.set_authorized(&connection, true);
+        let rsp = request(AttReq::Write { handle, data: &[1] });
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        assert_eq!(server.table().get(&characteristic).unwrap(), 1);
+
+        // Authorization is revoked on disconnect
+        server.cccd_tables.disconnect(&connection.peer_identity());
+        server.connect(&connection).unwrap();
+        let rsp = request(AttReq::Write { handle, data: &[2] });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INSUFFICIENT_AUTHORISATION));
+    }
+
+    #[cfg(feature = "security")]
+    #[test]
+    fn test_attribute_server_gatt_caching() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 2;
+
+        let mut service_changed_store = [0u8; 4];
+        let mut features_store = [0u8; 1];
+        let mut hash_store = [0u8; 16];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let (service_changed, features, hash) = {
+            let mut svc = table.add_service(Service::new(service::GATT));
+            let service_changed = svc
+                .add_characteristic(
+                    characteristic::SERVICE_CHANGED,
+                    &[CharacteristicProp::Indicate],
+                    [0u8; 4],
+                    &mut service_changed_store,
+                )
+                .build();
+            let features = svc
+                .add_characteristic(
+                    characteristic::CLIENT_SUPPORTED_FEATURES,
+                    &[CharacteristicProp::Read, CharacteristicProp::Write],
+                    [0u8; 1],
+                    &mut features_store,
+                )
+                .build();
+            let hash = svc
+                .add_characteristic(
+                    characteristic::DATABASE_HASH,
+                    &[CharacteristicProp::Read],
+                    [0u8; 16],
+                    &mut hash_store,
+                )
+                .build();
+            (service_changed, features, hash)
+        };
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        server.connect(&connection).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttClient<'_>| -> std::vec::Vec<u8> {
+            let len = server.process(&connection, &req, &mut buffer).unwrap().unwrap_or(0);
+            buffer[..len].to_vec()
+        };
+
+        // The database hash is exposed, and depends on the layout of the table
+        let current_hash = server.database_hash();
+        let rsp = request(AttClient::Request(AttReq::Read { handle: hash.handle }));
+        assert_eq!(&rsp[1..], &current_hash.to_le_bytes());
+        let other: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        other.add_service(Service::new(service::GATT));
+        assert_ne!(other.hash(), current_hash);
+
+        // Supported features can be enabled, but not disabled again
+        let rsp = request(AttClient::Request(AttReq::Write {
+            handle: features.handle,
+            data: &[ROBUST_CACHING],
+        }));
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        let rsp = request(AttClient::Request(AttReq::Write {
+            handle: features.handle,
+            data: &[0],
+        }));
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::VALUE_NOT_ALLOWED));
+        let rsp = request(AttClient::Request(AttReq::Read {
+            handle: features.handle,
+        }));
+        assert_eq!(rsp, [att::ATT_READ_RSP, ROBUST_CACHING]);
+
+        // Subscribe to Service Changed
+        let rsp = request(AttClient::Request(AttReq::Write {
+            handle: service_changed.cccd_handle.unwrap(),
+            data: &[0x02, 0x00],
+        }));
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+
+        // A client that is up to date is not told about changes
+        let state = server.get_client_cache_state(&connection).unwrap();
+        assert_eq!(
+            state,
+            ClientCacheState {
+                database_hash: current_hash,
+                supported_features: ROBUST_CACHING,
+            }
+        );
+        server.set_client_cache_state(&connection, state);
+        assert_eq!(server.take_service_changed(&connection), None);
+
+        // A client with a stale hash is change-unaware: it is sent one Service Changed indication, and the first
+        // request is rejected until it has been made aware of the change.
+        let stale = ClientCacheState {
+            database_hash: current_hash ^ 1,
+            ..state
+        };
+        server.set_client_cache_state(&connection, stale);
+        assert_eq!(server.get_client_cache_state(&connection), Some(stale));
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        assert_eq!(server.take_service_changed(&connection), None);
+        let rsp = request(AttClient::Request(AttReq::Read {
+            handle: features.handle,
+        }));
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::DATABASE_OUT_OF_SYNC));
+        let rsp = request(AttClient::Request(AttReq::Read {
+            handle: features.handle,
+        }));
+        assert_eq!(rsp, [att::ATT_READ_RSP, ROBUST_CACHING]);
+        assert_eq!(server.get_client_cache_state(&connection), Some(state));
+
+        // Confirming the Service Changed indication also makes the client change-aware, but the confirmation of
+        // another indication does not
+        server.set_client_cache_state(&connection, stale);
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        request(AttClient::Confirmation(att::AttCfm::ConfirmIndication));
+        assert_eq!(server.get_client_cache_state(&connection), Some(stale));
+        server.set_service_changed_pending(&connection, true);
+        request(AttClient::Confirmation(att::AttCfm::ConfirmIndication));
+        assert_eq!(server.get_client_cache_state(&connection), Some(state));
+
+        // The Service Changed indication waits for the confirmation of the outstanding indication.
+        server.set_client_cache_state(&connection, stale);
+        connection.start_indication().unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(server.poll_service_changed(&connection, &mut cx).is_pending());
+        connection.end_indication();
+        assert_eq!(
+            server.poll_service_changed(&connection, &mut cx),
+            Poll::Ready(service_changed.handle)
+        );
+        assert!(matches!(connection.start_indication(), Err(Error::Busy)));
+        assert!(server.poll_service_changed(&connection, &mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_attribute_server_runtime_services() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 4;
+
+        let mut service_changed_store = [0u8; 4];
+        let mut battery_store = [0u8; 1];
+        let mut level_store = [0u8; 1];
+        let mut runtime_store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let service_changed = table
+            .add_service(Service::new(service::GATT))
+            .add_characteristic(
+                characteristic::SERVICE_CHANGED,
+                &[CharacteristicProp::Indicate],
+                [0u8; 4],
+                &mut service_changed_store,
+            )
+            .build();
+        let mut battery_service = table.add_service(Service::new(service::BATTERY));
+        let battery = battery_service
+            .add_characteristic(
+                characteristic::BATTERY_LEVEL,
+                &[CharacteristicProp::Read, CharacteristicProp::Notify],
+                50u8,
+                &mut battery_store,
+            )
+            .build();
+        let battery_service = battery_service.build();
+        let level = table
+            .add_service(Service::new(service::TX_POWER))
+            .add_characteristic(
+                characteristic::TX_POWER_LEVEL,
+                &[CharacteristicProp::Read],
+                4u8,
+                &mut level_store,
+            )
+            .build();
+
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        server.connect(&connection).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttClient<'_>| -> std::vec::Vec<u8> {
+            let len = server.process(&connection, &req, &mut buffer).unwrap().unwrap_or(0);
+            buffer[..len].to_vec()
+        };
+
+        // Subscribe to Service Changed and to battery level notifications
+        let rsp = request(AttClient::Request(AttReq::Write {
+            handle: service_changed.cccd_handle.unwrap(),
+            data: &[0x02, 0x00],
+        }));
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        let rsp = request(AttClient::Request(AttReq::Write {
+            handle: battery.cccd_handle.unwrap(),
+            data: &[0x01, 0x00],
+        }));
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        assert_eq!(server.take_service_changed(&connection), None);
+
+        // A disabled service is hidden, and the client is told about the change
+        server.set_service_enabled(battery_service, false).unwrap();
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        assert_eq!(server.take_service_changed(&connection), None);
+        server.set_service_changed_pending(&connection, true);
+        request(AttClient::Confirmation(att::AttCfm::ConfirmIndication));
+        let rsp = request(AttClient::Request(AttReq::Read { handle: battery.handle }));
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::ATTRIBUTE_NOT_FOUND));
+
+        // Enabling it again restores the service with its handles, values and subscriptions
+        server.set_service_enabled(battery_service, true).unwrap();
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        server.set_service_changed_pending(&connection, true);
+        request(AttClient::Confirmation(att::AttCfm::ConfirmIndication));
+        let rsp = request(AttClient::Request(AttReq::Read { handle: battery.handle }));
+        assert_eq!(rsp, [att::ATT_READ_RSP, 50]);
+        assert!(server.should_notify(&connection, battery.cccd_handle.unwrap()));
+
+        // Services added at runtime do not move the existing handles
+        let runtime = server
+            .add_services(|table| {
+                table
+                    .add_service(Service::new(service::DEVICE_INFORMATION))
+                    .add_characteristic(
+                        characteristic::MODEL_NUMBER_STRING,
+                        &[CharacteristicProp::Read],
+                        7u8,
+                        &mut runtime_store,
+                    )
+                    .build()
+            })
+            .unwrap();
+        assert!(runtime.handle > level.handle);
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        server.set_service_changed_pending(&connection, true);
+        request(AttClient::Confirmation(att::AttCfm::ConfirmIndication));
+        let rsp = request(AttClient::Request(AttReq::Read { handle: level.handle }));
+        assert_eq!(rsp, [att::ATT_READ_RSP, 4]);
+        let rsp = request(AttClient::Request(AttReq::Read { handle: runtime.handle }));
+        assert_eq!(rsp, [att::ATT_READ_RSP, 7]);
+
+        // Removed services are gone for good
+        server.remove_service(battery_service).unwrap();
+        assert_eq!(server.take_service_changed(&connection), Some(service_changed.handle));
+        let rsp = request(AttClient::Request(AttReq::Read { handle: battery.handle }));
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::ATTRIBUTE_NOT_FOUND));
+        assert!(!server.should_notify(&connection, battery.cccd_handle.unwrap()));
+        assert!(matches!(server.remove_service(battery_service), Err(Error::NotFound)));
+        assert!(matches!(
+            server.set_service_enabled(level.handle, false),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_attribute_server_table_exhausted() {
+        let mut stores = [[0u8; 1]; 8];
+        let table: AttributeTable<'_, NoopRawMutex, 8> = AttributeTable::new();
+        let (first, stores) = stores.split_first_mut().unwrap();
+        table.add_service(Service::new(service::BATTERY)).add_characteristic(
+            characteristic::BATTERY_LEVEL,
+            &[CharacteristicProp::Notify],
+            0u8,
+            first,
+        );
+        let server = AttributeServer::<_, DefaultPacketPool, 8, 1, 1>::new(table);
+        let attributes = |server: &AttributeServer<'_, NoopRawMutex, DefaultPacketPool, 8, 1, 1>| {
+            server.table().iterate_all(|mut it| {
+                let mut handles = std::vec::Vec::new();
+                while let Some(att) = it.next() {
+                    handles.push(att.handle);
+                }
+                handles
+            })
+        };
+        let before = attributes(&server);
+        let mut stores = stores.iter_mut();
+
+        // Nothing is added if the CCCDs don't fit in the CCCD table
+        let res = server.add_services(|table| {
+            table
+                .add_service(Service::new(service::BATTERY))
+                .add_characteristic(
+                    characteristic::BATTERY_LEVEL,
+                    &[CharacteristicProp::Notify],
+                    0u8,
+                    stores.next().unwrap(),
+                )
+                .build()
+        });
+        assert!(matches!(res, Err(Error::InsufficientSpace)));
+        assert_eq!(attributes(&server), before);
+
+        // Nor if the attributes don't fit in the attribute table
+        let res = server.add_services(|table| {
+            let mut svc = table.add_service(Service::new(service::BATTERY));
+            for store in stores.by_ref().take(3) {
+                svc.add_characteristic(characteristic::BATTERY_LEVEL, &[CharacteristicProp::Read], 0u8, store);
+            }
+        });
+        assert!(matches!(res, Err(Error::InsufficientSpace)));
+        assert_eq!(attributes(&server), before);
+
+        // The handles of the services that weren't added are used again
+        let service = server
+            .add_services(|table| table.add_service(Service::new(service::BATTERY)).build())
+            .unwrap();
+        assert_eq!(service, 0x10);
+
+        // Nor if they don't fit in the handle space, handles of removed services aren't reused
+        for _ in 0..0xffd {
+            let handle = server
+                .add_services(|table| table.add_service(Service::new(service::BATTERY)).build())
+                .unwrap();
+            server.remove_service(handle).unwrap();
+        }
+        let service = server
+            .add_services(|table| table.add_service(Service::new(service::BATTERY)).build())
+            .unwrap();
+        assert_eq!(service, 0xfff0);
+        let res = server.add_services(|table| table.add_service(Service::new(service::BATTERY)).build());
+        assert!(matches!(res, Err(Error::InsufficientSpace)));
+        assert_eq!(attributes(&server), [1, 2, 3, 4, 0x10, 0xfff0]);
+    }
+
+    #[test]
+    fn test_attribute_server_included_services() {
+        use bt_hci::uuid::declarations::{INCLUDE, PRIMARY_SERVICE, SECONDARY_SERVICE};
+
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let mut level_store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let secondary = {
+            let mut svc = table.add_secondary_service(Service::new(service::BATTERY));
+            svc.add_characteristic(
+                characteristic::BATTERY_LEVEL,
+                &[CharacteristicProp::Read],
+                50u8,
+                &mut level_store,
+            );
+            svc.build()
+        };
+        let (primary, include) = {
+            let mut svc = table.add_service(Service::new(service::HUMAN_INTERFACE_DEVICE));
+            let include = svc.add_included_service(secondary).unwrap();
+            (svc.build(), include)
+        };
+        {
+            // Only services can be included
+            let mut svc = table.add_service(Service::new(service::TX_POWER));
+            assert!(matches!(svc.add_included_service(include), Err(Error::NotFound)));
+        }
+
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        server.connect(&connection).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttReq<'_>| -> std::vec::Vec<u8> {
+            let len = server
+                .process(&connection, &AttClient::Request(req), &mut buffer)
+                .unwrap()
+                .unwrap_or(0);
+            buffer[..len].to_vec()
+        };
+
+        // Primary service discovery skips the secondary service
+        let rsp = request(AttReq::ReadByGroupType {
+            start: 1,
+            end: 0xffff,
+            group_type: PRIMARY_SERVICE.into(),
+        });
+        assert_eq!(rsp[..2], [att::ATT_READ_BY_GROUP_TYPE_RSP, 6]);
+        assert_eq!(u16::from_le_bytes([rsp[2], rsp[3]]), primary);
+        let rsp = request(AttReq::ReadByGroupType {
+            start: 1,
+            end: 0xffff,
+            group_type: SECONDARY_SERVICE.into(),
+        });
+        assert_eq!(rsp[..4], [att::ATT_READ_BY_GROUP_TYPE_RSP, 6, secondary as u8, 0]);
+        let secondary_end = u16::from_le_bytes([rsp[4], rsp[5]]);
+
+        // The include declaration holds the range and the 16-bit UUID of the included service
+        let rsp = request(AttReq::ReadByType {
+            start: primary,
+            end: 0xffff,
+            attribute_type: INCLUDE.into(),
+        });
+        let mut expected = std::vec![att::ATT_READ_BY_TYPE_RSP, 8];
+        expected.extend_from_slice(&include.to_le_bytes());
+        expected.extend_from_slice(&secondary.to_le_bytes());
+        expected.extend_from_slice(&secondary_end.to_le_bytes());
+        expected.extend_from_slice(&service::BATTERY.to_le_bytes());
+        assert_eq!(rsp, expected);
+    }
+
+    #[test]
+    fn test_attribute_server_standard_descriptors() {
+        use bt_hci::uuid::units;
+
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        static FORMAT: PresentationFormat = PresentationFormat::new(PresentationFormat::UINT8, 0, units::PERCENTAGE);
+        let mut level_store = [0u8; 1];
+        let mut label_store = [0u8; 8];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let (level, extended, label, format) = {
+            let mut svc = table.add_service(Service::new(service::BATTERY));
+            let mut builder = svc.add_characteristic(
+                characteristic::BATTERY_LEVEL,
+                &[CharacteristicProp::Read],
+                50u8,
+                &mut level_store,
+            );
+            let extended = builder.add_extended_properties(false, true);
+            let label = builder.add_user_description_writable("Main", &mut label_store);
+            let format = builder.add_presentation_format(&FORMAT);
+            (builder.build(), extended, label, format)
+        };
+
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+        let connection = accept_connection();
+        server.connect(&connection).unwrap();
+
+        let mut buffer = [0u8; 64];
+        let mut request = |req: AttReq<'_>| -> std::vec::Vec<u8> {
+            let len = server
+                .process(&connection, &AttClient::Request(req), &mut buffer)
+                .unwrap()
+                .unwrap_or(0);
+            buffer[..len].to_vec()
+        };
+
+        // The characteristic declaration announces the extended properties
+        let rsp = request(AttReq::Read {
+            handle: level.handle - 1,
+        });
+        assert_eq!(
+            rsp[1],
+            CharacteristicProp::Read as u8 | CharacteristicProp::Extended as u8
+        );
+        let rsp = request(AttReq::Read {
+            handle: extended.handle,
+        });
+        assert_eq!(rsp, [att::ATT_READ_RSP, 0x02, 0x00]);
+
+        // The user description can be rewritten up to the size of its store
+        let rsp = request(AttReq::Read { handle: label.handle });
+        assert_eq!(rsp, [&[att::ATT_READ_RSP][..], b"Main"].concat());
+        let rsp = request(AttReq::Write {
+            handle: label.handle,
+            data: b"Backup",
+        });
+        assert_eq!(rsp, [att::ATT_WRITE_RSP]);
+        let rsp = request(AttReq::Read { handle: label.handle });
+        assert_eq!(rsp, [&[att::ATT_READ_RSP][..], b"Backup"].concat());
+        let rsp = request(AttReq::Write {
+            handle: label.handle,
+            data: b"Secondary",
+        });
+        assert_eq!(error_code(&rsp), Some(AttErrorCode::INVALID_OFFSET));
+
+        let rsp = request(AttReq::Read { handle: format.handle });
+        assert_eq!(rsp, [att::ATT_READ_RSP, 0x04, 0x00, 0xad, 0x27, 0x00, 0x00, 0x00]);
+        assert_eq!(FORMAT.unit(), units::PERCENTAGE);
+    }
+
+    #[test]
+    fn test_attribute_server_broadcast() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 8;
+        const CONNECTIONS_MAX: usize = 3;
+        const CCCD_MAX: usize = 1;
+
+        let mut level_store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let level = table
+            .add_service(Service::new(service::BATTERY))
+            .add_characteristic(
+                characteristic::BATTERY_LEVEL,
+                &[CharacteristicProp::Read, CharacteristicProp::Notify],
+                50u8,
+                &mut level_store,
+            )
+            .build();
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+
+        let mgr = setup();
+        let mut connections = std::vec::Vec::new();
+        for (handle, addr) in [(0, ADDR_1), (1, ADDR_2), (2, [0x01; 6])] {
+            assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+            unwrap!(mgr.connect(
+                ConnHandle::new(handle),
+                AddrKind::RANDOM,
+                BdAddr::new(addr),
+                LeConnRole::Peripheral
+            ));
+            let Poll::Ready(connection) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+                panic!("expected connection to be accepted");
+            };
+            server.connect(&connection).unwrap();
+            connections.push(connection);
+        }
+
+        // Only the first two clients subscribe
+        let mut buffer = [0u8; 64];
+        for connection in &connections[..2] {
+            let req = AttClient::Request(AttReq::Write {
+                handle: level.cccd_handle.unwrap(),
+                data: &[0x01, 0x00],
+            });
+            server.process(connection, &req, &mut buffer).unwrap();
+        }
+
+        let failures = block_on(server.broadcast(mgr, &level, &42, false)).unwrap();
+        assert!(failures.is_empty());
+        assert_eq!(server.table().get(&level).unwrap(), 42);
+        for expected in [ConnHandle::new(0), ConnHandle::new(1)] {
+            let (handle, pdu) = block_on(mgr.outbound());
+            assert_eq!(handle, expected);
+            // L2CAP header, then the notification
+            assert_eq!(
+                &pdu.as_ref()[4..],
+                &[att::ATT_HANDLE_VALUE_NTF, level.handle as u8, 0, 42]
+            );
+        }
+        assert!(poll_once(mgr.outbound()).is_pending());
+
+        // Nobody subscribed to indications
+        let failures = block_on(server.broadcast(mgr, &level, &43, true)).unwrap();
+        assert!(failures.is_empty());
+        assert!(poll_once(mgr.outbound()).is_pending());
+
+        // Clients whose link is gone are reported
+        unwrap!(mgr.disconnected(ConnHandle::new(1), Status::UNSPECIFIED));
+        let failures = block_on(server.broadcast(mgr, &level, &44, false)).unwrap();
+        assert!(matches!(failures[..], [(handle, Error::Disconnected)] if handle == ConnHandle::new(1)));
+    }
+
+    #[test]
+    fn test_attribute_server_multiple_notifications() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 2;
+
+        let mut x_store = [0u8; 2];
+        let mut y_store = [0u8; 2];
+        let mut features_store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let features = table
+            .add_service(Service::new(service::GATT))
+            .add_characteristic(
+                characteristic::CLIENT_SUPPORTED_FEATURES,
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+                [0u8; 1],
+                &mut features_store,
+            )
+            .build();
+        let (x, y) = {
+            let mut svc = table.add_service(Service::new(0x1234u16));
+            let x = svc
+                .add_characteristic(0x2b01u16, &[CharacteristicProp::Notify], 0u16, &mut x_store)
+                .build();
+            let y = svc
+                .add_characteristic(0x2b02u16, &[CharacteristicProp::Notify], 0u16, &mut y_store)
+                .build();
+            (x, y)
+        };
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+
+        let mgr = setup();
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+        unwrap!(mgr.connect(
+            ConnHandle::new(0),
+            AddrKind::RANDOM,
+            BdAddr::new(ADDR_1),
+            LeConnRole::Peripheral
+        ));
+        let Poll::Ready(connection) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        let gatt = GattConnection::try_new(connection.clone(), &server).unwrap();
+
+        let mut buffer = [0u8; 64];
+        for cccd in [x.cccd_handle.unwrap(), y.cccd_handle.unwrap()] {
+            let req = AttClient::Request(AttReq::Write {
+                handle: cccd,
+                data: &[0x01, 0x00],
+            });
+            server.process(&connection, &req, &mut buffer).unwrap();
+        }
+
+        // Without client support, the values are sent as individual notifications
+        let mut batch = gatt.notification_batch().unwrap();
+        batch.add(&x, &0x0102).unwrap();
+        batch.add(&y, &0x0304).unwrap();
+        block_on(batch.send()).unwrap();
+        for (handle, value) in [(x.handle, [0x02, 0x01]), (y.handle, [0x04, 0x03])] {
+            let (_, pdu) = block_on(mgr.outbound());
+            assert_eq!(
+                &pdu.as_ref()[4..],
+                &[att::ATT_HANDLE_VALUE_NTF, handle as u8, 0, value[0], value[1]]
+            );
+        }
+        assert!(poll_once(mgr.outbound()).is_pending());
+
+        // Once enabled by the client, they are sent in a single PDU
+        let req = AttClient::Request(AttReq::Write {
+            handle: features.handle,
+            data: &[MULTIPLE_HANDLE_VALUE_NOTIFICATIONS],
+        });
+        server.process(&connection, &req, &mut buffer).unwrap();
+        let mut batch = gatt.notification_batch().unwrap();
+        batch.add(&x, &0x0506).unwrap();
+        batch.add(&y, &0x0708).unwrap();
+        block_on(batch.send()).unwrap();
+        let (_, pdu) = block_on(mgr.outbound());
+        assert_eq!(
+            pdu.as_ref(),
+            &[
+                13,
+                0,
+                4,
+                0,
+                att::ATT_MULTIPLE_HANDLE_VALUE_NTF,
+                x.handle as u8,
+                0,
+                2,
+                0,
+                0x06,
+                0x05,
+                y.handle as u8,
+                0,
+                2,
+                0,
+                0x08,
+                0x07
+            ]
+        );
+        assert_eq!(server.table().get(&y).unwrap(), 0x0708);
+
+        // Values that do not fit in the ATT MTU of 23 are rejected
+        let mut batch = gatt.notification_batch().unwrap();
+        for _ in 0..3 {
+            batch.add(&x, &0).unwrap();
+        }
+        assert!(matches!(batch.add(&x, &0), Err(Error::InsufficientSpace)));
+    }
+
+    #[cfg(feature = "security")]
+    #[test]
+    fn test_attribute_server_signed_write() {
+        let _ = env_logger::try_init();
+        const MAX_ATTRIBUTES: usize = 16;
+        const CONNECTIONS_MAX: usize = 1;
+        const CCCD_MAX: usize = 1;
+
+        let mut signed_store = [0u8; 2];
+        let mut unsigned_store = [0u8; 2];
+        let table: AttributeTable<'_, NoopRawMutex, MAX_ATTRIBUTES> = AttributeTable::new();
+        let (signed, unsigned) = {
+            let mut svc = table.add_service(Service::new(0x1234u16));
+            let signed = svc
+                .add_characteristic(
+                    0x2b01u16,
+                    &[CharacteristicProp::Write, CharacteristicProp::AuthenticatedWrite],
+                    0u16,
+                    &mut signed_store,
+                )
+                .build();
+            let unsigned = svc
+                .add_characteristic(0x2b02u16, &[CharacteristicProp::Write], 0u16, &mut unsigned_store)
+                .build();
+            (signed, unsigned)
+        };
+        let server = AttributeServer::<_, DefaultPacketPool, MAX_ATTRIBUTES, CCCD_MAX, CONNECTIONS_MAX>::new(table);
+
+        let mgr = setup();
+        // The same key is used to sign and verify, as if the peer were signing with it.
+        let key = SigningKey::new(ConnectionSignatureResolvingKey::new(0x2b7e151628aed2a6abf7158809cf4f3c));
+        let mut bond = BondInformation::new(
+            Identity {
+                bd_addr: BdAddr::new(ADDR_1),
+                irk: None,
+            },
+            LongTermKey::new(0),
+            SecurityLevel::Encrypted,
+            true,
+        );
+        bond.local_csrk = Some(key);
+        bond.peer_csrk = Some(key);
+        unwrap!(mgr.security_manager.add_bond_information(bond));
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+        unwrap!(mgr.connect(
+            ConnHandle::new(0),
+            AddrKind::RANDOM,
+            BdAddr::new(ADDR_1),
+            LeConnRole::Peripheral
+        ));
+        let Poll::Ready(connection) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        let mut buffer = [0u8; 64];
+        let mut write = |handle: u16, value: &[u8], signature: [u8; 12]| {
+            let cmd = AttClient::Command(AttCmd::SignedWrite {
+                handle,
+                data: value,
+                signature,
+            });
+            server.process(&connection, &cmd, &mut buffer).unwrap();
+        };
+        let sign = |handle: u16, value: &[u8], counter: u32| {
+            let data = [&[att::ATT_SIGNED_WRITE_CMD][..], &handle.to_le_bytes(), value];
+            let mut signature = [0; 12];
+            signature[..4].copy_from_slice(&counter.to_le_bytes());
+            signature[4..].copy_from_slice(&key.csrk.sign(&data, counter));
+            signature
+        };
+
+        // A valid signature writes the value, with the security level of the bond
+        let signature = sign(signed.handle, &[0x01, 0x02], 0);
+        write(signed.handle, &[0x01, 0x02], signature);
+        assert_eq!(signed.get(&server).unwrap(), 0x0201);
+
+        // A replayed signature is ignored
+        signed.set(&server, &0).unwrap();
+        write(signed.handle, &[0x01, 0x02], signature);
+        assert_eq!(signed.get(&server).unwrap(), 0);
+
+        // A signature that does not match the data is ignored
+        let mut forged = sign(signed.handle, &[0x05, 0x06], 1);
+        write(signed.handle, &[0x05, 0x07], forged);
+        forged[11] ^= 1;
+        write(signed.handle, &[0x05, 0x06], forged);
+        assert_eq!(signed.get(&server).unwrap(), 0);
+
+        // Characteristics without the authenticated signed writes property are not written
+        write(unsigned.handle, &[0x03, 0x04], sign(unsigned.handle, &[0x03, 0x04], 2));
+        assert_eq!(unsigned.get(&server).unwrap(), 0);
+
+        // The counter can skip values, but its last value can't be recorded
+        write(
+            signed.handle,
+            &[0x07, 0x08],
+            sign(signed.handle, &[0x07, 0x08], u32::MAX),
+        );
+        assert_eq!(signed.get(&server).unwrap(), 0);
+        write(
+            signed.handle,
+            &[0x07, 0x08],
+            sign(signed.handle, &[0x07, 0x08], u32::MAX - 1),
+        );
+        assert_eq!(signed.get(&server).unwrap(), 0x0807);
+    }
 }
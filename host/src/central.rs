@@ -1,5 +1,9 @@
 //! Functionality for the BLE central role.
-use bt_hci::cmd::le::{LeAddDeviceToFilterAcceptList, LeClearFilterAcceptList, LeCreateConn, LeExtCreateConn};
+use bt_hci::cmd::le::{
+    LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+    LeCreateConn, LeExtCreateConn, LeReadResolvingListSize, LeSetAddrResolutionEnable, LeSetPrivacyMode,
+    LeSetRandomAddr, LeSetResolvablePrivateAddrTimeout,
+};
 use bt_hci::controller::{Controller, ControllerCmdAsync, ControllerCmdSync};
 use bt_hci::param::{AddrKind, BdAddr, InitiatingPhy, LeConnRole, PhyParams};
 use embassy_futures::select::{select, Either};
@@ -22,6 +26,13 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
     where
         C: ControllerCmdSync<LeClearFilterAcceptList>
             + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>
             + ControllerCmdAsync<LeCreateConn>,
     {
         if config.scan_config.filter_accept_list.is_empty() {
@@ -35,6 +46,7 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
         host.connect_command_state.request().await;
 
         self.set_accept_filter(config.scan_config.filter_accept_list).await?;
+        host.update_privacy().await?;
 
         host.async_command(LeCreateConn::new(
             bt_hci_duration(config.scan_config.interval),
@@ -42,7 +54,7 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
             true,
             AddrKind::PUBLIC,
             BdAddr::default(),
-            host.address.map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
+            host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
             bt_hci_duration(config.connect_params.min_connection_interval),
             bt_hci_duration(config.connect_params.max_connection_interval),
             config.connect_params.max_latency,
@@ -75,6 +87,13 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
     where
         C: ControllerCmdSync<LeClearFilterAcceptList>
             + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>
             + ControllerCmdAsync<LeExtCreateConn>,
     {
         if config.scan_config.filter_accept_list.is_empty() {
@@ -89,6 +108,7 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
         host.connect_command_state.request().await;
 
         self.set_accept_filter(config.scan_config.filter_accept_list).await?;
+        host.update_privacy().await?;
 
         let initiating = InitiatingPhy {
             scan_interval: bt_hci_duration(config.scan_config.interval),
@@ -104,7 +124,7 @@ impl<'stack, C: Controller, P: PacketPool> Central<'stack, C, P> {
 
         host.async_command(LeExtCreateConn::new(
             true,
-            host.address.map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
+            host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
             AddrKind::PUBLIC,
             BdAddr::default(),
             phy_params,
@@ -4,32 +4,58 @@ use core::task::{Context, Poll};
 
 use bt_hci::controller::{blocking, Controller};
 use bt_hci::param::ConnHandle;
-use bt_hci::FromHciBytes;
+use bt_hci::{FromHciBytes, FromHciBytesError, WriteHci};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::channel::Channel;
 use embassy_sync::waitqueue::WakerRegistration;
+use embassy_time::{with_timeout, Duration, Instant};
+use heapless::Vec;
 
+use crate::connection::Connection;
 use crate::connection_manager::ConnectionManager;
 use crate::cursor::WriteCursor;
 use crate::host::BleHost;
 #[cfg(not(feature = "l2cap-sdu-reassembly-optimization"))]
 use crate::l2cap::sar::PacketReassembly;
-use crate::l2cap::L2capChannel;
+use crate::l2cap::{L2capChannel, L2capChannelMode};
 use crate::pdu::{Pdu, Sdu};
 use crate::prelude::{ConnectionEvent, L2capChannelConfig};
 use crate::types::l2cap::{
-    CommandRejectRes, ConnParamUpdateReq, ConnParamUpdateRes, DisconnectionReq, DisconnectionRes, L2capSignalCode,
-    L2capSignalHeader, LeCreditConnReq, LeCreditConnRes, LeCreditConnResultCode, LeCreditFlowInd,
+    CommandRejectReason, CommandRejectRes, ConnParamUpdateReq, ConnParamUpdateRes, CreditConnReconfigReq,
+    CreditConnReconfigRes, CreditConnReconfigResultCode, CreditConnReq, CreditConnRes, DisconnectionReq,
+    DisconnectionRes, L2capHeader, L2capSignal, L2capSignalCode, L2capSignalHeader, LeCreditConnReq, LeCreditConnRes,
+    LeCreditConnResultCode, LeCreditFlowInd, L2CAP_ECFC_MAX_CHANNELS,
 };
 use crate::{config, BleHostError, Error, PacketPool};
 
 const BASE_ID: u16 = 0x40;
 
+// Minimum MTU and MPS of channels in enhanced credit based flow control mode.
+const ECFC_MIN_MTU: u16 = 64;
+
+// Time to wait for the response to a signaling request (RTX timer).
+const L2CAP_RTX_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Maximum number of signaling requests awaiting a response.
+const L2CAP_MAX_PENDING_REQUESTS: usize = 8;
+
+/// A signaling request awaiting a response from the peer.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct PendingRequest {
+    conn: ConnHandle,
+    identifier: u8,
+    code: L2capSignalCode,
+    deadline: Instant,
+}
+
 struct State<'d, P> {
     next_req_id: u8,
+    pending: Vec<PendingRequest, L2CAP_MAX_PENDING_REQUESTS>,
     channels: &'d mut [ChannelStorage<P>],
     accept_waker: WakerRegistration,
     create_waker: WakerRegistration,
+    reconfigure_waker: WakerRegistration,
     disconnect_waker: WakerRegistration,
 }
 
@@ -81,13 +107,82 @@ impl<P> State<'_, P> {
         }
     }
     fn next_request_id(&mut self) -> u8 {
-        // 0 is an invalid identifier
-        if self.next_req_id == 0 {
-            self.next_req_id += 1;
+        loop {
+            // 0 is an invalid identifier
+            if self.next_req_id == 0 {
+                self.next_req_id += 1;
+            }
+            let next = self.next_req_id;
+            self.next_req_id = self.next_req_id.wrapping_add(1);
+            // Identifiers of outstanding requests can not be reused
+            if !self.pending.iter().any(|r| r.identifier == next) {
+                return next;
+            }
+        }
+    }
+
+    // Start tracking a request sent to the peer.
+    fn track(&mut self, conn: ConnHandle, identifier: u8, code: L2capSignalCode) -> Result<(), Error> {
+        self.expire(Instant::now());
+        self.pending
+            .push(PendingRequest {
+                conn,
+                identifier,
+                code,
+                deadline: Instant::now() + L2CAP_RTX_TIMEOUT,
+            })
+            .map_err(|_| Error::Busy)
+    }
+
+    // Stop tracking a request, returning it if it was outstanding.
+    fn complete(&mut self, conn: ConnHandle, identifier: u8, code: Option<L2capSignalCode>) -> Option<PendingRequest> {
+        let pos = self
+            .pending
+            .iter()
+            .position(|r| r.conn == conn && r.identifier == identifier && code.is_none_or(|code| r.code == code))?;
+        Some(self.pending.swap_remove(pos))
+    }
+
+    // Fail the operation waiting for a request that was rejected by the peer or timed out.
+    fn fail(&mut self, request: &PendingRequest, timed_out: bool) {
+        let conn = Some(request.conn);
+        for storage in self.channels.iter_mut().filter(|storage| storage.conn == conn) {
+            match request.code {
+                L2capSignalCode::LeCreditConnReq | L2capSignalCode::CreditConnReq
+                    if storage.state == ChannelState::Connecting(request.identifier) =>
+                {
+                    if timed_out {
+                        storage.close();
+                    } else {
+                        storage.state = ChannelState::Refused;
+                    }
+                }
+                L2capSignalCode::CreditConnReconfigReq if matches!(storage.reconfigure, Some((id, None)) if id == request.identifier) =>
+                {
+                    storage.reconfigure = None;
+                }
+                L2capSignalCode::DisconnectionReq
+                    if storage.state == ChannelState::DisconnectPending(request.identifier) =>
+                {
+                    storage.close();
+                }
+                _ => {}
+            }
+        }
+        self.create_waker.wake();
+        self.reconfigure_waker.wake();
+    }
+
+    // Fail all requests for which the RTX timer expired.
+    fn expire(&mut self, now: Instant) {
+        while let Some(pos) = self.pending.iter().position(|r| r.deadline <= now) {
+            let request = self.pending.swap_remove(pos);
+            warn!(
+                "[l2cap][conn = {:?}] request {} timed out: {:?}",
+                request.conn, request.identifier, request.code
+            );
+            self.fail(&request, true);
         }
-        let next = self.next_req_id;
-        self.next_req_id = self.next_req_id.wrapping_add(1);
-        next
     }
 
     fn inc_ref(&mut self, index: ChannelIndex) {
@@ -101,9 +196,11 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         Self {
             state: RefCell::new(State {
                 next_req_id: 0,
+                pending: Vec::new(),
                 channels,
                 accept_waker: WakerRegistration::new(),
                 create_waker: WakerRegistration::new(),
+                reconfigure_waker: WakerRegistration::new(),
                 disconnect_waker: WakerRegistration::new(),
             }),
         }
@@ -113,6 +210,19 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         self.state.borrow_mut().next_request_id()
     }
 
+    fn track(&self, conn: ConnHandle, identifier: u8, code: L2capSignalCode) -> Result<(), Error> {
+        self.with_mut(|state| state.track(conn, identifier, code))
+    }
+
+    // Give up on an outstanding request, e.g. when its response timed out.
+    fn abort_request(&self, conn: ConnHandle, identifier: u8) {
+        self.with_mut(|state| {
+            if let Some(request) = state.complete(conn, identifier, None) {
+                state.fail(&request, true);
+            }
+        })
+    }
+
     pub(crate) fn psm(&self, index: ChannelIndex) -> u16 {
         self.with_mut(|state| {
             let chan = &mut state.channels[index.0 as usize];
@@ -135,6 +245,7 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
 
     pub(crate) fn disconnected(&self, conn: ConnHandle) -> Result<(), Error> {
         let mut state = self.state.borrow_mut();
+        state.pending.retain(|r| r.conn != conn);
         for storage in state.channels.iter_mut() {
             if Some(conn) == storage.conn {
                 let _ = storage.inbound.close();
@@ -152,6 +263,8 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
 
     fn alloc<F: FnOnce(&mut ChannelStorage<P::Packet>)>(&self, conn: ConnHandle, f: F) -> Result<ChannelIndex, Error> {
         let mut state = self.state.borrow_mut();
+        // Release channels waiting for a disconnection response that will not come
+        state.expire(Instant::now());
         for (idx, storage) in state.channels.iter_mut().enumerate() {
             if ChannelState::Disconnected == storage.state && storage.refcount == 0 {
                 // Ensure inbound is empty.
@@ -175,28 +288,31 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         config: &L2capChannelConfig,
         ble: &BleHost<'d, T, P>,
     ) -> Result<L2capChannel<'d, P>, BleHostError<T::Error>> {
+        if config.mode == L2capChannelMode::EnhancedCreditBased {
+            let mut channels = self.accept_multiple::<T, 1>(conn, psm, config, ble).await?;
+            return Ok(unwrap!(channels.pop()));
+        }
+
+        let (mtu, mps) = Self::local_params(config)?;
         let L2capChannelConfig {
-            mtu,
-            mps,
             flow_policy,
             initial_credits,
+            ..
         } = config;
 
-        let mtu = mtu.unwrap_or(P::MTU as u16 - 6);
-        let mps = mps.unwrap_or(P::MTU as u16 - 4);
-        if mps > P::MTU as u16 - 4 {
-            return Err(Error::InsufficientSpace.into());
-        }
-
         // Wait until we find a channel for our connection in the connecting state matching our PSM.
         let (channel, req_id, mps, mtu, cid, credits) = poll_fn(|cx| {
             let mut state = self.state.borrow_mut();
             state.accept_waker.register(cx.waker());
             for (idx, chan) in state.channels.iter_mut().enumerate() {
                 match chan.state {
-                    ChannelState::PeerConnecting(req_id) if chan.conn == Some(conn) && psm.contains(&chan.psm) => {
-                        chan.mtu = chan.mtu.min(mtu);
-                        chan.mps = chan.mps.min(mps);
+                    ChannelState::PeerConnecting(req_id)
+                        if chan.conn == Some(conn)
+                            && chan.mode == L2capChannelMode::LeCreditBased
+                            && psm.contains(&chan.psm) =>
+                    {
+                        chan.mtu = mtu;
+                        chan.mps = mps;
                         chan.flow_control = CreditFlowControl::new(
                             *flow_policy,
                             initial_credits.unwrap_or(config::L2CAP_RX_QUEUE_SIZE.min(P::capacity()) as u16),
@@ -241,6 +357,118 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         Ok(channel)
     }
 
+    pub(crate) async fn accept_multiple<T: Controller, const N: usize>(
+        &'d self,
+        conn: ConnHandle,
+        psm: &[u16],
+        config: &L2capChannelConfig,
+        ble: &BleHost<'d, T, P>,
+    ) -> Result<Vec<L2capChannel<'d, P>, N>, BleHostError<T::Error>> {
+        self.accept_multiple_checked(conn, psm, config, ble, || LeCreditConnResultCode::Success)
+            .await
+    }
+
+    /// Like [`Self::accept_multiple`], but `check` decides whether the request is accepted when it arrives.
+    ///
+    /// If `check` returns anything other than [`LeCreditConnResultCode::Success`], all the channels of the request
+    /// are refused with that result and no channel is returned.
+    pub(crate) async fn accept_multiple_checked<T: Controller, const N: usize>(
+        &'d self,
+        conn: ConnHandle,
+        psm: &[u16],
+        config: &L2capChannelConfig,
+        ble: &BleHost<'d, T, P>,
+        check: impl Fn() -> LeCreditConnResultCode,
+    ) -> Result<Vec<L2capChannel<'d, P>, N>, BleHostError<T::Error>> {
+        let (mtu, mps) = Self::local_params(config)?;
+        let credits = config
+            .initial_credits
+            .unwrap_or(config::L2CAP_RX_QUEUE_SIZE.min(P::capacity()) as u16);
+
+        // Wait until we find a request for our connection matching our PSM, and accept the channels in request order.
+        let (channels, req_id, dcids, refused) = poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            state.accept_waker.register(cx.waker());
+            let Some(req_id) = state.channels.iter().find_map(|chan| match chan.state {
+                ChannelState::PeerConnecting(req_id)
+                    if chan.conn == Some(conn)
+                        && chan.mode == L2capChannelMode::EnhancedCreditBased
+                        && psm.contains(&chan.psm) =>
+                {
+                    Some(req_id)
+                }
+                _ => None,
+            }) else {
+                return Poll::Pending;
+            };
+
+            let refused = Some(check()).filter(|result| *result != LeCreditConnResultCode::Success);
+            let mut channels = Vec::new();
+            let mut dcids = Vec::new();
+            for (idx, chan) in state.channels.iter_mut().enumerate() {
+                if chan.state != ChannelState::PeerConnecting(req_id)
+                    || chan.conn != Some(conn)
+                    || chan.mode != L2capChannelMode::EnhancedCreditBased
+                {
+                    continue;
+                }
+                if channels.len() < N && refused.is_none() {
+                    chan.mtu = mtu;
+                    chan.mps = mps;
+                    chan.flow_control = CreditFlowControl::new(config.flow_policy, credits);
+                    chan.state = ChannelState::Connected;
+                    assert_eq!(chan.refcount, 0);
+                    chan.refcount = 1;
+                    // At most L2CAP_ECFC_MAX_CHANNELS channels are allocated per request.
+                    let _ = dcids.push(chan.cid);
+                    let _ = channels.push(L2capChannel::new(ChannelIndex(idx as u8), self));
+                } else {
+                    chan.close();
+                    let _ = dcids.push(0);
+                }
+            }
+            Poll::Ready((channels, req_id, dcids, refused))
+        })
+        .await;
+
+        let result = if let Some(result) = refused {
+            result
+        } else if dcids.contains(&0) {
+            LeCreditConnResultCode::NoResources
+        } else {
+            LeCreditConnResultCode::Success
+        };
+        let mut tx = [0; 32];
+        // Respond with the channels we accept.
+        ble.l2cap_signal(
+            conn,
+            req_id,
+            &CreditConnRes {
+                mtu,
+                mps,
+                credits,
+                result,
+                dcids,
+            },
+            &mut tx[..],
+        )
+        .await?;
+        Ok(channels)
+    }
+
+    // Local MTU and MPS for a new channel.
+    fn local_params(config: &L2capChannelConfig) -> Result<(u16, u16), Error> {
+        let mtu = config.mtu.unwrap_or(P::MTU as u16 - 6);
+        let mps = config.mps.unwrap_or(P::MTU as u16 - 4);
+        if mps > P::MTU as u16 - 4 {
+            return Err(Error::InsufficientSpace);
+        }
+        if config.mode == L2capChannelMode::EnhancedCreditBased && (mtu < ECFC_MIN_MTU || mps < ECFC_MIN_MTU) {
+            return Err(Error::InvalidValue);
+        }
+        Ok((mtu, mps))
+    }
+
     pub(crate) async fn create<T: Controller>(
         &'d self,
         conn: ConnHandle,
@@ -248,23 +476,22 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         config: &L2capChannelConfig,
         ble: &BleHost<'_, T, P>,
     ) -> Result<L2capChannel<'d, P>, BleHostError<T::Error>> {
+        if config.mode == L2capChannelMode::EnhancedCreditBased {
+            let mut channels = self.create_multiple::<T, 1>(conn, psm, config, ble).await?;
+            return Ok(unwrap!(channels.pop()));
+        }
+
+        let (mtu, mps) = Self::local_params(config)?;
         let L2capChannelConfig {
-            mtu,
-            mps,
             flow_policy,
             initial_credits,
+            ..
         } = config;
 
         let req_id = self.next_request_id();
         let mut credits = 0;
         let mut cid: u16 = 0;
 
-        let mtu = mtu.unwrap_or(P::MTU as u16 - 6);
-        let mps = mps.unwrap_or(P::MTU as u16 - 4);
-        if mps > P::MTU as u16 - 4 {
-            return Err(Error::InsufficientSpace.into());
-        }
-
         // Allocate space for our new channel.
         let idx = self.alloc(conn, |storage| {
             cid = storage.cid;
@@ -273,8 +500,13 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
             storage.mtu = mtu;
             storage.mps = mps;
             storage.flow_control = CreditFlowControl::new(*flow_policy, credits);
+            storage.mode = L2capChannelMode::LeCreditBased;
             storage.state = ChannelState::Connecting(req_id);
         })?;
+        if let Err(e) = self.track(conn, req_id, L2capSignalCode::LeCreditConnReq) {
+            self.with_mut(|state| state.channels[idx.0 as usize].close());
+            return Err(e.into());
+        }
 
         let mut tx = [0; 18];
         // Send the initial connect request.
@@ -285,10 +517,231 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
             mtu,
             credits,
         };
-        ble.l2cap_signal(conn, req_id, &command, &mut tx[..]).await?;
+        if let Err(e) = ble.l2cap_signal(conn, req_id, &command, &mut tx[..]).await {
+            self.abort_request(conn, req_id);
+            return Err(e);
+        }
 
         // Wait until a response is accepted.
-        poll_fn(|cx| self.poll_created(conn, idx, ble, Some(cx))).await
+        match with_timeout(
+            L2CAP_RTX_TIMEOUT,
+            poll_fn(|cx| self.poll_created(conn, idx, ble, Some(cx))),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                self.abort_request(conn, req_id);
+                Err(Error::Timeout.into())
+            }
+        }
+    }
+
+    pub(crate) async fn create_multiple<T: Controller, const N: usize>(
+        &'d self,
+        conn: ConnHandle,
+        psm: u16,
+        config: &L2capChannelConfig,
+        ble: &BleHost<'_, T, P>,
+    ) -> Result<Vec<L2capChannel<'d, P>, N>, BleHostError<T::Error>> {
+        if N == 0 || N > L2CAP_ECFC_MAX_CHANNELS {
+            return Err(Error::InvalidValue.into());
+        }
+        let (mtu, mps) = Self::local_params(&L2capChannelConfig {
+            mode: L2capChannelMode::EnhancedCreditBased,
+            ..*config
+        })?;
+        let credits = config
+            .initial_credits
+            .unwrap_or(config::L2CAP_RX_QUEUE_SIZE.min(P::capacity()) as u16);
+
+        let req_id = self.next_request_id();
+        let mut indices: Vec<ChannelIndex, N> = Vec::new();
+        let mut scids = Vec::new();
+        for _ in 0..N {
+            let mut cid = 0;
+            let allocated = self.alloc(conn, |storage| {
+                cid = storage.cid;
+                storage.psm = psm;
+                storage.mtu = mtu;
+                storage.mps = mps;
+                storage.flow_control = CreditFlowControl::new(config.flow_policy, credits);
+                storage.mode = L2capChannelMode::EnhancedCreditBased;
+                storage.state = ChannelState::Connecting(req_id);
+            });
+            match allocated {
+                Ok(idx) => {
+                    // Capacity is checked above.
+                    let _ = indices.push(idx);
+                    let _ = scids.push(cid);
+                }
+                Err(e) => {
+                    self.with_mut(|state| {
+                        for idx in indices.iter() {
+                            state.channels[idx.0 as usize].close();
+                        }
+                    });
+                    return Err(e.into());
+                }
+            }
+        }
+        if let Err(e) = self.track(conn, req_id, L2capSignalCode::CreditConnReq) {
+            self.with_mut(|state| {
+                for idx in indices.iter() {
+                    state.channels[idx.0 as usize].close();
+                }
+            });
+            return Err(e.into());
+        }
+
+        let mut tx = [0; 32];
+        // Send a single connect request for all channels.
+        let command = CreditConnReq {
+            spsm: psm,
+            mtu,
+            mps,
+            credits,
+            scids,
+        };
+        if let Err(e) = ble.l2cap_signal(conn, req_id, &command, &mut tx[..]).await {
+            self.abort_request(conn, req_id);
+            return Err(e);
+        }
+
+        // Wait until a response is accepted.
+        match with_timeout(
+            L2CAP_RTX_TIMEOUT,
+            poll_fn(|cx| self.poll_created_multiple(conn, &indices, ble, Some(cx))),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                self.abort_request(conn, req_id);
+                Err(Error::Timeout.into())
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_created_multiple<T: Controller, const N: usize>(
+        &'d self,
+        conn: ConnHandle,
+        indices: &[ChannelIndex],
+        ble: &BleHost<'_, T, P>,
+        cx: Option<&mut Context<'_>>,
+    ) -> Poll<Result<Vec<L2capChannel<'d, P>, N>, BleHostError<T::Error>>> {
+        let mut state = self.state.borrow_mut();
+        if let Some(cx) = cx {
+            state.create_waker.register(cx.waker());
+        }
+        // Check if we've been disconnected while waiting
+        if !ble.connections.is_handle_connected(conn) {
+            return Poll::Ready(Err(Error::Disconnected.into()));
+        }
+
+        // All channels are resolved by the same response.
+        if indices
+            .iter()
+            .any(|idx| matches!(state.channels[idx.0 as usize].state, ChannelState::Connecting(_)))
+        {
+            return Poll::Pending;
+        }
+
+        let mut channels = Vec::new();
+        for idx in indices.iter() {
+            let storage = &mut state.channels[idx.0 as usize];
+            assert_eq!(Some(conn), storage.conn);
+            if storage.state == ChannelState::Connected {
+                assert_eq!(storage.refcount, 0);
+                storage.refcount = 1;
+                // Capacity is the same as the number of requested channels.
+                let _ = channels.push(L2capChannel::new(*idx, self));
+            } else if storage.state == ChannelState::Refused {
+                storage.close();
+            }
+        }
+        if channels.is_empty() {
+            return Poll::Ready(Err(Error::ChannelRefused.into()));
+        }
+        Poll::Ready(Ok(channels))
+    }
+
+    pub(crate) async fn reconfigure<T: Controller>(
+        &self,
+        index: ChannelIndex,
+        mtu: u16,
+        mps: u16,
+        ble: &BleHost<'_, T, P>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        if mps > P::MTU as u16 - 4 {
+            return Err(Error::InsufficientSpace.into());
+        }
+        let req_id = self.next_request_id();
+        let (conn, cid) = self.with_mut(|state| {
+            let chan = &mut state.channels[index.0 as usize];
+            if chan.state != ChannelState::Connected {
+                return Err(Error::ChannelClosed);
+            }
+            if chan.mode != L2capChannelMode::EnhancedCreditBased {
+                return Err(Error::NotSupported);
+            }
+            if mtu < chan.mtu || mtu < ECFC_MIN_MTU || mps < ECFC_MIN_MTU {
+                return Err(Error::InvalidValue);
+            }
+            let conn = unwrap!(chan.conn);
+            state.track(conn, req_id, L2capSignalCode::CreditConnReconfigReq)?;
+            state.channels[index.0 as usize].reconfigure = Some((req_id, None));
+            Ok((conn, state.channels[index.0 as usize].cid))
+        })?;
+
+        let mut tx = [0; 16];
+        let mut dcids = Vec::new();
+        let _ = dcids.push(cid);
+        if let Err(e) = ble
+            .l2cap_signal(conn, req_id, &CreditConnReconfigReq { mtu, mps, dcids }, &mut tx[..])
+            .await
+        {
+            self.abort_request(conn, req_id);
+            return Err(e);
+        }
+
+        let result = poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            state.reconfigure_waker.register(cx.waker());
+            let chan = &mut state.channels[index.0 as usize];
+            if chan.state != ChannelState::Connected {
+                return Poll::Ready(Err(Error::ChannelClosed));
+            }
+            match chan.reconfigure {
+                Some((_, Some(result))) => {
+                    chan.reconfigure = None;
+                    if result == CreditConnReconfigResultCode::Success {
+                        chan.mtu = mtu;
+                        chan.mps = mps;
+                    }
+                    Poll::Ready(Ok(result))
+                }
+                // Rejected by the peer or timed out.
+                None => Poll::Ready(Err(Error::ChannelRefused)),
+                _ => Poll::Pending,
+            }
+        });
+        let result = match with_timeout(L2CAP_RTX_TIMEOUT, result).await {
+            Ok(result) => result?,
+            Err(_) => {
+                self.abort_request(conn, req_id);
+                return Err(Error::Timeout.into());
+            }
+        };
+
+        match result {
+            CreditConnReconfigResultCode::Success => Ok(()),
+            other => {
+                warn!("[l2cap][cid = {}] reconfiguration refused: {:?}", cid, other);
+                Err(Error::ChannelRefused.into())
+            }
+        }
     }
 
     fn poll_created<T: Controller>(
@@ -312,9 +765,13 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         assert_eq!(Some(conn), storage.conn);
 
         match storage.state {
-            ChannelState::Disconnecting | ChannelState::PeerDisconnecting => {
+            ChannelState::Disconnecting | ChannelState::PeerDisconnecting(_) | ChannelState::DisconnectPending(_) => {
                 return Poll::Ready(Err(Error::Disconnected.into()));
             }
+            ChannelState::Refused => {
+                storage.close();
+                return Poll::Ready(Err(Error::ChannelRefused.into()));
+            }
             ChannelState::Connected => {
                 if storage.refcount != 0 {
                     state.print(true);
@@ -436,13 +893,75 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         data: &[u8],
         manager: &ConnectionManager<'_, P>,
     ) -> Result<(), Error> {
-        let (header, data) = L2capSignalHeader::from_hci_bytes(data)?;
+        let (header, data) = match L2capSignalHeader::from_hci_bytes(data) {
+            Ok(res) => res,
+            Err(FromHciBytesError::InvalidValue) => {
+                warn!("[l2cap][conn = {:?}] unknown signal code {}", conn, data[0]);
+                return self.reject(conn, data[1], CommandRejectReason::CommandNotUnderstood, &[], manager);
+            }
+            Err(e) => return Err(e.into()),
+        };
         //trace!(
         //    "[l2cap][conn = {:?}] received signal (req {}) code {:?}",
         //    conn,
         //    header.identifier,
         //    header.code
         //);
+
+        // Responses are only accepted for outstanding requests.
+        if let Some(request) = header.code.request() {
+            if self
+                .with_mut(|state| state.complete(conn, header.identifier, Some(request)))
+                .is_none()
+            {
+                debug!(
+                    "[l2cap][conn = {:?}] discarding unexpected response {:?} (req {})",
+                    conn, header.code, header.identifier
+                );
+                return Ok(());
+            }
+        }
+
+        match self.handle_signal(conn, &header, data, manager) {
+            Err(Error::HciDecode(_) | Error::NotSupported) if !header.code.is_response() => {
+                warn!(
+                    "[l2cap][conn = {:?}] rejecting signal {:?} (req {})",
+                    conn, header.code, header.identifier
+                );
+                self.reject(
+                    conn,
+                    header.identifier,
+                    CommandRejectReason::CommandNotUnderstood,
+                    &[],
+                    manager,
+                )
+            }
+            res => res,
+        }
+    }
+
+    fn reject(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        reason: CommandRejectReason,
+        data: &[u16],
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        let reject = CommandRejectRes {
+            reason: reason as u16,
+            data: Vec::from_slice(data).map_err(|_| Error::InvalidValue)?,
+        };
+        self.try_signal(conn, identifier, &reject, manager)
+    }
+
+    fn handle_signal(
+        &self,
+        conn: ConnHandle,
+        header: &L2capSignalHeader,
+        data: &[u8],
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
         match header.code {
             L2capSignalCode::LeCreditConnReq => {
                 let req = LeCreditConnReq::from_hci_bytes_complete(data)?;
@@ -452,23 +971,44 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
                 let res = LeCreditConnRes::from_hci_bytes_complete(data)?;
                 self.handle_connect_response(conn, header.identifier, &res)?;
             }
+            L2capSignalCode::CreditConnReq => {
+                let req = CreditConnReq::from_hci_bytes_complete(data)?;
+                self.handle_credit_conn_request(conn, header.identifier, &req, manager)?;
+            }
+            L2capSignalCode::CreditConnRes => {
+                let res = CreditConnRes::from_hci_bytes_complete(data)?;
+                self.handle_credit_conn_response(conn, header.identifier, &res)?;
+            }
+            L2capSignalCode::CreditConnReconfigReq => {
+                let req = CreditConnReconfigReq::from_hci_bytes_complete(data)?;
+                self.handle_reconfigure_request(conn, header.identifier, &req, manager)?;
+            }
+            L2capSignalCode::CreditConnReconfigRes => {
+                let res = CreditConnReconfigRes::from_hci_bytes_complete(data)?;
+                self.handle_reconfigure_response(conn, header.identifier, &res)?;
+            }
             L2capSignalCode::LeCreditFlowInd => {
                 let req = LeCreditFlowInd::from_hci_bytes_complete(data)?;
                 //trace!("[l2cap] credit flow: {:?}", req);
                 self.handle_credit_flow(conn, &req)?;
             }
             L2capSignalCode::CommandRejectRes => {
-                let (reject, _) = CommandRejectRes::from_hci_bytes(data)?;
+                let reject = CommandRejectRes::from_hci_bytes_complete(data)?;
+                warn!(
+                    "[l2cap][conn = {:?}] request {} rejected: {:?}",
+                    conn, header.identifier, reject
+                );
+                self.handle_command_reject(conn, header.identifier);
             }
             L2capSignalCode::DisconnectionReq => {
                 let req = DisconnectionReq::from_hci_bytes_complete(data)?;
                 debug!("[l2cap][conn = {:?}, cid = {}] disconnect request", conn, req.dcid);
-                self.handle_disconnect_request(req.dcid)?;
+                self.handle_disconnect_request(conn, header.identifier, &req, manager)?;
             }
             L2capSignalCode::DisconnectionRes => {
                 let res = DisconnectionRes::from_hci_bytes_complete(data)?;
                 debug!("[l2cap][conn = {:?}, cid = {}] disconnect response", conn, res.scid);
-                self.handle_disconnect_response(res.scid)?;
+                self.handle_disconnect_response(conn, res.scid)?;
             }
             L2capSignalCode::ConnParamUpdateReq => {
                 let req = ConnParamUpdateReq::from_hci_bytes_complete(data)?;
@@ -508,8 +1048,9 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
             storage.psm = req.psm;
             storage.peer_cid = req.scid;
             storage.peer_credits = req.credits;
-            storage.mps = req.mps;
-            storage.mtu = req.mtu;
+            storage.peer_mps = req.mps;
+            storage.peer_mtu = req.mtu;
+            storage.mode = L2capChannelMode::LeCreditBased;
             storage.state = ChannelState::PeerConnecting(identifier);
         })?;
         self.state.borrow_mut().accept_waker.wake();
@@ -526,8 +1067,8 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
                         ChannelState::Connecting(req_id) if identifier == req_id && Some(conn) == storage.conn => {
                             storage.peer_cid = res.dcid;
                             storage.peer_credits = res.credits;
-                            storage.mps = storage.mps.min(res.mps);
-                            storage.mtu = storage.mtu.min(res.mtu);
+                            storage.peer_mps = res.mps;
+                            storage.peer_mtu = res.mtu;
                             storage.state = ChannelState::Connected;
                             state.create_waker.wake();
                             return Ok(());
@@ -544,9 +1085,220 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
             }
             other => {
                 warn!("Channel open request failed: {:?}", other);
-                Err(Error::NotSupported)
+                let mut state = self.state.borrow_mut();
+                for storage in state.channels.iter_mut() {
+                    match storage.state {
+                        ChannelState::Connecting(req_id) if identifier == req_id && Some(conn) == storage.conn => {
+                            storage.state = ChannelState::Refused;
+                            state.create_waker.wake();
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+                Err(Error::NotFound)
+            }
+        }
+    }
+
+    fn handle_credit_conn_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &CreditConnReq,
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        let result = if req.mtu < ECFC_MIN_MTU || req.mps < ECFC_MIN_MTU {
+            Some(LeCreditConnResultCode::UnacceptableParameters)
+        } else if req.scids.iter().any(|scid| *scid < BASE_ID) {
+            Some(LeCreditConnResultCode::InvalidSourceId)
+        } else if self.with_mut(|state| {
+            state.channels.iter().any(|chan| {
+                chan.state != ChannelState::Disconnected
+                    && chan.conn == Some(conn)
+                    && req.scids.contains(&chan.peer_cid)
+            })
+        }) {
+            Some(LeCreditConnResultCode::ScidAlreadyAllocated)
+        } else {
+            None
+        };
+
+        // Channels of a request are allocated all or nothing.
+        let mut allocated: Vec<ChannelIndex, L2CAP_ECFC_MAX_CHANNELS> = Vec::new();
+        if result.is_none() {
+            for scid in req.scids.iter() {
+                match self.alloc(conn, |storage| {
+                    storage.psm = req.spsm;
+                    storage.peer_cid = *scid;
+                    storage.peer_credits = req.credits;
+                    storage.peer_mps = req.mps;
+                    storage.peer_mtu = req.mtu;
+                    storage.mode = L2capChannelMode::EnhancedCreditBased;
+                    storage.state = ChannelState::PeerConnecting(identifier);
+                }) {
+                    Ok(idx) => {
+                        let _ = allocated.push(idx);
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+
+        if result.is_none() && allocated.len() == req.scids.len() {
+            self.state.borrow_mut().accept_waker.wake();
+            return Ok(());
+        }
+
+        self.with_mut(|state| {
+            for idx in allocated.iter() {
+                state.channels[idx.0 as usize].close();
+            }
+        });
+        let result = result.unwrap_or(LeCreditConnResultCode::NoResources);
+        warn!("[l2cap][conn = {:?}] refusing channels: {:?}", conn, result);
+        let mut dcids = Vec::new();
+        for _ in req.scids.iter() {
+            let _ = dcids.push(0);
+        }
+        self.try_signal(
+            conn,
+            identifier,
+            &CreditConnRes {
+                mtu: 0,
+                mps: 0,
+                credits: 0,
+                result,
+                dcids,
+            },
+            manager,
+        )
+    }
+
+    fn handle_credit_conn_response(&self, conn: ConnHandle, identifier: u8, res: &CreditConnRes) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        let mut dcids = res.dcids.iter();
+        let mut found = false;
+        // Destination CIDs are listed in the same order as the channels were requested.
+        for storage in state.channels.iter_mut() {
+            match storage.state {
+                ChannelState::Connecting(req_id)
+                    if identifier == req_id
+                        && Some(conn) == storage.conn
+                        && storage.mode == L2capChannelMode::EnhancedCreditBased =>
+                {
+                    found = true;
+                    match dcids.next() {
+                        Some(dcid) if *dcid != 0 => {
+                            storage.peer_cid = *dcid;
+                            storage.peer_credits = res.credits;
+                            storage.peer_mps = res.mps;
+                            storage.peer_mtu = res.mtu;
+                            storage.state = ChannelState::Connected;
+                        }
+                        _ => storage.state = ChannelState::Refused,
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !found {
+            debug!(
+                "[l2cap][handle_credit_conn_response][link = {}] request with id {} not found",
+                conn.raw(),
+                identifier
+            );
+            return Err(Error::NotFound);
+        }
+        if res.result != LeCreditConnResultCode::Success {
+            warn!("Channel open request failed: {:?}", res.result);
+        }
+        state.create_waker.wake();
+        Ok(())
+    }
+
+    fn handle_reconfigure_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &CreditConnReconfigReq,
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        let result = self.with_mut(|state| {
+            let is_reconfigured = |chan: &ChannelStorage<P::Packet>| {
+                chan.state == ChannelState::Connected
+                    && chan.conn == Some(conn)
+                    && chan.mode == L2capChannelMode::EnhancedCreditBased
+                    && req.dcids.contains(&chan.peer_cid)
+            };
+            if req.mtu < ECFC_MIN_MTU || req.mps < ECFC_MIN_MTU {
+                return CreditConnReconfigResultCode::UnacceptableParameters;
+            }
+            if state.channels.iter().filter(|chan| is_reconfigured(chan)).count() != req.dcids.len() {
+                return CreditConnReconfigResultCode::InvalidDestinationCid;
+            }
+            for chan in state.channels.iter().filter(|chan| is_reconfigured(chan)) {
+                if req.mtu < chan.peer_mtu {
+                    return CreditConnReconfigResultCode::MtuReductionNotAllowed;
+                }
+                if req.dcids.len() > 1 && req.mps < chan.peer_mps {
+                    return CreditConnReconfigResultCode::MpsReductionNotAllowed;
+                }
+            }
+            for chan in state.channels.iter_mut().filter(|chan| is_reconfigured(chan)) {
+                chan.peer_mtu = req.mtu;
+                chan.peer_mps = req.mps;
+            }
+            CreditConnReconfigResultCode::Success
+        });
+        self.try_signal(conn, identifier, &CreditConnReconfigRes { result }, manager)
+    }
+
+    fn handle_reconfigure_response(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        res: &CreditConnReconfigRes,
+    ) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        for storage in state.channels.iter_mut() {
+            match storage.reconfigure {
+                Some((req_id, None)) if req_id == identifier && Some(conn) == storage.conn => {
+                    storage.reconfigure = Some((req_id, Some(res.result)));
+                    state.reconfigure_waker.wake();
+                    return Ok(());
+                }
+                _ => {}
             }
         }
+        Err(Error::NotFound)
+    }
+
+    // Send a signal from a context that can not wait for the controller.
+    fn try_signal<D: L2capSignal>(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        signal: &D,
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        let header = L2capSignalHeader {
+            identifier,
+            code: D::code(),
+            length: signal.size() as u16,
+        };
+        let l2cap = L2capHeader {
+            channel: D::channel(),
+            length: header.size() as u16 + header.length,
+        };
+
+        let mut packet = P::allocate().ok_or(Error::OutOfMemory)?;
+        let mut w = WriteCursor::new(packet.as_mut());
+        w.write_hci(&l2cap)?;
+        w.write_hci(&header)?;
+        w.write_hci(signal)?;
+        let len = w.len();
+        manager.try_outbound(conn, Pdu::new(packet, len))
     }
 
     fn handle_credit_flow(&self, conn: ConnHandle, req: &LeCreditFlowInd) -> Result<(), Error> {
@@ -571,23 +1323,56 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         Err(Error::NotFound)
     }
 
-    fn handle_disconnect_request(&self, cid: u16) -> Result<(), Error> {
-        let mut state = self.state.borrow_mut();
-        for (idx, storage) in state.channels.iter_mut().enumerate() {
-            if cid == storage.cid {
-                storage.state = ChannelState::PeerDisconnecting;
-                let _ = storage.inbound.close();
-                state.disconnect_waker.wake();
-                break;
+    fn handle_command_reject(&self, conn: ConnHandle, identifier: u8) {
+        self.with_mut(|state| {
+            if let Some(request) = state.complete(conn, identifier, None) {
+                state.fail(&request, false);
+            }
+        })
+    }
+
+    fn handle_disconnect_request(
+        &self,
+        conn: ConnHandle,
+        identifier: u8,
+        req: &DisconnectionReq,
+        manager: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        let found = self.with_mut(|state| {
+            for storage in state.channels.iter_mut() {
+                if storage.conn == Some(conn) && storage.cid == req.dcid && storage.state != ChannelState::Disconnected
+                {
+                    storage.state = ChannelState::PeerDisconnecting(identifier);
+                    let _ = storage.inbound.close();
+                    state.disconnect_waker.wake();
+                    return true;
+                }
             }
+            false
+        });
+        if !found {
+            warn!(
+                "[l2cap][conn = {:?}] disconnect request for unknown cid {}",
+                conn, req.dcid
+            );
+            self.reject(
+                conn,
+                identifier,
+                CommandRejectReason::InvalidCid,
+                &[req.dcid, req.scid],
+                manager,
+            )?;
         }
         Ok(())
     }
 
-    fn handle_disconnect_response(&self, cid: u16) -> Result<(), Error> {
+    fn handle_disconnect_response(&self, conn: ConnHandle, cid: u16) -> Result<(), Error> {
         let mut state = self.state.borrow_mut();
         for storage in state.channels.iter_mut() {
-            if storage.state == ChannelState::Disconnecting && cid == storage.cid {
+            if storage.conn == Some(conn)
+                && matches!(storage.state, ChannelState::DisconnectPending(_))
+                && cid == storage.cid
+            {
                 storage.close();
                 break;
             }
@@ -740,6 +1525,70 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         Ok(())
     }
 
+    /// Send the provided buffer over a given l2cap channel through the outbound queue of the connection.
+    ///
+    /// Unlike [`Self::send`], the controller is not needed, so this can be used wherever only the connection is
+    /// available. Waits until enough credits are available.
+    pub(crate) async fn send_queued(
+        &self,
+        index: ChannelIndex,
+        buf: &[u8],
+        connection: &Connection<'_, P>,
+    ) -> Result<(), Error> {
+        let (_, mps, mtu, peer_cid) = self.connected_channel_params(index)?;
+        if buf.len() > mtu as usize {
+            return Err(Error::InsufficientSpace);
+        }
+        let n_packets = (buf.len() as u16).saturating_add(2).div_ceil(mps);
+        let mut grant = poll_fn(|cx| self.poll_request_to_send(index, n_packets, Some(cx))).await?;
+
+        let (first, remaining) = buf.split_at(buf.len().min(mps as usize - 2));
+        connection
+            .send(Self::frame(first, peer_cid, Some(buf.len() as u16))?)
+            .await;
+        grant.confirm(1);
+        for chunk in remaining.chunks(mps as usize) {
+            connection.send(Self::frame(chunk, peer_cid, None)?).await;
+            grant.confirm(1);
+        }
+        Ok(())
+    }
+
+    /// Send the provided buffer over a given l2cap channel through the outbound queue of the connection.
+    ///
+    /// Returns [`Error::Busy`] if there are not enough credits to send it.
+    pub(crate) fn try_send_queued(
+        &self,
+        index: ChannelIndex,
+        buf: &[u8],
+        connection: &Connection<'_, P>,
+    ) -> Result<(), Error> {
+        let (_, mps, mtu, peer_cid) = self.connected_channel_params(index)?;
+        if buf.len() > mtu as usize {
+            return Err(Error::InsufficientSpace);
+        }
+        let n_packets = (buf.len() as u16).saturating_add(2).div_ceil(mps);
+        let mut grant = match self.poll_request_to_send(index, n_packets, None) {
+            Poll::Ready(res) => res?,
+            Poll::Pending => return Err(Error::Busy),
+        };
+
+        let (first, remaining) = buf.split_at(buf.len().min(mps as usize - 2));
+        connection.try_send(Self::frame(first, peer_cid, Some(buf.len() as u16))?)?;
+        grant.confirm(1);
+        for chunk in remaining.chunks(mps as usize) {
+            connection.try_send(Self::frame(chunk, peer_cid, None)?)?;
+            grant.confirm(1);
+        }
+        Ok(())
+    }
+
+    fn frame(data: &[u8], peer_cid: u16, header: Option<u16>) -> Result<Pdu<P::Packet>, Error> {
+        let mut packet = P::allocate().ok_or(Error::OutOfMemory)?;
+        let len = encode(data, packet.as_mut(), peer_cid, header)?;
+        Ok(Pdu::new(packet, len))
+    }
+
     pub(crate) async fn send_conn_param_update_req<T: Controller>(
         &self,
         handle: ConnHandle,
@@ -747,6 +1596,8 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         param: &ConnParamUpdateReq,
     ) -> Result<(), BleHostError<T::Error>> {
         let identifier = self.next_request_id();
+        // The response is only used for logging, so the request is sent even if it can not be tracked.
+        let _ = self.track(handle, identifier, L2capSignalCode::ConnParamUpdateReq);
         let mut tx = [0; 16];
         host.l2cap_signal(handle, identifier, param, &mut tx[..]).await
     }
@@ -762,11 +1613,35 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         host.l2cap_signal(handle, identifier, param, &mut tx[..]).await
     }
 
-    fn connected_channel_params(&self, index: ChannelIndex) -> Result<(ConnHandle, u16, u16, u16), Error> {
+    /// Open an enhanced credit based channel to the peer channel `peer_cid` without signaling, for tests.
+    #[cfg(test)]
+    pub(crate) fn open_for_test(&'d self, conn: ConnHandle, peer_cid: u16) -> Result<L2capChannel<'d, P>, Error> {
+        let index = self.alloc(conn, |storage| {
+            storage.state = ChannelState::Connected;
+            storage.mode = L2capChannelMode::EnhancedCreditBased;
+            storage.mtu = 64;
+            storage.mps = 64;
+            storage.peer_cid = peer_cid;
+            storage.peer_mtu = 64;
+            storage.peer_mps = 64;
+            storage.peer_credits = 10;
+            // No credits are returned to the peer, which would need the controller.
+            storage.flow_control = CreditFlowControl::new(CreditFlowPolicy::MinThreshold(0), 10);
+            storage.refcount = 1;
+        })?;
+        Ok(L2capChannel::new(index, self))
+    }
+
+    pub(crate) fn connected_channel_params(&self, index: ChannelIndex) -> Result<(ConnHandle, u16, u16, u16), Error> {
         let state = self.state.borrow();
         let chan = &state.channels[index.0 as usize];
         if chan.state == ChannelState::Connected {
-            return Ok((chan.conn.unwrap(), chan.mps, chan.mtu, chan.peer_cid));
+            return Ok((
+                chan.conn.unwrap(),
+                chan.mps.min(chan.peer_mps),
+                chan.mtu.min(chan.peer_mtu),
+                chan.peer_cid,
+            ));
         }
         //trace!("[l2cap][connected_channel_params] channel {} closed", index);
         Err(Error::ChannelClosed)
@@ -848,7 +1723,7 @@ impl<'d, P: PacketPool> ChannelManager<'d, P> {
         }
         for (idx, storage) in state.channels.iter().enumerate() {
             match storage.state {
-                ChannelState::Disconnecting | ChannelState::PeerDisconnecting => {
+                ChannelState::Disconnecting | ChannelState::PeerDisconnecting(_) => {
                     return Poll::Ready(DisconnectRequest {
                         index: ChannelIndex(idx as u8),
                         handle: storage.conn.unwrap(),
@@ -916,24 +1791,38 @@ impl<'a, 'd, P: PacketPool> DisconnectRequest<'a, 'd, P> {
     }
 
     pub async fn send<T: Controller>(&self, host: &BleHost<'_, T, P>) -> Result<(), BleHostError<T::Error>> {
-        let (state, conn, identifier, dcid, scid) = {
-            let mut state = self.state.borrow_mut();
-            let identifier = state.next_request_id();
+        let (state, conn, dcid, scid) = {
+            let state = self.state.borrow();
             let chan = &state.channels[self.index.0 as usize];
-            (chan.state.clone(), chan.conn, identifier, chan.peer_cid, chan.cid)
+            (chan.state.clone(), chan.conn, chan.peer_cid, chan.cid)
         };
 
         let mut tx = [0; 18];
         match state {
-            ChannelState::PeerDisconnecting => {
+            ChannelState::PeerDisconnecting(identifier) => {
                 assert_eq!(Some(self.handle), conn);
                 host.l2cap_signal(self.handle, identifier, &DisconnectionRes { dcid, scid }, &mut tx[..])
                     .await?;
             }
             ChannelState::Disconnecting => {
                 assert_eq!(Some(self.handle), conn);
+                let identifier = {
+                    let mut state = self.state.borrow_mut();
+                    let identifier = state.next_request_id();
+                    // Without tracking, the channel is released without waiting for the response.
+                    let _ = state.track(self.handle, identifier, L2capSignalCode::DisconnectionReq);
+                    identifier
+                };
                 host.l2cap_signal(self.handle, identifier, &DisconnectionReq { dcid, scid }, &mut tx[..])
                     .await?;
+                let mut state = self.state.borrow_mut();
+                if state
+                    .pending
+                    .iter()
+                    .any(|r| r.conn == self.handle && r.identifier == identifier)
+                {
+                    state.channels[self.index.0 as usize].state = ChannelState::DisconnectPending(identifier);
+                }
             }
             _ => {}
         }
@@ -941,7 +1830,13 @@ impl<'a, 'd, P: PacketPool> DisconnectRequest<'a, 'd, P> {
     }
 
     pub fn confirm(self) {
-        self.state.borrow_mut().channels[self.index.0 as usize].state = ChannelState::Disconnected;
+        let mut state = self.state.borrow_mut();
+        let chan = &mut state.channels[self.index.0 as usize];
+        // Channels waiting for a disconnection response are released by the response, a command
+        // reject or the expiry of the request.
+        if !matches!(chan.state, ChannelState::DisconnectPending(_)) {
+            chan.state = ChannelState::Disconnected;
+        }
     }
 }
 
@@ -969,10 +1864,14 @@ pub struct ChannelStorage<P> {
     psm: u16,
     mps: u16,
     mtu: u16,
+    mode: L2capChannelMode,
     flow_control: CreditFlowControl,
     refcount: u8,
+    reconfigure: Option<(u8, Option<CreditConnReconfigResultCode>)>,
 
     peer_cid: u16,
+    peer_mps: u16,
+    peer_mtu: u16,
     peer_credits: u16,
     credit_waker: WakerRegistration,
 
@@ -1054,6 +1953,8 @@ impl<P> core::fmt::Debug for ChannelStorage<P> {
             .field("peer_cid", &self.peer_cid)
             .field("mps", &self.mps)
             .field("mtu", &self.mtu)
+            .field("peer_mps", &self.peer_mps)
+            .field("peer_mtu", &self.peer_mtu)
             .field("peer_credits", &self.peer_credits)
             .field("available", &self.flow_control.available())
             .field("refcount", &self.refcount);
@@ -1068,13 +1969,15 @@ impl<P> defmt::Format for ChannelStorage<P> {
     fn format(&self, f: defmt::Formatter<'_>) {
         defmt::write!(
             f,
-            "state = {}, c = {}, cid = {}, peer = {}, mps = {}, mtu = {}, cred out {}, cred in = {}, ref = {}",
+            "state = {}, c = {}, cid = {}, peer = {}, mps = {}/{}, mtu = {}/{}, cred out {}, cred in = {}, ref = {}",
             self.state,
             self.conn,
             self.cid,
             self.peer_cid,
             self.mps,
+            self.peer_mps,
             self.mtu,
+            self.peer_mtu,
             self.peer_credits,
             self.flow_control.available(),
             self.refcount,
@@ -1093,9 +1996,13 @@ impl<P> ChannelStorage<P> {
             mps: 0,
             mtu: 0,
             psm: 0,
+            mode: L2capChannelMode::LeCreditBased,
+            reconfigure: None,
 
             flow_control: CreditFlowControl::new(CreditFlowPolicy::Every(1), 0),
             peer_cid: 0,
+            peer_mps: 0,
+            peer_mtu: 0,
             peer_credits: 0,
             credit_waker: WakerRegistration::new(),
             refcount: 0,
@@ -1114,7 +2021,11 @@ impl<P> ChannelStorage<P> {
         self.mps = 0;
         self.mtu = 0;
         self.psm = 0;
+        self.mode = L2capChannelMode::LeCreditBased;
+        self.reconfigure = None;
         self.peer_cid = 0;
+        self.peer_mps = 0;
+        self.peer_mtu = 0;
         self.flow_control = CreditFlowControl::new(CreditFlowPolicy::Every(1), 0);
         self.peer_credits = 0;
     }
@@ -1127,8 +2038,10 @@ pub enum ChannelState {
     Connecting(u8),
     PeerConnecting(u8),
     Connected,
-    PeerDisconnecting,
+    Refused,
+    PeerDisconnecting(u8),
     Disconnecting,
+    DisconnectPending(u8),
 }
 
 /// Control how credits are issued by the receiving end.
@@ -1280,4 +2193,210 @@ mod tests {
             Poll::Ready(Err(BleHostError::BleHost(Error::Disconnected)))
         ));
     }
+
+    #[test]
+    fn signal_command_reject() {
+        let mut resources: HostResources<DefaultPacketPool, 2, 2> = HostResources::new();
+        let ble = MockController::new();
+
+        let builder = crate::new(ble, &mut resources);
+        let ble = builder.host;
+
+        let conn = ConnHandle::new(33);
+        ble.connections
+            .connect(conn, AddrKind::PUBLIC, BdAddr::new([0; 6]), LeConnRole::Central)
+            .unwrap();
+        let idx = ble
+            .channels
+            .alloc(conn, |storage| {
+                storage.state = ChannelState::Connecting(7);
+            })
+            .unwrap();
+        ble.channels.track(conn, 7, L2capSignalCode::LeCreditConnReq).unwrap();
+
+        // A response to a request that is not outstanding is discarded.
+        ble.channels
+            .signal(
+                conn,
+                &[0x15, 8, 10, 0, 0x40, 0, 100, 0, 100, 0, 1, 0, 0, 0],
+                &ble.connections,
+            )
+            .unwrap();
+        assert!(matches!(
+            ble.channels.poll_created(conn, idx, &ble, None),
+            Poll::Pending
+        ));
+
+        ble.channels
+            .signal(conn, &[0x01, 7, 2, 0, 0, 0], &ble.connections)
+            .unwrap();
+        assert!(matches!(
+            ble.channels.poll_created(conn, idx, &ble, None),
+            Poll::Ready(Err(BleHostError::BleHost(Error::ChannelRefused)))
+        ));
+
+        // Unknown signals are rejected using the identifier of the signal.
+        ble.channels.signal(conn, &[0x30, 9, 0, 0], &ble.connections).unwrap();
+        let (handle, pdu) = embassy_futures::block_on(ble.connections.outbound());
+        assert_eq!(handle, conn);
+        assert_eq!(pdu.as_ref(), &[6, 0, 5, 0, 0x01, 9, 2, 0, 0, 0]);
+
+        // Disconnect requests for unknown channels are rejected with the channel ids.
+        ble.channels
+            .signal(conn, &[0x06, 3, 4, 0, 0x41, 0, 0x50, 0], &ble.connections)
+            .unwrap();
+        let (_, pdu) = embassy_futures::block_on(ble.connections.outbound());
+        assert_eq!(pdu.as_ref(), &[10, 0, 5, 0, 0x01, 3, 6, 0, 2, 0, 0x41, 0, 0x50, 0]);
+    }
+
+    #[test]
+    fn disconnect_request_timeout() {
+        let mut resources: HostResources<DefaultPacketPool, 2, 2> = HostResources::new();
+        let ble = MockController::new();
+
+        let builder = crate::new(ble, &mut resources);
+        let ble = builder.host;
+
+        let conn = ConnHandle::new(33);
+        ble.connections
+            .connect(conn, AddrKind::PUBLIC, BdAddr::new([0; 6]), LeConnRole::Central)
+            .unwrap();
+        let idx = ble
+            .channels
+            .alloc(conn, |storage| {
+                storage.state = ChannelState::DisconnectPending(5);
+            })
+            .unwrap();
+        ble.channels.track(conn, 5, L2capSignalCode::DisconnectionReq).unwrap();
+
+        ble.channels.with_mut(|state| {
+            state.expire(Instant::now());
+            assert_eq!(state.channels[idx.0 as usize].state, ChannelState::DisconnectPending(5));
+            // Identifiers of outstanding requests are skipped.
+            state.next_req_id = 5;
+            assert_eq!(state.next_request_id(), 6);
+
+            state.expire(Instant::now() + L2CAP_RTX_TIMEOUT);
+            assert_eq!(state.channels[idx.0 as usize].state, ChannelState::Disconnected);
+            assert!(state.pending.is_empty());
+        });
+    }
+
+    #[test]
+    fn ecfc_create_partially_refused() {
+        let mut resources: HostResources<DefaultPacketPool, 2, 4> = HostResources::new();
+        let ble = MockController::new();
+
+        let builder = crate::new(ble, &mut resources);
+        let ble = builder.host;
+
+        let conn = ConnHandle::new(33);
+        ble.connections
+            .connect(conn, AddrKind::PUBLIC, BdAddr::new([0; 6]), LeConnRole::Central)
+            .unwrap();
+        let mut indices: Vec<ChannelIndex, 2> = Vec::new();
+        for _ in 0..2 {
+            let idx = ble
+                .channels
+                .alloc(conn, |storage| {
+                    storage.mtu = 100;
+                    storage.mps = 100;
+                    storage.mode = L2capChannelMode::EnhancedCreditBased;
+                    storage.state = ChannelState::Connecting(7);
+                })
+                .unwrap();
+            indices.push(idx).unwrap();
+        }
+
+        let chans = ble.channels.poll_created_multiple::<_, 2>(conn, &indices, &ble, None);
+        assert!(matches!(chans, Poll::Pending));
+
+        let res = CreditConnRes {
+            mtu: 80,
+            mps: 120,
+            credits: 5,
+            result: LeCreditConnResultCode::NoResources,
+            dcids: Vec::from_slice(&[0x50, 0]).unwrap(),
+        };
+        ble.channels.handle_credit_conn_response(conn, 7, &res).unwrap();
+
+        let Poll::Ready(Ok(chans)) = ble.channels.poll_created_multiple::<_, 2>(conn, &indices, &ble, None) else {
+            panic!("expected channels to be created");
+        };
+        assert_eq!(chans.len(), 1);
+        assert_eq!(
+            ble.channels.connected_channel_params(indices[0]).unwrap(),
+            (conn, 100, 80, 0x50)
+        );
+        ble.channels.with_mut(|state| {
+            assert_eq!(state.channels[indices[1].0 as usize].state, ChannelState::Disconnected);
+        });
+    }
+
+    #[test]
+    fn ecfc_peer_request_and_reconfigure() {
+        let mut resources: HostResources<DefaultPacketPool, 2, 2> = HostResources::new();
+        let ble = MockController::new();
+
+        let builder = crate::new(ble, &mut resources);
+        let ble = builder.host;
+
+        let conn = ConnHandle::new(33);
+        ble.connections
+            .connect(conn, AddrKind::PUBLIC, BdAddr::new([0; 6]), LeConnRole::Peripheral)
+            .unwrap();
+
+        // More channels than available are refused as a whole.
+        let req = CreditConnReq {
+            spsm: 0x81,
+            mtu: 100,
+            mps: 100,
+            credits: 10,
+            scids: Vec::from_slice(&[0x40, 0x41, 0x42]).unwrap(),
+        };
+        ble.channels
+            .handle_credit_conn_request(conn, 1, &req, &ble.connections)
+            .unwrap();
+        ble.channels.with_mut(|state| {
+            assert!(state
+                .channels
+                .iter()
+                .all(|chan| chan.state == ChannelState::Disconnected));
+        });
+
+        let req = CreditConnReq {
+            scids: Vec::from_slice(&[0x40, 0x41]).unwrap(),
+            ..req
+        };
+        ble.channels
+            .handle_credit_conn_request(conn, 2, &req, &ble.connections)
+            .unwrap();
+        ble.channels.with_mut(|state| {
+            for (chan, scid) in state.channels.iter_mut().zip([0x40, 0x41]) {
+                assert_eq!(chan.state, ChannelState::PeerConnecting(2));
+                assert_eq!(chan.peer_cid, scid);
+                chan.state = ChannelState::Connected;
+            }
+        });
+
+        let mut reconfigure = CreditConnReconfigReq {
+            mtu: 90,
+            mps: 100,
+            dcids: Vec::from_slice(&[0x40, 0x41]).unwrap(),
+        };
+        ble.channels
+            .handle_reconfigure_request(conn, 3, &reconfigure, &ble.connections)
+            .unwrap();
+        ble.channels.with_mut(|state| {
+            assert!(state.channels.iter().all(|chan| chan.peer_mtu == 100));
+        });
+
+        reconfigure.mtu = 200;
+        ble.channels
+            .handle_reconfigure_request(conn, 4, &reconfigure, &ble.connections)
+            .unwrap();
+        ble.channels.with_mut(|state| {
+            assert!(state.channels.iter().all(|chan| chan.peer_mtu == 200));
+        });
+    }
 }
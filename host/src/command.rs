@@ -54,6 +54,17 @@ impl<CTX: Clone + Copy> CommandState<CTX> {
         .await
     }
 
+    /// Take the command state if it is idle, without waiting.
+    pub fn try_request(&self) -> bool {
+        self.with_inner(|inner| match inner.state {
+            State::Idle => {
+                inner.state = State::Active;
+                true
+            }
+            _ => false,
+        })
+    }
+
     /// Request a new command.
     pub async fn wait_idle(&self) {
         poll_fn(|cx| {
@@ -68,6 +79,11 @@ impl<CTX: Clone + Copy> CommandState<CTX> {
         .await
     }
 
+    /// Whether a command is active and not being canceled.
+    pub fn is_active(&self) -> bool {
+        self.with_inner(|inner| matches!(inner.state, State::Active))
+    }
+
     /// Poll if the command should be canceled
     pub fn poll_cancelled(&self, cx: &mut Context<'_>) -> Poll<CTX> {
         self.with_inner(|inner| {
@@ -71,3 +71,20 @@ pub const GATT_CLIENT_NOTIFICATION_MAX_SUBSCRIBERS: usize = raw::GATT_CLIENT_NOT
 ///
 /// Default: 1.
 pub const GATT_CLIENT_NOTIFICATION_QUEUE_SIZE: usize = raw::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE;
+
+/// GATT server prepare write queue size.
+///
+/// This is the number of bytes of prepared writes (including a 6 byte header per
+/// queued fragment) that can be buffered for each connection before an
+/// Execute Write Request commits them.
+///
+/// Default: 512.
+pub const GATT_SERVER_PREPARE_WRITE_QUEUE_SIZE: usize = raw::GATT_SERVER_PREPARE_WRITE_QUEUE_SIZE;
+
+/// GATT client EATT bearers.
+///
+/// This is the maximum number of Enhanced ATT bearers a GATT client can open over
+/// a connection, in addition to the unenhanced ATT bearer.
+///
+/// Default: 2.
+pub const GATT_CLIENT_EATT_BEARERS_MAX: usize = raw::GATT_CLIENT_EATT_BEARERS_MAX;
@@ -1,5 +1,8 @@
 //! BLE connection.
 
+#[cfg(feature = "gatt")]
+use core::task::{Context, Poll};
+
 use bt_hci::cmd::le::{LeConnUpdate, LeReadLocalSupportedFeatures, LeReadPhy, LeSetDataLength, LeSetPhy};
 use bt_hci::cmd::status::ReadRssi;
 use bt_hci::controller::{ControllerCmdAsync, ControllerCmdSync};
@@ -22,7 +25,7 @@ use crate::pdu::Pdu;
 #[cfg(feature = "gatt")]
 use crate::prelude::{AttributeServer, GattConnection};
 #[cfg(feature = "security")]
-use crate::security_manager::{BondInformation, PassKey};
+use crate::security_manager::{BondInformation, OobData, PassKey};
 #[cfg(feature = "connection-params-update")]
 use crate::types::l2cap::ConnParamUpdateRes;
 use crate::{bt_hci_duration, BleHostError, Error, Identity, PacketPool, Stack};
@@ -321,6 +324,80 @@ impl<'stack, P: PacketPool> Connection<'stack, P> {
         self.manager.request_security(self.index)
     }
 
+    /// Pair again with MITM protection if the link is encrypted but not authenticated.
+    #[cfg(feature = "security")]
+    pub(crate) fn request_authentication(&self) -> Result<(), Error> {
+        self.manager.request_authentication(self.index)
+    }
+
+    /// Wait for the pairing procedure started by [`Self::request_security`] to complete or fail.
+    #[cfg(feature = "security")]
+    pub(crate) async fn wait_pairing(&self) -> Result<SecurityLevel, Error> {
+        self.manager.wait_pairing(self.index).await
+    }
+
+    #[cfg(feature = "gatt")]
+    pub(crate) fn start_indication(&self) -> Result<(), Error> {
+        self.manager.start_indication(self.index)
+    }
+
+    #[cfg(feature = "gatt")]
+    pub(crate) fn poll_start_indication(&self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.manager.poll_start_indication(self.index, cx)
+    }
+
+    #[cfg(feature = "gatt")]
+    pub(crate) fn end_indication(&self) {
+        self.manager.end_indication(self.index)
+    }
+
+    /// Fail with [`Error::Timeout`] if the unenhanced ATT bearer is unusable after an indication timed out.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn check_att_bearer(&self) -> Result<(), Error> {
+        self.manager.check_att_bearer(self.index)
+    }
+
+    #[cfg(feature = "gatt")]
+    pub(crate) fn indication_timed_out(&self) {
+        self.manager.indication_timed_out(self.index)
+    }
+
+    /// Apply the ATT transaction timeout to the indications sent on the unenhanced ATT bearer, never returning.
+    #[cfg(feature = "gatt")]
+    pub(crate) async fn expire_indications(&self) {
+        self.manager.expire_indications(self.index).await
+    }
+
+    /// Wait for the confirmation of the indication started by [`Self::start_indication`].
+    #[cfg(feature = "gatt")]
+    pub(crate) async fn wait_confirmation(&self) -> Result<(), Error> {
+        self.manager.wait_confirmation(self.index).await
+    }
+
+    /// Sign data sent to the peer with the local signing key of its bond ([Vol 3] Part H, Section 2.4.5).
+    #[cfg(feature = "security")]
+    pub(crate) fn sign(&self, data: &[&[u8]]) -> Result<[u8; 12], Error> {
+        self.manager.security_manager.sign(&self.peer_identity(), data)
+    }
+
+    /// Verify data signed by the peer with the signing key of its bond, returning the security level of the bond.
+    ///
+    /// The sign counter of the signature is recorded, so the same signature is rejected afterwards.
+    #[cfg(feature = "security")]
+    pub(crate) fn verify_signature(&self, data: &[&[u8]], signature: &[u8; 12]) -> Result<SecurityLevel, Error> {
+        self.manager
+            .security_manager
+            .verify(&self.peer_identity(), data, signature, true)
+    }
+
+    /// Check data signed by the peer like [`Self::verify_signature`], without recording the sign counter.
+    #[cfg(feature = "security")]
+    pub(crate) fn check_signature(&self, data: &[&[u8]], signature: &[u8; 12]) -> Result<SecurityLevel, Error> {
+        self.manager
+            .security_manager
+            .verify(&self.peer_identity(), data, signature, false)
+    }
+
     /// Get the encrypted state of the connection
     pub fn security_level(&self) -> Result<SecurityLevel, Error> {
         self.manager.get_security_level(self.index)
@@ -351,6 +428,17 @@ impl<'stack, P: PacketPool> Connection<'stack, P> {
         self.manager.set_bondable(self.index, bondable)
     }
 
+    /// Set the out-of-band data received from the peer, such as over NFC or a QR code.
+    ///
+    /// This must be set before pairing is initiated. LE Secure Connections pairing then uses the out-of-band method,
+    /// which is also the case if the peer has received the local data from [`Stack::generate_oob_data()`].
+    ///
+    /// [`Stack::generate_oob_data()`]: crate::Stack::generate_oob_data
+    #[cfg(feature = "security")]
+    pub fn set_peer_oob_data(&self, data: OobData) -> Result<(), Error> {
+        self.manager.set_peer_oob_data(self.index, data)
+    }
+
     /// Confirm that the displayed pass key matches the one displayed on the other party
     pub fn pass_key_confirm(&self) -> Result<(), Error> {
         self.manager.pass_key_confirm(self.index, true)
@@ -10,15 +10,21 @@ use embassy_sync::channel::Channel;
 use embassy_sync::waitqueue::WakerRegistration;
 #[cfg(feature = "security")]
 use embassy_time::TimeoutError;
+#[cfg(feature = "gatt")]
+use embassy_time::{Duration, Instant, Timer};
 
 use crate::connection::{Connection, ConnectionEvent, SecurityLevel};
 use crate::host::EventHandler;
 use crate::pdu::Pdu;
 use crate::prelude::sar::PacketReassembly;
 #[cfg(feature = "security")]
-use crate::security_manager::{SecurityEventData, SecurityManager};
+use crate::security_manager::{OobData, SecurityEventData, SecurityManager};
 use crate::{config, Error, Identity, PacketPool};
 
+/// Time allowed for the client to confirm an indication ([Vol 3] Part F, Section 3.3.3).
+#[cfg(feature = "gatt")]
+pub(crate) const ATT_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
 struct State<'d, P> {
     connections: &'d mut [ConnectionStorage<P>],
     central_waker: WakerRegistration,
@@ -48,6 +54,10 @@ impl<P> State<'_, P> {
 
 type EventChannel = Channel<NoopRawMutex, ConnectionEvent, { config::CONNECTION_EVENT_QUEUE_SIZE }>;
 type GattChannel<P> = Channel<NoopRawMutex, Pdu<P>, { config::L2CAP_RX_QUEUE_SIZE }>;
+#[cfg(feature = "security")]
+type PairingChannel = Channel<NoopRawMutex, Result<SecurityLevel, Error>, 1>;
+#[cfg(feature = "gatt")]
+type ConfirmationChannel = Channel<NoopRawMutex, Result<(), Error>, 1>;
 
 pub(crate) struct ConnectionManager<'d, P: PacketPool> {
     state: RefCell<State<'d, P::Packet>>,
@@ -161,10 +171,7 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
     pub(crate) fn peer_address(&self, index: u8) -> BdAddr {
         self.with_mut(|state| {
             let state = &mut state.connections[index as usize];
-            match state.peer_identity {
-                Some(identity) => identity.bd_addr, // TODO: If irk is used, this addr might be outdated.
-                _ => BdAddr::default(),
-            }
+            state.peer_addr.unwrap_or_default()
         })
     }
 
@@ -286,13 +293,23 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
                 storage.reassembly.clear();
                 let _ = storage.events.try_send(ConnectionEvent::Disconnected { reason });
                 #[cfg(feature = "gatt")]
-                storage.gatt.clear();
+                {
+                    storage.gatt.clear();
+                    storage.indication_deadline = None;
+                    storage.att_timed_out = false;
+                    storage.indication_waker.wake();
+                    storage.confirmation.clear();
+                    let _ = storage.confirmation.try_send(Err(Error::Disconnected));
+                }
                 #[cfg(feature = "connection-metrics")]
                 storage.metrics.reset();
                 #[cfg(feature = "security")]
                 {
                     storage.security_level = SecurityLevel::NoEncryption;
                     storage.bondable = false;
+                    storage.peer_oob_data = None;
+                    storage.pairing.clear();
+                    let _ = storage.pairing.try_send(Err(Error::Disconnected));
                     let _ = self.security_manager.disconnect(h, storage.peer_identity);
                 }
                 return Ok(());
@@ -322,12 +339,21 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
                 storage.att_mtu = 23;
                 storage.handle.replace(handle);
                 storage.peer_addr_kind.replace(peer_addr_kind);
-                storage.peer_identity.replace(Identity {
+                storage.peer_addr.replace(peer_addr);
+                let identity = Identity {
                     bd_addr: peer_addr,
                     #[cfg(feature = "security")]
                     irk: None,
-                });
+                };
+                // Bonded peers using a resolvable private address are resolved with their identity resolving key.
+                #[cfg(feature = "security")]
+                let identity = self.security_manager.resolve_identity(&peer_addr).unwrap_or(identity);
+                storage.peer_identity.replace(identity);
                 storage.role.replace(role);
+                #[cfg(feature = "security")]
+                {
+                    storage.local_addr = self.security_manager.own_address();
+                }
 
                 match role {
                     LeConnRole::Central => {
@@ -579,6 +605,7 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
             if current_level != SecurityLevel::NoEncryption {
                 return Err(Error::NotSupported);
             }
+            self.state.borrow().connections[index as usize].pairing.clear();
             self.security_manager
                 .initiate(self, &self.state.borrow().connections[index as usize])
         }
@@ -586,6 +613,110 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
         Err(Error::NotSupported)
     }
 
+    /// Pair again with MITM protection on an encrypted but unauthenticated link.
+    #[cfg(feature = "security")]
+    pub(crate) fn request_authentication(&self, index: u8) -> Result<(), Error> {
+        self.state.borrow().connections[index as usize].pairing.clear();
+        self.security_manager
+            .reauthenticate(self, &self.state.borrow().connections[index as usize])
+    }
+
+    /// Wait for the outcome of the pairing procedure started by [`Self::request_security`].
+    #[cfg(feature = "security")]
+    pub(crate) async fn wait_pairing(&self, index: u8) -> Result<SecurityLevel, Error> {
+        poll_fn(|cx| self.with_mut(|state| state.connections[index as usize].pairing.poll_receive(cx))).await
+    }
+
+    /// Claim the unenhanced ATT bearer for an indication, failing if the confirmation of another one is outstanding.
+    ///
+    /// Fails with [`Error::Timeout`] once the bearer is unusable, see [`Self::check_att_bearer`].
+    #[cfg(feature = "gatt")]
+    pub(crate) fn start_indication(&self, index: u8) -> Result<(), Error> {
+        self.with_mut(|state| state.connections[index as usize].start_indication(Instant::now()))
+    }
+
+    /// Claim the unenhanced ATT bearer for an indication, waiting for the confirmation of the outstanding one.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn poll_start_indication(&self, index: u8, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.with_mut(|state| {
+            let storage = &mut state.connections[index as usize];
+            match storage.start_indication(Instant::now()) {
+                Err(Error::Busy) => {
+                    storage.indication_waker.register(cx.waker());
+                    Poll::Pending
+                }
+                result => Poll::Ready(result),
+            }
+        })
+    }
+
+    /// Release the unenhanced ATT bearer without waiting for the confirmation of the indication.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn end_indication(&self, index: u8) {
+        self.with_mut(|state| {
+            let storage = &mut state.connections[index as usize];
+            storage.indication_deadline = None;
+            storage.indication_waker.wake();
+        })
+    }
+
+    /// Fail with [`Error::Timeout`] if the unenhanced ATT bearer is unusable because an indication was not confirmed
+    /// within the ATT transaction timeout. No more ATT PDUs are sent on it until the link is disconnected.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn check_att_bearer(&self, index: u8) -> Result<(), Error> {
+        self.with_mut(|state| state.connections[index as usize].check_att_bearer(Instant::now()))
+    }
+
+    /// Consider the unenhanced ATT bearer unusable, as the confirmation of the outstanding indication timed out.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn indication_timed_out(&self, index: u8) {
+        self.with_mut(|state| state.connections[index as usize].expire_indication())
+    }
+
+    /// Apply the ATT transaction timeout to the outstanding indications, never returning.
+    #[cfg(feature = "gatt")]
+    pub(crate) async fn expire_indications(&self, index: u8) {
+        loop {
+            let deadline = poll_fn(|cx| {
+                self.with_mut(|state| {
+                    let storage = &mut state.connections[index as usize];
+                    match storage.indication_deadline {
+                        Some(deadline) if !storage.att_timed_out => Poll::Ready(deadline),
+                        _ => {
+                            storage.indication_waker.register(cx.waker());
+                            Poll::Pending
+                        }
+                    }
+                })
+            })
+            .await;
+            Timer::at(deadline).await;
+            if self.check_att_bearer(index).is_err() {
+                warn!("[link][indicate] indication not confirmed in time, ATT bearer unusable");
+            }
+        }
+    }
+
+    /// Handle a Handle Value Confirmation received from the client.
+    #[cfg(feature = "gatt")]
+    pub(crate) fn confirm_indication(&self, h: ConnHandle) -> Result<(), Error> {
+        self.with_connected_handle(h, |storage| {
+            if storage.indication_deadline.take().is_some() {
+                storage.indication_waker.wake();
+                let _ = storage.confirmation.try_send(Ok(()));
+            } else {
+                warn!("[link][indicate] unexpected confirmation on {:?}", h);
+            }
+            Ok(())
+        })
+    }
+
+    /// Wait for the confirmation of the indication started by [`Self::start_indication`].
+    #[cfg(feature = "gatt")]
+    pub(crate) async fn wait_confirmation(&self, index: u8) -> Result<(), Error> {
+        poll_fn(|cx| self.with_mut(|state| state.connections[index as usize].confirmation.poll_receive(cx))).await
+    }
+
     pub(crate) fn get_security_level(&self, index: u8) -> Result<SecurityLevel, Error> {
         let state = self.state.borrow();
         match state.connections[index as usize].state {
@@ -632,6 +763,18 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
         Err(Error::NotSupported)
     }
 
+    #[cfg(feature = "security")]
+    pub(crate) fn set_peer_oob_data(&self, index: u8, data: OobData) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        match state.connections[index as usize].state {
+            ConnectionState::Connected => {
+                state.connections[index as usize].peer_oob_data = Some(data);
+                Ok(())
+            }
+            _ => Err(Error::Disconnected),
+        }
+    }
+
     pub(crate) fn handle_security_channel(
         &self,
         handle: ConnHandle,
@@ -688,7 +831,7 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
         use bt_hci::cmd::link_control::Disconnect;
 
         match _event {
-            crate::security_manager::SecurityEventData::SendLongTermKey(handle) => {
+            crate::security_manager::SecurityEventData::SendLongTermKey(handle, ediv, rand) => {
                 let conn_info = self.state.borrow().connections.iter().find_map(|connection| {
                     match (connection.handle, connection.peer_identity) {
                         (Some(connection_handle), Some(identity)) => {
@@ -703,7 +846,7 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
                 });
 
                 if let Some((conn, identity)) = conn_info {
-                    if let Some(ltk) = self.security_manager.get_peer_long_term_key(&identity) {
+                    if let Some(ltk) = self.security_manager.get_peer_long_term_key(&identity, ediv, rand) {
                         let _ = host
                             .command(LeLongTermKeyRequestReply::new(handle, ltk.to_le_bytes()))
                             .await?;
@@ -738,10 +881,15 @@ impl<'d, P: PacketPool> ConnectionManager<'d, P> {
                             },
                         );
                 if let Some((index, role, identity)) = connection_data {
-                    if let Some(ltk) = self.security_manager.get_peer_long_term_key(&identity) {
+                    if let Some(bond) = self.security_manager.get_peer_bond_information(&identity) {
                         if let Some(LeConnRole::Central) = role {
-                            host.async_command(LeEnableEncryption::new(handle, [0; 8], 0, ltk.to_le_bytes()))
-                                .await?;
+                            host.async_command(LeEnableEncryption::new(
+                                handle,
+                                bond.rand,
+                                bond.ediv,
+                                bond.ltk.to_le_bytes(),
+                            ))
+                            .await?;
                         }
                     } else {
                         warn!("[host] Enable encryption failed, no long term key")
@@ -801,7 +949,12 @@ pub struct ConnectionStorage<P> {
     pub handle: Option<ConnHandle>,
     pub role: Option<LeConnRole>,
     pub peer_addr_kind: Option<AddrKind>,
+    /// Address used by the peer when the connection was established, which may be a resolvable private address.
+    pub peer_addr: Option<BdAddr>,
     pub peer_identity: Option<Identity>,
+    /// Local address used when the connection was established.
+    #[cfg(feature = "security")]
+    pub local_addr: Option<crate::Address>,
     pub att_mtu: u16,
     pub link_credits: usize,
     pub link_credit_waker: WakerRegistration,
@@ -812,12 +965,30 @@ pub struct ConnectionStorage<P> {
     pub security_level: SecurityLevel,
     #[cfg(feature = "security")]
     pub bondable: bool,
+    /// Out-of-band data received from the peer for pairing.
+    #[cfg(feature = "security")]
+    pub peer_oob_data: Option<OobData>,
+    /// Outcome of the last pairing procedure, mirroring the pairing connection events.
+    #[cfg(feature = "security")]
+    pub pairing: PairingChannel,
     pub events: EventChannel,
     pub reassembly: PacketReassembly<P>,
     #[cfg(feature = "gatt")]
     pub gatt: GattChannel<P>,
     #[cfg(feature = "gatt")]
     pub(crate) gatt_client: GattChannel<P>,
+    /// An indication was sent on the unenhanced ATT bearer and its confirmation is due by this instant.
+    #[cfg(feature = "gatt")]
+    pub(crate) indication_deadline: Option<Instant>,
+    /// An indication was not confirmed in time, no more ATT PDUs may be sent on the unenhanced ATT bearer.
+    #[cfg(feature = "gatt")]
+    pub(crate) att_timed_out: bool,
+    /// Woken when the outstanding indication is confirmed or released.
+    #[cfg(feature = "gatt")]
+    pub(crate) indication_waker: WakerRegistration,
+    /// Outcome of the outstanding indication.
+    #[cfg(feature = "gatt")]
+    pub(crate) confirmation: ConfirmationChannel,
 }
 
 /// Connection metrics
@@ -883,13 +1054,48 @@ impl defmt::Format for Metrics {
 }
 
 impl<P> ConnectionStorage<P> {
+    #[cfg(feature = "gatt")]
+    fn start_indication(&mut self, now: Instant) -> Result<(), Error> {
+        self.check_att_bearer(now)?;
+        if self.indication_deadline.is_some() {
+            return Err(Error::Busy);
+        }
+        self.indication_deadline = Some(now + ATT_TRANSACTION_TIMEOUT);
+        self.indication_waker.wake();
+        self.confirmation.clear();
+        Ok(())
+    }
+
+    #[cfg(feature = "gatt")]
+    fn check_att_bearer(&mut self, now: Instant) -> Result<(), Error> {
+        if self.indication_deadline.is_some_and(|deadline| now >= deadline) {
+            self.expire_indication();
+        }
+        if self.att_timed_out {
+            Err(Error::Timeout)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "gatt")]
+    fn expire_indication(&mut self) {
+        self.indication_deadline = None;
+        self.att_timed_out = true;
+        self.indication_waker.wake();
+        let _ = self.confirmation.try_send(Err(Error::Timeout));
+    }
+
     pub(crate) const fn new() -> ConnectionStorage<P> {
         ConnectionStorage {
             state: ConnectionState::Disconnected,
             handle: None,
             role: None,
             peer_addr_kind: None,
+            peer_addr: None,
             peer_identity: None,
+            #[cfg(feature = "security")]
+            local_addr: None,
             att_mtu: 23,
             link_credits: 0,
             link_credit_waker: WakerRegistration::new(),
@@ -903,9 +1109,21 @@ impl<P> ConnectionStorage<P> {
             gatt: GattChannel::new(),
             #[cfg(feature = "gatt")]
             gatt_client: GattChannel::new(),
+            #[cfg(feature = "gatt")]
+            indication_deadline: None,
+            #[cfg(feature = "gatt")]
+            att_timed_out: false,
+            #[cfg(feature = "gatt")]
+            indication_waker: WakerRegistration::new(),
+            #[cfg(feature = "gatt")]
+            confirmation: ConfirmationChannel::new(),
             reassembly: PacketReassembly::new(),
             #[cfg(feature = "security")]
             bondable: false,
+            #[cfg(feature = "security")]
+            peer_oob_data: None,
+            #[cfg(feature = "security")]
+            pairing: PairingChannel::new(),
         }
     }
 }
@@ -1050,6 +1268,31 @@ pub(crate) mod tests {
         handle.disconnect();
     }
 
+    #[cfg(feature = "security")]
+    #[test]
+    fn peer_identity_resolved() {
+        let mgr = setup();
+        let identity = Identity {
+            bd_addr: BdAddr::new(ADDR_2),
+            irk: Some(IdentityResolvingKey::new(0x8b3958c158ed64467bd27bc90d3cf54d)),
+        };
+        let bond = BondInformation::new(identity, LongTermKey::new(0), SecurityLevel::Encrypted, true);
+        unwrap!(mgr.security_manager.add_bond_information(bond));
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+
+        // Resolvable private address of the bonded peer
+        let rpa = BdAddr::new([0x92, 0xf2, 0x8f, 0x84, 0x72, 0x4f]);
+        unwrap!(mgr.connect(ConnHandle::new(0), AddrKind::RANDOM, rpa, LeConnRole::Peripheral));
+
+        let Poll::Ready(handle) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        assert_eq!(handle.peer_address(), rpa);
+        assert_eq!(handle.peer_identity(), identity);
+
+        handle.disconnect();
+    }
+
     #[test]
     fn central_connection_established() {
         let mgr = setup();
@@ -1253,6 +1496,89 @@ pub(crate) mod tests {
         assert!(!conn2.is_connected());
     }
 
+    #[cfg(feature = "security")]
+    #[test]
+    fn pairing_wait_ends_on_disconnect() {
+        let mgr = setup();
+
+        assert!(mgr.poll_accept(LeConnRole::Central, &[], None).is_pending());
+        let handle = ConnHandle::new(7);
+        unwrap!(mgr.connect(handle, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Central));
+        let Poll::Ready(conn) = mgr.poll_accept(LeConnRole::Central, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        let mut wait = core::pin::pin!(conn.wait_pairing());
+        assert!(embassy_futures::poll_once(wait.as_mut()).is_pending());
+
+        unwrap!(mgr.disconnected(handle, Status::UNSPECIFIED));
+        assert!(matches!(block_on(wait), Err(Error::Disconnected)));
+    }
+
+    #[cfg(feature = "gatt")]
+    #[test]
+    fn indication_confirmation() {
+        let mgr = setup();
+
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+        let handle = ConnHandle::new(8);
+        unwrap!(mgr.connect(handle, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Peripheral));
+        let Poll::Ready(conn) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        unwrap!(conn.start_indication());
+        assert!(matches!(conn.start_indication(), Err(Error::Busy)));
+
+        let mut wait = core::pin::pin!(conn.wait_confirmation());
+        assert!(embassy_futures::poll_once(wait.as_mut()).is_pending());
+        unwrap!(mgr.confirm_indication(handle));
+        assert!(block_on(wait).is_ok());
+
+        // The bearer is free again once the confirmation has been received.
+        unwrap!(conn.start_indication());
+        let mut wait = core::pin::pin!(conn.wait_confirmation());
+        assert!(embassy_futures::poll_once(wait.as_mut()).is_pending());
+        unwrap!(mgr.disconnected(handle, Status::UNSPECIFIED));
+        assert!(matches!(block_on(wait), Err(Error::Disconnected)));
+    }
+
+    #[cfg(feature = "gatt")]
+    #[test]
+    fn indication_timeout() {
+        let mgr = setup();
+
+        assert!(mgr.poll_accept(LeConnRole::Peripheral, &[], None).is_pending());
+        let handle = ConnHandle::new(8);
+        unwrap!(mgr.connect(handle, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Peripheral));
+        let Poll::Ready(conn) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+
+        unwrap!(conn.start_indication());
+        let wait = conn.wait_confirmation();
+        let expired = Instant::now() + ATT_TRANSACTION_TIMEOUT;
+        assert!(matches!(
+            mgr.with_connected_handle(handle, |storage| storage.check_att_bearer(expired)),
+            Err(Error::Timeout)
+        ));
+        assert!(matches!(block_on(wait), Err(Error::Timeout)));
+
+        // No more ATT PDUs are sent on the bearer, even once the confirmation is received.
+        unwrap!(mgr.confirm_indication(handle));
+        assert!(matches!(conn.check_att_bearer(), Err(Error::Timeout)));
+        assert!(matches!(conn.start_indication(), Err(Error::Timeout)));
+
+        // The bearer is usable again on the next connection.
+        unwrap!(mgr.disconnected(handle, Status::UNSPECIFIED));
+        drop(conn);
+        unwrap!(mgr.connect(handle, AddrKind::RANDOM, BdAddr::new(ADDR_1), LeConnRole::Peripheral));
+        let Poll::Ready(conn) = mgr.poll_accept(LeConnRole::Peripheral, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        unwrap!(conn.check_att_bearer());
+    }
+
     #[test]
     fn disconnecting_iterator_invalid() {
         let mgr = setup();
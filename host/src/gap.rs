@@ -17,13 +17,33 @@ use crate::prelude::*;
 const DEVICE_NAME_MAX_LENGTH: usize = 22;
 
 /// The number of attributes added by the GAP and GATT services
-/// GAP_SERVICE:       1
-/// ├── DEVICE_NAME:   2
-/// └── APPEARANCE:    2
-/// GATT_SERVICE:    + 1
-///                  ---
-///                  = 6
-pub const GAP_SERVICE_ATTRIBUTE_COUNT: usize = 6;
+/// GAP_SERVICE:                     1
+/// ├── DEVICE_NAME:                 2
+/// └── APPEARANCE:                  2
+/// GATT_SERVICE:                  + 1
+/// ├── SERVICE_CHANGED:             3 (including CCCD)
+/// ├── CLIENT_SUPPORTED_FEATURES:   2 (`security` feature only)
+/// └── DATABASE_HASH:               2 (`security` feature only)
+///                                ---
+///                                = 9 (13 with `security`)
+#[cfg(not(feature = "security"))]
+pub const GAP_SERVICE_ATTRIBUTE_COUNT: usize = 9;
+
+/// The number of attributes added by the GAP and GATT services
+/// GAP_SERVICE:                     1
+/// ├── DEVICE_NAME:                 2
+/// └── APPEARANCE:                  2
+/// GATT_SERVICE:                  + 1
+/// ├── SERVICE_CHANGED:             3 (including CCCD)
+/// ├── CLIENT_SUPPORTED_FEATURES:   2
+/// └── DATABASE_HASH:               2
+///                                ---
+///                                = 13
+#[cfg(feature = "security")]
+pub const GAP_SERVICE_ATTRIBUTE_COUNT: usize = 13;
+
+/// The number of CCCDs added by the GAP and GATT services (Service Changed)
+pub const GAP_SERVICE_CCCD_COUNT: usize = 1;
 
 /// Configuration for the GAP Service.
 pub enum GapConfig<'a> {
@@ -81,40 +101,149 @@ impl<'a> GapConfig<'a> {
 
 impl<'a> PeripheralConfig<'a> {
     /// Add the peripheral GAP config to the attribute table
+    ///
+    /// A peripheral config can only be added to a single attribute table.
     fn build<M: RawMutex, const MAX: usize>(self, table: &mut AttributeTable<'a, M, MAX>) -> Result<(), &'static str> {
         static PERIPHERAL_NAME: StaticCell<String<DEVICE_NAME_MAX_LENGTH>> = StaticCell::new();
-        let peripheral_name = PERIPHERAL_NAME.init(String::new());
+        static PERIPHERAL_GATT: StaticCell<GattServiceValues> = StaticCell::new();
+        let peripheral_name = PERIPHERAL_NAME
+            .try_init(String::new())
+            .ok_or("Peripheral GAP config has already been built")?;
         peripheral_name
             .push_str(self.name)
             .map_err(|_| "Device name is too long. Max length is 22 bytes")?;
+        let gatt_values = PERIPHERAL_GATT
+            .try_init(GattServiceValues::new())
+            .ok_or("Peripheral GAP config has already been built")?;
 
         let mut gap_builder = table.add_service(Service::new(service::GAP));
         gap_builder.add_characteristic_ro(characteristic::DEVICE_NAME, peripheral_name);
         gap_builder.add_characteristic_ro(characteristic::APPEARANCE, self.appearance);
         gap_builder.build();
 
-        table.add_service(Service::new(service::GATT));
+        build_gatt_service(table, gatt_values);
 
         Ok(())
     }
 }
 
 impl<'a> CentralConfig<'a> {
-    /// Add the peripheral GAP config to the attribute table
+    /// Add the central GAP config to the attribute table
+    ///
+    /// A central config can only be added to a single attribute table.
     fn build<M: RawMutex, const MAX: usize>(self, table: &mut AttributeTable<'a, M, MAX>) -> Result<(), &'static str> {
         static CENTRAL_NAME: StaticCell<String<DEVICE_NAME_MAX_LENGTH>> = StaticCell::new();
-        let central_name = CENTRAL_NAME.init(String::new());
+        static CENTRAL_GATT: StaticCell<GattServiceValues> = StaticCell::new();
+        let central_name = CENTRAL_NAME
+            .try_init(String::new())
+            .ok_or("Central GAP config has already been built")?;
         central_name
             .push_str(self.name)
             .map_err(|_| "Device name is too long. Max length is 22 bytes")?;
+        let gatt_values = CENTRAL_GATT
+            .try_init(GattServiceValues::new())
+            .ok_or("Central GAP config has already been built")?;
 
         let mut gap_builder = table.add_service(Service::new(service::GAP));
         gap_builder.add_characteristic_ro(characteristic::DEVICE_NAME, central_name);
         gap_builder.add_characteristic_ro(characteristic::APPEARANCE, self.appearance);
         gap_builder.build();
 
-        table.add_service(Service::new(service::GATT));
+        build_gatt_service(table, gatt_values);
 
         Ok(())
     }
 }
+
+/// Storage for the characteristic values of the Generic Attribute service.
+struct GattServiceValues {
+    service_changed: [u8; 4],
+    #[cfg(feature = "security")]
+    client_supported_features: [u8; 1],
+    #[cfg(feature = "security")]
+    database_hash: [u8; 16],
+}
+
+impl GattServiceValues {
+    const fn new() -> Self {
+        Self {
+            service_changed: [0; 4],
+            #[cfg(feature = "security")]
+            client_supported_features: [0; 1],
+            #[cfg(feature = "security")]
+            database_hash: [0; 16],
+        }
+    }
+}
+
+/// Add the Generic Attribute service to the attribute table
+///
+/// The values of the Client Supported Features and Database Hash characteristics are managed by the attribute server.
+fn build_gatt_service<'a, M: RawMutex, const MAX: usize>(
+    table: &mut AttributeTable<'a, M, MAX>,
+    values: &'a mut GattServiceValues,
+) {
+    let mut gatt_builder = table.add_service(Service::new(service::GATT));
+    gatt_builder.add_characteristic(
+        characteristic::SERVICE_CHANGED,
+        &[CharacteristicProp::Indicate],
+        [0u8; 4],
+        &mut values.service_changed,
+    );
+
+    #[cfg(feature = "security")]
+    {
+        gatt_builder.add_characteristic(
+            characteristic::CLIENT_SUPPORTED_FEATURES,
+            &[CharacteristicProp::Read, CharacteristicProp::Write],
+            [0u8; 1],
+            &mut values.client_supported_features,
+        );
+        gatt_builder.add_characteristic(
+            characteristic::DATABASE_HASH,
+            &[CharacteristicProp::Read],
+            [0u8; 16],
+            &mut values.database_hash,
+        );
+    }
+    gatt_builder.build();
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+
+    fn count<const MAX: usize>(table: &AttributeTable<'_, NoopRawMutex, MAX>) -> usize {
+        table.iterate(|mut it| {
+            let mut n = 0;
+            while it.next().is_some() {
+                n += 1;
+            }
+            n
+        })
+    }
+
+    #[test]
+    fn test_gap_config_build() {
+        let mut peripheral: AttributeTable<'_, NoopRawMutex, GAP_SERVICE_ATTRIBUTE_COUNT> = AttributeTable::new();
+        let mut central: AttributeTable<'_, NoopRawMutex, GAP_SERVICE_ATTRIBUTE_COUNT> = AttributeTable::new();
+        let mut again: AttributeTable<'_, NoopRawMutex, GAP_SERVICE_ATTRIBUTE_COUNT> = AttributeTable::new();
+
+        // Each config has its own storage, so both can be built
+        GapConfig::default("peripheral").build(&mut peripheral).unwrap();
+        GapConfig::Central(CentralConfig {
+            name: "central",
+            appearance: &appearance::UNKNOWN,
+        })
+        .build(&mut central)
+        .unwrap();
+        assert_eq!(count(&peripheral), GAP_SERVICE_ATTRIBUTE_COUNT);
+        assert_eq!(count(&central), GAP_SERVICE_ATTRIBUTE_COUNT);
+
+        // Building a config a second time is an error rather than a panic
+        assert!(GapConfig::default("peripheral").build(&mut again).is_err());
+        assert_eq!(count(&again), 0);
+    }
+}
@@ -1,35 +1,43 @@
 //! GATT server and client implementation.
-use core::cell::RefCell;
-use core::future::Future;
+use core::cell::{Cell, RefCell};
+use core::future::{poll_fn, Future};
 use core::marker::PhantomData;
 
 use bt_hci::controller::Controller;
-use bt_hci::param::{ConnHandle, PhyKind, Status};
-use bt_hci::uuid::declarations::{CHARACTERISTIC, PRIMARY_SERVICE};
+use bt_hci::param::{BdAddr, ConnHandle, PhyKind, Status};
+use bt_hci::uuid::characteristic::{CLIENT_SUPPORTED_FEATURES, DATABASE_HASH, SERVICE_CHANGED};
+use bt_hci::uuid::declarations::{CHARACTERISTIC, INCLUDE, PRIMARY_SERVICE};
 use bt_hci::uuid::descriptors::CLIENT_CHARACTERISTIC_CONFIGURATION;
-use embassy_futures::select::{select, Either};
+use embassy_futures::select::{select3, select4, select_array, Either3, Either4};
 use embassy_sync::blocking_mutex::raw::{NoopRawMutex, RawMutex};
 use embassy_sync::channel::Channel;
 use embassy_sync::pubsub::{self, PubSubChannel, WaitResult};
+use embassy_sync::signal::Signal;
 use embassy_time::Duration;
 use heapless::Vec;
 
-use crate::att::{self, Att, AttClient, AttCmd, AttErrorCode, AttReq, AttRsp, AttServer, AttUns, ATT_HANDLE_VALUE_NTF};
-use crate::attribute::{AttributeData, Characteristic, CharacteristicProp, Uuid};
-use crate::attribute_server::{AttributeServer, DynamicAttributeServer};
+use crate::att::{
+    self, Att, AttCfm, AttClient, AttCmd, AttErrorCode, AttReq, AttRsp, AttServer, AttUns, ATT_HANDLE_VALUE_IND,
+    ATT_HANDLE_VALUE_NTF, ATT_MULTIPLE_HANDLE_VALUE_NTF,
+};
+use crate::attribute::{AttributeData, Characteristic, CharacteristicProp, CharacteristicProps, Uuid};
+use crate::attribute_server::{
+    AttributeServer, DynamicAttributeServer, EATT_SUPPORTED, MULTIPLE_HANDLE_VALUE_NOTIFICATIONS,
+};
 use crate::connection::Connection;
 #[cfg(feature = "security")]
 use crate::connection::SecurityLevel;
 use crate::cursor::{ReadCursor, WriteCursor};
-use crate::pdu::Pdu;
+use crate::l2cap::{L2capChannel, L2capChannelConfig, L2capChannelMode, L2capChannelRef};
+use crate::pdu::{Pdu, Sdu};
 use crate::prelude::ConnectionEvent;
 #[cfg(feature = "security")]
 use crate::security_manager::PassKey;
-use crate::types::gatt_traits::{AsGatt, FromGatt, FromGattError};
-use crate::types::l2cap::L2capHeader;
+use crate::types::gatt_traits::{AsGatt, FixedGattValue, FromGatt, FromGattError};
+use crate::types::l2cap::{L2capHeader, LeCreditConnResultCode};
+use crate::{config, BleHostError, Error, Identity, PacketPool, Stack};
 #[cfg(feature = "security")]
-use crate::BondInformation;
-use crate::{config, BleHostError, Error, PacketPool, Stack};
+use crate::{BondInformation, IdentityResolvingKey};
 
 /// A GATT connection event.
 pub enum GattConnectionEvent<'stack, 'server, P: PacketPool> {
@@ -148,75 +156,207 @@ impl<'stack, 'server, P: PacketPool> GattConnection<'stack, 'server, P> {
     ///
     /// Uses the attribute server to handle the protocol.
     pub async fn next(&self) -> GattConnectionEvent<'stack, 'server, P> {
-        match select(self.connection.next(), self.connection.next_gatt()).await {
-            Either::First(event) => match event {
-                ConnectionEvent::Disconnected { reason } => GattConnectionEvent::Disconnected { reason },
-                ConnectionEvent::ConnectionParamsUpdated {
-                    conn_interval,
-                    peripheral_latency,
-                    supervision_timeout,
-                } => GattConnectionEvent::ConnectionParamsUpdated {
-                    conn_interval,
-                    peripheral_latency,
-                    supervision_timeout,
-                },
-                ConnectionEvent::RequestConnectionParams {
-                    min_connection_interval,
-                    max_connection_interval,
-                    max_latency,
-                    supervision_timeout,
-                } => GattConnectionEvent::RequestConnectionParams {
-                    min_connection_interval,
-                    max_connection_interval,
-                    max_latency,
-                    supervision_timeout,
-                },
-                ConnectionEvent::PhyUpdated { tx_phy, rx_phy } => GattConnectionEvent::PhyUpdated { tx_phy, rx_phy },
-                ConnectionEvent::DataLengthUpdated {
-                    max_tx_octets,
-                    max_tx_time,
-                    max_rx_octets,
-                    max_rx_time,
-                } => GattConnectionEvent::DataLengthUpdated {
-                    max_tx_octets,
-                    max_tx_time,
-                    max_rx_octets,
-                    max_rx_time,
-                },
-
-                #[cfg(feature = "security")]
-                ConnectionEvent::PassKeyDisplay(key) => GattConnectionEvent::PassKeyDisplay(key),
-
-                #[cfg(feature = "security")]
-                ConnectionEvent::PassKeyConfirm(key) => GattConnectionEvent::PassKeyConfirm(key),
-
-                #[cfg(feature = "security")]
-                ConnectionEvent::PassKeyInput => GattConnectionEvent::PassKeyInput,
+        loop {
+            let service_changed = poll_fn(|cx| self.server.poll_service_changed(&self.connection, cx));
+            let handle = match select4(
+                service_changed,
+                self.connection.next(),
+                self.connection.next_gatt(),
+                self.connection.expire_indications(),
+            )
+            .await
+            {
+                Either4::First(handle) => handle,
+                Either4::Second(event) => {
+                    return match event {
+                        ConnectionEvent::Disconnected { reason } => GattConnectionEvent::Disconnected { reason },
+                        ConnectionEvent::ConnectionParamsUpdated {
+                            conn_interval,
+                            peripheral_latency,
+                            supervision_timeout,
+                        } => GattConnectionEvent::ConnectionParamsUpdated {
+                            conn_interval,
+                            peripheral_latency,
+                            supervision_timeout,
+                        },
+                        ConnectionEvent::RequestConnectionParams {
+                            min_connection_interval,
+                            max_connection_interval,
+                            max_latency,
+                            supervision_timeout,
+                        } => GattConnectionEvent::RequestConnectionParams {
+                            min_connection_interval,
+                            max_connection_interval,
+                            max_latency,
+                            supervision_timeout,
+                        },
+                        ConnectionEvent::PhyUpdated { tx_phy, rx_phy } => {
+                            GattConnectionEvent::PhyUpdated { tx_phy, rx_phy }
+                        }
+                        ConnectionEvent::DataLengthUpdated {
+                            max_tx_octets,
+                            max_tx_time,
+                            max_rx_octets,
+                            max_rx_time,
+                        } => GattConnectionEvent::DataLengthUpdated {
+                            max_tx_octets,
+                            max_tx_time,
+                            max_rx_octets,
+                            max_rx_time,
+                        },
+
+                        #[cfg(feature = "security")]
+                        ConnectionEvent::PassKeyDisplay(key) => GattConnectionEvent::PassKeyDisplay(key),
+
+                        #[cfg(feature = "security")]
+                        ConnectionEvent::PassKeyConfirm(key) => GattConnectionEvent::PassKeyConfirm(key),
+
+                        #[cfg(feature = "security")]
+                        ConnectionEvent::PassKeyInput => GattConnectionEvent::PassKeyInput,
+
+                        #[cfg(feature = "security")]
+                        ConnectionEvent::PairingComplete { security_level, bond } => {
+                            GattConnectionEvent::PairingComplete { security_level, bond }
+                        }
 
-                #[cfg(feature = "security")]
-                ConnectionEvent::PairingComplete { security_level, bond } => {
-                    GattConnectionEvent::PairingComplete { security_level, bond }
+                        #[cfg(feature = "security")]
+                        ConnectionEvent::PairingFailed(err) => GattConnectionEvent::PairingFailed(err),
+                    }
+                }
+                Either4::Third(data) => {
+                    // Signed writes with an invalid signature are ignored ([Vol 3] Part C, Section 10.4.2). The
+                    // signature is verified again when the write is processed, which records its sign counter.
+                    if data.as_ref().first() == Some(&att::ATT_SIGNED_WRITE_CMD) && !self.check_signature(&data) {
+                        continue;
+                    }
+                    return GattConnectionEvent::Gatt {
+                        event: GattEvent::new(GattData::new(data, self.connection.clone()), self.server),
+                    };
                 }
+                Either4::Fourth(()) => continue,
+            };
 
-                #[cfg(feature = "security")]
-                ConnectionEvent::PairingFailed(err) => GattConnectionEvent::PairingFailed(err),
-            },
-            Either::Second(data) => GattConnectionEvent::Gatt {
-                event: GattEvent::new(GattData::new(data, self.connection.clone()), self.server),
-            },
+            // The client has a stale view of the attribute table, tell it that any handle may have changed.
+            let uns = AttUns::Indicate {
+                handle,
+                data: &[0x01, 0x00, 0xff, 0xff],
+            };
+            if let Err(e) = GattData::send_unsolicited(&self.connection, uns).await {
+                self.server.retry_service_changed(&self.connection);
+                self.connection.end_indication();
+                warn!("[gatt] error sending service changed indication: {:?}", e);
+            }
+        }
+    }
+
+    fn check_signature(&self, pdu: &Pdu<P::Packet>) -> bool {
+        #[cfg(feature = "security")]
+        {
+            let pdu = pdu.as_ref();
+            let Some(len) = pdu.len().checked_sub(12) else {
+                return false;
+            };
+            let signature = unwrap!(pdu[len..].try_into());
+            self.connection.check_signature(&[&pdu[..len]], signature).is_ok()
         }
+        #[cfg(not(feature = "security"))]
+        false
     }
 
     /// Get a reference to the underlying BLE connection.
     pub fn raw(&self) -> &Connection<'stack, P> {
         &self.connection
     }
+
+    /// Start a batch of notifications sent together to the client.
+    ///
+    /// See [`NotificationBatch`] for details.
+    pub fn notification_batch(&self) -> Result<NotificationBatch<'_, 'stack, 'server, P>, Error> {
+        let tx = P::allocate().ok_or(Error::OutOfMemory)?;
+        Ok(NotificationBatch {
+            connection: self,
+            tx,
+            len: 0,
+            count: 0,
+        })
+    }
+}
+
+/// A batch of characteristic values notified to the client together.
+///
+/// If the client enabled Multiple Handle Value Notifications in its Client Supported Features, the values are sent in
+/// a single ATT_MULTIPLE_HANDLE_VALUE_NTF PDU. Otherwise, they are sent as individual notifications.
+pub struct NotificationBatch<'conn, 'stack, 'server, P: PacketPool> {
+    connection: &'conn GattConnection<'stack, 'server, P>,
+    tx: P::Packet,
+    // Length of the handle, length and value tuples written after the L2CAP header and opcode
+    len: usize,
+    count: usize,
+}
+
+impl<P: PacketPool> NotificationBatch<'_, '_, '_, P> {
+    const OFFSET: usize = 5;
+
+    /// Write a value to a characteristic, and add it to the batch.
+    ///
+    /// If the client has not subscribed for this characteristic, the value is not added.
+    ///
+    /// If the characteristic does not support notifications, an error is returned. If the value does not fit in the
+    /// ATT MTU with the values already added, [`Error::InsufficientSpace`] is returned.
+    pub fn add<T: AsGatt>(&mut self, characteristic: &Characteristic<T>, value: &T) -> Result<(), Error> {
+        let value = value.as_gatt();
+        let server = self.connection.server;
+        server.set(characteristic.handle, value)?;
+
+        let cccd_handle = characteristic.cccd_handle.ok_or(Error::NotFound)?;
+        let connection = &self.connection.connection;
+        if !server.should_notify(connection, cccd_handle) {
+            return Ok(());
+        }
+
+        let mtu = connection.get_att_mtu() as usize;
+        if 1 + self.len + 4 + value.len() > mtu {
+            return Err(Error::InsufficientSpace);
+        }
+        let mut w = WriteCursor::new(&mut self.tx.as_mut()[Self::OFFSET + self.len..]);
+        w.write(characteristic.handle)?;
+        w.write(value.len() as u16)?;
+        w.append(value)?;
+        self.len += w.len();
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Send the values in the batch to the client.
+    pub async fn send(self) -> Result<(), Error> {
+        let connection = &self.connection.connection;
+        if self.count > 1 && self.connection.server.supports_multiple_notifications(connection) {
+            let mut tx = self.tx;
+            let mut w = WriteCursor::new(tx.as_mut());
+            w.write(1 + self.len as u16)?;
+            w.write(4_u16)?;
+            w.write(ATT_MULTIPLE_HANDLE_VALUE_NTF)?;
+            connection.send(Pdu::new(tx, Self::OFFSET + self.len)).await;
+            return Ok(());
+        }
+
+        let mut r = ReadCursor::new(&self.tx.as_ref()[Self::OFFSET..Self::OFFSET + self.len]);
+        while r.available() > 0 {
+            let handle: u16 = r.read()?;
+            let len: u16 = r.read()?;
+            let data = r.slice(len as usize)?;
+            GattData::send_unsolicited(connection, AttUns::Notify { handle, data }).await?;
+        }
+        Ok(())
+    }
 }
 
 /// A GATT payload ready for processing.
 pub struct GattData<'stack, P: PacketPool> {
     pdu: Option<Pdu<P::Packet>>,
     connection: Connection<'stack, P>,
+    // EATT bearer the payload was received on, the reply is sent on the same bearer.
+    bearer: Option<L2capChannelRef<'stack, P>>,
 }
 
 impl<'stack, P: PacketPool> GattData<'stack, P> {
@@ -224,6 +364,19 @@ impl<'stack, P: PacketPool> GattData<'stack, P> {
         Self {
             pdu: Some(pdu),
             connection,
+            bearer: None,
+        }
+    }
+
+    pub(crate) const fn with_bearer(
+        pdu: Pdu<P::Packet>,
+        connection: Connection<'stack, P>,
+        bearer: L2capChannelRef<'stack, P>,
+    ) -> Self {
+        Self {
+            pdu: Some(pdu),
+            connection,
+            bearer: Some(bearer),
         }
     }
 
@@ -234,8 +387,10 @@ impl<'stack, P: PacketPool> GattData<'stack, P> {
         match self.incoming() {
             AttClient::Request(AttReq::Write { handle, .. }) => Some(handle),
             AttClient::Command(AttCmd::Write { handle, .. }) => Some(handle),
+            AttClient::Command(AttCmd::SignedWrite { handle, .. }) => Some(handle),
             AttClient::Request(AttReq::Read { handle }) => Some(handle),
             AttClient::Request(AttReq::ReadBlob { handle, .. }) => Some(handle),
+            AttClient::Request(AttReq::PrepareWrite { handle, .. }) => Some(handle),
             _ => None,
         }
     }
@@ -255,8 +410,12 @@ impl<'stack, P: PacketPool> GattData<'stack, P> {
 
     /// Respond directly to request.
     pub async fn reply(self, rsp: AttRsp<'_>) -> Result<(), Error> {
-        let pdu = assemble(&self.connection, AttServer::Response(rsp))?;
-        self.connection.send(pdu).await;
+        let mtu = att_mtu(&self.connection, self.bearer.as_ref())?;
+        let pdu = assemble_mtu::<P>(mtu, AttServer::Response(rsp))?;
+        match &self.bearer {
+            Some(bearer) => bearer.send_queued(&self.connection, &pdu.as_ref()[4..]).await?,
+            None => self.connection.send(pdu).await,
+        }
         Ok(())
     }
 
@@ -283,9 +442,10 @@ impl<'stack, 'server, P: PacketPool> GattEvent<'stack, 'server, P> {
     pub fn new(data: GattData<'stack, P>, server: &'server dyn DynamicAttributeServer<P>) -> Self {
         let att = data.incoming();
         match att {
-            AttClient::Request(AttReq::Write { .. }) | AttClient::Command(AttCmd::Write { .. }) => {
-                GattEvent::Write(WriteEvent { data, server })
-            }
+            AttClient::Request(AttReq::Write { .. })
+            | AttClient::Command(AttCmd::Write { .. })
+            | AttClient::Command(AttCmd::SignedWrite { .. })
+            | AttClient::Request(AttReq::PrepareWrite { .. }) => GattEvent::Write(WriteEvent { data, server }),
             AttClient::Request(AttReq::Read { .. }) | AttClient::Request(AttReq::ReadBlob { .. }) => {
                 GattEvent::Read(ReadEvent { data, server })
             }
@@ -349,6 +509,16 @@ impl<'stack, P: PacketPool> ReadEvent<'stack, '_, P> {
         unwrap!(self.data.handle())
     }
 
+    /// Offset into the characteristic value that was read.
+    ///
+    /// Non-zero for Read Blob requests, which clients use to read values longer than the ATT MTU.
+    pub fn offset(&self) -> usize {
+        match self.data.incoming() {
+            AttClient::Request(AttReq::ReadBlob { offset, .. }) => offset as usize,
+            _ => 0,
+        }
+    }
+
     /// Accept the event, making it processed by the server.
     ///
     /// Automatically called if drop() is invoked.
@@ -361,6 +531,23 @@ impl<'stack, P: PacketPool> ReadEvent<'stack, '_, P> {
         process(&mut self.data, self.server, Err(err))
     }
 
+    /// Answer a read of a deferred attribute with a value provided by the application.
+    ///
+    /// `value` holds the attribute value starting at [`Self::offset`], it is truncated to fit in the response. Reads
+    /// at an offset past the end of the value must be rejected with [`AttErrorCode::INVALID_OFFSET`] instead.
+    pub fn respond(mut self, value: &[u8]) -> Result<Reply<'stack, P>, Error> {
+        let Some(pdu) = self.data.pdu.take() else {
+            return Ok(Reply::new(self.data.connection.clone(), None, None));
+        };
+        let server = self.server;
+        process_with(
+            &pdu,
+            &self.data.connection,
+            self.data.bearer.take(),
+            |connection, att, buf| server.respond(connection, att, value, buf),
+        )
+    }
+
     /// Get a reference to the underlying `GattData` payload that this event is enclosing
     pub fn payload(&self) -> &GattData<'stack, P> {
         &self.data
@@ -376,6 +563,7 @@ impl<'stack, P: PacketPool> ReadEvent<'stack, '_, P> {
         GattData {
             pdu: self.data.pdu.take(),
             connection: self.data.connection.clone(),
+            bearer: self.data.bearer.take(),
         }
     }
 }
@@ -400,10 +588,27 @@ impl<'stack, P: PacketPool> WriteEvent<'stack, '_, P> {
         unwrap!(self.data.handle())
     }
 
-    /// Raw data to be written
+    /// Offset into the characteristic value that is written.
+    ///
+    /// Long writes are split into Prepare Write requests, each holding the part of the value starting at this
+    /// offset. The parts are queued by the client and only take effect once it executes the queue with an Execute
+    /// Write request, delivered as [`GattEvent::Other`], or are discarded if it cancels the queue.
+    pub fn offset(&self) -> usize {
+        match self.data.incoming() {
+            AttClient::Request(AttReq::PrepareWrite { offset, .. }) => offset as usize,
+            _ => 0,
+        }
+    }
+
+    /// Raw data to be written, starting at [`Self::offset`].
     pub fn data(&self) -> &[u8] {
-        // Note: write event data is always at offset 3, right?
-        &self.data.pdu.as_ref().unwrap().as_ref()[3..]
+        let pdu = self.data.pdu.as_ref().unwrap().as_ref();
+        match pdu[0] {
+            // Followed by the 12 byte signature.
+            att::ATT_SIGNED_WRITE_CMD => &pdu[3..pdu.len() - 12],
+            att::ATT_PREPARE_WRITE_REQ => &pdu[5..],
+            _ => &pdu[3..],
+        }
     }
 
     /// Characteristic data to be written
@@ -438,6 +643,7 @@ impl<'stack, P: PacketPool> WriteEvent<'stack, '_, P> {
         GattData {
             pdu: self.data.pdu.take(),
             connection: self.data.connection.clone(),
+            bearer: self.data.bearer.take(),
         }
     }
 }
@@ -482,6 +688,7 @@ impl<'stack, P: PacketPool> OtherEvent<'stack, '_, P> {
         GattData {
             pdu: self.data.pdu.take(),
             connection: self.data.connection.clone(),
+            bearer: self.data.bearer.take(),
         }
     }
 }
@@ -501,23 +708,40 @@ where
     P: PacketPool,
 {
     if let Some(pdu) = data.pdu.take() {
+        let bearer = data.bearer.take();
         let res = match result {
-            Ok(_) => process_accept(&pdu, &data.connection, server),
-            Err(code) => process_reject(&pdu, &data.connection, code),
+            Ok(_) => process_accept(&pdu, &data.connection, bearer, server),
+            Err(code) => process_reject(&pdu, &data.connection, bearer, code),
         };
         res
     } else {
-        Ok(Reply::new(data.connection.clone(), None))
+        Ok(Reply::new(data.connection.clone(), None, None))
     }
 }
 
 fn process_accept<'stack, P>(
     pdu: &Pdu<P::Packet>,
     connection: &Connection<'stack, P>,
+    bearer: Option<L2capChannelRef<'stack, P>>,
     server: &dyn DynamicAttributeServer<P>,
 ) -> Result<Reply<'stack, P>, Error>
 where
     P: PacketPool,
+{
+    process_with(pdu, connection, bearer, |connection, att, buf| {
+        server.process(connection, att, buf)
+    })
+}
+
+fn process_with<'stack, P, F>(
+    pdu: &Pdu<P::Packet>,
+    connection: &Connection<'stack, P>,
+    bearer: Option<L2capChannelRef<'stack, P>>,
+    f: F,
+) -> Result<Reply<'stack, P>, Error>
+where
+    P: PacketPool,
+    F: FnOnce(&Connection<'stack, P>, &AttClient<'_>, &mut [u8]) -> Result<Option<usize>, Error>,
 {
     // - The PDU is decodable, as it was already decoded once before adding it to the connection queue
     // - The PDU is of type `Att::Client` because only those types of PDUs are added to the connection queue
@@ -528,23 +752,24 @@ where
     let mut tx = P::allocate().ok_or(Error::OutOfMemory)?;
     let mut w = WriteCursor::new(tx.as_mut());
     let (mut header, mut data) = w.split(4)?;
-    if let Some(written) = server.process(connection, &att, data.write_buf())? {
-        let mtu = connection.get_att_mtu();
+    if let Some(written) = f(connection, &att, data.write_buf())? {
+        let mtu = att_mtu(connection, bearer.as_ref())?;
         data.commit(written)?;
         data.truncate(mtu as usize);
         header.write(data.len() as u16)?;
         header.write(4_u16)?;
         let len = header.len() + data.len();
         let pdu = Pdu::new(tx, len);
-        Ok(Reply::new(connection.clone(), Some(pdu)))
+        Ok(Reply::new(connection.clone(), bearer, Some(pdu)))
     } else {
-        Ok(Reply::new(connection.clone(), None))
+        Ok(Reply::new(connection.clone(), bearer, None))
     }
 }
 
 fn process_reject<'stack, P: PacketPool>(
     pdu: &Pdu<P::Packet>,
     connection: &Connection<'stack, P>,
+    bearer: Option<L2capChannelRef<'stack, P>>,
     code: AttErrorCode,
 ) -> Result<Reply<'stack, P>, Error> {
     // - The PDU is decodable, as it was already decoded once before adding it to the connection queue
@@ -556,27 +781,48 @@ fn process_reject<'stack, P: PacketPool>(
     let handle = match att {
         AttClient::Request(AttReq::Write { handle, .. }) => handle,
         AttClient::Command(AttCmd::Write { handle, .. }) => handle,
+        AttClient::Command(AttCmd::SignedWrite { handle, .. }) => handle,
         AttClient::Request(AttReq::Read { handle }) => handle,
         AttClient::Request(AttReq::ReadBlob { handle, .. }) => handle,
+        AttClient::Request(AttReq::PrepareWrite { handle, .. }) => handle,
         _ => 0, // As per spec, if the incoming ATT does not have an ATT handle, we should report with handle 0
     };
     // We know it has been checked, therefore this cannot fail
     let request = pdu.as_ref()[0];
     let rsp = AttRsp::Error { request, handle, code };
-    let pdu = assemble(connection, AttServer::Response(rsp))?;
-    Ok(Reply::new(connection.clone(), Some(pdu)))
+    let mtu = att_mtu(connection, bearer.as_ref())?;
+    let pdu = assemble_mtu::<P>(mtu, AttServer::Response(rsp))?;
+    Ok(Reply::new(connection.clone(), bearer, Some(pdu)))
+}
+
+/// ATT MTU of the bearer used to reply, either an EATT bearer or the unenhanced ATT bearer of the connection.
+fn att_mtu<P: PacketPool>(
+    connection: &Connection<'_, P>,
+    bearer: Option<&L2capChannelRef<'_, P>>,
+) -> Result<u16, Error> {
+    match bearer {
+        Some(bearer) => bearer.mtu(),
+        None => {
+            connection.check_att_bearer()?;
+            Ok(connection.get_att_mtu())
+        }
+    }
 }
 
 pub(crate) fn assemble<'stack, P: PacketPool>(
     conn: &Connection<'stack, P>,
     att: AttServer<'_>,
 ) -> Result<Pdu<P::Packet>, Error> {
+    conn.check_att_bearer()?;
+    assemble_mtu::<P>(conn.get_att_mtu(), att)
+}
+
+fn assemble_mtu<P: PacketPool>(mtu: u16, att: AttServer<'_>) -> Result<Pdu<P::Packet>, Error> {
     let mut tx = P::allocate().ok_or(Error::OutOfMemory)?;
     let mut w = WriteCursor::new(tx.as_mut());
     let (mut header, mut data) = w.split(4)?;
     data.write(Att::Server(att))?;
 
-    let mtu = conn.get_att_mtu();
     data.truncate(mtu as usize);
     header.write(data.len() as u16)?;
     header.write(4_u16)?;
@@ -588,31 +834,49 @@ pub(crate) fn assemble<'stack, P: PacketPool>(
 ///
 /// The reply may be sent immediately or queued for sending later. To guarantee delivery of a reply
 /// in case of a full outbound queue, the async send() should be used rather than relying on the Drop implementation.
+///
+/// Replies to requests received on an EATT bearer are sent on the same bearer.
 pub struct Reply<'stack, P: PacketPool> {
     connection: Connection<'stack, P>,
+    bearer: Option<L2capChannelRef<'stack, P>>,
     pdu: Option<Pdu<P::Packet>>,
 }
 
 impl<'stack, P: PacketPool> Reply<'stack, P> {
-    fn new(connection: Connection<'stack, P>, pdu: Option<Pdu<P::Packet>>) -> Self {
-        Self { connection, pdu }
+    fn new(
+        connection: Connection<'stack, P>,
+        bearer: Option<L2capChannelRef<'stack, P>>,
+        pdu: Option<Pdu<P::Packet>>,
+    ) -> Self {
+        Self {
+            connection,
+            bearer,
+            pdu,
+        }
     }
 
     /// Send the reply.
     ///
-    /// May fail if the outbound queue is full.
+    /// May fail if the outbound queue is full, or if the EATT bearer has no credits left.
     pub fn try_send(mut self) -> Result<(), Error> {
-        if let Some(pdu) = self.pdu.take() {
-            self.connection.try_send(pdu)
-        } else {
-            Ok(())
+        match (self.pdu.take(), &self.bearer) {
+            // The PDU is assembled with the header of the unenhanced ATT bearer.
+            (Some(pdu), Some(bearer)) => bearer.try_send_queued(&self.connection, &pdu.as_ref()[4..]),
+            (Some(pdu), None) => self.connection.try_send(pdu),
+            (None, _) => Ok(()),
         }
     }
 
     /// Send the reply.
     pub async fn send(mut self) {
-        if let Some(pdu) = self.pdu.take() {
-            self.connection.send(pdu).await
+        match (self.pdu.take(), &self.bearer) {
+            (Some(pdu), Some(bearer)) => {
+                if let Err(e) = bearer.send_queued(&self.connection, &pdu.as_ref()[4..]).await {
+                    warn!("[gatt] error sending reply on EATT bearer: {:?}", e);
+                }
+            }
+            (Some(pdu), None) => self.connection.send(pdu).await,
+            (None, _) => {}
         }
     }
 }
@@ -620,13 +884,136 @@ impl<'stack, P: PacketPool> Reply<'stack, P> {
 impl<P: PacketPool> Drop for Reply<'_, P> {
     fn drop(&mut self) {
         if let Some(pdu) = self.pdu.take() {
-            if self.connection.try_send(pdu).is_err() {
+            let res = match &self.bearer {
+                Some(bearer) => bearer.try_send_queued(&self.connection, &pdu.as_ref()[4..]),
+                None => self.connection.try_send(pdu),
+            };
+            if res.is_err() {
                 warn!("[gatt] error sending reply (outbound buffer full)");
             }
         }
     }
 }
 
+/// Protocol/Service Multiplexer of the Enhanced ATT bearers.
+pub const EATT_PSM: u16 = 0x0027;
+
+fn eatt_config() -> L2capChannelConfig {
+    L2capChannelConfig {
+        mode: L2capChannelMode::EnhancedCreditBased,
+        ..Default::default()
+    }
+}
+
+/// An Enhanced ATT bearer used to serve a GATT client.
+///
+/// Each bearer is an L2CAP channel in enhanced credit based flow control mode, which carries ATT requests
+/// and unsolicited PDUs independently of the unenhanced ATT bearer and of the other bearers.
+pub struct EattBearer<'stack, 'server, P: PacketPool> {
+    channel: L2capChannel<'stack, P>,
+    connection: Connection<'stack, P>,
+    server: &'server dyn DynamicAttributeServer<P>,
+}
+
+impl<'stack, 'server, P: PacketPool> EattBearer<'stack, 'server, P> {
+    /// Await the EATT bearers opened by the client of a GATT connection.
+    ///
+    /// Up to `N` of the bearers requested by the client are accepted, the remaining ones are refused.
+    ///
+    /// EATT bearers are only accepted on an encrypted link, from a client that enabled EATT in its Client
+    /// Supported Features ([Vol 3] Part G, Section 5.3.1). Other requests are refused and the next one is awaited.
+    pub async fn accept<T: Controller, const N: usize>(
+        stack: &'stack Stack<'stack, T, P>,
+        connection: &GattConnection<'stack, 'server, P>,
+    ) -> Result<Vec<Self, N>, BleHostError<T::Error>> {
+        let check = || {
+            if !connection
+                .connection
+                .security_level()
+                .is_ok_and(|level| level.encrypted())
+            {
+                LeCreditConnResultCode::InsufficientEncryption
+            } else if !connection.server.supports_eatt(&connection.connection) {
+                LeCreditConnResultCode::InsufficientAuthorization
+            } else {
+                LeCreditConnResultCode::Success
+            }
+        };
+        let channels: Vec<L2capChannel<'stack, P>, N> = loop {
+            let channels = stack
+                .host
+                .channels
+                .accept_multiple_checked(
+                    connection.connection.handle(),
+                    &[EATT_PSM],
+                    &eatt_config(),
+                    &stack.host,
+                    check,
+                )
+                .await?;
+            if !channels.is_empty() {
+                break channels;
+            }
+        };
+        Ok(channels
+            .into_iter()
+            .map(|channel| Self {
+                channel,
+                connection: connection.connection.clone(),
+                server: connection.server,
+            })
+            .collect())
+    }
+
+    /// Wait for the next GATT event received on this bearer.
+    ///
+    /// Events are handled in the same way as the events of [`GattConnection::next`], including deferred
+    /// attributes, and the replies are sent on this bearer.
+    pub async fn next<T: Controller>(
+        &mut self,
+        stack: &Stack<'_, T, P>,
+    ) -> Result<GattEvent<'stack, 'server, P>, BleHostError<T::Error>> {
+        loop {
+            let sdu = self.channel.receive_sdu(stack).await?;
+            let len = sdu.len();
+            let pdu = Pdu::new(sdu.into_inner(), len);
+            if !matches!(Att::decode(pdu.as_ref()), Ok(Att::Client(_))) {
+                warn!("[gatt] unexpected PDU on EATT bearer");
+                continue;
+            }
+            let data = GattData::with_bearer(pdu, self.connection.clone(), self.channel.channel_ref());
+            return Ok(GattEvent::new(data, self.server));
+        }
+    }
+
+    /// Process the next ATT PDU received on this bearer and send the response on the same bearer.
+    ///
+    /// The event returned by [`Self::next`] is accepted, use that instead to handle the events.
+    pub async fn process<T: Controller>(&mut self, stack: &Stack<'_, T, P>) -> Result<(), BleHostError<T::Error>> {
+        let event = self.next(stack).await?;
+        event.accept()?.send().await;
+        Ok(())
+    }
+
+    /// Send a notification or indication over this bearer.
+    pub async fn send_unsolicited<T: Controller>(
+        &mut self,
+        stack: &Stack<'_, T, P>,
+        uns: AttUns<'_>,
+    ) -> Result<(), BleHostError<T::Error>> {
+        let mut tx = P::allocate().ok_or(Error::OutOfMemory)?;
+        let mut w = WriteCursor::new(tx.as_mut());
+        w.write(Att::Server(AttServer::Unsolicited(uns)))?;
+        let len = w.len();
+        self.channel.send(stack, &tx.as_ref()[..len]).await
+    }
+
+    /// Get a reference to the underlying BLE connection.
+    pub fn raw(&self) -> &Connection<'stack, P> {
+        &self.connection
+    }
+}
+
 /// Notification listener for GATT client.
 pub struct NotificationListener<'lst, const MTU: usize> {
     handle: u16,
@@ -649,14 +1036,35 @@ impl<'lst, const MTU: usize> NotificationListener<'lst, MTU> {
 
 const MAX_NOTIF: usize = config::GATT_CLIENT_NOTIFICATION_MAX_SUBSCRIBERS;
 const NOTIF_QSIZE: usize = config::GATT_CLIENT_NOTIFICATION_QUEUE_SIZE;
+const EATT_BEARERS: usize = config::GATT_CLIENT_EATT_BEARERS_MAX;
+/// Layout version of a serialized [`GattCache`], bumped whenever the layout changes.
+const CACHE_FORMAT: u8 = 1;
+
+type EattResponseChannel<P> = Channel<NoopRawMutex, Result<Pdu<P>, Error>, 1>;
 
 /// A GATT client capable of using the GATT protocol.
 pub struct GattClient<'reference, T: Controller, P: PacketPool, const MAX_SERVICES: usize> {
     known_services: RefCell<Vec<ServiceHandle, MAX_SERVICES>>,
+    service_changed_handle: Cell<Option<u16>>,
+    services_changed: Cell<bool>,
+    // Features enabled in the Client Supported Features of the server
+    client_features: Cell<u8>,
     stack: &'reference Stack<'reference, T, P>,
     connection: Connection<'reference, P>,
     response_channel: Channel<NoopRawMutex, (ConnHandle, Pdu<P::Packet>), 1>,
 
+    eatt: RefCell<Vec<L2capChannel<'reference, P>, EATT_BEARERS>>,
+    // Bitmaps of the EATT bearers with an outstanding request, of the bearers still awaiting the response to a
+    // cancelled request and of the closed bearers
+    eatt_busy: Cell<u8>,
+    eatt_stale: Cell<u8>,
+    eatt_closed: Cell<u8>,
+    eatt_responses: [EattResponseChannel<P::Packet>; EATT_BEARERS],
+    eatt_changed: Signal<NoopRawMutex, ()>,
+
+    #[cfg(feature = "security")]
+    security_elevation: Cell<bool>,
+
     // TODO: Wait for something like https://github.com/rust-lang/rust/issues/132980 (min_generic_const_args) to allow using P::MTU
     notifications: PubSubChannel<NoopRawMutex, Notification<512>, NOTIF_QSIZE, MAX_NOTIF, 1>,
 }
@@ -685,6 +1093,257 @@ pub struct ServiceHandle {
     uuid: Uuid,
 }
 
+impl ServiceHandle {
+    /// Handle of the service declaration.
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// Last handle belonging to the service.
+    pub fn end(&self) -> u16 {
+        self.end
+    }
+
+    /// UUID of the service.
+    pub fn uuid(&self) -> &Uuid {
+        &self.uuid
+    }
+}
+
+/// A characteristic found by discovery.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CharacteristicInfo {
+    /// Handle of the characteristic declaration.
+    pub declaration_handle: u16,
+    /// Handle of the characteristic value.
+    pub value_handle: u16,
+    /// Last handle belonging to the characteristic, including its descriptors.
+    pub end_handle: u16,
+    /// Properties of the characteristic.
+    pub props: CharacteristicProps,
+    /// UUID of the characteristic.
+    pub uuid: Uuid,
+}
+
+/// A characteristic descriptor found by discovery.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DescriptorInfo {
+    /// Handle of the descriptor.
+    pub handle: u16,
+    /// UUID of the descriptor.
+    pub uuid: Uuid,
+}
+
+/// A characteristic and its descriptors, as found by [`GattClient::discover`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscoveredCharacteristic<const DESCRIPTORS: usize> {
+    /// The characteristic.
+    pub info: CharacteristicInfo,
+    /// Descriptors of the characteristic.
+    pub descriptors: Vec<DescriptorInfo, DESCRIPTORS>,
+}
+
+impl<const DESCRIPTORS: usize> DiscoveredCharacteristic<DESCRIPTORS> {
+    /// Create a characteristic handle that can be used to read, write or subscribe to the characteristic.
+    pub fn characteristic<T: AsGatt>(&self) -> Characteristic<T> {
+        let cccd: Uuid = CLIENT_CHARACTERISTIC_CONFIGURATION.into();
+        Characteristic {
+            handle: self.info.value_handle,
+            cccd_handle: self.descriptors.iter().find(|d| d.uuid == cccd).map(|d| d.handle),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// A primary service and its characteristics, as found by [`GattClient::discover`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscoveredService<const CHARACTERISTICS: usize, const DESCRIPTORS: usize> {
+    /// The service.
+    pub service: ServiceHandle,
+    /// Characteristics of the service.
+    pub characteristics: Vec<DiscoveredCharacteristic<DESCRIPTORS>, CHARACTERISTICS>,
+}
+
+/// Cached GATT database of a peer, as built by [`GattClient::build_cache`].
+///
+/// The cache can be stored alongside the bond information of the peer and handed to
+/// [`GattClient::restore_cache`] on the next connection, which avoids running service discovery again.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct GattCache<const SERVICES: usize, const CHARACTERISTICS: usize, const DESCRIPTORS: usize> {
+    /// Identity of the peer the cache belongs to.
+    pub identity: Identity,
+    /// Database Hash of the peer when the cache was built, if the peer exposes one.
+    pub database_hash: Option<u128>,
+    /// Value handle of the Service Changed characteristic of the peer, if present.
+    pub service_changed_handle: Option<u16>,
+    /// Discovered services.
+    pub services: Vec<DiscoveredService<CHARACTERISTICS, DESCRIPTORS>, SERVICES>,
+}
+
+impl<const SERVICES: usize, const CHARACTERISTICS: usize, const DESCRIPTORS: usize>
+    GattCache<SERVICES, CHARACTERISTICS, DESCRIPTORS>
+{
+    /// Find a characteristic by service and characteristic UUID.
+    pub fn characteristic<T: AsGatt>(&self, service: &Uuid, uuid: &Uuid) -> Option<Characteristic<T>> {
+        self.services
+            .iter()
+            .filter(|s| s.service.uuid == *service)
+            .flat_map(|s| s.characteristics.iter())
+            .find(|c| c.info.uuid == *uuid)
+            .map(|c| c.characteristic())
+    }
+
+    /// Serialize the cache into the provided buffer, returning the number of bytes written.
+    ///
+    /// Fails with [`Error::InvalidValue`] if more than 255 services, characteristics of a service or descriptors of
+    /// a characteristic are cached.
+    pub fn serialize(&self, dest: &mut [u8]) -> Result<usize, Error> {
+        let mut w = WriteCursor::new(dest);
+        w.write(CACHE_FORMAT)?;
+        w.append(self.identity.bd_addr.raw())?;
+        #[cfg(feature = "security")]
+        let irk = self.identity.irk.map(|irk| irk.0);
+        #[cfg(not(feature = "security"))]
+        let irk: Option<u128> = None;
+        write_optional_u128(&mut w, irk)?;
+        write_optional_u128(&mut w, self.database_hash)?;
+        match self.service_changed_handle {
+            Some(handle) => {
+                w.write(1u8)?;
+                w.write(handle)?;
+            }
+            None => w.write(0u8)?,
+        }
+        write_count(&mut w, self.services.len())?;
+        for service in self.services.iter() {
+            w.write(service.service.start)?;
+            w.write(service.service.end)?;
+            write_uuid(&mut w, &service.service.uuid)?;
+            write_count(&mut w, service.characteristics.len())?;
+            for characteristic in service.characteristics.iter() {
+                let info = &characteristic.info;
+                w.write(info.declaration_handle)?;
+                w.write(info.value_handle)?;
+                w.write(info.end_handle)?;
+                w.append(FixedGattValue::as_gatt(&info.props))?;
+                write_uuid(&mut w, &info.uuid)?;
+                write_count(&mut w, characteristic.descriptors.len())?;
+                for descriptor in characteristic.descriptors.iter() {
+                    w.write(descriptor.handle)?;
+                    write_uuid(&mut w, &descriptor.uuid)?;
+                }
+            }
+        }
+        Ok(w.len())
+    }
+
+    /// Deserialize a cache previously written by [`GattCache::serialize`].
+    ///
+    /// A cache written with a different layout version is rejected with [`Error::InvalidValue`].
+    pub fn deserialize(src: &[u8]) -> Result<Self, Error> {
+        let mut r = ReadCursor::new(src);
+        if r.read::<u8>()? != CACHE_FORMAT {
+            return Err(Error::InvalidValue);
+        }
+        let bd_addr = BdAddr::new(r.slice(6)?.try_into().map_err(|_| Error::InvalidValue)?);
+        let irk = read_optional_u128(&mut r)?;
+        let identity = Identity {
+            bd_addr,
+            #[cfg(feature = "security")]
+            irk: irk.map(IdentityResolvingKey::new),
+        };
+        let database_hash = read_optional_u128(&mut r)?;
+        let service_changed_handle = match r.read::<u8>()? {
+            0 => None,
+            _ => Some(r.read()?),
+        };
+        let mut services = Vec::new();
+        for _ in 0..r.read::<u8>()? {
+            let service = ServiceHandle {
+                start: r.read()?,
+                end: r.read()?,
+                uuid: read_uuid(&mut r)?,
+            };
+            let mut characteristics = Vec::new();
+            for _ in 0..r.read::<u8>()? {
+                let info = CharacteristicInfo {
+                    declaration_handle: r.read()?,
+                    value_handle: r.read()?,
+                    end_handle: r.read()?,
+                    props: <CharacteristicProps as FixedGattValue>::from_gatt(r.slice(1)?)
+                        .map_err(|_| Error::InvalidValue)?,
+                    uuid: read_uuid(&mut r)?,
+                };
+                let mut descriptors = Vec::new();
+                for _ in 0..r.read::<u8>()? {
+                    let descriptor = DescriptorInfo {
+                        handle: r.read()?,
+                        uuid: read_uuid(&mut r)?,
+                    };
+                    descriptors.push(descriptor).map_err(|_| Error::InsufficientSpace)?;
+                }
+                characteristics
+                    .push(DiscoveredCharacteristic { info, descriptors })
+                    .map_err(|_| Error::InsufficientSpace)?;
+            }
+            services
+                .push(DiscoveredService {
+                    service,
+                    characteristics,
+                })
+                .map_err(|_| Error::InsufficientSpace)?;
+        }
+        Ok(Self {
+            identity,
+            database_hash,
+            service_changed_handle,
+            services,
+        })
+    }
+}
+
+fn write_count(w: &mut WriteCursor<'_>, count: usize) -> Result<(), Error> {
+    w.write(u8::try_from(count).map_err(|_| Error::InvalidValue)?)?;
+    Ok(())
+}
+
+fn write_optional_u128(w: &mut WriteCursor<'_>, value: Option<u128>) -> Result<(), Error> {
+    match value {
+        Some(value) => {
+            w.write(1u8)?;
+            w.append(&value.to_le_bytes())?;
+        }
+        None => w.write(0u8)?,
+    }
+    Ok(())
+}
+
+fn read_optional_u128(r: &mut ReadCursor<'_>) -> Result<Option<u128>, Error> {
+    match r.read::<u8>()? {
+        0 => Ok(None),
+        _ => Ok(Some(u128::from_le_bytes(
+            r.slice(16)?.try_into().map_err(|_| Error::InvalidValue)?,
+        ))),
+    }
+}
+
+fn write_uuid(w: &mut WriteCursor<'_>, uuid: &Uuid) -> Result<(), Error> {
+    let raw = uuid.as_raw();
+    w.write(raw.len() as u8)?;
+    w.append(raw)?;
+    Ok(())
+}
+
+fn read_uuid(r: &mut ReadCursor<'_>) -> Result<Uuid, Error> {
+    let len = r.read::<u8>()? as usize;
+    Uuid::try_from(r.slice(len)?)
+}
+
 pub(crate) struct Response<P> {
     pdu: Pdu<P>,
     handle: ConnHandle,
@@ -701,14 +1360,8 @@ impl<'reference, T: Controller, P: PacketPool, const MAX_SERVICES: usize> Client
     for GattClient<'reference, T, P, MAX_SERVICES>
 {
     async fn request(&self, req: AttReq<'_>) -> Result<Response<P::Packet>, BleHostError<T::Error>> {
-        let data = Att::Client(AttClient::Request(req));
-
-        self.send_att_data(data).await?;
-
-        let (h, pdu) = self.response_channel.receive().await;
-
-        assert_eq!(h, self.connection.handle());
-        Ok(Response { handle: h, pdu })
+        let mut bearer = self.acquire_eatt_bearer();
+        self.request_on(&mut bearer, req).await
     }
 
     async fn command(&self, cmd: AttCmd<'_>) -> Result<(), BleHostError<T::Error>> {
@@ -721,6 +1374,74 @@ impl<'reference, T: Controller, P: PacketPool, const MAX_SERVICES: usize> Client
 }
 
 impl<'reference, T: Controller, P: PacketPool, const MAX_SERVICES: usize> GattClient<'reference, T, P, MAX_SERVICES> {
+    /// Perform a request on the reserved EATT bearer, or on the unenhanced ATT bearer if there is none.
+    async fn request_on(
+        &self,
+        bearer: &mut Option<EattBusyGuard<'_>>,
+        req: AttReq<'_>,
+    ) -> Result<Response<P::Packet>, BleHostError<T::Error>> {
+        #[cfg(feature = "security")]
+        if self.security_elevation.get() {
+            let response = self.request_once(bearer.as_mut(), req.clone()).await?;
+            let code = match Self::response(response.pdu.as_ref())? {
+                AttRsp::Error { code, .. } => code,
+                _ => return Ok(response),
+            };
+            let level = self.connection.security_level()?;
+            // An unauthenticated key is replaced by pairing again with MITM protection.
+            let requested = if code == AttErrorCode::INSUFFICIENT_AUTHENTICATION && level.encrypted() {
+                self.connection.request_authentication()
+            } else if code == AttErrorCode::INSUFFICIENT_AUTHENTICATION || code == AttErrorCode::INSUFFICIENT_ENCRYPTION
+            {
+                self.connection.request_security()
+            } else {
+                return Ok(response);
+            };
+            // The original error is returned if security could not be raised.
+            if requested.is_err() {
+                return Ok(response);
+            }
+            if let Err(e) = self.connection.wait_pairing().await {
+                warn!("[gatt] pairing to elevate security failed: {:?}", e);
+                return Ok(response);
+            }
+            drop(response);
+            return self.request_once(bearer.as_mut(), req).await;
+        }
+
+        self.request_once(bearer.as_mut(), req).await
+    }
+
+    async fn request_once(
+        &self,
+        bearer: Option<&mut EattBusyGuard<'_>>,
+        req: AttReq<'_>,
+    ) -> Result<Response<P::Packet>, BleHostError<T::Error>> {
+        let data = Att::Client(AttClient::Request(req));
+
+        if let Some(guard) = bearer {
+            guard.awaiting_response = true;
+            if let Err(e) = self.send_eatt_data(guard.bearer, data).await {
+                guard.awaiting_response = false;
+                return Err(e);
+            }
+            let pdu = self.eatt_responses[guard.bearer].receive().await;
+            guard.awaiting_response = false;
+            let pdu = pdu?;
+            return Ok(Response {
+                handle: self.connection.handle(),
+                pdu,
+            });
+        }
+
+        self.send_att_data(data).await?;
+
+        let (h, pdu) = self.response_channel.receive().await;
+
+        assert_eq!(h, self.connection.handle());
+        Ok(Response { handle: h, pdu })
+    }
+
     async fn send_att_data(&self, data: Att<'_>) -> Result<(), BleHostError<T::Error>> {
         let header = L2capHeader {
             channel: crate::types::l2cap::L2CAP_CID_ATT,
@@ -736,31 +1457,112 @@ impl<'reference, T: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         self.connection.send(Pdu::new(buf, len)).await;
         Ok(())
     }
-}
 
-impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattClient<'reference, C, P, MAX_SERVICES> {
-    /// Creates a GATT client capable of processing the GATT protocol using the provided table of attributes.
-    pub async fn new(
-        stack: &'reference Stack<'reference, C, P>,
-        connection: &Connection<'reference, P>,
-    ) -> Result<GattClient<'reference, C, P, MAX_SERVICES>, BleHostError<C::Error>> {
-        let l2cap = L2capHeader { channel: 4, length: 3 };
+    async fn send_eatt_data(&self, bearer: usize, data: Att<'_>) -> Result<(), BleHostError<T::Error>> {
+        let index = self.eatt.borrow()[bearer].index();
+
         let mut buf = P::allocate().ok_or(Error::OutOfMemory)?;
         let mut w = WriteCursor::new(buf.as_mut());
-        w.write_hci(&l2cap)?;
-        w.write(att::Att::Client(att::AttClient::Request(att::AttReq::ExchangeMtu {
-            mtu: P::MTU as u16 - 4,
-        })))?;
-
+        w.write(data)?;
+        let len = w.len();
+
+        self.stack
+            .host
+            .channels
+            .send_queued(index, &buf.as_ref()[..len], &self.connection)
+            .await?;
+        Ok(())
+    }
+
+    /// Reserve an idle EATT bearer for requests, if any.
+    fn acquire_eatt_bearer(&self) -> Option<EattBusyGuard<'_>> {
+        let busy = self.eatt_busy.get() | self.eatt_closed.get();
+        let bearer = (0..self.eatt.borrow().len()).find(|i| busy & (1 << i) == 0)?;
+        self.eatt_busy.set(self.eatt_busy.get() | (1 << bearer));
+        Some(EattBusyGuard {
+            busy: &self.eatt_busy,
+            stale: &self.eatt_stale,
+            bearer,
+            awaiting_response: false,
+        })
+    }
+
+    /// ATT MTU of the reserved EATT bearer, or of the unenhanced ATT bearer if there is none.
+    fn bearer_mtu(&self, bearer: Option<&EattBusyGuard<'_>>) -> Result<u16, Error> {
+        let Some(guard) = bearer else {
+            return Ok(self.connection.att_mtu());
+        };
+        let index = self.eatt.borrow()[guard.bearer].index();
+        let (_, _, mtu, _) = self.stack.host.channels.connected_channel_params(index)?;
+        Ok(mtu)
+    }
+
+    async fn receive_eatt(&self, bearer: usize) -> Result<Sdu<P::Packet>, BleHostError<T::Error>> {
+        let index = self.eatt.borrow().get(bearer).map(|channel| channel.index());
+        let Some(index) = index.filter(|_| self.eatt_closed.get() & (1 << bearer) == 0) else {
+            return core::future::pending().await;
+        };
+        self.stack.host.channels.receive_sdu(index, &self.stack.host).await
+    }
+}
+
+/// Releases an EATT bearer once the requests using it complete or are cancelled.
+///
+/// A bearer whose request is cancelled before the response arrives is marked stale instead: it stays busy until
+/// the response is received and discarded, so it is not mistaken for the response to the next request.
+struct EattBusyGuard<'a> {
+    busy: &'a Cell<u8>,
+    stale: &'a Cell<u8>,
+    bearer: usize,
+    awaiting_response: bool,
+}
+
+impl Drop for EattBusyGuard<'_> {
+    fn drop(&mut self) {
+        if self.awaiting_response {
+            self.stale.set(self.stale.get() | (1 << self.bearer));
+        } else {
+            self.busy.set(self.busy.get() & !(1 << self.bearer));
+        }
+    }
+}
+
+impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattClient<'reference, C, P, MAX_SERVICES> {
+    /// Creates a GATT client capable of processing the GATT protocol using the provided table of attributes.
+    pub async fn new(
+        stack: &'reference Stack<'reference, C, P>,
+        connection: &Connection<'reference, P>,
+    ) -> Result<GattClient<'reference, C, P, MAX_SERVICES>, BleHostError<C::Error>> {
+        let l2cap = L2capHeader { channel: 4, length: 3 };
+        let mut buf = P::allocate().ok_or(Error::OutOfMemory)?;
+        let mut w = WriteCursor::new(buf.as_mut());
+        w.write_hci(&l2cap)?;
+        w.write(att::Att::Client(att::AttClient::Request(att::AttReq::ExchangeMtu {
+            mtu: P::MTU as u16 - 4,
+        })))?;
+
         let len = w.len();
         connection.send(Pdu::new(buf, len)).await;
         Ok(Self {
             known_services: RefCell::new(heapless::Vec::new()),
+            service_changed_handle: Cell::new(None),
+            services_changed: Cell::new(false),
+            client_features: Cell::new(0),
             stack,
             connection: connection.clone(),
 
             response_channel: Channel::new(),
 
+            eatt: RefCell::new(Vec::new()),
+            eatt_busy: Cell::new(0),
+            eatt_stale: Cell::new(0),
+            eatt_closed: Cell::new(0),
+            eatt_responses: core::array::from_fn(|_| Channel::new()),
+            eatt_changed: Signal::new(),
+
+            #[cfg(feature = "security")]
+            security_elevation: Cell::new(false),
+
             notifications: PubSubChannel::new(),
         })
     }
@@ -821,6 +1623,376 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         Ok(result)
     }
 
+    /// Discover all primary services.
+    pub async fn services(&self) -> Result<Vec<ServiceHandle, MAX_SERVICES>, BleHostError<C::Error>> {
+        let mut start: u16 = 0x0001;
+        let mut result = Vec::new();
+
+        loop {
+            let data = att::AttReq::ReadByGroupType {
+                start,
+                end: 0xffff,
+                group_type: PRIMARY_SERVICE.into(),
+            };
+
+            let response = self.request(data).await?;
+            match Self::response(response.pdu.as_ref())? {
+                AttRsp::Error { code, .. } => {
+                    if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND {
+                        break;
+                    }
+                    return Err(Error::Att(code).into());
+                }
+                AttRsp::ReadByGroupType { mut it } => {
+                    let mut end: u16 = 0;
+                    while let Some(res) = it.next() {
+                        let (handle, e, uuid) = res?;
+                        end = e;
+                        let svc = ServiceHandle {
+                            start: handle,
+                            end,
+                            uuid: Uuid::try_from(uuid)?,
+                        };
+                        result.push(svc.clone()).map_err(|_| Error::InsufficientSpace)?;
+                        let mut known_services = self.known_services.borrow_mut();
+                        if !known_services.contains(&svc) {
+                            known_services.push(svc).map_err(|_| Error::InsufficientSpace)?;
+                        }
+                    }
+                    if end < start {
+                        return Err(Error::InvalidValue.into());
+                    }
+                    if end == 0xFFFF {
+                        break;
+                    }
+                    start = end + 1;
+                }
+                res => {
+                    trace!("[gatt client] response: {:?}", res);
+                    return Err(Error::UnexpectedGattResponse.into());
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Discover the services included by a service.
+    pub async fn included_services<const N: usize>(
+        &self,
+        service: &ServiceHandle,
+    ) -> Result<Vec<ServiceHandle, N>, BleHostError<C::Error>> {
+        let mut start: u16 = service.start;
+        let mut result = Vec::new();
+
+        while start <= service.end {
+            let data = att::AttReq::ReadByType {
+                start,
+                end: service.end,
+                attribute_type: INCLUDE.into(),
+            };
+
+            let response = self.request(data).await?;
+            match Self::response(response.pdu.as_ref())? {
+                AttRsp::Error { code, .. } => {
+                    if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND {
+                        break;
+                    }
+                    return Err(Error::Att(code).into());
+                }
+                AttRsp::ReadByType { mut it } => {
+                    let mut last = None;
+                    while let Some(res) = it.next() {
+                        let (handle, item) = res?;
+                        last = Some(handle);
+                        let mut r = ReadCursor::new(item);
+                        let included_start: u16 = r.read()?;
+                        let included_end: u16 = r.read()?;
+                        let uuid = match r.remaining() {
+                            // 128-bit UUIDs are not part of the include declaration, read them from the service declaration.
+                            [] => self.read_service_uuid(included_start).await?,
+                            uuid => Uuid::try_from(uuid)?,
+                        };
+                        result
+                            .push(ServiceHandle {
+                                start: included_start,
+                                end: included_end,
+                                uuid,
+                            })
+                            .map_err(|_| Error::InsufficientSpace)?;
+                    }
+                    match last {
+                        Some(handle) if handle < service.end => start = handle + 1,
+                        Some(_) => break,
+                        None => return Err(Error::InvalidValue.into()),
+                    }
+                }
+                _ => return Err(Error::UnexpectedGattResponse.into()),
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn read_service_uuid(&self, handle: u16) -> Result<Uuid, BleHostError<C::Error>> {
+        let response = self.request(att::AttReq::Read { handle }).await?;
+        match Self::response(response.pdu.as_ref())? {
+            AttRsp::Read { data } => Ok(Uuid::try_from(data)?),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::UnexpectedGattResponse.into()),
+        }
+    }
+
+    /// Discover all characteristics of a service.
+    pub async fn characteristics<const N: usize>(
+        &self,
+        service: &ServiceHandle,
+    ) -> Result<Vec<CharacteristicInfo, N>, BleHostError<C::Error>> {
+        let mut start: u16 = service.start;
+        let mut result: Vec<CharacteristicInfo, N> = Vec::new();
+
+        while start <= service.end {
+            let data = att::AttReq::ReadByType {
+                start,
+                end: service.end,
+                attribute_type: CHARACTERISTIC.into(),
+            };
+
+            let response = self.request(data).await?;
+            match Self::response(response.pdu.as_ref())? {
+                AttRsp::Error { code, .. } => {
+                    if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND {
+                        break;
+                    }
+                    return Err(Error::Att(code).into());
+                }
+                AttRsp::ReadByType { mut it } => {
+                    let mut last = None;
+                    while let Some(res) = it.next() {
+                        let (handle, item) = res?;
+                        last = Some(handle);
+                        if item.len() < 5 {
+                            return Err(Error::MalformedCharacteristicDeclaration {
+                                expected: 5,
+                                actual: item.len(),
+                            }
+                            .into());
+                        }
+                        let AttributeData::Declaration {
+                            props,
+                            handle: value_handle,
+                            uuid,
+                        } = AttributeData::decode_declaration(item)?
+                        else {
+                            return Err(Error::InvalidCharacteristicDeclarationData.into());
+                        };
+                        // The previous characteristic ends right before this declaration.
+                        if let Some(previous) = result.last_mut() {
+                            previous.end_handle = handle.saturating_sub(1);
+                        }
+                        result
+                            .push(CharacteristicInfo {
+                                declaration_handle: handle,
+                                value_handle,
+                                end_handle: service.end,
+                                props,
+                                uuid,
+                            })
+                            .map_err(|_| Error::InsufficientSpace)?;
+                    }
+                    match last {
+                        Some(handle) if handle < service.end => start = handle + 1,
+                        Some(_) => break,
+                        None => return Err(Error::InvalidValue.into()),
+                    }
+                }
+                _ => return Err(Error::UnexpectedGattResponse.into()),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Discover all descriptors of a characteristic.
+    pub async fn descriptors<const N: usize>(
+        &self,
+        characteristic: &CharacteristicInfo,
+    ) -> Result<Vec<DescriptorInfo, N>, BleHostError<C::Error>> {
+        let mut result = Vec::new();
+        if characteristic.value_handle >= characteristic.end_handle {
+            return Ok(result);
+        }
+        let mut start = characteristic.value_handle + 1;
+
+        while start <= characteristic.end_handle {
+            let data = att::AttReq::FindInformation {
+                start_handle: start,
+                end_handle: characteristic.end_handle,
+            };
+
+            let response = self.request(data).await?;
+            match Self::response(response.pdu.as_ref())? {
+                AttRsp::Error { code, .. } => {
+                    if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND {
+                        break;
+                    }
+                    return Err(Error::Att(code).into());
+                }
+                AttRsp::FindInformation { mut it } => {
+                    let mut last = None;
+                    while let Some(res) = it.next() {
+                        let (handle, uuid) = res?;
+                        last = Some(handle);
+                        result
+                            .push(DescriptorInfo { handle, uuid })
+                            .map_err(|_| Error::InsufficientSpace)?;
+                    }
+                    match last {
+                        Some(handle) if handle < characteristic.end_handle => start = handle + 1,
+                        Some(_) => break,
+                        None => return Err(Error::InvalidValue.into()),
+                    }
+                }
+                _ => return Err(Error::UnexpectedGattResponse.into()),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Discover all primary services, their characteristics and the descriptors of each characteristic.
+    ///
+    /// Services included by another service, such as secondary services, are discovered as well and follow the
+    /// primary services in the result.
+    ///
+    /// Up to `CHARACTERISTICS` characteristics per service and `DESCRIPTORS` descriptors per characteristic
+    /// are discovered, otherwise [`Error::InsufficientSpace`] is returned.
+    pub async fn discover<const CHARACTERISTICS: usize, const DESCRIPTORS: usize>(
+        &self,
+    ) -> Result<Vec<DiscoveredService<CHARACTERISTICS, DESCRIPTORS>, MAX_SERVICES>, BleHostError<C::Error>> {
+        let mut services = self.services().await?;
+        // Included services may include further services, the list grows while it is walked.
+        let mut index = 0;
+        while let Some(service) = services.get(index).cloned() {
+            for included in self.included_services::<MAX_SERVICES>(&service).await? {
+                if services.iter().any(|s| s.start == included.start) {
+                    continue;
+                }
+                services.push(included.clone()).map_err(|_| Error::InsufficientSpace)?;
+                let mut known_services = self.known_services.borrow_mut();
+                if !known_services.contains(&included) {
+                    known_services.push(included).map_err(|_| Error::InsufficientSpace)?;
+                }
+            }
+            index += 1;
+        }
+
+        let mut result = Vec::new();
+        for service in services {
+            let mut characteristics = Vec::new();
+            for info in self.characteristics::<CHARACTERISTICS>(&service).await? {
+                let descriptors = self.descriptors(&info).await?;
+                // Capacity is the same as the discovered characteristics vector.
+                let _ = characteristics.push(DiscoveredCharacteristic { info, descriptors });
+            }
+            result
+                .push(DiscoveredService {
+                    service,
+                    characteristics,
+                })
+                .map_err(|_| Error::InsufficientSpace)?;
+        }
+        Ok(result)
+    }
+
+    /// Discover the GATT database of the peer and return it as a cache that can be persisted.
+    ///
+    /// Besides running [`GattClient::discover`], the Database Hash of the peer is read and indications of the
+    /// Service Changed characteristic are enabled, so that a stale cache can be detected on later connections.
+    pub async fn build_cache<const CHARACTERISTICS: usize, const DESCRIPTORS: usize>(
+        &self,
+    ) -> Result<GattCache<MAX_SERVICES, CHARACTERISTICS, DESCRIPTORS>, BleHostError<C::Error>> {
+        let services = self.discover::<CHARACTERISTICS, DESCRIPTORS>().await?;
+        let database_hash = self.read_database_hash().await?;
+
+        let service_changed = services
+            .iter()
+            .flat_map(|s| s.characteristics.iter())
+            .find(|c| c.info.uuid == SERVICE_CHANGED.into())
+            .map(|c| c.characteristic::<[u8; 4]>());
+        if let Some(characteristic) = &service_changed {
+            if characteristic.cccd_handle.is_some() {
+                self.write_cccd(characteristic, 0x02).await?;
+            }
+        }
+
+        let service_changed_handle = service_changed.map(|c| c.handle);
+        self.service_changed_handle.set(service_changed_handle);
+        self.services_changed.set(false);
+        Ok(GattCache {
+            identity: self.connection.peer_identity(),
+            database_hash,
+            service_changed_handle,
+            services,
+        })
+    }
+
+    /// Restore a cache built by [`GattClient::build_cache`] on an earlier connection.
+    ///
+    /// The cache is only used if it belongs to the connected peer and its Database Hash matches the one currently
+    /// exposed by the peer, which takes a single request. Returns `false` if the cache is stale, in which case a new
+    /// one should be built. Without a Database Hash there is no way to tell, so a cache is never restored if either
+    /// the cache or the peer lacks one.
+    pub async fn restore_cache<const CHARACTERISTICS: usize, const DESCRIPTORS: usize>(
+        &self,
+        cache: &GattCache<MAX_SERVICES, CHARACTERISTICS, DESCRIPTORS>,
+    ) -> Result<bool, BleHostError<C::Error>> {
+        if !cache.identity.match_identity(&self.connection.peer_identity()) {
+            return Ok(false);
+        }
+        match (self.read_database_hash().await?, cache.database_hash) {
+            (Some(current), Some(cached)) if current == cached => {}
+            _ => return Ok(false),
+        }
+
+        let mut known_services = self.known_services.borrow_mut();
+        known_services.clear();
+        for service in cache.services.iter() {
+            // Capacity is the same as the cached services vector.
+            let _ = known_services.push(service.service.clone());
+        }
+        self.service_changed_handle.set(cache.service_changed_handle);
+        self.services_changed.set(false);
+        Ok(true)
+    }
+
+    /// Returns true if the peer indicated that its services changed since the cache was built or restored.
+    ///
+    /// Any cache of the peer is stale once this happens and should be rebuilt.
+    pub fn services_changed(&self) -> bool {
+        self.services_changed.get()
+    }
+
+    async fn read_database_hash(&self) -> Result<Option<u128>, BleHostError<C::Error>> {
+        let data = att::AttReq::ReadByType {
+            start: 0x0001,
+            end: 0xffff,
+            attribute_type: DATABASE_HASH.into(),
+        };
+        let response = self.request(data).await?;
+        match Self::response(response.pdu.as_ref())? {
+            AttRsp::ReadByType { mut it } => match it.next() {
+                Some(Ok((_, value))) => Ok(Some(u128::from_le_bytes(
+                    value.try_into().map_err(|_| Error::InvalidValue)?,
+                ))),
+                _ => Err(Error::InvalidValue.into()),
+            },
+            AttRsp::Error { code, .. } if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND => Ok(None),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::UnexpectedGattResponse.into()),
+        }
+    }
+
     /// Discover characteristics in a given service using a UUID.
     pub async fn characteristic_by_uuid<T: AsGatt>(
         &self,
@@ -1060,6 +2232,93 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         }
     }
 
+    /// Read several characteristics with a single request.
+    ///
+    /// ATT Read Multiple is used if `T` has a fixed size, ATT Read Multiple Variable Length otherwise.
+    /// The value of each characteristic is copied into the buffer at the same index in `dest`, and the
+    /// number of bytes copied into each buffer is returned. At least two characteristics must be given.
+    pub async fn read_characteristics<T: AsGatt, const N: usize>(
+        &self,
+        characteristics: &[Characteristic<T>; N],
+        dest: &mut [&mut [u8]; N],
+    ) -> Result<[usize; N], BleHostError<C::Error>> {
+        let handles = characteristics.each_ref().map(|c| c.handle);
+        if T::MIN_SIZE == T::MAX_SIZE {
+            self.read_multiple_inner(&handles, Some(&[T::MAX_SIZE; N]), dest).await
+        } else {
+            self.read_multiple_inner(&handles, None, dest).await
+        }
+    }
+
+    /// Read several attributes described by handles using ATT Read Multiple.
+    ///
+    /// The response only contains the concatenated values, so it is split according to the length of each
+    /// buffer in `dest`: every buffer must be exactly as long as the value of the attribute at the same index.
+    /// The number of bytes copied into each buffer is returned. At least two handles must be given.
+    pub async fn read_multiple<const N: usize>(
+        &self,
+        handles: &[u16; N],
+        dest: &mut [&mut [u8]; N],
+    ) -> Result<[usize; N], BleHostError<C::Error>> {
+        let sizes = dest.each_ref().map(|d| d.len());
+        self.read_multiple_inner(handles, Some(&sizes), dest).await
+    }
+
+    /// Read several attributes described by handles using ATT Read Multiple Variable Length.
+    ///
+    /// The value of each attribute is copied into the buffer at the same index in `dest`, and the number of
+    /// bytes copied into each buffer is returned. At least two handles must be given.
+    pub async fn read_multiple_variable<const N: usize>(
+        &self,
+        handles: &[u16; N],
+        dest: &mut [&mut [u8]; N],
+    ) -> Result<[usize; N], BleHostError<C::Error>> {
+        self.read_multiple_inner(handles, None, dest).await
+    }
+
+    async fn read_multiple_inner<const N: usize>(
+        &self,
+        handles: &[u16; N],
+        sizes: Option<&[usize; N]>,
+        dest: &mut [&mut [u8]; N],
+    ) -> Result<[usize; N], BleHostError<C::Error>> {
+        let raw = handles.map(u16::to_le_bytes);
+        let handles = raw.as_flattened();
+        let request = match sizes {
+            Some(_) => att::AttReq::ReadMultiple { handles },
+            None => att::AttReq::ReadMultipleVariable { handles },
+        };
+
+        let response = self.request(request).await?;
+
+        let mut copied = [0; N];
+        match (Self::response(response.pdu.as_ref())?, sizes) {
+            (AttRsp::ReadMultiple { mut data }, Some(sizes)) => {
+                // The response may be truncated to the ATT MTU, in which case the trailing values are partial or missing.
+                for ((copied, dest), size) in copied.iter_mut().zip(dest.iter_mut()).zip(sizes) {
+                    let (value, rest) = data.split_at((*size).min(data.len()));
+                    *copied = value.len().min(dest.len());
+                    dest[..*copied].copy_from_slice(&value[..*copied]);
+                    data = rest;
+                }
+                Ok(copied)
+            }
+            (AttRsp::ReadMultipleVariable { mut it }, None) => {
+                for (copied, dest) in copied.iter_mut().zip(dest.iter_mut()) {
+                    let Some(item) = it.next() else {
+                        break;
+                    };
+                    let (_len, value) = item?;
+                    *copied = value.len().min(dest.len());
+                    dest[..*copied].copy_from_slice(&value[..*copied]);
+                }
+                Ok(copied)
+            }
+            (AttRsp::Error { code, .. }, _) => Err(Error::Att(code).into()),
+            _ => Err(Error::UnexpectedGattResponse.into()),
+        }
+    }
+
     /// Write to a characteristic described by a handle.
     pub async fn write_characteristic<T: FromGatt>(
         &self,
@@ -1095,6 +2354,132 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         Ok(())
     }
 
+    /// Write a signed value without waiting for a response to a characteristic described by a handle.
+    ///
+    /// The value is signed with the signing key distributed to the server when bonding, allowing
+    /// authenticated writes on an unencrypted link. If the link is already encrypted, a plain write
+    /// command is sent instead ([Vol 3] Part C, Section 10.4.1).
+    #[cfg(feature = "security")]
+    pub async fn write_characteristic_signed<T: FromGatt>(
+        &self,
+        handle: &Characteristic<T>,
+        buf: &[u8],
+    ) -> Result<(), BleHostError<C::Error>> {
+        if self.connection.security_level()?.encrypted() {
+            return self.write_characteristic_without_response(handle, buf).await;
+        }
+        let signature = self
+            .connection
+            .sign(&[&[att::ATT_SIGNED_WRITE_CMD], &handle.handle.to_le_bytes(), buf])?;
+        let data = att::AttCmd::SignedWrite {
+            handle: handle.handle,
+            data: buf,
+            signature,
+        };
+
+        self.command(data).await?;
+
+        Ok(())
+    }
+
+    /// Write a value that may be longer than the ATT MTU to a characteristic described by a handle.
+    ///
+    /// The value is queued on the server in fragments using Prepare Write requests and then written with
+    /// an Execute Write request. If the server echoes back a fragment that differs from the one sent, the
+    /// queued fragments are discarded and [`Error::PreparedWriteMismatch`] is returned. A fragment offset must fit
+    /// in 16 bits, so [`Error::InvalidValue`] is returned for longer values.
+    pub async fn write_characteristic_long<T: FromGatt>(
+        &self,
+        handle: &Characteristic<T>,
+        buf: &[u8],
+    ) -> Result<(), BleHostError<C::Error>> {
+        self.execute_reliable_write(&[(handle.handle, buf)]).await
+    }
+
+    /// Start a reliable write of one or more characteristics.
+    ///
+    /// Up to `N` writes can be added to the returned builder, which are applied by the server all at once
+    /// when the builder is committed.
+    pub fn reliable_write<'d, const N: usize>(&self) -> ReliableWrite<'_, 'reference, 'd, C, P, MAX_SERVICES, N> {
+        ReliableWrite {
+            client: self,
+            writes: Vec::new(),
+        }
+    }
+
+    async fn execute_reliable_write(&self, writes: &[(u16, &[u8])]) -> Result<(), BleHostError<C::Error>> {
+        // Every request of the write is sent on the same bearer, whose queue is executed at the end.
+        let mut bearer = self.acquire_eatt_bearer();
+        for (handle, value) in writes {
+            if let Err(e) = self.prepare_write(&mut bearer, *handle, value).await {
+                // Discard whatever has been queued so far, the original error is more useful than a failed cancel.
+                let _ = self.execute_write(&mut bearer, false).await;
+                return Err(e);
+            }
+        }
+        self.execute_write(&mut bearer, true).await
+    }
+
+    async fn prepare_write(
+        &self,
+        bearer: &mut Option<EattBusyGuard<'_>>,
+        handle: u16,
+        value: &[u8],
+    ) -> Result<(), BleHostError<C::Error>> {
+        // Prepare Write Request header: opcode (1 byte) + handle (2 bytes) + offset (2 bytes)
+        let max_fragment = self.bearer_mtu(bearer.as_ref())? as usize - 5;
+        let mut offset = 0;
+        // An empty value still needs one (empty) fragment to be written.
+        loop {
+            let end = (offset + max_fragment).min(value.len());
+            let fragment = &value[offset..end];
+            let response = self
+                .request_on(
+                    bearer,
+                    att::AttReq::PrepareWrite {
+                        handle,
+                        offset: u16::try_from(offset).map_err(|_| Error::InvalidValue)?,
+                        value: fragment,
+                    },
+                )
+                .await?;
+
+            match Self::response(response.pdu.as_ref())? {
+                AttRsp::PrepareWrite {
+                    handle: echoed_handle,
+                    offset: echoed_offset,
+                    value: echoed_value,
+                } => {
+                    if echoed_handle != handle || echoed_offset as usize != offset || echoed_value != fragment {
+                        return Err(Error::PreparedWriteMismatch.into());
+                    }
+                }
+                AttRsp::Error { code, .. } => return Err(Error::Att(code).into()),
+                _ => return Err(Error::UnexpectedGattResponse.into()),
+            }
+
+            offset = end;
+            if offset >= value.len() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn execute_write(
+        &self,
+        bearer: &mut Option<EattBusyGuard<'_>>,
+        commit: bool,
+    ) -> Result<(), BleHostError<C::Error>> {
+        let response = self
+            .request_on(bearer, att::AttReq::ExecuteWrite { flags: commit as u8 })
+            .await?;
+        match Self::response(response.pdu.as_ref())? {
+            AttRsp::ExecuteWrite => Ok(()),
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::UnexpectedGattResponse.into()),
+        }
+    }
+
     /// Subscribe to indication/notification of a given Characteristic
     ///
     /// A listener is returned, which has a `next()` method
@@ -1103,29 +2488,17 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         characteristic: &Characteristic<T>,
         indication: bool,
     ) -> Result<NotificationListener<'_, 512>, BleHostError<C::Error>> {
-        let properties = u16::to_le_bytes(if indication { 0x02 } else { 0x01 });
-
-        let data = att::AttReq::Write {
-            handle: characteristic.cccd_handle.ok_or(Error::NotSupported)?,
-            data: &properties,
-        };
-
-        // set the CCCD
-        let response = self.request(data).await?;
-
-        match Self::response(response.pdu.as_ref())? {
-            AttRsp::Write => match self.notifications.dyn_subscriber() {
-                Ok(listener) => Ok(NotificationListener {
-                    listener,
-                    handle: characteristic.handle,
-                }),
-                Err(embassy_sync::pubsub::Error::MaximumSubscribersReached) => {
-                    Err(Error::GattSubscriberLimitReached.into())
-                }
-                Err(_) => Err(Error::Other.into()),
-            },
-            AttRsp::Error { request, handle, code } => Err(Error::Att(code).into()),
-            _ => Err(Error::UnexpectedGattResponse.into()),
+        self.write_cccd(characteristic, if indication { 0x02 } else { 0x01 })
+            .await?;
+        match self.notifications.dyn_subscriber() {
+            Ok(listener) => Ok(NotificationListener {
+                listener,
+                handle: characteristic.handle,
+            }),
+            Err(embassy_sync::pubsub::Error::MaximumSubscribersReached) => {
+                Err(Error::GattSubscriberLimitReached.into())
+            }
+            Err(_) => Err(Error::Other.into()),
         }
     }
 
@@ -1134,10 +2507,17 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         &self,
         characteristic: &Characteristic<T>,
     ) -> Result<(), BleHostError<C::Error>> {
-        let properties = u16::to_le_bytes(0);
+        self.write_cccd(characteristic, 0).await
+    }
+
+    async fn write_cccd<T: AsGatt>(
+        &self,
+        characteristic: &Characteristic<T>,
+        value: u16,
+    ) -> Result<(), BleHostError<C::Error>> {
         let data = att::AttReq::Write {
             handle: characteristic.cccd_handle.ok_or(Error::NotSupported)?,
-            data: &[0, 0],
+            data: &value.to_le_bytes(),
         };
 
         // set the CCCD
@@ -1154,9 +2534,27 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
     async fn handle_notification_packet(&self, data: &[u8]) -> Result<(), BleHostError<C::Error>> {
         let mut r = ReadCursor::new(data);
         let value_handle: u16 = r.read()?;
-        let value_attr = r.remaining();
+        self.publish_notification(value_handle, r.remaining());
+        Ok(())
+    }
 
-        let handle = value_handle;
+    /// Handle a Multiple Handle Value Notification, publishing each value as a separate notification.
+    async fn handle_multiple_notification_packet(&self, data: &[u8]) -> Result<(), BleHostError<C::Error>> {
+        let mut r = ReadCursor::new(data);
+        while r.available() > 0 {
+            let handle: u16 = r.read()?;
+            let len: u16 = r.read()?;
+            let value = r.slice(len as usize)?;
+            self.publish_notification(handle, value);
+        }
+        Ok(())
+    }
+
+    fn publish_notification(&self, handle: u16, value_attr: &[u8]) {
+        if Some(handle) == self.service_changed_handle.get() {
+            self.services_changed.set(true);
+            self.known_services.borrow_mut().clear();
+        }
 
         // TODO
         let mut data = [0u8; 512];
@@ -1168,24 +2566,167 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
             len: to_copy,
         };
         self.notifications.immediate_publisher().publish_immediate(n);
-        Ok(())
+    }
+
+    /// Enable Multiple Handle Value Notifications in the Client Supported Features of the server.
+    ///
+    /// The server may then send the values of several subscribed characteristics in a single notification, which
+    /// are published to the listeners one by one. Servers without Client Supported Features are left as they are.
+    pub async fn enable_multiple_notifications(&self) -> Result<(), BleHostError<C::Error>> {
+        self.enable_client_features(MULTIPLE_HANDLE_VALUE_NOTIFICATIONS).await
+    }
+
+    /// Open Enhanced ATT bearers to the server.
+    ///
+    /// Up to `GATT_CLIENT_EATT_BEARERS_MAX` bearers are requested, and the number of bearers accepted by the
+    /// server is returned. Requests are then sent over an idle EATT bearer, falling back to the unenhanced
+    /// ATT bearer when all of them are busy. The bearers can only be opened once per client.
+    ///
+    /// EATT is first enabled in the Client Supported Features of the server, and the link must be encrypted for
+    /// the server to accept the bearers.
+    pub async fn open_eatt_bearers(&self) -> Result<usize, BleHostError<C::Error>> {
+        if !self.eatt.borrow().is_empty() {
+            return Err(Error::InvalidState.into());
+        }
+        self.enable_client_features(EATT_SUPPORTED).await?;
+        let channels: Vec<L2capChannel<'reference, P>, EATT_BEARERS> =
+            L2capChannel::create_multiple(self.stack, &self.connection, EATT_PSM, &eatt_config()).await?;
+        let n = channels.len();
+        *self.eatt.borrow_mut() = channels;
+        self.eatt_closed.set(0);
+        self.eatt_changed.signal(());
+        Ok(n)
+    }
+
+    /// Enable `features` in the Client Supported Features characteristic of the server, if it has one.
+    ///
+    /// Features cannot be disabled once enabled, so the features already enabled are kept.
+    async fn enable_client_features(&self, features: u8) -> Result<(), BleHostError<C::Error>> {
+        if self.client_features.get() & features == features {
+            return Ok(());
+        }
+        let data = att::AttReq::ReadByType {
+            start: 0x0001,
+            end: 0xffff,
+            attribute_type: CLIENT_SUPPORTED_FEATURES.into(),
+        };
+        let response = self.request(data).await?;
+        let (handle, enabled) = match Self::response(response.pdu.as_ref())? {
+            AttRsp::ReadByType { mut it } => match it.next() {
+                Some(Ok((handle, value))) => (handle, value.first().copied().unwrap_or(0)),
+                _ => return Err(Error::InvalidValue.into()),
+            },
+            // There is nothing to enable on a server without the characteristic.
+            AttRsp::Error { code, .. } if code == att::AttErrorCode::ATTRIBUTE_NOT_FOUND => {
+                self.client_features.set(self.client_features.get() | features);
+                return Ok(());
+            }
+            AttRsp::Error { code, .. } => return Err(Error::Att(code).into()),
+            _ => return Err(Error::UnexpectedGattResponse.into()),
+        };
+        if enabled & features == features {
+            self.client_features.set(enabled);
+            return Ok(());
+        }
+
+        let value = [enabled | features];
+        let response = self.request(att::AttReq::Write { handle, data: &value }).await?;
+        match Self::response(response.pdu.as_ref())? {
+            AttRsp::Write => {
+                self.client_features.set(value[0]);
+                Ok(())
+            }
+            AttRsp::Error { code, .. } => Err(Error::Att(code).into()),
+            _ => Err(Error::UnexpectedGattResponse.into()),
+        }
+    }
+
+    /// Number of EATT bearers currently open.
+    pub fn eatt_bearers(&self) -> usize {
+        let closed = self.eatt_closed.get();
+        (0..self.eatt.borrow().len()).filter(|i| closed & (1 << i) == 0).count()
+    }
+
+    /// Enable or disable automatic security elevation. Disabled by default.
+    ///
+    /// When enabled, a request rejected with Insufficient Authentication or Insufficient Encryption
+    /// makes the client request security on the connection, wait for pairing to complete and then retry
+    /// the request once. If security cannot be raised, the original error is returned.
+    ///
+    /// Pairing events such as [`ConnectionEvent::PassKeyDisplay`] are still delivered to the connection,
+    /// so the application must keep handling them while a request is pending.
+    #[cfg(feature = "security")]
+    pub fn set_security_elevation(&self, enabled: bool) {
+        self.security_elevation.set(enabled);
     }
 
     /// Task which handles GATT rx data (needed for notifications to work)
     pub async fn task(&self) -> Result<(), BleHostError<C::Error>> {
         loop {
             let handle = self.connection.handle();
-            let pdu = self.connection.next_gatt_client().await;
-            let data = pdu.as_ref();
-            // handle notifications
-            if pdu.as_ref()[0] == ATT_HANDLE_VALUE_NTF {
-                self.handle_notification_packet(&pdu.as_ref()[1..]).await?;
-            } else {
-                self.response_channel.send((handle, pdu)).await;
+            let bearers = select_array(core::array::from_fn::<_, EATT_BEARERS, _>(|i| self.receive_eatt(i)));
+            match select3(self.connection.next_gatt_client(), bearers, self.eatt_changed.wait()).await {
+                Either3::First(pdu) => {
+                    // handle notifications and indications
+                    match pdu.as_ref()[0] {
+                        ATT_HANDLE_VALUE_NTF => self.handle_notification_packet(&pdu.as_ref()[1..]).await?,
+                        ATT_MULTIPLE_HANDLE_VALUE_NTF => {
+                            self.handle_multiple_notification_packet(&pdu.as_ref()[1..]).await?
+                        }
+                        ATT_HANDLE_VALUE_IND => {
+                            self.handle_notification_packet(&pdu.as_ref()[1..]).await?;
+                            self.send_att_data(Att::Client(AttClient::Confirmation(AttCfm::ConfirmIndication)))
+                                .await?;
+                        }
+                        _ => self.response_channel.send((handle, pdu)).await,
+                    }
+                }
+                Either3::Second((Ok(sdu), bearer)) => {
+                    let len = sdu.len();
+                    let pdu = Pdu::new(sdu.into_inner(), len);
+                    match pdu.as_ref().first() {
+                        Some(&ATT_HANDLE_VALUE_NTF) => self.handle_notification_packet(&pdu.as_ref()[1..]).await?,
+                        Some(&ATT_MULTIPLE_HANDLE_VALUE_NTF) => {
+                            self.handle_multiple_notification_packet(&pdu.as_ref()[1..]).await?
+                        }
+                        Some(&ATT_HANDLE_VALUE_IND) => {
+                            self.handle_notification_packet(&pdu.as_ref()[1..]).await?;
+                            self.send_eatt_data(
+                                bearer,
+                                Att::Client(AttClient::Confirmation(AttCfm::ConfirmIndication)),
+                            )
+                            .await?;
+                        }
+                        Some(_) if self.eatt_stale.get() & (1 << bearer) != 0 => {
+                            // Late response to a cancelled request, the bearer can be used again.
+                            self.release_stale_eatt_bearer(bearer);
+                        }
+                        Some(_) if self.eatt_busy.get() & (1 << bearer) != 0 => {
+                            self.eatt_responses[bearer].send(Ok(pdu)).await
+                        }
+                        Some(_) => warn!("[gatt] unexpected response on EATT bearer {}", bearer),
+                        None => {}
+                    }
+                }
+                Either3::Second((Err(_), bearer)) => {
+                    warn!("[gatt] EATT bearer {} closed", bearer);
+                    self.eatt_closed.set(self.eatt_closed.get() | (1 << bearer));
+                    if self.eatt_stale.get() & (1 << bearer) != 0 {
+                        self.release_stale_eatt_bearer(bearer);
+                    } else if self.eatt_busy.get() & (1 << bearer) != 0 {
+                        let _ = self.eatt_responses[bearer].try_send(Err(Error::ChannelClosed));
+                    }
+                }
+                Either3::Third(_) => {}
             }
         }
     }
 
+    fn release_stale_eatt_bearer(&self, bearer: usize) {
+        self.eatt_stale.set(self.eatt_stale.get() & !(1 << bearer));
+        self.eatt_busy.set(self.eatt_busy.get() & !(1 << bearer));
+    }
+
     fn response<'a>(data: &'a [u8]) -> Result<AttRsp<'a>, BleHostError<C::Error>> {
         let att = Att::decode(data)?;
         match att {
@@ -1194,3 +2735,734 @@ impl<'reference, C: Controller, P: PacketPool, const MAX_SERVICES: usize> GattCl
         }
     }
 }
+
+/// A reliable write of one or more characteristics.
+///
+/// Writes are collected by the builder and nothing is sent until [`ReliableWrite::commit`] is called. Every value
+/// echoed by the server is verified before the writes are executed, and the server applies all of them at once.
+pub struct ReliableWrite<
+    'client,
+    'reference,
+    'd,
+    C: Controller,
+    P: PacketPool,
+    const MAX_SERVICES: usize,
+    const N: usize,
+> {
+    client: &'client GattClient<'reference, C, P, MAX_SERVICES>,
+    writes: Vec<(u16, &'d [u8]), N>,
+}
+
+impl<'d, C: Controller, P: PacketPool, const MAX_SERVICES: usize, const N: usize>
+    ReliableWrite<'_, '_, 'd, C, P, MAX_SERVICES, N>
+{
+    /// Add a write of `value` to a characteristic described by a handle.
+    ///
+    /// Returns [`Error::InsufficientSpace`] if `N` writes have already been added.
+    pub fn write<T: FromGatt>(
+        &mut self,
+        characteristic: &Characteristic<T>,
+        value: &'d [u8],
+    ) -> Result<&mut Self, Error> {
+        self.writes
+            .push((characteristic.handle, value))
+            .map_err(|_| Error::InsufficientSpace)?;
+        Ok(self)
+    }
+
+    /// Send the queued writes and execute them.
+    ///
+    /// If the server rejects a write or echoes back a value that differs from the one sent, the transaction is
+    /// cancelled and none of the writes are applied.
+    pub async fn commit(self) -> Result<(), BleHostError<C::Error>> {
+        self.client.execute_reliable_write(&self.writes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::task::Poll;
+
+    use bt_hci::param::{AddrKind, LeConnRole};
+    use bt_hci::uuid::characteristic::BATTERY_LEVEL;
+    use bt_hci::uuid::service::BATTERY;
+    use embassy_futures::join::join;
+    use embassy_futures::select::{select, Either};
+    use embassy_futures::{block_on, yield_now};
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use super::*;
+    use crate::attribute::{AttributeTable, Service};
+    use crate::mock_controller::MockController;
+    use crate::prelude::DefaultPacketPool;
+    use crate::HostResources;
+
+    type TestClient<'a> = GattClient<'a, MockController, DefaultPacketPool, 4>;
+
+    fn connect<'a>(stack: &'a Stack<'a, MockController, DefaultPacketPool>) -> Connection<'a, DefaultPacketPool> {
+        let connections = &stack.host.connections;
+        connections
+            .connect(
+                ConnHandle::new(0),
+                AddrKind::PUBLIC,
+                BdAddr::new([1; 6]),
+                LeConnRole::Central,
+            )
+            .unwrap();
+        let Poll::Ready(connection) = connections.poll_accept(LeConnRole::Central, &[], None) else {
+            panic!("expected connection to be accepted");
+        };
+        connection
+    }
+
+    /// Create a client, discarding the MTU exchange it starts with.
+    async fn client<'a>(
+        stack: &'a Stack<'a, MockController, DefaultPacketPool>,
+        connection: &Connection<'a, DefaultPacketPool>,
+    ) -> TestClient<'a> {
+        let client = GattClient::new(stack, connection).await.unwrap();
+        let _ = stack.host.connections.outbound().await;
+        client
+    }
+
+    /// Receive the next PDU sent by the client, without its L2CAP header.
+    async fn sent(client: &TestClient<'_>) -> Pdu<<DefaultPacketPool as PacketPool>::Packet> {
+        let (_, pdu) = client.stack.host.connections.outbound().await;
+        let len = pdu.len();
+        let mut packet = DefaultPacketPool::allocate().unwrap();
+        packet.as_mut()[..len - 4].copy_from_slice(&pdu.as_ref()[4..]);
+        Pdu::new(packet, len - 4)
+    }
+
+    /// Expect `request` from the client, and answer it with `response`.
+    async fn respond(client: &TestClient<'_>, request: &[u8], response: &[u8]) {
+        assert_eq!(sent(client).await.as_ref(), request);
+        let mut packet = DefaultPacketPool::allocate().unwrap();
+        packet.as_mut()[..response.len()].copy_from_slice(response);
+        client
+            .response_channel
+            .send((client.connection.handle(), Pdu::new(packet, response.len())))
+            .await;
+    }
+
+    /// Answer the prepared writes of the client by echoing them, until it executes or cancels them.
+    async fn echo_prepared_writes(client: &TestClient<'_>) -> (usize, u8) {
+        let mut prepared = 0;
+        loop {
+            let pdu = sent(client).await;
+            let mut packet = DefaultPacketPool::allocate().unwrap();
+            let len = pdu.len();
+            packet.as_mut()[..len].copy_from_slice(pdu.as_ref());
+            match pdu.as_ref()[0] {
+                att::ATT_PREPARE_WRITE_REQ => {
+                    prepared += 1;
+                    packet.as_mut()[0] = att::ATT_PREPARE_WRITE_RSP;
+                }
+                _ => {
+                    packet.as_mut()[0] = att::ATT_EXECUTE_WRITE_RSP;
+                    let handle = client.connection.handle();
+                    client.response_channel.send((handle, Pdu::new(packet, 1))).await;
+                    return (prepared, pdu.as_ref()[1]);
+                }
+            }
+            let handle = client.connection.handle();
+            client.response_channel.send((handle, Pdu::new(packet, len))).await;
+        }
+    }
+
+    #[test]
+    fn test_gatt_client_write_long() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        let characteristic: Characteristic<[u8; 1]> = Characteristic {
+            handle: 3,
+            cccd_handle: None,
+            phantom: PhantomData,
+        };
+        block_on(async {
+            let client = client(&stack, &connection).await;
+
+            // Fragments hold up to ATT_MTU - 5 bytes, then the queue is executed
+            let value = [7u8; 40];
+            let (res, echoed) = join(
+                client.write_characteristic_long(&characteristic, &value),
+                echo_prepared_writes(&client),
+            )
+            .await;
+            res.unwrap();
+            assert_eq!(echoed, (3, 1));
+
+            // Offsets beyond 16 bits cannot be written, the queued fragments are cancelled
+            let value = std::vec![0u8; 0x10000 + 18];
+            let (res, echoed) = join(
+                client.write_characteristic_long(&characteristic, &value),
+                echo_prepared_writes(&client),
+            )
+            .await;
+            assert!(matches!(res, Err(BleHostError::BleHost(Error::InvalidValue))));
+            assert_eq!(echoed, (0x10000 / 18 + 1, 0));
+        });
+    }
+
+    /// Answer each request of the client in turn, checking it is the expected one.
+    async fn respond_all(client: &TestClient<'_>, exchanges: &[(&[u8], &[u8])]) {
+        for (request, response) in exchanges {
+            respond(client, request, response).await;
+        }
+    }
+
+    #[test]
+    fn test_gatt_client_discover() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        block_on(async {
+            let client = client(&stack, &connection).await;
+
+            // A primary service at 1..=5 including a secondary service at 6..=8
+            let (discovered, _) = join(
+                client.discover::<2, 2>(),
+                respond_all(
+                    &client,
+                    &[
+                        (
+                            &[0x10, 1, 0, 0xff, 0xff, 0x00, 0x28],
+                            &[0x11, 6, 1, 0, 5, 0, 0x0d, 0x18],
+                        ),
+                        (&[0x10, 6, 0, 0xff, 0xff, 0x00, 0x28], &[0x01, 0x10, 6, 0, 0x0a]),
+                        (
+                            &[0x08, 1, 0, 5, 0, 0x02, 0x28],
+                            &[0x09, 8, 2, 0, 6, 0, 8, 0, 0x0f, 0x18],
+                        ),
+                        (&[0x08, 3, 0, 5, 0, 0x02, 0x28], &[0x01, 0x08, 3, 0, 0x0a]),
+                        (&[0x08, 6, 0, 8, 0, 0x02, 0x28], &[0x01, 0x08, 6, 0, 0x0a]),
+                        (
+                            &[0x08, 1, 0, 5, 0, 0x03, 0x28],
+                            &[0x09, 7, 3, 0, 0x02, 4, 0, 0x19, 0x2a],
+                        ),
+                        (&[0x08, 4, 0, 5, 0, 0x03, 0x28], &[0x01, 0x08, 4, 0, 0x0a]),
+                        (&[0x04, 5, 0, 5, 0], &[0x05, 1, 5, 0, 0x02, 0x29]),
+                        (&[0x08, 6, 0, 8, 0, 0x03, 0x28], &[0x01, 0x08, 6, 0, 0x0a]),
+                    ],
+                ),
+            )
+            .await;
+            let discovered = discovered.unwrap();
+            assert_eq!(discovered.len(), 2);
+            assert_eq!(
+                discovered[1].service,
+                ServiceHandle {
+                    start: 6,
+                    end: 8,
+                    uuid: Uuid::new_short(0x180f),
+                }
+            );
+            let characteristics = &discovered[0].characteristics;
+            assert_eq!(characteristics.len(), 1);
+            assert_eq!(characteristics[0].info.value_handle, 4);
+            assert_eq!(characteristics[0].info.uuid, Uuid::new_short(0x2a19));
+            assert_eq!(characteristics[0].descriptors[0].handle, 5);
+            assert!(discovered[1].characteristics.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_gatt_client_multiple_notifications() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        let characteristic: Characteristic<[u8; 2]> = Characteristic {
+            handle: 3,
+            cccd_handle: Some(4),
+            phantom: PhantomData,
+        };
+        block_on(async {
+            let client = client(&stack, &connection).await;
+
+            // Subscribing leaves the Client Supported Features alone
+            let (listener, _) = join(
+                client.subscribe(&characteristic, true),
+                respond(&client, &[att::ATT_WRITE_REQ, 4, 0, 0x02, 0], &[att::ATT_WRITE_RSP]),
+            )
+            .await;
+            drop(listener.unwrap());
+
+            // Multiple Handle Value Notifications are enabled on request
+            let (enabled, _) = join(
+                client.enable_multiple_notifications(),
+                respond_all(
+                    &client,
+                    &[
+                        (
+                            &[att::ATT_READ_BY_TYPE_REQ, 1, 0, 0xff, 0xff, 0x29, 0x2b],
+                            &[att::ATT_READ_BY_TYPE_RSP, 3, 0x10, 0, EATT_SUPPORTED],
+                        ),
+                        (
+                            &[
+                                att::ATT_WRITE_REQ,
+                                0x10,
+                                0,
+                                EATT_SUPPORTED | MULTIPLE_HANDLE_VALUE_NOTIFICATIONS,
+                            ],
+                            &[att::ATT_WRITE_RSP],
+                        ),
+                    ],
+                ),
+            )
+            .await;
+            enabled.unwrap();
+
+            // The features are only enabled once
+            client.enable_multiple_notifications().await.unwrap();
+            let (listener, _) = join(
+                client.subscribe(&characteristic, false),
+                respond(&client, &[att::ATT_WRITE_REQ, 4, 0, 0x01, 0], &[att::ATT_WRITE_RSP]),
+            )
+            .await;
+            let mut listener = listener.unwrap();
+
+            // Each value of a Multiple Handle Value Notification is published as a notification
+            client
+                .handle_multiple_notification_packet(&[5, 0, 1, 0, 0xcc, 3, 0, 2, 0, 0xaa, 0xbb])
+                .await
+                .unwrap();
+            assert_eq!(listener.next().await.as_ref(), [0xaa, 0xbb]);
+
+            // A truncated value is rejected
+            assert!(client
+                .handle_multiple_notification_packet(&[3, 0, 4, 0, 0xaa])
+                .await
+                .is_err());
+        });
+    }
+
+    #[test]
+    fn test_gatt_client_read_multiple() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        block_on(async {
+            let client = client(&stack, &connection).await;
+
+            let (mut first, mut second) = ([0u8; 2], [0u8; 3]);
+            let (copied, _) = join(
+                client.read_multiple(&[3, 5], &mut [&mut first, &mut second]),
+                respond(
+                    &client,
+                    &[att::ATT_READ_MULTIPLE_REQ, 3, 0, 5, 0],
+                    &[att::ATT_READ_MULTIPLE_RSP, 1, 2, 3, 4, 5],
+                ),
+            )
+            .await;
+            assert_eq!(copied.unwrap(), [2, 3]);
+            assert_eq!((first, second), ([1, 2], [3, 4, 5]));
+
+            let (mut first, mut second) = ([0u8; 4], [0u8; 4]);
+            let (copied, _) = join(
+                client.read_multiple_variable(&[3, 5], &mut [&mut first, &mut second]),
+                respond(
+                    &client,
+                    &[att::ATT_READ_MULTIPLE_VARIABLE_REQ, 3, 0, 5, 0],
+                    &[att::ATT_READ_MULTIPLE_VARIABLE_RSP, 1, 0, 9, 3, 0, 6, 7, 8],
+                ),
+            )
+            .await;
+            assert_eq!(copied.unwrap(), [1, 3]);
+            assert_eq!((&first[..1], &second[..3]), (&[9][..], &[6, 7, 8][..]));
+
+            // Errors reported by the server are returned
+            let (copied, _) = join(
+                client.read_multiple(&[3, 5], &mut [&mut first, &mut second]),
+                respond(
+                    &client,
+                    &[att::ATT_READ_MULTIPLE_REQ, 3, 0, 5, 0],
+                    &[att::ATT_ERROR_RSP, att::ATT_READ_MULTIPLE_REQ, 5, 0, 0x02],
+                ),
+            )
+            .await;
+            assert!(matches!(
+                copied,
+                Err(BleHostError::BleHost(Error::Att(AttErrorCode::READ_NOT_PERMITTED)))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_gatt_client_restore_cache() {
+        const READ_HASH: &[u8] = &[att::ATT_READ_BY_TYPE_REQ, 1, 0, 0xff, 0xff, 0x2a, 0x2b];
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        block_on(async {
+            let client = client(&stack, &connection).await;
+            let hash_rsp = |hash: u8| {
+                let mut rsp = [hash; 20];
+                rsp[..4].copy_from_slice(&[att::ATT_READ_BY_TYPE_RSP, 18, 0x10, 0x00]);
+                rsp
+            };
+            let mut cache: GattCache<4, 1, 1> = GattCache {
+                identity: connection.peer_identity(),
+                database_hash: Some(u128::from_le_bytes([7; 16])),
+                service_changed_handle: None,
+                services: Vec::new(),
+            };
+
+            // The cache is only restored if the hash of the peer matches
+            let (restored, _) = join(client.restore_cache(&cache), respond(&client, READ_HASH, &hash_rsp(7))).await;
+            assert!(restored.unwrap());
+            let (restored, _) = join(client.restore_cache(&cache), respond(&client, READ_HASH, &hash_rsp(8))).await;
+            assert!(!restored.unwrap());
+
+            // A missing hash, on either side, is a cache miss
+            let not_found = [att::ATT_ERROR_RSP, att::ATT_READ_BY_TYPE_REQ, 1, 0, 0x0a];
+            let (restored, _) = join(client.restore_cache(&cache), respond(&client, READ_HASH, &not_found)).await;
+            assert!(!restored.unwrap());
+            cache.database_hash = None;
+            let (restored, _) = join(client.restore_cache(&cache), respond(&client, READ_HASH, &not_found)).await;
+            assert!(!restored.unwrap());
+            let (restored, _) = join(client.restore_cache(&cache), respond(&client, READ_HASH, &hash_rsp(7))).await;
+            assert!(!restored.unwrap());
+        });
+    }
+
+    #[cfg(feature = "security")]
+    #[test]
+    fn test_gatt_client_security_elevation() {
+        use bt_hci::event::{EventKind, EventPacket};
+
+        use crate::prelude::{Address, Identity, LongTermKey};
+        use crate::security_manager::BondInformation;
+
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources).set_random_address(Address::random([2; 6]));
+        let identity = Identity {
+            bd_addr: BdAddr::new([1; 6]),
+            irk: None,
+        };
+        let bond = BondInformation::new(identity, LongTermKey::new(0), SecurityLevel::Encrypted, true);
+        stack
+            .host
+            .connections
+            .security_manager
+            .add_bond_information(bond)
+            .unwrap();
+        let connection = connect(&stack);
+        // The link is encrypted with the unauthenticated key of the bond
+        let encryption_change = EventPacket {
+            kind: EventKind::EncryptionChangeV1,
+            data: &[0, 0, 0, 1],
+        };
+        stack
+            .host
+            .connections
+            .handle_security_hci_event(encryption_change)
+            .unwrap();
+        assert_eq!(connection.security_level().unwrap(), SecurityLevel::Encrypted);
+
+        let characteristic: Characteristic<u8> = Characteristic {
+            handle: 3,
+            cccd_handle: None,
+            phantom: PhantomData,
+        };
+        block_on(async {
+            let client = client(&stack, &connection).await;
+            client.set_security_elevation(true);
+
+            // Insufficient authentication on an encrypted link pairs again, with MITM protection
+            let mut value = [0u8; 1];
+            let request = client.read_characteristic(&characteristic, &mut value);
+            let insufficient = [att::ATT_ERROR_RSP, att::ATT_READ_REQ, 3, 0, 0x05];
+            let pairing_request = async {
+                respond(&client, &[att::ATT_READ_REQ, 3, 0], &insufficient).await;
+                sent(&client).await
+            };
+            let Either::Second(pdu) = select(request, pairing_request).await else {
+                panic!("expected pairing to be started");
+            };
+            assert_eq!(pdu.as_ref()[0], 0x01);
+            assert_ne!(pdu.as_ref()[3] & 0x04, 0);
+        });
+    }
+
+    // Channel of the peer that EATT bearers are opened to, and local channel of the first bearer opened.
+    const PEER_CID: u16 = 0x80;
+    const BEARER_CID: u16 = 0x40;
+
+    /// Receive the next SDU sent on an EATT bearer, reassembled from its K-frames.
+    async fn sent_eatt(stack: &Stack<'_, MockController, DefaultPacketPool>) -> std::vec::Vec<u8> {
+        let mut sdu = std::vec::Vec::new();
+        let mut len = None;
+        while len != Some(sdu.len()) {
+            let (_, pdu) = stack.host.connections.outbound().await;
+            let frame = pdu.as_ref();
+            assert_eq!(u16::from_le_bytes([frame[2], frame[3]]), PEER_CID);
+            if len.is_none() {
+                len = Some(u16::from_le_bytes([frame[4], frame[5]]) as usize);
+                sdu.extend_from_slice(&frame[6..]);
+            } else {
+                sdu.extend_from_slice(&frame[4..]);
+            }
+        }
+        sdu
+    }
+
+    /// Receive `sdu` from the peer on the first EATT bearer, split into K-frames of up to 64 bytes.
+    fn receive_eatt(stack: &Stack<'_, MockController, DefaultPacketPool>, sdu: &[u8]) {
+        let mut frame = (sdu.len() as u16).to_le_bytes().to_vec();
+        frame.extend_from_slice(sdu);
+        for chunk in frame.chunks(64) {
+            let mut packet = DefaultPacketPool::allocate().unwrap();
+            packet.as_mut()[..chunk.len()].copy_from_slice(chunk);
+            stack
+                .host
+                .channels
+                .dispatch(BEARER_CID, Pdu::new(packet, chunk.len()))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_gatt_client_eatt_routing() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+        let characteristic: Characteristic<u8> = Characteristic {
+            handle: 3,
+            cccd_handle: None,
+            phantom: PhantomData,
+        };
+        block_on(async {
+            let client = client(&stack, &connection).await;
+            let bearer = stack
+                .host
+                .channels
+                .open_for_test(connection.handle(), PEER_CID)
+                .unwrap();
+            assert!(client.eatt.borrow_mut().push(bearer).is_ok());
+
+            select(client.task(), async {
+                let mut value = [0u8; 1];
+
+                // Requests are sent on the idle bearer, and answered by the response received on it
+                let (read, _) = join(client.read_characteristic(&characteristic, &mut value), async {
+                    assert_eq!(sent_eatt(&stack).await, [att::ATT_READ_REQ, 3, 0]);
+                    receive_eatt(&stack, &[att::ATT_READ_RSP, 1]);
+                })
+                .await;
+                assert_eq!(read.unwrap(), 1);
+                assert_eq!(value, [1]);
+
+                // A request cancelled before its response arrives keeps the bearer busy, so the next request is
+                // sent on the unenhanced ATT bearer
+                let mut late = [0u8; 1];
+                let cancelled = select(
+                    client.read_characteristic(&characteristic, &mut late),
+                    sent_eatt(&stack),
+                )
+                .await;
+                assert!(matches!(cancelled, Either::Second(_)));
+                join(
+                    client.read_characteristic(&characteristic, &mut value),
+                    respond(&client, &[att::ATT_READ_REQ, 3, 0], &[att::ATT_READ_RSP, 2]),
+                )
+                .await
+                .0
+                .unwrap();
+                assert_eq!(value, [2]);
+
+                // The late response is discarded rather than taken as the response to the next request
+                receive_eatt(&stack, &[att::ATT_READ_RSP, 9]);
+                yield_now().await;
+                join(client.read_characteristic(&characteristic, &mut value), async {
+                    assert_eq!(sent_eatt(&stack).await, [att::ATT_READ_REQ, 3, 0]);
+                    receive_eatt(&stack, &[att::ATT_READ_RSP, 3]);
+                })
+                .await
+                .0
+                .unwrap();
+                assert_eq!(value, [3]);
+                assert_eq!(late, [0]);
+
+                // Long writes stay on one bearer, with fragments sized from its MTU
+                let long = [5u8; 100];
+                let (write, _) = join(client.write_characteristic_long(&characteristic, &long), async {
+                    for (offset, len) in [(0u8, 59), (59, 41)] {
+                        let pdu = sent_eatt(&stack).await;
+                        assert_eq!(pdu[..5], [att::ATT_PREPARE_WRITE_REQ, 3, 0, offset, 0]);
+                        assert_eq!(pdu.len(), 5 + len);
+                        let mut response = pdu;
+                        response[0] = att::ATT_PREPARE_WRITE_RSP;
+                        receive_eatt(&stack, &response);
+                    }
+                    assert_eq!(sent_eatt(&stack).await, [att::ATT_EXECUTE_WRITE_REQ, 1]);
+                    receive_eatt(&stack, &[att::ATT_EXECUTE_WRITE_RSP]);
+                })
+                .await;
+                write.unwrap();
+            })
+            .await;
+        });
+    }
+
+    #[test]
+    fn test_gatt_server_eatt_bearer() {
+        let mut resources: HostResources<DefaultPacketPool, 1, 2> = HostResources::new();
+        let stack = crate::new(MockController::new(), &mut resources);
+        let connection = connect(&stack);
+
+        let mut level_store = [0u8; 1];
+        let table: AttributeTable<'_, NoopRawMutex, 8> = AttributeTable::new();
+        let mut service = table.add_service(Service::new(BATTERY));
+        let level = service
+            .add_characteristic(BATTERY_LEVEL, &[CharacteristicProp::Read], 50u8, &mut level_store)
+            .build();
+        let deferred = service
+            .add_characteristic_deferred::<[u8; 4], _>(
+                BATTERY_LEVEL,
+                &[CharacteristicProp::Read, CharacteristicProp::Write],
+            )
+            .build();
+        service.build();
+        let server = AttributeServer::<_, DefaultPacketPool, 8, 1, 1>::new(table);
+        let gatt = GattConnection::try_new(connection.clone(), &server).unwrap();
+        let mut bearer = EattBearer {
+            channel: stack
+                .host
+                .channels
+                .open_for_test(connection.handle(), PEER_CID)
+                .unwrap(),
+            connection: gatt.raw().clone(),
+            server: gatt.server,
+        };
+
+        block_on(async {
+            // Requests are answered on the bearer they were received on
+            receive_eatt(&stack, &[att::ATT_READ_REQ, level.handle as u8, 0]);
+            bearer.process(&stack).await.unwrap();
+            assert_eq!(sent_eatt(&stack).await, [att::ATT_READ_RSP, 50]);
+
+            // Deferred attributes are handed to the application as events
+            receive_eatt(&stack, &[att::ATT_READ_REQ, deferred.handle as u8, 0]);
+            let GattEvent::Read(event) = bearer.next(&stack).await.unwrap() else {
+                panic!("expected a read event");
+            };
+            assert_eq!(event.handle(), deferred.handle);
+            event.respond(&[1, 2, 3, 4]).unwrap().send().await;
+            assert_eq!(sent_eatt(&stack).await, [att::ATT_READ_RSP, 1, 2, 3, 4]);
+
+            // So are the parts of long writes
+            let prepare = [att::ATT_PREPARE_WRITE_REQ, deferred.handle as u8, 0, 2, 0, 5, 6];
+            receive_eatt(&stack, &prepare);
+            let GattEvent::Write(event) = bearer.next(&stack).await.unwrap() else {
+                panic!("expected a write event");
+            };
+            assert_eq!(event.handle(), deferred.handle);
+            assert_eq!(event.offset(), 2);
+            assert_eq!(event.data(), &[5, 6]);
+            event.accept().unwrap().send().await;
+            let mut response = prepare;
+            response[0] = att::ATT_PREPARE_WRITE_RSP;
+            assert_eq!(sent_eatt(&stack).await, response);
+        });
+    }
+
+    #[test]
+    fn test_gatt_cache_serialize() {
+        let mut characteristics = Vec::new();
+        let _ = characteristics.push(DiscoveredCharacteristic {
+            info: CharacteristicInfo {
+                declaration_handle: 0x0010,
+                value_handle: 0x0011,
+                end_handle: 0x0012,
+                props: [CharacteristicProp::Read, CharacteristicProp::Notify].into(),
+                uuid: BATTERY_LEVEL.into(),
+            },
+            descriptors: Vec::from_slice(&[DescriptorInfo {
+                handle: 0x0012,
+                uuid: CLIENT_CHARACTERISTIC_CONFIGURATION.into(),
+            }])
+            .unwrap(),
+        });
+        let mut services = Vec::new();
+        let _ = services.push(DiscoveredService {
+            service: ServiceHandle {
+                start: 0x000f,
+                end: 0x0012,
+                uuid: BATTERY.into(),
+            },
+            characteristics,
+        });
+        let _ = services.push(DiscoveredService {
+            service: ServiceHandle {
+                start: 0x0013,
+                end: 0xffff,
+                uuid: Uuid::new_long([0x12; 16]),
+            },
+            characteristics: Vec::new(),
+        });
+        let cache: GattCache<2, 1, 1> = GattCache {
+            identity: Identity {
+                bd_addr: BdAddr::new([1, 2, 3, 4, 5, 6]),
+                #[cfg(feature = "security")]
+                irk: Some(IdentityResolvingKey::new(0x1234)),
+            },
+            database_hash: Some(0xdead_beef),
+            service_changed_handle: Some(0x0003),
+            services,
+        };
+
+        let mut buf = [0; 128];
+        let len = cache.serialize(&mut buf).unwrap();
+        assert_eq!(GattCache::deserialize(&buf[..len]).unwrap(), cache);
+        assert_eq!(
+            cache.characteristic::<u8>(&BATTERY.into(), &BATTERY_LEVEL.into()),
+            Some(Characteristic {
+                handle: 0x0011,
+                cccd_handle: Some(0x0012),
+                phantom: PhantomData,
+            })
+        );
+
+        // Not enough capacity for the cached services.
+        assert!(GattCache::<1, 1, 1>::deserialize(&buf[..len]).is_err());
+        assert!(cache.serialize(&mut buf[..16]).is_err());
+
+        // Caches written with another layout are rejected.
+        buf[0] = CACHE_FORMAT + 1;
+        assert_eq!(GattCache::<2, 1, 1>::deserialize(&buf[..len]), Err(Error::InvalidValue));
+
+        // Counts that don't fit the layout are not truncated.
+        let mut characteristics: Vec<DiscoveredCharacteristic<1>, 256> = Vec::new();
+        for i in 0..256 {
+            let _ = characteristics.push(DiscoveredCharacteristic {
+                info: CharacteristicInfo {
+                    declaration_handle: i,
+                    value_handle: i,
+                    end_handle: i,
+                    props: [CharacteristicProp::Read].into(),
+                    uuid: BATTERY_LEVEL.into(),
+                },
+                descriptors: Vec::new(),
+            });
+        }
+        let cache: GattCache<1, 256, 1> = GattCache {
+            identity: cache.identity,
+            database_hash: None,
+            service_changed_handle: None,
+            services: Vec::from_slice(&[DiscoveredService {
+                service: ServiceHandle {
+                    start: 0x0001,
+                    end: 0xffff,
+                    uuid: BATTERY.into(),
+                },
+                characteristics,
+            }])
+            .unwrap(),
+        };
+        let mut buf = [0; 4096];
+        assert_eq!(cache.serialize(&mut buf), Err(Error::InvalidValue));
+    }
+}
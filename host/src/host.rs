@@ -1,6 +1,8 @@
 //! BleHost
 //!
 //! The host module contains the main entry point for the TrouBLE host.
+#[cfg(feature = "security")]
+use core::cell::Cell;
 use core::cell::RefCell;
 use core::future::poll_fn;
 use core::mem::MaybeUninit;
@@ -12,9 +14,10 @@ use bt_hci::cmd::controller_baseband::{
 };
 use bt_hci::cmd::info::ReadBdAddr;
 use bt_hci::cmd::le::{
-    LeConnUpdate, LeCreateConnCancel, LeEnableEncryption, LeLongTermKeyRequestReply, LeReadBufferSize,
-    LeReadFilterAcceptListSize, LeSetAdvEnable, LeSetEventMask, LeSetExtAdvEnable, LeSetExtScanEnable, LeSetRandomAddr,
-    LeSetScanEnable,
+    LeAddDeviceToResolvingList, LeClearResolvingList, LeConnUpdate, LeCreateConnCancel, LeEnableEncryption,
+    LeLongTermKeyRequestReply, LeReadBufferSize, LeReadFilterAcceptListSize, LeReadResolvingListSize,
+    LeSetAddrResolutionEnable, LeSetAdvEnable, LeSetAdvSetRandomAddr, LeSetEventMask, LeSetExtAdvEnable,
+    LeSetExtScanEnable, LeSetPrivacyMode, LeSetRandomAddr, LeSetResolvablePrivateAddrTimeout, LeSetScanEnable,
 };
 use bt_hci::cmd::link_control::Disconnect;
 use bt_hci::cmd::{AsyncCmd, SyncCmd};
@@ -38,6 +41,8 @@ use embassy_futures::select::{select3, select4, Either3, Either4};
 use embassy_sync::once_lock::OnceLock;
 use embassy_sync::waitqueue::WakerRegistration;
 use embassy_time::Duration;
+#[cfg(feature = "security")]
+use embassy_time::Timer;
 use futures::pin_mut;
 
 use crate::att::{AttClient, AttServer};
@@ -55,6 +60,10 @@ use crate::types::l2cap::{
 };
 use crate::{att, Address, BleHostError, Error, PacketPool, Stack};
 
+/// Delay before renewing the resolvable private address again, when a connection was being created.
+#[cfg(feature = "security")]
+const RPA_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
 /// A BLE Host.
 ///
 /// The BleHost holds the runtime state of the host, and is the entry point
@@ -73,6 +82,9 @@ pub(crate) struct BleHost<'d, T, P: PacketPool> {
     pub(crate) advertise_command_state: CommandState<bool>,
     pub(crate) connect_command_state: CommandState<bool>,
     pub(crate) scan_command_state: CommandState<bool>,
+    /// Scanning is enabled, and with the extended scanning commands.
+    #[cfg(feature = "security")]
+    pub(crate) scanning: Cell<Option<bool>>,
 }
 
 #[derive(Clone, Copy)]
@@ -84,12 +96,14 @@ pub(crate) struct InitialState {
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum AdvHandleState {
     None,
-    Advertising(AdvHandle),
+    Advertising(AdvSet),
     Terminated(AdvHandle),
 }
 
 pub(crate) struct AdvInnerState<'d> {
     handles: &'d mut [AdvHandleState],
+    /// The advertising sets were started with the extended advertising commands.
+    extended: bool,
     waker: WakerRegistration,
 }
 
@@ -102,6 +116,7 @@ impl<'d> AdvState<'d> {
         Self {
             state: RefCell::new(AdvInnerState {
                 handles,
+                extended: false,
                 waker: WakerRegistration::new(),
             }),
         }
@@ -120,7 +135,7 @@ impl<'d> AdvState<'d> {
         let mut state = self.state.borrow_mut();
         for entry in state.handles.iter_mut() {
             match entry {
-                AdvHandleState::Advertising(h) if *h == handle => {
+                AdvHandleState::Advertising(set) if set.adv_handle == handle => {
                     *entry = AdvHandleState::Terminated(handle);
                 }
                 _ => {}
@@ -134,7 +149,7 @@ impl<'d> AdvState<'d> {
         state.handles.len()
     }
 
-    pub(crate) fn start(&self, sets: &[AdvSet]) {
+    pub(crate) fn start(&self, sets: &[AdvSet], extended: bool) {
         let mut state = self.state.borrow_mut();
         assert!(sets.len() <= state.handles.len());
         for handle in state.handles.iter_mut() {
@@ -142,7 +157,42 @@ impl<'d> AdvState<'d> {
         }
 
         for (idx, entry) in sets.iter().enumerate() {
-            state.handles[idx] = AdvHandleState::Advertising(entry.adv_handle);
+            state.handles[idx] = AdvHandleState::Advertising(*entry);
+        }
+        state.extended = extended;
+    }
+
+    /// Legacy advertising stops once a connection is established from it.
+    pub(crate) fn connected(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.extended {
+            return;
+        }
+        for entry in state.handles.iter_mut() {
+            if let AdvHandleState::Advertising(set) = entry {
+                *entry = AdvHandleState::Terminated(set.adv_handle);
+            }
+        }
+        state.waker.wake();
+    }
+
+    /// Whether advertising is enabled, and with the extended advertising commands.
+    #[cfg(feature = "security")]
+    pub(crate) fn advertising(&self) -> Option<bool> {
+        let state = self.state.borrow();
+        state
+            .handles
+            .iter()
+            .any(|entry| matches!(entry, AdvHandleState::Advertising(_)))
+            .then_some(state.extended)
+    }
+
+    /// Advertising set at `index`, if it is enabled.
+    #[cfg(feature = "security")]
+    pub(crate) fn advertising_set(&self, index: usize) -> Option<AdvSet> {
+        match self.state.borrow().handles.get(index) {
+            Some(AdvHandleState::Advertising(set)) => Some(*set),
+            _ => None,
         }
     }
 
@@ -211,6 +261,8 @@ where
             advertise_command_state: CommandState::new(),
             scan_command_state: CommandState::new(),
             connect_command_state: CommandState::new(),
+            #[cfg(feature = "security")]
+            scanning: Cell::new(None),
         }
     }
 
@@ -225,6 +277,214 @@ where
         Ok(ret)
     }
 
+    /// Address used by advertising, scanning and connection procedures.
+    ///
+    /// With privacy enabled, this is the current resolvable private address.
+    pub(crate) fn own_address(&self) -> Option<Address> {
+        #[cfg(feature = "security")]
+        if let Some(rpa) = self.connections.security_manager.resolvable_private_address() {
+            return Some(rpa);
+        }
+        self.address
+    }
+
+    /// Update the privacy state of the controller before starting advertising, scanning and connection procedures.
+    ///
+    /// The controller resolving list is loaded with the bonded peers if they changed, and the resolvable private
+    /// address is renewed if it has expired. The controller only allows both while no procedure is active, so
+    /// they are otherwise left as they are until the next attempt.
+    pub(crate) async fn update_privacy(&self) -> Result<(), BleHostError<T::Error>>
+    where
+        T: ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
+    {
+        #[cfg(feature = "security")]
+        {
+            const DISALLOWED: Error = Error::Hci(bt_hci::param::Error::CMD_DISALLOWED);
+            match self.load_resolving_list().await {
+                Ok(()) => {}
+                Err(BleHostError::BleHost(DISALLOWED)) => {
+                    warn!("[host] unable to load resolving list while active");
+                }
+                Err(e) => return Err(e),
+            }
+
+            let security_manager = &self.connections.security_manager;
+            if let Some(rpa) = security_manager.next_resolvable_private_address(embassy_time::Instant::now()) {
+                match self.command(LeSetRandomAddr::new(rpa.addr)).await {
+                    Ok(_) => {
+                        debug!("[host] resolvable private address {:?}", rpa.addr);
+                        security_manager.set_resolvable_private_address(rpa, embassy_time::Instant::now());
+                    }
+                    Err(BleHostError::BleHost(DISALLOWED)) => {
+                        warn!("[host] unable to renew resolvable private address while active");
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Renew the resolvable private address once it has expired, while advertising or scanning.
+    ///
+    /// The controller only allows the address to change while no procedure is active, so advertising and scanning
+    /// are stopped and then restarted with the new address. The address can't be renewed while a connection is
+    /// being created or advertising or scanning is being started or stopped, in which case `false` is returned and
+    /// the address is left as it is.
+    #[cfg(feature = "security")]
+    async fn rotate_address(&self) -> Result<bool, BleHostError<T::Error>>
+    where
+        T: ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeSetAdvSetRandomAddr>
+            + for<'t> ControllerCmdSync<LeSetAdvEnable>
+            + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+            + ControllerCmdSync<LeSetScanEnable>
+            + ControllerCmdSync<LeSetExtScanEnable>,
+    {
+        let security_manager = &self.connections.security_manager;
+        let Some(rpa) = security_manager.next_resolvable_private_address(embassy_time::Instant::now()) else {
+            return Ok(true);
+        };
+        if self.connect_command_state.is_active() {
+            return Ok(false);
+        }
+
+        // Hold idle advertise and scan command states so neither procedure is started while the address changes.
+        let advertise_held = self.advertise_command_state.try_request();
+        let scan_held = self.scan_command_state.try_request();
+        let _release = OnDrop::new(|| {
+            if advertise_held {
+                self.advertise_command_state.canceled();
+            }
+            if scan_held {
+                self.scan_command_state.canceled();
+            }
+        });
+        let advertising = if advertise_held {
+            None
+        } else {
+            self.advertise_state.advertising()
+        };
+        let scanning = if scan_held { None } else { self.scanning.get() };
+        // A procedure that is still being started or stopped is not reflected in the state yet.
+        if (!advertise_held && (advertising.is_none() || !self.advertise_command_state.is_active()))
+            || (!scan_held && (scanning.is_none() || !self.scan_command_state.is_active()))
+        {
+            return Ok(false);
+        }
+        match advertising {
+            Some(true) => self.command(LeSetExtAdvEnable::new(false, &[])).await?,
+            Some(false) => self.command(LeSetAdvEnable::new(false)).await?,
+            None => {}
+        }
+        match scanning {
+            Some(true) => {
+                self.command(LeSetExtScanEnable::new(
+                    false,
+                    FilterDuplicates::Disabled,
+                    bt_hci::param::Duration::from_secs(0),
+                    bt_hci::param::Duration::from_secs(0),
+                ))
+                .await?
+            }
+            Some(false) => self.command(LeSetScanEnable::new(false, false)).await?,
+            None => {}
+        }
+
+        self.command(LeSetRandomAddr::new(rpa.addr)).await?;
+        security_manager.set_resolvable_private_address(rpa, embassy_time::Instant::now());
+        debug!("[host] resolvable private address {:?}", rpa.addr);
+
+        match advertising {
+            Some(true) => {
+                // Advertising sets have their own random address.
+                for index in 0..self.advertise_state.len() {
+                    if let Some(set) = self.advertise_state.advertising_set(index) {
+                        self.command(LeSetAdvSetRandomAddr::new(set.adv_handle, rpa.addr))
+                            .await?;
+                        self.command(LeSetExtAdvEnable::new(true, &[set])).await?;
+                    }
+                }
+            }
+            Some(false) => self.command(LeSetAdvEnable::new(true)).await?,
+            None => {}
+        }
+        match scanning {
+            Some(true) => {
+                self.command(LeSetExtScanEnable::new(
+                    true,
+                    FilterDuplicates::Disabled,
+                    bt_hci::param::Duration::from_secs(0),
+                    bt_hci::param::Duration::from_secs(0),
+                ))
+                .await?
+            }
+            Some(false) => self.command(LeSetScanEnable::new(true, true)).await?,
+            None => {}
+        }
+        Ok(true)
+    }
+
+    /// Load the identity resolving keys of the bonded peers into the controller resolving list.
+    #[cfg(feature = "security")]
+    async fn load_resolving_list(&self) -> Result<(), BleHostError<T::Error>>
+    where
+        T: ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
+    {
+        let security_manager = &self.connections.security_manager;
+        if security_manager.resolving_list_size().is_none() {
+            // Without a resolving list, peers are only resolved by the host.
+            let size = self.command(LeReadResolvingListSize::new()).await.unwrap_or(0);
+            info!("[host] controller resolving list size {}", size);
+            security_manager.set_resolving_list_size(size);
+        }
+        let Some(update) = security_manager.resolving_list_update() else {
+            return Ok(());
+        };
+
+        self.command(LeSetAddrResolutionEnable::new(false)).await?;
+        self.command(LeClearResolvingList::new()).await?;
+        for (address, irk) in update.entries.iter() {
+            self.command(LeAddDeviceToResolvingList::new(
+                address.kind,
+                address.addr,
+                irk.to_le_bytes(),
+                update.local_irk.to_le_bytes(),
+            ))
+            .await?;
+            match self
+                .command(LeSetPrivacyMode::new(address.kind, address.addr, update.privacy_mode))
+                .await
+            {
+                // Controllers before Bluetooth 5.0 only support network privacy mode.
+                Ok(_) | Err(BleHostError::BleHost(Error::Hci(bt_hci::param::Error::UNKNOWN_CMD))) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(timeout) = update.rpa_timeout {
+            let timeout = timeout.as_secs().clamp(1, 0xa1b8) as u32;
+            self.command(LeSetResolvablePrivateAddrTimeout::new(
+                bt_hci::param::Duration::from_secs(timeout),
+            ))
+            .await?;
+        }
+        self.command(LeSetAddrResolutionEnable::new(true)).await?;
+        security_manager.resolving_list_updated();
+        debug!("[host] loaded {} peers into the resolving list", update.entries.len());
+        Ok(())
+    }
+
     /// Run an async HCI command where the response will generate an event later.
     pub(crate) async fn async_command<C>(&self, cmd: C) -> Result<(), BleHostError<T::Error>>
     where
@@ -261,6 +521,9 @@ where
                         "[host] connection with handle {:?} established to {:02x?}",
                         handle, peer_addr
                     );
+                    if role == LeConnRole::Peripheral {
+                        self.advertise_state.connected();
+                    }
                     let mut m = self.metrics.borrow_mut();
                     m.connect_events = m.connect_events.wrapping_add(1);
                 }
@@ -460,7 +723,10 @@ where
                 } else {
                     #[cfg(feature = "gatt")]
                     match a {
-                        Ok(att::Att::Client(_)) => {
+                        Ok(att::Att::Client(client)) => {
+                            if let att::AttClient::Confirmation(_) = client {
+                                self.connections.confirm_indication(acl.handle())?;
+                            }
                             self.connections.post_gatt(acl.handle(), pdu)?;
                         }
                         Ok(att::Att::Server(_)) => {
@@ -719,6 +985,7 @@ impl<'d, C: Controller, P: PacketPool> Runner<'d, C, P> {
             + ControllerCmdSync<LeSetExtScanEnable>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+            + ControllerCmdSync<LeSetAdvSetRandomAddr>
             + for<'t> ControllerCmdSync<HostNumberOfCompletedPackets<'t>>
             + ControllerCmdSync<LeReadBufferSize>
             + ControllerCmdSync<LeLongTermKeyRequestReply>
@@ -743,6 +1010,7 @@ impl<'d, C: Controller, P: PacketPool> Runner<'d, C, P> {
             + ControllerCmdSync<SetControllerToHostFlowControl>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+            + ControllerCmdSync<LeSetAdvSetRandomAddr>
             + for<'t> ControllerCmdSync<HostNumberOfCompletedPackets<'t>>
             + ControllerCmdSync<LeSetScanEnable>
             + ControllerCmdSync<LeSetExtScanEnable>
@@ -856,13 +1124,20 @@ impl<'d, C: Controller, P: PacketPool> RxRunner<'d, C, P> {
                                 }
                                 LeEventKind::LeEnhancedConnectionComplete => {
                                     let e = unwrap!(LeEnhancedConnectionComplete::from_hci_bytes_complete(event.data));
-                                    if !host.handle_connection(
-                                        e.status,
-                                        e.handle,
-                                        e.peer_addr_kind,
-                                        e.peer_addr,
-                                        e.role,
-                                    ) {
+                                    // The identity of peers resolved by the controller is looked up again by the
+                                    // host, so the connection is given the address actually used by the peer.
+                                    let (peer_addr_kind, peer_addr) = match e.peer_addr_kind {
+                                        AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC
+                                        | AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM
+                                            if e.peer_resolvable_private_addr != BdAddr::default() =>
+                                        {
+                                            (AddrKind::RANDOM, e.peer_resolvable_private_addr)
+                                        }
+                                        AddrKind::RESOLVABLE_PRIVATE_OR_PUBLIC => (AddrKind::PUBLIC, e.peer_addr),
+                                        AddrKind::RESOLVABLE_PRIVATE_OR_RANDOM => (AddrKind::RANDOM, e.peer_addr),
+                                        kind => (kind, e.peer_addr),
+                                    };
+                                    if !host.handle_connection(e.status, e.handle, peer_addr_kind, peer_addr, e.role) {
                                         let _ = host
                                             .command(Disconnect::new(
                                                 e.handle,
@@ -872,7 +1147,10 @@ impl<'d, C: Controller, P: PacketPool> RxRunner<'d, C, P> {
                                         host.connect_command_state.canceled();
                                     }
                                 }
-                                LeEventKind::LeScanTimeout => {}
+                                LeEventKind::LeScanTimeout => {
+                                    #[cfg(feature = "security")]
+                                    host.scanning.set(None);
+                                }
                                 LeEventKind::LeAdvertisingSetTerminated => {
                                     let set = unwrap!(LeAdvertisingSetTerminated::from_hci_bytes_complete(event.data));
                                     host.advertise_state.terminate(set.adv_handle);
@@ -1007,7 +1285,7 @@ impl<'d, C: Controller, P: PacketPool> RxRunner<'d, C, P> {
                             let vendor = unwrap!(Vendor::from_hci_bytes_complete(event.data));
                             event_handler.on_vendor(&vendor);
                         }
-                        EventKind::EncryptionChangeV1 => {
+                        EventKind::EncryptionChangeV1 | EventKind::EncryptionKeyRefreshComplete => {
                             host.connections.handle_security_hci_event(event)?;
                         }
                         // Ignore
@@ -1041,6 +1319,7 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
             + ControllerCmdSync<LeCreateConnCancel>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+            + ControllerCmdSync<LeSetAdvSetRandomAddr>
             + ControllerCmdSync<LeSetScanEnable>
             + ControllerCmdSync<LeSetExtScanEnable>
             + for<'t> ControllerCmdSync<HostNumberOfCompletedPackets<'t>>
@@ -1063,7 +1342,8 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                 .enable_conn_complete(true)
                 .enable_hardware_error(true)
                 .enable_disconnection_complete(true)
-                .enable_encryption_change_v1(true),
+                .enable_encryption_change_v1(true)
+                .enable_encryption_key_refresh_complete(true),
         )
         .exec(&host.controller)
         .await?;
@@ -1142,8 +1422,17 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
             }
         }
 
+        #[cfg(feature = "security")]
+        let mut rpa_retry = embassy_time::Instant::MIN;
         loop {
-            match select3(
+            #[cfg(feature = "security")]
+            let rpa_expired = async move {
+                let expires = poll_fn(|cx| host.connections.security_manager.poll_rpa_expiry(cx)).await;
+                Timer::at(expires.max(rpa_retry)).await;
+            };
+            #[cfg(not(feature = "security"))]
+            let rpa_expired = poll_fn(|cx| Poll::<()>::Pending);
+            match select4(
                 poll_fn(|cx| host.connections.poll_disconnecting(Some(cx))),
                 poll_fn(|cx| host.channels.poll_disconnecting(Some(cx))),
                 select4(
@@ -1159,10 +1448,11 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                         poll_fn(|cx| Poll::<()>::Pending)
                     },
                 ),
+                rpa_expired,
             )
             .await
             {
-                Either3::First(request) => {
+                Either4::First(request) => {
                     trace!("[host] poll disconnecting links");
                     match host.command(Disconnect::new(request.handle(), request.reason())).await {
                         Ok(_) => {}
@@ -1173,7 +1463,7 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                     }
                     request.confirm();
                 }
-                Either3::Second(request) => {
+                Either4::Second(request) => {
                     trace!("[host] poll disconnecting channels");
                     match request.send(host).await {
                         Ok(_) => {}
@@ -1185,7 +1475,7 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                     }
                     request.confirm();
                 }
-                Either3::Third(states) => match states {
+                Either4::Third(states) => match states {
                     Either4::First(_) => {
                         trace!("[host] cancel connection create");
                         // trace!("[host] cancelling create connection");
@@ -1218,6 +1508,8 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                         } else {
                             host.command(LeSetScanEnable::new(false, false)).await?;
                         }
+                        #[cfg(feature = "security")]
+                        host.scanning.set(None);
                         host.scan_command_state.canceled();
                     }
                     Either4::Fourth(request) => {
@@ -1228,6 +1520,19 @@ impl<'d, C: Controller, P: PacketPool> ControlRunner<'d, C, P> {
                         }
                     }
                 },
+                Either4::Fourth(()) => {
+                    #[cfg(feature = "security")]
+                    {
+                        rpa_retry = match host.rotate_address().await {
+                            Ok(true) => embassy_time::Instant::MIN,
+                            Ok(false) => embassy_time::Instant::now() + RPA_RETRY_INTERVAL,
+                            Err(_) => {
+                                warn!("[host] error renewing resolvable private address, retrying");
+                                embassy_time::Instant::now() + RPA_RETRY_INTERVAL
+                            }
+                        };
+                    }
+                }
             }
         }
     }
@@ -1,5 +1,6 @@
 //! L2CAP channels.
 use bt_hci::controller::{blocking, Controller};
+use heapless::Vec;
 
 pub use crate::channel_manager::CreditFlowPolicy;
 #[cfg(feature = "channel-metrics")]
@@ -83,6 +84,20 @@ impl<P: PacketPool> Drop for L2capChannelReader<'_, P> {
     }
 }
 
+/// Flow control mode of an L2CAP channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum L2capChannelMode {
+    /// LE Credit Based Flow Control Mode.
+    #[default]
+    LeCreditBased,
+    /// Enhanced Credit Based Flow Control Mode.
+    ///
+    /// Up to 5 channels can be opened with a single request, and the MTU and MPS of the channels can be
+    /// reconfigured while they are connected.
+    EnhancedCreditBased,
+}
+
 /// Configuration for an L2CAP channel.
 #[derive(Default)]
 pub struct L2capChannelConfig {
@@ -94,6 +109,8 @@ pub struct L2capChannelConfig {
     pub flow_policy: CreditFlowPolicy,
     /// Initial credits for connection oriented channels.
     pub initial_credits: Option<u16>,
+    /// Flow control mode of the channel. Defaults to LE credit based flow control.
+    pub mode: L2capChannelMode,
 }
 
 impl<'d, P: PacketPool> L2capChannel<'d, P> {
@@ -101,6 +118,10 @@ impl<'d, P: PacketPool> L2capChannel<'d, P> {
         Self { index, manager }
     }
 
+    pub(crate) fn index(&self) -> ChannelIndex {
+        self.index
+    }
+
     /// Disconnect this channel.
     pub fn disconnect(&mut self) {
         self.manager.disconnect(self.index);
@@ -175,6 +196,15 @@ impl<'d, P: PacketPool> L2capChannel<'d, P> {
         self.manager.metrics(self.index, f)
     }
 
+    /// Create a channel reference for the l2cap channel.
+    pub fn channel_ref(&mut self) -> L2capChannelRef<'d, P> {
+        self.manager.inc_ref(self.index);
+        L2capChannelRef {
+            index: self.index,
+            manager: self.manager,
+        }
+    }
+
     /// Await an incoming connection request matching the list of PSM.
     pub async fn accept<T: Controller>(
         stack: &'d Stack<'d, T, P>,
@@ -186,6 +216,24 @@ impl<'d, P: PacketPool> L2capChannel<'d, P> {
         stack.host.channels.accept(handle, psm, config, &stack.host).await
     }
 
+    /// Await an incoming enhanced credit based connection request matching the list of PSM.
+    ///
+    /// Up to `N` of the channels requested by the peer are accepted, the remaining ones are refused. The
+    /// channels are opened in enhanced credit based flow control mode regardless of the configured mode.
+    pub async fn accept_multiple<T: Controller, const N: usize>(
+        stack: &'d Stack<'d, T, P>,
+        connection: &Connection<'_, P>,
+        psm: &[u16],
+        config: &L2capChannelConfig,
+    ) -> Result<Vec<Self, N>, BleHostError<T::Error>> {
+        let handle = connection.handle();
+        stack
+            .host
+            .channels
+            .accept_multiple(handle, psm, config, &stack.host)
+            .await
+    }
+
     /// Create a new connection request with the provided PSM.
     pub async fn create<T: Controller>(
         stack: &'d Stack<'d, T, P>,
@@ -200,6 +248,36 @@ impl<'d, P: PacketPool> L2capChannel<'d, P> {
             .await
     }
 
+    /// Open `N` channels with the provided PSM using a single enhanced credit based connection request.
+    ///
+    /// At most 5 channels can be requested at once. The channels accepted by the peer are returned, which
+    /// may be fewer than requested. The channels are opened in enhanced credit based flow control mode
+    /// regardless of the configured mode.
+    pub async fn create_multiple<T: Controller, const N: usize>(
+        stack: &'d Stack<'d, T, P>,
+        connection: &Connection<'_, P>,
+        psm: u16,
+        config: &L2capChannelConfig,
+    ) -> Result<Vec<Self, N>, BleHostError<T::Error>> {
+        stack
+            .host
+            .channels
+            .create_multiple(connection.handle(), psm, config, &stack.host)
+            .await
+    }
+
+    /// Change the MTU and MPS of this channel.
+    ///
+    /// Only supported for channels in enhanced credit based flow control mode. The MTU can not be decreased.
+    pub async fn reconfigure<T: Controller>(
+        &mut self,
+        stack: &Stack<'_, T, P>,
+        mtu: u16,
+        mps: u16,
+    ) -> Result<(), BleHostError<T::Error>> {
+        stack.host.channels.reconfigure(self.index, mtu, mps, &stack.host).await
+    }
+
     /// Split the channel into a writer and reader for concurrently
     /// writing to/reading from the channel.
     pub fn split(self) -> (L2capChannelWriter<'d, P>, L2capChannelReader<'d, P>) {
@@ -277,6 +355,24 @@ impl<'d, P: PacketPool> L2capChannelReader<'d, P> {
 }
 
 impl<'d, P: PacketPool> L2capChannelRef<'d, P> {
+    /// MTU of the channel, the largest SDU that can be sent on it.
+    pub(crate) fn mtu(&self) -> Result<u16, Error> {
+        let (_, _, mtu, _) = self.manager.connected_channel_params(self.index)?;
+        Ok(mtu)
+    }
+
+    /// Send the provided buffer over this l2cap channel through the outbound queue of the connection.
+    pub(crate) async fn send_queued(&self, connection: &Connection<'_, P>, buf: &[u8]) -> Result<(), Error> {
+        self.manager.send_queued(self.index, buf, connection).await
+    }
+
+    /// Send the provided buffer over this l2cap channel through the outbound queue of the connection.
+    ///
+    /// If there are no available credits to send, returns Error::Busy.
+    pub(crate) fn try_send_queued(&self, connection: &Connection<'_, P>, buf: &[u8]) -> Result<(), Error> {
+        self.manager.try_send_queued(self.index, buf, connection)
+    }
+
     #[cfg(feature = "channel-metrics")]
     /// Read metrics of the l2cap channel.
     pub fn metrics<F: FnOnce(&ChannelMetrics) -> R, R>(&self, f: F) -> R {
@@ -10,6 +10,8 @@ use core::mem::MaybeUninit;
 use advertise::AdvertisementDataError;
 use bt_hci::cmd::status::ReadRssi;
 use bt_hci::cmd::{AsyncCmd, SyncCmd};
+#[cfg(feature = "security")]
+use bt_hci::param::PrivacyMode;
 use bt_hci::param::{AddrKind, BdAddr};
 use bt_hci::FromHciBytesError;
 use embassy_time::Duration;
@@ -21,7 +23,9 @@ use crate::att::AttErrorCode;
 use crate::channel_manager::ChannelStorage;
 use crate::connection_manager::ConnectionStorage;
 #[cfg(feature = "security")]
-pub use crate::security_manager::{BondInformation, IdentityResolvingKey, LongTermKey};
+pub use crate::security_manager::{
+    BondInformation, ConnectionSignatureResolvingKey, IdentityResolvingKey, LongTermKey, OobData, SigningKey,
+};
 pub use crate::types::capabilities::IoCapabilities;
 
 /// Number of bonding information stored
@@ -72,6 +76,8 @@ use host::{AdvHandleState, BleHost, HostMetrics, Runner};
 pub mod prelude {
     //! Convenience include of most commonly used types.
     pub use bt_hci::controller::ExternalController;
+    #[cfg(feature = "security")]
+    pub use bt_hci::param::PrivacyMode;
     pub use bt_hci::param::{AddrKind, BdAddr, LeConnRole as Role, PhyKind, PhyMask};
     pub use bt_hci::transport::SerialTransport;
     pub use bt_hci::uuid::*;
@@ -105,7 +111,9 @@ pub mod prelude {
     #[cfg(feature = "scan")]
     pub use crate::scan::*;
     #[cfg(feature = "security")]
-    pub use crate::security_manager::{BondInformation, IdentityResolvingKey, LongTermKey};
+    pub use crate::security_manager::{
+        BondInformation, ConnectionSignatureResolvingKey, IdentityResolvingKey, LongTermKey, OobData, SigningKey,
+    };
     pub use crate::types::capabilities::IoCapabilities;
     #[cfg(feature = "gatt")]
     pub use crate::types::gatt_traits::{AsGatt, FixedGattValue, FromGatt};
@@ -293,6 +301,9 @@ pub enum Error {
     /// Unexpected GATT response.
     UnexpectedGattResponse,
 
+    /// Value echoed in a Prepare Write Response does not match the value that was sent.
+    PreparedWriteMismatch,
+
     /// Received characteristic declaration data shorter than the minimum required length (5 bytes).
     MalformedCharacteristicDeclaration {
         /// Expected length.
@@ -337,6 +348,8 @@ pub enum Error {
     NotSupported,
     /// L2cap channel closed.
     ChannelClosed,
+    /// L2cap channel connection or reconfiguration refused by the peer.
+    ChannelRefused,
     /// Operation timed out.
     Timeout,
     /// Controller is busy.
@@ -424,6 +437,12 @@ pub trait Controller:
     + ControllerCmdSync<SetEventMaskPage2>
     + ControllerCmdSync<LeSetEventMask>
     + ControllerCmdSync<LeSetRandomAddr>
+    + ControllerCmdSync<LeReadResolvingListSize>
+    + ControllerCmdSync<LeSetAddrResolutionEnable>
+    + ControllerCmdSync<LeClearResolvingList>
+    + ControllerCmdSync<LeAddDeviceToResolvingList>
+    + ControllerCmdSync<LeSetPrivacyMode>
+    + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>
     + ControllerCmdSync<HostBufferSize>
     + ControllerCmdAsync<LeConnUpdate>
     + ControllerCmdSync<LeReadFilterAcceptListSize>
@@ -438,6 +457,7 @@ pub trait Controller:
     + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
     + for<'t> ControllerCmdSync<LeSetAdvEnable>
     + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+    + ControllerCmdSync<LeSetAdvSetRandomAddr>
     + for<'t> ControllerCmdSync<HostNumberOfCompletedPackets<'t>>
     + ControllerCmdSync<LeReadBufferSize>
     + for<'t> ControllerCmdSync<LeSetAdvData>
@@ -459,6 +479,12 @@ impl<
             + ControllerCmdSync<SetEventMaskPage2>
             + ControllerCmdSync<LeSetEventMask>
             + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>
             + ControllerCmdSync<HostBufferSize>
             + ControllerCmdAsync<LeConnUpdate>
             + ControllerCmdSync<LeReadFilterAcceptListSize>
@@ -473,6 +499,7 @@ impl<
             + ControllerCmdAsync<LeCreateConn>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
+            + ControllerCmdSync<LeSetAdvSetRandomAddr>
             + for<'t> ControllerCmdSync<HostNumberOfCompletedPackets<'t>>
             + ControllerCmdSync<LeReadBufferSize>
             + for<'t> ControllerCmdSync<LeSetAdvData>
@@ -607,6 +634,30 @@ impl<'stack, C: Controller, P: PacketPool> Stack<'stack, C, P> {
         self.host.connections.security_manager.set_local_address(address);
         self
     }
+    /// Enable LE Privacy with the local identity resolving key ([Vol 3] Part C, Section 10.7).
+    ///
+    /// Advertising, scanning and connection procedures use a resolvable private address generated from `irk`
+    /// instead of the identity address, renewed every `rpa_timeout` (15 minutes is recommended). Advertising and
+    /// scanning are restarted by the host runner to renew it. Only peers that have bonded and received `irk` can resolve the address to
+    /// the identity address, so the key should be stored and restored along with the bonds.
+    #[cfg(feature = "security")]
+    pub fn set_privacy(self, irk: IdentityResolvingKey, rpa_timeout: Duration) -> Self {
+        self.host.connections.security_manager.set_privacy(irk, rpa_timeout);
+        self
+    }
+
+    /// Set the privacy mode used for bonded peers ([Vol 3] Part C, Section 10.7).
+    ///
+    /// Bonded peers with an identity resolving key are loaded into the resolving list of the controller, if it
+    /// has one. In network privacy mode (the default), the controller ignores them when they use their identity
+    /// address instead of a resolvable private address. Device privacy mode accepts both, for peers that don't
+    /// always use privacy. Peers are otherwise resolved by the host when connecting.
+    #[cfg(feature = "security")]
+    pub fn set_privacy_mode(self, mode: PrivacyMode) -> Self {
+        self.host.connections.security_manager.set_privacy_mode(mode);
+        self
+    }
+
     /// Set the random generator seed for random generator used by security manager
     pub fn set_random_generator_seed<RNG: RngCore + CryptoRng>(self, _random_generator: &mut RNG) -> Self {
         #[cfg(feature = "security")]
@@ -681,6 +732,26 @@ impl<'stack, C: Controller, P: PacketPool> Stack<'stack, C, P> {
         self.host.log_status(verbose);
     }
 
+    #[cfg(feature = "security")]
+    /// Get the identity address of this host.
+    pub fn identity_address(&self) -> Option<Address> {
+        self.host.connections.security_manager.identity_address()
+    }
+
+    #[cfg(feature = "security")]
+    /// Get the resolvable private address currently used by this host, if privacy is enabled.
+    pub fn resolvable_private_address(&self) -> Option<Address> {
+        self.host.connections.security_manager.resolvable_private_address()
+    }
+
+    #[cfg(feature = "security")]
+    /// Generate new local out-of-band data for LE Secure Connections pairing, replacing the previous one.
+    ///
+    /// The data is passed to the peer over another channel, such as NFC or a QR code, before pairing.
+    pub fn generate_oob_data(&self) -> OobData {
+        self.host.connections.security_manager.generate_oob_data()
+    }
+
     #[cfg(feature = "security")]
     /// Get bonded devices
     pub fn add_bond_information(&self, bond_information: BondInformation) -> Result<(), Error> {
@@ -2,9 +2,10 @@
 use core::task::Poll;
 
 use bt_hci::cmd::le::{
-    LeClearAdvSets, LeReadNumberOfSupportedAdvSets, LeSetAdvData, LeSetAdvEnable, LeSetAdvParams,
+    LeAddDeviceToResolvingList, LeClearAdvSets, LeClearResolvingList, LeReadNumberOfSupportedAdvSets,
+    LeReadResolvingListSize, LeSetAddrResolutionEnable, LeSetAdvData, LeSetAdvEnable, LeSetAdvParams,
     LeSetAdvSetRandomAddr, LeSetExtAdvData, LeSetExtAdvEnable, LeSetExtAdvParams, LeSetExtScanResponseData,
-    LeSetScanResponseData,
+    LeSetPrivacyMode, LeSetRandomAddr, LeSetResolvablePrivateAddrTimeout, LeSetScanResponseData,
 };
 use bt_hci::controller::{Controller, ControllerCmdSync};
 use bt_hci::param::{AddrKind, AdvChannelMap, AdvHandle, AdvKind, AdvSet, BdAddr, LeConnRole, Operation};
@@ -34,7 +35,14 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
         C: for<'t> ControllerCmdSync<LeSetAdvData>
             + ControllerCmdSync<LeSetAdvParams>
             + for<'t> ControllerCmdSync<LeSetAdvEnable>
-            + for<'t> ControllerCmdSync<LeSetScanResponseData>,
+            + for<'t> ControllerCmdSync<LeSetScanResponseData>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
     {
         let host = &self.stack.host;
 
@@ -46,6 +54,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
 
         // Clear current advertising terminations
         host.advertise_state.reset();
+        host.update_privacy().await?;
 
         let data: RawAdvertisement = data.into();
         if !data.props.legacy_adv() {
@@ -72,7 +81,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
             bt_hci_duration(params.interval_min),
             bt_hci_duration(params.interval_max),
             kind,
-            host.address.map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
+            host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
             peer.kind,
             peer.addr,
             params.channel_map.unwrap_or(AdvChannelMap::ALL),
@@ -101,7 +110,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
         }];
 
         trace!("[host] enabling advertising");
-        host.advertise_state.start(&advset[..]);
+        host.advertise_state.start(&advset[..], false);
         host.command(LeSetAdvEnable::new(true)).await?;
         drop.defuse();
         Ok(Advertiser {
@@ -161,7 +170,14 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
             + ControllerCmdSync<LeSetAdvSetRandomAddr>
             + ControllerCmdSync<LeReadNumberOfSupportedAdvSets>
             + for<'t> ControllerCmdSync<LeSetExtAdvEnable<'t>>
-            + for<'t> ControllerCmdSync<LeSetExtScanResponseData<'t>>,
+            + for<'t> ControllerCmdSync<LeSetExtScanResponseData<'t>>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
     {
         assert_eq!(sets.len(), handles.len());
         let host = &self.stack.host;
@@ -181,6 +197,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
 
         // Clear current advertising terminations
         host.advertise_state.reset();
+        host.update_privacy().await?;
 
         for (i, set) in sets.iter().enumerate() {
             let handle = AdvHandle::new(i as u8);
@@ -196,7 +213,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
                 bt_hci_ext_duration(params.interval_min),
                 bt_hci_ext_duration(params.interval_max),
                 params.channel_map.unwrap_or(AdvChannelMap::ALL),
-                host.address.map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
+                host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
                 peer.kind,
                 peer.addr,
                 params.filter_policy,
@@ -209,7 +226,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
             ))
             .await?;
 
-            if let Some(address) = host.address.as_ref() {
+            if let Some(address) = host.own_address() {
                 host.command(LeSetAdvSetRandomAddr::new(handle, address.addr)).await?;
             }
 
@@ -238,7 +255,7 @@ impl<'d, C: Controller, P: PacketPool> Peripheral<'d, C, P> {
         }
 
         trace!("[host] enabling extended advertising");
-        host.advertise_state.start(handles);
+        host.advertise_state.start(handles, true);
         host.command(LeSetExtAdvEnable::new(true, handles)).await?;
         drop.defuse();
         Ok(Advertiser {
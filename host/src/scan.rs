@@ -1,7 +1,8 @@
 //! Scan config.
 use bt_hci::cmd::le::{
-    LeAddDeviceToFilterAcceptList, LeClearFilterAcceptList, LeSetExtScanEnable, LeSetExtScanParams, LeSetScanEnable,
-    LeSetScanParams,
+    LeAddDeviceToFilterAcceptList, LeAddDeviceToResolvingList, LeClearFilterAcceptList, LeClearResolvingList,
+    LeReadResolvingListSize, LeSetAddrResolutionEnable, LeSetExtScanEnable, LeSetExtScanParams, LeSetPrivacyMode,
+    LeSetRandomAddr, LeSetResolvablePrivateAddrTimeout, LeSetScanEnable, LeSetScanParams,
 };
 use bt_hci::controller::{Controller, ControllerCmdSync};
 use bt_hci::param::{AddrKind, FilterDuplicates, ScanningPhy};
@@ -40,7 +41,14 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         C: ControllerCmdSync<LeSetExtScanEnable>
             + ControllerCmdSync<LeSetExtScanParams>
             + ControllerCmdSync<LeClearFilterAcceptList>
-            + ControllerCmdSync<LeAddDeviceToFilterAcceptList>,
+            + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
     {
         let host = &self.central.stack.host;
         let drop = crate::host::OnDrop::new(|| {
@@ -48,6 +56,7 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         });
         host.scan_command_state.request().await;
         self.central.set_accept_filter(config.filter_accept_list).await?;
+        host.update_privacy().await?;
 
         let scanning = ScanningPhy {
             active_scan: config.active,
@@ -57,7 +66,7 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         let phy_params = crate::central::create_phy_params(scanning, config.phys);
         let host = &self.central.stack.host;
         host.command(LeSetExtScanParams::new(
-            host.address.map(|s| s.kind).unwrap_or(AddrKind::PUBLIC),
+            host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
             if config.filter_accept_list.is_empty() {
                 bt_hci::param::ScanningFilterPolicy::BasicUnfiltered
             } else {
@@ -74,6 +83,8 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
             bt_hci::param::Duration::from_secs(0),
         ))
         .await?;
+        #[cfg(feature = "security")]
+        host.scanning.set(Some(true));
         drop.defuse();
         Ok(ScanSession {
             command_state: &self.central.stack.host.scan_command_state,
@@ -94,7 +105,14 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         C: ControllerCmdSync<LeSetScanParams>
             + ControllerCmdSync<LeSetScanEnable>
             + ControllerCmdSync<LeClearFilterAcceptList>
-            + ControllerCmdSync<LeAddDeviceToFilterAcceptList>,
+            + ControllerCmdSync<LeAddDeviceToFilterAcceptList>
+            + ControllerCmdSync<LeSetRandomAddr>
+            + ControllerCmdSync<LeReadResolvingListSize>
+            + ControllerCmdSync<LeSetAddrResolutionEnable>
+            + ControllerCmdSync<LeClearResolvingList>
+            + ControllerCmdSync<LeAddDeviceToResolvingList>
+            + ControllerCmdSync<LeSetPrivacyMode>
+            + ControllerCmdSync<LeSetResolvablePrivateAddrTimeout>,
     {
         let host = &self.central.stack.host;
         let drop = crate::host::OnDrop::new(|| {
@@ -103,6 +121,7 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         host.scan_command_state.request().await;
 
         self.central.set_accept_filter(config.filter_accept_list).await?;
+        host.update_privacy().await?;
 
         let params = LeSetScanParams::new(
             if config.active {
@@ -112,7 +131,7 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
             },
             bt_hci_duration(config.interval),
             bt_hci_duration(config.window),
-            host.address.map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
+            host.own_address().map(|a| a.kind).unwrap_or(AddrKind::PUBLIC),
             if config.filter_accept_list.is_empty() {
                 bt_hci::param::ScanningFilterPolicy::BasicUnfiltered
             } else {
@@ -122,6 +141,8 @@ impl<'d, C: Controller, P: PacketPool> Scanner<'d, C, P> {
         host.command(params).await?;
 
         host.command(LeSetScanEnable::new(true, true)).await?;
+        #[cfg(feature = "security")]
+        host.scanning.set(Some(false));
         drop.defuse();
         Ok(ScanSession {
             command_state: &self.central.stack.host.scan_command_state,
@@ -92,8 +92,11 @@ impl IdentityResolvingKey {
         prand[2] &= 0b00111111; // Clear top 2 bits
         prand[2] |= 0b01000000; // Set 2nd bit from top
 
-        // Calculate hash using ah function
-        let hash = self.ah(prand);
+        // Calculate hash using ah function, which takes and returns big endian values
+        let mut r = prand;
+        r.reverse();
+        let mut hash = self.ah(r);
+        hash.reverse();
 
         // Construct the address: prand || hash
         let mut address = [0u8; 6];
@@ -162,6 +165,68 @@ impl defmt::Format for IdentityResolvingKey {
     }
 }
 
+/// Connection Signature Resolving Key.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[must_use]
+#[repr(transparent)]
+pub struct ConnectionSignatureResolvingKey(pub u128);
+
+impl ConnectionSignatureResolvingKey {
+    /// Creates a Connection Signature Resolving Key from a `u128` value.
+    #[inline(always)]
+    pub const fn new(k: u128) -> Self {
+        Self(k)
+    }
+
+    /// Creates a Connection Signature Resolving Key from a `[u8; 16]` value in little endian.
+    #[inline(always)]
+    pub const fn from_le_bytes(k: [u8; 16]) -> Self {
+        Self(u128::from_le_bytes(k))
+    }
+
+    /// Returns the Connection Signature Resolving Key as `[u8; 16]` value in little endian.
+    #[inline(always)]
+    pub const fn to_le_bytes(self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    /// Computes the MAC of a signed data PDU ([Vol 3] Part H, Section 2.4.5).
+    ///
+    /// The PDU is given as a sequence of little endian fragments, which are followed by the sign counter. The 64 most
+    /// significant bits of the AES-CMAC are returned, as transmitted in the authentication signature.
+    pub(crate) fn sign(&self, data: &[&[u8]], counter: u32) -> [u8; 8] {
+        // AES-CMAC takes the message most significant octet first
+        let mut cmac = AesCmac::new(&Key::new(self.0));
+        cmac.update(counter.to_be_bytes());
+        for fragment in data.iter().rev() {
+            for b in fragment.iter().rev() {
+                cmac.update([*b]);
+            }
+        }
+        ((cmac.finalize() >> 64) as u64).to_le_bytes()
+    }
+}
+
+impl From<&ConnectionSignatureResolvingKey> for u128 {
+    #[inline(always)]
+    fn from(k: &ConnectionSignatureResolvingKey) -> Self {
+        k.0
+    }
+}
+
+impl core::fmt::Display for ConnectionSignatureResolvingKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ConnectionSignatureResolvingKey {
+    fn format(&self, fmt: defmt::Formatter) {
+        defmt::write!(fmt, "{:016x}", self.0)
+    }
+}
+
 /// RFC-4493 AES-CMAC ([Vol 3] Part H, Section 2.2.5).
 #[derive(Debug)]
 #[repr(transparent)]
@@ -311,6 +376,50 @@ impl Nonce {
     }
 }
 
+/// LE legacy pairing Temporary Key (TK), zero for Just Works or the passkey for Passkey Entry
+/// ([Vol 3] Part H, Section 2.3.5.5).
+#[cfg(feature = "legacy-pairing")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct TemporaryKey(pub u128);
+
+#[cfg(feature = "legacy-pairing")]
+impl TemporaryKey {
+    /// Generates LE legacy pairing confirm value of random value `r`
+    /// ([Vol 3] Part H, Section 2.2.3).
+    ///
+    /// `preq` and `pres` are the pairing request and pairing response commands, `ia` and `ra` the initiator and
+    /// responder addresses.
+    pub fn c1(&self, r: &Nonce, preq: &[u8; 7], pres: &[u8; 7], ia: Address, ra: Address) -> Confirm {
+        let mut p1 = [0u8; 16];
+        p1[0] = ia.kind.into_inner();
+        p1[1] = ra.kind.into_inner();
+        p1[2..9].copy_from_slice(preq);
+        p1[9..16].copy_from_slice(pres);
+        let mut p2 = [0u8; 16];
+        p2[0..6].copy_from_slice(ra.addr.raw());
+        p2[6..12].copy_from_slice(ia.addr.raw());
+
+        let p1 = u128::from_le_bytes(p1);
+        let p2 = u128::from_le_bytes(p2);
+        Confirm(self.e(self.e(r.0 ^ p1) ^ p2))
+    }
+
+    /// Generates LE legacy pairing Short Term Key (STK) from the responder random value `r1` and the initiator
+    /// random value `r2` ([Vol 3] Part H, Section 2.2.4).
+    pub fn s1(&self, r1: &Nonce, r2: &Nonce) -> LongTermKey {
+        LongTermKey(self.e((r1.0 << 64) | (r2.0 & u128::from(u64::MAX))))
+    }
+
+    /// Security function `e` ([Vol 3] Part H, Section 2.2.1).
+    fn e(&self, plaintext: u128) -> u128 {
+        let cipher = Aes128::new_from_slice(&self.0.to_be_bytes()).unwrap();
+        let mut block = plaintext.to_be_bytes();
+        cipher.encrypt_block((&mut block).into());
+        u128::from_be_bytes(block)
+    }
+}
+
 /// LE Secure Connections confirm value generated by [`Nonce::f4`].
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[must_use]
@@ -326,6 +435,7 @@ pub struct NumCompare(pub u32);
 
 /// P-256 elliptic curve secret key.
 #[must_use]
+#[derive(Clone)]
 #[repr(transparent)]
 pub struct SecretKey(p256::NonZeroScalar);
 
@@ -527,6 +637,18 @@ mod tests {
         assert!(pk.is_debug());
     }
 
+    /// Data signing, checked against the 16 byte example of RFC-4493.
+    #[test]
+    fn csrk_sign() {
+        let csrk = ConnectionSignatureResolvingKey::new(0x2b7e1516_28aed2a6_abf71588_09cf4f3c);
+        // Reversed, the PDU and counter form the message 6bc1bee2_2e409f96_e93d7e11_7393172a
+        let pdu = [0x2a, 0x17, 0x93, 0x73, 0x11, 0x7e, 0x3d, 0xe9, 0x96, 0x9f, 0x40, 0x2e];
+        let mac = 0x070a16b4_6b4d4144_u64.to_le_bytes();
+        assert_eq!(csrk.sign(&[&pdu], 0x6bc1bee2), mac);
+        assert_eq!(csrk.sign(&[&pdu[..3], &pdu[3..]], 0x6bc1bee2), mac);
+        assert_ne!(csrk.sign(&[&pdu], 0x6bc1bee3), mac);
+    }
+
     /// P-256 data set 1 ([Vol 2] Part G, Section 7.1.2.1).
     #[test]
     fn p256_1() {
@@ -799,4 +921,34 @@ mod tests {
         let re = irk.resolve_address(&address);
         assert_eq!(re, true);
     }
+
+    #[cfg(feature = "legacy-pairing")]
+    #[test]
+    fn temporary_key_c1() {
+        let k = TemporaryKey(0);
+        let r = Nonce(0x5783D52156AD6F0E6388274EC6702EE0);
+        let preq = [0x01, 0x01, 0x00, 0x00, 0x10, 0x07, 0x07];
+        let pres = [0x02, 0x03, 0x00, 0x00, 0x08, 0x00, 0x05];
+        let ia = Address {
+            kind: AddrKind::RANDOM,
+            addr: BdAddr::new([0xA6, 0xA5, 0xA4, 0xA3, 0xA2, 0xA1]),
+        };
+        let ra = Address {
+            kind: AddrKind::PUBLIC,
+            addr: BdAddr::new([0xB6, 0xB5, 0xB4, 0xB3, 0xB2, 0xB1]),
+        };
+        assert_eq!(
+            k.c1(&r, &preq, &pres, ia, ra),
+            Confirm(0x1e1e3fef878988ead2a74dc5bef13b86)
+        );
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    #[test]
+    fn temporary_key_s1() {
+        let k = TemporaryKey(0);
+        let r1 = Nonce(0x000F0E0D0C0B0A091122334455667788);
+        let r2 = Nonce(0x010203040506070899AABBCCDDEEFF00);
+        assert_eq!(k.s1(&r1, &r2), LongTermKey(0x9a1fe1f0e8b0f49b5b4216ae796da062));
+    }
 }
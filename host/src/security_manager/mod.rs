@@ -9,15 +9,19 @@ mod types;
 use core::cell::RefCell;
 use core::future::{poll_fn, Future};
 use core::ops::DerefMut;
+use core::task::{Context, Poll};
 
 use bt_hci::event::le::{LeEventKind, LeEventPacket, LeLongTermKeyRequest};
-use bt_hci::event::{EncryptionChangeV1, EventKind, EventPacket};
-use bt_hci::param::{ConnHandle, EncryptionEnabledLevel, LeConnRole};
+use bt_hci::event::{EncryptionChangeV1, EncryptionKeyRefreshComplete, EventKind, EventPacket};
+use bt_hci::param::{AddrKind, BdAddr, ConnHandle, EncryptionEnabledLevel, LeConnRole, PrivacyMode};
 use bt_hci::FromHciBytes;
-pub use crypto::{IdentityResolvingKey, LongTermKey};
+pub(crate) use crypto::AesCmac;
+pub use crypto::{ConnectionSignatureResolvingKey, IdentityResolvingKey, LongTermKey};
+use crypto::{Nonce, SecretKey};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embassy_sync::channel::Channel;
-use embassy_time::{Instant, TimeoutError, WithTimeout};
+use embassy_sync::waitqueue::WakerRegistration;
+use embassy_time::{Duration, Instant, TimeoutError, WithTimeout};
 use heapless::Vec;
 use rand_chacha::ChaCha12Rng;
 use rand_core::SeedableRng;
@@ -35,8 +39,8 @@ use crate::{Address, Error, Identity, IoCapabilities, PacketPool};
 
 /// Events of interest to the security manager
 pub(crate) enum SecurityEventData {
-    /// A long term key request has been issued
-    SendLongTermKey(ConnHandle),
+    /// A long term key request has been issued for the key identified by the EDIV and Rand
+    SendLongTermKey(ConnHandle, u16, [u8; 8]),
     /// Enable encryption on channel
     EnableEncryption(ConnHandle, BondInformation),
     /// Pairing timeout
@@ -50,26 +54,64 @@ pub(crate) enum SecurityEventData {
 pub struct BondInformation {
     /// Long Term Key (LTK)
     pub ltk: LongTermKey,
+    /// Encrypted Diversifier (EDIV) identifying the long term key, zero unless distributed by LE legacy pairing.
+    pub ediv: u16,
+    /// Random Number (Rand) identifying the long term key, zero unless distributed by LE legacy pairing.
+    pub rand: [u8; 8],
     /// Peer identity
     pub identity: Identity,
+    /// Type of the peer identity address, public or static random.
+    pub identity_kind: AddrKind,
     /// True if this bond information is from a bonded pairing
     pub is_bonded: bool,
     /// Security level of this long term key.
     pub security_level: SecurityLevel,
+    /// Signing key distributed to the peer, used to sign data sent to it.
+    pub local_csrk: Option<SigningKey>,
+    /// Signing key distributed by the peer, used to verify data signed by it.
+    pub peer_csrk: Option<SigningKey>,
 }
 
 impl BondInformation {
     /// Create a BondInformation
+    ///
+    /// The identity address is assumed to be public, set [`identity_kind`](Self::identity_kind) for a static random
+    /// identity address.
     pub fn new(identity: Identity, ltk: LongTermKey, security_level: SecurityLevel, is_bonded: bool) -> Self {
         Self {
             ltk,
+            ediv: 0,
+            rand: [0; 8],
             identity,
+            identity_kind: AddrKind::PUBLIC,
             is_bonded,
             security_level,
+            local_csrk: None,
+            peer_csrk: None,
         }
     }
 }
 
+/// Connection Signature Resolving Key (CSRK) and its sign counter ([Vol 3] Part H, Section 2.4.5).
+///
+/// The counter changes with every signed PDU, so it must be stored along with the rest of the bond information.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SigningKey {
+    /// Connection Signature Resolving Key
+    pub csrk: ConnectionSignatureResolvingKey,
+    /// For a local key, the counter of the next signed PDU. For a peer key, the lowest counter accepted for the next
+    /// signed PDU.
+    pub counter: u32,
+}
+
+impl SigningKey {
+    /// Create a signing key with the counter starting at zero.
+    pub fn new(csrk: ConnectionSignatureResolvingKey) -> Self {
+        Self { csrk, counter: 0 }
+    }
+}
+
 impl core::fmt::Display for BondInformation {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Identity {:?} LTK {}", self.identity, self.ltk)
@@ -83,14 +125,61 @@ impl defmt::Format for BondInformation {
     }
 }
 
+/// Out-of-band data of LE Secure Connections pairing ([Vol 3] Part H, Section 2.3.5.6.4)
+///
+/// The local data is exchanged with the peer over another channel, such as NFC or a QR code, and the data received
+/// from the peer is supplied with [`Connection::set_peer_oob_data()`](crate::connection::Connection::set_peer_oob_data).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OobData {
+    /// Random value (r)
+    pub random: u128,
+    /// Confirm value (C), binding the random value to the public key used for pairing
+    pub confirm: u128,
+}
+
+/// LE Privacy state ([Vol 3] Part C, Section 10.7)
+struct Privacy {
+    /// Local identity resolving key
+    irk: IdentityResolvingKey,
+    /// Time after which the resolvable private address is renewed
+    timeout: Duration,
+    /// Current resolvable private address and when it expires
+    rpa: Option<(Address, Instant)>,
+}
+
+/// Contents of the controller resolving list ([Vol 6] Part B, Section 4.7)
+pub(crate) struct ResolvingListUpdate<const BOND_COUNT: usize> {
+    /// Identity address and identity resolving key of the bonded peers
+    pub(crate) entries: Vec<(Address, IdentityResolvingKey), BOND_COUNT>,
+    /// Local identity resolving key, zero if privacy is disabled
+    pub(crate) local_irk: IdentityResolvingKey,
+    /// Timeout of resolvable private addresses generated by the controller, if privacy is enabled
+    pub(crate) rpa_timeout: Option<Duration>,
+    /// Privacy mode used for the bonded peers
+    pub(crate) privacy_mode: PrivacyMode,
+}
+
 /// Security manager data
 struct SecurityManagerData<const BOND_COUNT: usize> {
     /// Local device address
     local_address: Option<Address>,
+    /// Privacy state, if enabled
+    privacy: Option<Privacy>,
+    /// Woken when a resolvable private address is set
+    rpa_waker: WakerRegistration,
+    /// Size of the controller resolving list once read, zero if the controller can't resolve addresses
+    resolving_list_size: Option<u8>,
+    /// The bonded identities changed since the resolving list was last loaded
+    resolving_list_dirty: bool,
+    /// Privacy mode used for bonded peers in the resolving list
+    privacy_mode: PrivacyMode,
     /// Current bonds with other devices
     bond: Vec<BondInformation, BOND_COUNT>,
     /// Random generator seeded
     random_generator_seeded: bool,
+    /// Key pair and random value of the local out-of-band data, if generated
+    local_oob: Option<(SecretKey, Nonce)>,
 }
 
 impl<const BOND_COUNT: usize> SecurityManagerData<BOND_COUNT> {
@@ -98,8 +187,14 @@ impl<const BOND_COUNT: usize> SecurityManagerData<BOND_COUNT> {
     pub(crate) fn new() -> Self {
         Self {
             local_address: None,
+            privacy: None,
+            rpa_waker: WakerRegistration::new(),
+            resolving_list_size: None,
+            resolving_list_dirty: true,
+            privacy_mode: PrivacyMode::Network,
             bond: Vec::new(),
             random_generator_seeded: false,
+            local_oob: None,
         }
     }
 }
@@ -162,8 +257,6 @@ enum PairingMethod {
     LeSecureConnectionOob,
 }
 
-// TODO: IRK exchange, HCI_LE_­Add_­Device_­To_­Resolving_­List
-
 /// Security manager that handles SM packet
 pub struct SecurityManager<const BOND_COUNT: usize> {
     /// Random generator
@@ -207,7 +300,147 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         self.state.borrow_mut().local_address = Some(address);
     }
 
-    fn get_peer_bond_information(&self, identity: &Identity) -> Option<BondInformation> {
+    /// Local identity address
+    pub(crate) fn identity_address(&self) -> Option<Address> {
+        self.state.borrow().local_address
+    }
+
+    /// Enable privacy with the local identity resolving key, renewing the resolvable private address after `timeout`
+    pub(crate) fn set_privacy(&self, irk: IdentityResolvingKey, timeout: Duration) {
+        self.state.borrow_mut().privacy = Some(Privacy {
+            irk,
+            timeout,
+            rpa: None,
+        });
+    }
+
+    /// Local identity resolving key, if privacy is enabled
+    pub(crate) fn local_irk(&self) -> Option<IdentityResolvingKey> {
+        self.state.borrow().privacy.as_ref().map(|privacy| privacy.irk)
+    }
+
+    /// Current resolvable private address, if privacy is enabled and one has been generated
+    pub(crate) fn resolvable_private_address(&self) -> Option<Address> {
+        self.state
+            .borrow()
+            .privacy
+            .as_ref()
+            .and_then(|privacy| privacy.rpa)
+            .map(|(rpa, _)| rpa)
+    }
+
+    /// Generate a new resolvable private address if privacy is enabled and the current one has expired at `now`.
+    ///
+    /// The address is not used until it is set with [`Self::set_resolvable_private_address`].
+    pub(crate) fn next_resolvable_private_address(&self, now: Instant) -> Option<Address> {
+        let state = self.state.borrow();
+        let privacy = state.privacy.as_ref()?;
+        match privacy.rpa {
+            Some((_, expires)) if now < expires => None,
+            _ => Some(Address {
+                kind: AddrKind::RANDOM,
+                addr: BdAddr::new(
+                    privacy
+                        .irk
+                        .generate_resolvable_address(self.rng.borrow_mut().deref_mut()),
+                ),
+            }),
+        }
+    }
+
+    /// Set the resolvable private address in use from `now`
+    pub(crate) fn set_resolvable_private_address(&self, rpa: Address, now: Instant) {
+        let mut state = self.state.borrow_mut();
+        if let Some(privacy) = state.privacy.as_mut() {
+            privacy.rpa = Some((rpa, now + privacy.timeout));
+            state.rpa_waker.wake();
+        }
+    }
+
+    /// Poll for the time the resolvable private address in use expires
+    pub(crate) fn poll_rpa_expiry(&self, cx: &mut Context<'_>) -> Poll<Instant> {
+        let mut state = self.state.borrow_mut();
+        match state.privacy.as_ref().and_then(|privacy| privacy.rpa) {
+            Some((_, expires)) => Poll::Ready(expires),
+            None => {
+                state.rpa_waker.register(cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Address used by the local device for new connections
+    pub(crate) fn own_address(&self) -> Option<Address> {
+        self.resolvable_private_address().or(self.identity_address())
+    }
+
+    /// Identity of the bonded peer using the address, resolving it with the bonded identity resolving keys
+    pub(crate) fn resolve_identity(&self, address: &BdAddr) -> Option<Identity> {
+        self.state
+            .borrow()
+            .bond
+            .iter()
+            .find(|bond| bond.identity.match_address(address))
+            .map(|bond| bond.identity)
+    }
+
+    /// Size of the controller resolving list, if it was read
+    pub(crate) fn resolving_list_size(&self) -> Option<u8> {
+        self.state.borrow().resolving_list_size
+    }
+
+    /// Set the size of the controller resolving list
+    pub(crate) fn set_resolving_list_size(&self, size: u8) {
+        self.state.borrow_mut().resolving_list_size = Some(size);
+    }
+
+    /// Set the privacy mode used for bonded peers in the resolving list
+    pub(crate) fn set_privacy_mode(&self, mode: PrivacyMode) {
+        let mut state = self.state.borrow_mut();
+        state.privacy_mode = mode;
+        state.resolving_list_dirty = true;
+    }
+
+    /// Entries to load into the controller resolving list if the bonded identities changed since it was last loaded.
+    ///
+    /// Returns `None` if the resolving list is up to date or the controller can't resolve addresses.
+    pub(crate) fn resolving_list_update(&self) -> Option<ResolvingListUpdate<BOND_COUNT>> {
+        let state = self.state.borrow();
+        let size = state.resolving_list_size.unwrap_or(0);
+        if !state.resolving_list_dirty || size == 0 {
+            return None;
+        }
+        let mut entries = Vec::new();
+        for bond in state
+            .bond
+            .iter()
+            .filter(|bond| bond.identity.irk.is_some())
+            .take(usize::from(size))
+        {
+            if let Some(irk) = bond.identity.irk {
+                // Entries were taken from the bonds, so there's room for all of them.
+                let address = Address {
+                    kind: bond.identity_kind,
+                    addr: bond.identity.bd_addr,
+                };
+                let _ = entries.push((address, irk));
+            }
+        }
+        let privacy = state.privacy.as_ref();
+        Some(ResolvingListUpdate {
+            entries,
+            local_irk: privacy.map(|privacy| privacy.irk).unwrap_or_default(),
+            rpa_timeout: privacy.map(|privacy| privacy.timeout),
+            privacy_mode: state.privacy_mode,
+        })
+    }
+
+    /// The resolving list was loaded with the last update
+    pub(crate) fn resolving_list_updated(&self) {
+        self.state.borrow_mut().resolving_list_dirty = false;
+    }
+
+    pub(crate) fn get_peer_bond_information(&self, identity: &Identity) -> Option<BondInformation> {
         trace!("[security manager] Find long term key for {:?}", identity);
         self.state.borrow().bond.iter().find_map(|bond| {
             if bond.identity.match_identity(identity) {
@@ -219,10 +452,11 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
     }
 
     /// Get the long term key for peer
-    pub(crate) fn get_peer_long_term_key(&self, identity: &Identity) -> Option<LongTermKey> {
+    /// Long term key of the peer identified by the EDIV and Rand of a long term key request
+    pub(crate) fn get_peer_long_term_key(&self, identity: &Identity, ediv: u16, rand: [u8; 8]) -> Option<LongTermKey> {
         trace!("[security manager] Find long term key for {:?}", identity);
         self.state.borrow().bond.iter().find_map(|bond| {
-            if bond.identity.match_identity(identity) {
+            if bond.identity.match_identity(identity) && bond.ediv == ediv && bond.rand == rand {
                 Some(bond.ltk)
             } else {
                 None
@@ -230,6 +464,25 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         })
     }
 
+    /// Generate new local out-of-band data, replacing the previous one
+    pub(crate) fn generate_oob_data(&self) -> OobData {
+        let mut rng = self.rng.borrow_mut();
+        let secret_key = SecretKey::new(rng.deref_mut());
+        let random = Nonce::new(rng.deref_mut());
+        let public_key = secret_key.public_key();
+        let confirm = random.f4(public_key.x(), public_key.x(), 0);
+        self.state.borrow_mut().local_oob = Some((secret_key, random));
+        OobData {
+            random: random.0,
+            confirm: confirm.0,
+        }
+    }
+
+    /// Key pair and random value of the local out-of-band data
+    pub(crate) fn local_oob_data(&self) -> Option<(SecretKey, Nonce)> {
+        self.state.borrow().local_oob.clone()
+    }
+
     /// Has the random generator been seeded?
     pub(crate) fn get_random_generator_seeded(&self) -> bool {
         self.state.borrow().random_generator_seeded
@@ -247,15 +500,20 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         match index {
             Some(index) => {
                 // Replace existing bond if it exists
-                self.state.borrow_mut().bond[index] = bond_information;
+                let mut state = self.state.borrow_mut();
+                if state.bond[index].identity != bond_information.identity {
+                    state.resolving_list_dirty = true;
+                }
+                state.bond[index] = bond_information;
                 Ok(())
             }
-            None => self
-                .state
-                .borrow_mut()
-                .bond
-                .push(bond_information)
-                .map_err(|_| Error::OutOfMemory),
+            None => {
+                let mut state = self.state.borrow_mut();
+                if bond_information.identity.irk.is_some() {
+                    state.resolving_list_dirty = true;
+                }
+                state.bond.push(bond_information).map_err(|_| Error::OutOfMemory)
+            }
         }
     }
 
@@ -270,7 +528,10 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
             .position(|bond| bond.identity.match_identity(&identity));
         match index {
             Some(index) => {
-                self.state.borrow_mut().bond.remove(index);
+                let mut state = self.state.borrow_mut();
+                if state.bond.remove(index).identity.irk.is_some() {
+                    state.resolving_list_dirty = true;
+                }
                 Ok(())
             }
             None => Err(Error::NotFound),
@@ -282,6 +543,54 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         Vec::from_slice(self.state.borrow().bond.as_slice()).unwrap()
     }
 
+    /// Sign data sent to a peer with the local CSRK of its bond, returning the 12 octet authentication signature.
+    pub(crate) fn sign(&self, identity: &Identity, data: &[&[u8]]) -> Result<[u8; 12], Error> {
+        let mut state = self.state.borrow_mut();
+        let key = state
+            .bond
+            .iter_mut()
+            .find(|bond| bond.identity.match_identity(identity))
+            .and_then(|bond| bond.local_csrk.as_mut())
+            .ok_or(Error::NotFound)?;
+        let counter = key.counter;
+        key.counter = counter.checked_add(1).ok_or(Error::InvalidState)?;
+
+        let mut signature = [0; 12];
+        signature[..4].copy_from_slice(&counter.to_le_bytes());
+        signature[4..].copy_from_slice(&key.csrk.sign(data, counter));
+        Ok(signature)
+    }
+
+    /// Verify data signed by a peer with the CSRK it distributed, returning the security level of its bond.
+    ///
+    /// Signatures with a counter that was already seen are rejected ([Vol 3] Part C, Section 10.4.2). The counter is
+    /// only recorded as seen when `commit` is set, so that a signature can be checked before the data is processed.
+    pub(crate) fn verify(
+        &self,
+        identity: &Identity,
+        data: &[&[u8]],
+        signature: &[u8; 12],
+        commit: bool,
+    ) -> Result<SecurityLevel, Error> {
+        let mut state = self.state.borrow_mut();
+        let bond = state
+            .bond
+            .iter_mut()
+            .find(|bond| bond.identity.match_identity(identity))
+            .ok_or(Error::NotFound)?;
+        let key = bond.peer_csrk.as_mut().ok_or(Error::NotFound)?;
+        let counter = u32::from_le_bytes(unwrap!(signature[..4].try_into()));
+        // The last counter value can't be recorded as seen, the peer has to distribute a new key.
+        if counter < key.counter || counter == u32::MAX || key.csrk.sign(data, counter) != signature[4..] {
+            warn!("[security manager] Invalid signature from {:?}", identity);
+            return Err(Error::Security(Reason::AuthenticationRequirements));
+        }
+        if commit {
+            key.counter = counter + 1;
+        }
+        Ok(bond.security_level)
+    }
+
     fn handle_peripheral<P: PacketPool>(
         &self,
         pdu: Pdu<P::Packet>,
@@ -293,7 +602,7 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         let peer_identity = storage.peer_identity.ok_or(Error::InvalidValue)?;
         let peer_address = Address {
             kind: peer_address_kind,
-            addr: peer_identity.bd_addr,
+            addr: storage.peer_addr.ok_or(Error::InvalidValue)?,
         };
         let mut buffer = [0u8; 72];
         let size = {
@@ -323,7 +632,7 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
             let mut state_machine = self.pairing_sm.borrow_mut();
             if state_machine.is_none() {
                 *state_machine = Some(Pairing::new_peripheral(
-                    self.state.borrow().local_address.unwrap(),
+                    storage.local_addr.or(self.state.borrow().local_address).unwrap(),
                     peer_address,
                     *self.io_capabilities.borrow(),
                 ));
@@ -367,7 +676,7 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         let peer_identity = storage.peer_identity.ok_or(Error::InvalidValue)?;
         let peer_address = Address {
             kind: peer_address_kind,
-            addr: peer_identity.bd_addr,
+            addr: storage.peer_addr.ok_or(Error::InvalidValue)?,
         };
         let mut buffer = [0u8; 72];
         let size = {
@@ -397,7 +706,7 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
             let mut state_machine = self.pairing_sm.borrow_mut();
             if state_machine.is_none() {
                 *state_machine = Some(Pairing::new_central(
-                    self.state.borrow().local_address.unwrap(),
+                    storage.local_addr.or(self.state.borrow().local_address).unwrap(),
                     peer_address,
                     *self.io_capabilities.borrow(),
                 ));
@@ -500,45 +809,65 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         if storage.security_level != SecurityLevel::NoEncryption {
             return Err(Error::Security(Reason::UnspecifiedReason));
         }
+        if self.pairing_sm.borrow().is_some() {
+            return Err(Error::InvalidState);
+        }
+        self.start_pairing(connections, storage, true)
+    }
+
+    /// Pair again with MITM protection on an encrypted but unauthenticated link, ignoring any existing bond
+    pub(crate) fn reauthenticate<P: PacketPool>(
+        &self,
+        connections: &ConnectionManager<'_, P>,
+        storage: &ConnectionStorage<<P as PacketPool>::Packet>,
+    ) -> Result<(), Error> {
+        if !storage.security_level.encrypted() || storage.security_level.authenticated() {
+            return Err(Error::Security(Reason::UnspecifiedReason));
+        }
+        if self.pairing_sm.borrow().as_ref().is_some_and(|sm| !sm.is_finished()) {
+            return Err(Error::InvalidState);
+        }
+        self.start_pairing(connections, storage, false)
+    }
 
+    fn start_pairing<P: PacketPool>(
+        &self,
+        connections: &ConnectionManager<'_, P>,
+        storage: &ConnectionStorage<<P as PacketPool>::Packet>,
+        use_bond: bool,
+    ) -> Result<(), Error> {
         let role = storage.role.ok_or(Error::InvalidValue)?;
-        let mut pairing_sm = self.pairing_sm.borrow_mut();
-        if pairing_sm.is_none() {
-            let handle = storage.handle.ok_or(Error::InvalidValue)?;
-            let local_address = self.state.borrow().local_address.ok_or(Error::InvalidValue)?;
-            let peer_address_kind = storage.peer_addr_kind.ok_or(Error::InvalidValue)?;
-            let peer_identity = storage.peer_identity.ok_or(Error::InvalidValue)?;
-            let peer_address = Address {
-                kind: peer_address_kind,
-                addr: peer_identity.bd_addr,
-            };
-            let mut ops = PairingOpsImpl {
-                security_manager: self,
-                conn_handle: handle,
-                connections,
-                storage,
-                peer_identity,
-            };
-            if role == LeConnRole::Peripheral {
-                *pairing_sm = Some(Pairing::initiate_peripheral(
-                    local_address,
-                    peer_address,
-                    &mut ops,
-                    *self.io_capabilities.borrow(),
-                )?);
-                Ok(())
-            } else {
-                *pairing_sm = Some(Pairing::initiate_central(
-                    local_address,
-                    peer_address,
-                    &mut ops,
-                    *self.io_capabilities.borrow(),
-                )?);
-                Ok(())
-            }
+        let handle = storage.handle.ok_or(Error::InvalidValue)?;
+        let local_address = storage
+            .local_addr
+            .or(self.state.borrow().local_address)
+            .ok_or(Error::InvalidValue)?;
+        let peer_address_kind = storage.peer_addr_kind.ok_or(Error::InvalidValue)?;
+        let peer_identity = storage.peer_identity.ok_or(Error::InvalidValue)?;
+        let peer_address = Address {
+            kind: peer_address_kind,
+            addr: storage.peer_addr.ok_or(Error::InvalidValue)?,
+        };
+        let mut ops = PairingOpsImpl {
+            security_manager: self,
+            conn_handle: handle,
+            connections,
+            storage,
+            peer_identity,
+        };
+        let pairing = if role == LeConnRole::Peripheral {
+            Pairing::initiate_peripheral(local_address, peer_address, &mut ops, *self.io_capabilities.borrow())?
         } else {
-            Err(Error::InvalidState)
-        }
+            Pairing::initiate_central(
+                local_address,
+                peer_address,
+                &mut ops,
+                *self.io_capabilities.borrow(),
+                use_bond,
+            )?
+        };
+        self.pairing_sm.replace(Some(pairing));
+        Ok(())
     }
 
     /// Cancel pairing after timeout
@@ -571,7 +900,11 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         match event.kind {
             LeEventKind::LeLongTermKeyRequest => {
                 let event_data = LeLongTermKeyRequest::from_hci_bytes_complete(event.data)?;
-                self.try_send_event(SecurityEventData::SendLongTermKey(event_data.handle))?;
+                self.try_send_event(SecurityEventData::SendLongTermKey(
+                    event_data.handle,
+                    event_data.encrypted_diversifier,
+                    event_data.random_number,
+                ))?;
             }
             _ => (),
         }
@@ -584,66 +917,90 @@ impl<const BOND_COUNT: usize> SecurityManager<BOND_COUNT> {
         event: EventPacket,
         connections: &ConnectionManager<'_, P>,
     ) -> Result<(), Error> {
-        #[allow(clippy::single_match)]
         match event.kind {
             EventKind::EncryptionChangeV1 => {
                 let event_data = EncryptionChangeV1::from_hci_bytes_complete(event.data)?;
                 match event_data.status.to_result() {
                     Ok(()) => {
                         trace!("[smp] Encryption Changed event {:?}", event_data.enabled);
-                        connections.with_connected_handle(event_data.handle, |storage| {
-                            let sm = self.pairing_sm.borrow();
-                            if let Some(sm) = &*sm {
-                                let mut rng = self.rng.borrow_mut();
-                                let res = sm.handle_event(
-                                    pairing::Event::LinkEncryptedResult(
-                                        event_data.enabled != EncryptionEnabledLevel::Off,
-                                    ),
-                                    &mut PairingOpsImpl {
-                                        security_manager: self,
-                                        peer_identity: storage.peer_identity.ok_or(Error::InvalidValue)?,
-                                        connections,
-                                        storage,
-                                        conn_handle: storage.handle.ok_or(Error::InvalidValue)?,
-                                    },
-                                    rng.deref_mut(),
-                                );
-                                let _ = self.handle_security_error(connections, storage, &res);
-                                match res {
-                                    Ok(_) => {
-                                        storage.security_level = sm.security_level();
-                                        Ok(())
-                                    }
-                                    x => x,
-                                }?
-                            } else if let Some(identity) = storage.peer_identity.as_ref() {
-                                match self.get_peer_bond_information(identity) {
-                                    Some(bond) if event_data.enabled != EncryptionEnabledLevel::Off => {
-                                        info!("[smp] Encryption changed to true using bond {:?}", bond.identity);
-                                        storage.security_level = bond.security_level;
-                                    }
-                                    _ => {
-                                        warn!(
-                                            "[smp] Either encryption failed to enable or bond not found for {:?}",
-                                            identity
-                                        );
-                                        storage.security_level = SecurityLevel::NoEncryption
-                                    }
-                                }
-                            }
-                            Ok(())
-                        })?;
+                        self.handle_link_encrypted(
+                            event_data.handle,
+                            event_data.enabled != EncryptionEnabledLevel::Off,
+                            connections,
+                        )?;
                     }
                     Err(error) => {
                         error!("[security manager] Encryption Changed Handle Error {:?}", error);
                     }
                 }
             }
+            // Encrypting an already encrypted link, e.g. with the key of a new pairing, refreshes the key
+            EventKind::EncryptionKeyRefreshComplete => {
+                let event_data = EncryptionKeyRefreshComplete::from_hci_bytes_complete(event.data)?;
+                match event_data.status.to_result() {
+                    Ok(()) => {
+                        trace!("[smp] Encryption Key Refresh Complete event");
+                        self.handle_link_encrypted(event_data.handle, true, connections)?;
+                    }
+                    Err(error) => {
+                        error!("[security manager] Encryption Key Refresh Handle Error {:?}", error);
+                    }
+                }
+            }
             _ => (),
         }
         Ok(())
     }
 
+    fn handle_link_encrypted<P: PacketPool>(
+        &self,
+        handle: ConnHandle,
+        enabled: bool,
+        connections: &ConnectionManager<'_, P>,
+    ) -> Result<(), Error> {
+        connections.with_connected_handle(handle, |storage| {
+            let sm = self.pairing_sm.borrow();
+            if let Some(sm) = &*sm {
+                let mut rng = self.rng.borrow_mut();
+                let res = sm.handle_event(
+                    pairing::Event::LinkEncryptedResult(enabled),
+                    &mut PairingOpsImpl {
+                        security_manager: self,
+                        peer_identity: storage.peer_identity.ok_or(Error::InvalidValue)?,
+                        connections,
+                        storage,
+                        conn_handle: storage.handle.ok_or(Error::InvalidValue)?,
+                    },
+                    rng.deref_mut(),
+                );
+                let _ = self.handle_security_error(connections, storage, &res);
+                match res {
+                    Ok(_) => {
+                        storage.security_level = sm.security_level();
+                        Ok(())
+                    }
+                    x => x,
+                }?
+            } else if let Some(identity) = storage.peer_identity.as_ref() {
+                match self.get_peer_bond_information(identity) {
+                    Some(bond) if enabled => {
+                        info!("[smp] Encryption changed to true using bond {:?}", bond.identity);
+                        storage.security_level = bond.security_level;
+                        let _ = storage.pairing.try_send(Ok(bond.security_level));
+                    }
+                    _ => {
+                        warn!(
+                            "[smp] Either encryption failed to enable or bond not found for {:?}",
+                            identity
+                        );
+                        storage.security_level = SecurityLevel::NoEncryption
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
     fn handle_event<P: PacketPool>(
         &self,
         pairing_event: pairing::Event,
@@ -763,12 +1120,20 @@ impl<'sm, 'cm, 'cm2, 'cs, const B: usize, P: PacketPool> PairingOps<P> for Pairi
         is_bonded: bool,
     ) -> Result<BondInformation, Error> {
         info!("Enabling encryption for {:?}", self.peer_identity);
-        let bond_info = BondInformation {
-            ltk: *ltk,
-            identity: self.peer_identity,
-            is_bonded,
-            security_level,
-        };
+        let mut bond_info = BondInformation::new(self.peer_identity, *ltk, security_level, is_bonded);
+        // Keep the type of an identity address distributed earlier, otherwise the peer is identified by the address
+        // it connected with.
+        let bonded_kind = self
+            .security_manager
+            .state
+            .borrow()
+            .bond
+            .iter()
+            .find(|x| x.identity.match_identity(&self.peer_identity))
+            .map(|x| x.identity_kind);
+        if let Some(kind) = bonded_kind.or(self.storage.peer_addr_kind) {
+            bond_info.identity_kind = kind;
+        }
         self.try_update_bond_information(&bond_info)?;
         self.security_manager
             .try_send_event(SecurityEventData::EnableEncryption(self.conn_handle, bond_info.clone()))?;
@@ -800,15 +1165,34 @@ impl<'sm, 'cm, 'cm2, 'cs, const B: usize, P: PacketPool> PairingOps<P> for Pairi
         }
     }
 
+    fn local_identity(&self) -> Option<(IdentityResolvingKey, Address)> {
+        self.security_manager
+            .local_irk()
+            .zip(self.security_manager.identity_address())
+    }
+
+    fn local_oob_data(&self) -> Option<(SecretKey, Nonce)> {
+        self.security_manager.local_oob_data()
+    }
+
+    fn peer_oob_data(&self) -> Option<OobData> {
+        self.storage.peer_oob_data
+    }
+
     fn connection_handle(&mut self) -> ConnHandle {
         self.conn_handle
     }
 
     fn try_send_connection_event(&mut self, event: ConnectionEvent) -> Result<(), Error> {
-        let timer_changed = matches!(
-            event,
-            ConnectionEvent::PairingComplete { .. } | ConnectionEvent::PairingFailed(_)
-        );
+        let outcome = match &event {
+            ConnectionEvent::PairingComplete { security_level, .. } => Some(Ok(*security_level)),
+            ConnectionEvent::PairingFailed(error) => Some(Err(error.clone())),
+            _ => None,
+        };
+        let timer_changed = outcome.is_some();
+        if let Some(outcome) = outcome {
+            let _ = self.storage.pairing.try_send(outcome);
+        }
         self.storage.events.try_send(event).map_err(|_| Error::OutOfMemory)?;
         if timer_changed {
             let _ = self.security_manager.events.try_send(SecurityEventData::TimerChange);
@@ -816,3 +1200,90 @@ impl<'sm, 'cm, 'cm2, 'cs, const B: usize, P: PacketPool> PairingOps<P> for Pairi
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolvable_private_address_rotation() {
+        let security_manager = SecurityManager::<1>::new();
+        let now = Instant::from_secs(0);
+        assert!(security_manager.next_resolvable_private_address(now).is_none());
+
+        let irk = IdentityResolvingKey::new(0xec0234a357c8ad05341010a60a397d9b);
+        security_manager.set_privacy(irk, Duration::from_secs(900));
+        let rpa = security_manager.next_resolvable_private_address(now).unwrap();
+        assert_eq!(rpa.kind, AddrKind::RANDOM);
+        assert!(irk.resolve_address(&rpa.addr));
+        assert_eq!(security_manager.resolvable_private_address(), None);
+        let mut cx = Context::from_waker(core::task::Waker::noop());
+        assert!(security_manager.poll_rpa_expiry(&mut cx).is_pending());
+
+        // The host renews the address when it expires
+        security_manager.set_resolvable_private_address(rpa, now);
+        assert_eq!(security_manager.own_address(), Some(rpa));
+        assert_eq!(
+            security_manager.poll_rpa_expiry(&mut cx),
+            Poll::Ready(now + Duration::from_secs(900))
+        );
+        assert!(security_manager
+            .next_resolvable_private_address(now + Duration::from_secs(899))
+            .is_none());
+
+        // Renewed once expired
+        let next = security_manager
+            .next_resolvable_private_address(now + Duration::from_secs(900))
+            .unwrap();
+        assert_ne!(next, rpa);
+        assert!(irk.resolve_address(&next.addr));
+    }
+
+    #[test]
+    fn resolving_list_update() {
+        let security_manager = SecurityManager::<2>::new();
+        let identity = Identity {
+            bd_addr: BdAddr::new([0x01, 0x02, 0x03, 0x04, 0x05, 0xc6]),
+            irk: Some(IdentityResolvingKey::new(0x8b3958c158ed64467bd27bc90d3cf54d)),
+        };
+        let mut bond = BondInformation::new(identity, LongTermKey::new(0), SecurityLevel::Encrypted, true);
+        bond.identity_kind = AddrKind::RANDOM;
+        security_manager.add_bond_information(bond.clone()).unwrap();
+        security_manager
+            .add_bond_information(BondInformation::new(
+                Identity {
+                    bd_addr: BdAddr::new([0x11, 0x12, 0x13, 0x14, 0x15, 0x16]),
+                    irk: None,
+                },
+                LongTermKey::new(0),
+                SecurityLevel::Encrypted,
+                true,
+            ))
+            .unwrap();
+
+        // Nothing to load until the controller is known to have a resolving list
+        assert!(security_manager.resolving_list_update().is_none());
+        security_manager.set_resolving_list_size(8);
+        let update = security_manager.resolving_list_update().unwrap();
+        assert_eq!(
+            update.entries.as_slice(),
+            &[(
+                Address {
+                    kind: AddrKind::RANDOM,
+                    addr: identity.bd_addr
+                },
+                identity.irk.unwrap()
+            )]
+        );
+        assert_eq!(update.rpa_timeout, None);
+        security_manager.resolving_list_updated();
+        assert!(security_manager.resolving_list_update().is_none());
+
+        // Updating the keys of a bond doesn't change the resolving list, removing it does
+        security_manager.add_bond_information(bond).unwrap();
+        assert!(security_manager.resolving_list_update().is_none());
+        security_manager.remove_bond_information(identity).unwrap();
+        let update = security_manager.resolving_list_update().unwrap();
+        assert!(update.entries.is_empty());
+    }
+}
@@ -8,15 +8,23 @@ use rand_core::{CryptoRng, RngCore};
 use crate::codec::{Decode, Encode};
 use crate::connection::{ConnectionEvent, SecurityLevel};
 use crate::security_manager::constants::ENCRYPTION_KEY_SIZE_128_BITS;
-use crate::security_manager::crypto::{Confirm, DHKey, MacKey, Nonce, PublicKey, SecretKey};
+#[cfg(feature = "legacy-pairing")]
+use crate::security_manager::crypto::TemporaryKey;
+use crate::security_manager::crypto::{
+    Confirm, ConnectionSignatureResolvingKey, DHKey, MacKey, Nonce, PublicKey, SecretKey,
+};
 use crate::security_manager::pairing::util::{
-    choose_pairing_method, make_confirm_packet, make_dhkey_check_packet, make_pairing_random, make_public_key_packet,
-    prepare_packet, CommandAndPayload, PairingMethod, PassKeyEntryAction,
+    check_peer_oob_data, choose_pairing_method, make_confirm_packet, make_dhkey_check_packet,
+    make_identity_address_information_packet, make_identity_information_packet, make_pairing_random,
+    make_public_key_packet, make_signing_information_packet, parse_identity_address, prepare_packet, CommandAndPayload,
+    PairingMethod, PassKeyEntryAction,
 };
+#[cfg(feature = "legacy-pairing")]
+use crate::security_manager::pairing::util::{choose_legacy_pairing_method, pairing_features_command};
 use crate::security_manager::pairing::{Event, PairingOps};
-use crate::security_manager::types::{AuthReq, BondingFlag, Command, PairingFeatures};
-use crate::security_manager::{PassKey, Reason};
-use crate::{Address, BondInformation, Error, IoCapabilities, LongTermKey, PacketPool};
+use crate::security_manager::types::{AuthReq, BondingFlag, Command, PairingFeatures, UseOutOfBand};
+use crate::security_manager::{PassKey, Reason, SigningKey};
+use crate::{Address, BondInformation, Error, IdentityResolvingKey, IoCapabilities, LongTermKey, PacketPool};
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -32,12 +40,24 @@ enum Step {
     WaitingPassKeyInput,
     WaitingPassKeyEntryConfirm(PassKeyEntryConfirmSentTag),
     WaitingPassKeyEntryRandom(i32),
-    // TODO add OOB
+    // Out of band
+    WaitingOobRandom,
     WaitingDHKeyEb(DHKeyEaSentTag),
+    // LE legacy pairing
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingPassKeyInput,
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingConfirm,
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingRandom,
     WaitingLinkEncrypted,
     WaitingBondedLinkEncryption,
-    ReceivingKeys(i32),
-    SendingKeys(i32),
+    // Keys distributed by the responder
+    WaitingEncryptionInformation,
+    WaitingCentralIdentification,
+    WaitingIdentityInformation,
+    WaitingIdentityAddressInformation,
+    WaitingSigningInformation,
     Success,
     Error(Error),
 }
@@ -48,6 +68,32 @@ struct PairingRequestSentTag {}
 
 impl PairingRequestSentTag {
     fn new<P: PacketPool, OPS: PairingOps<P>>(pairing_data: &mut PairingData, ops: &mut OPS) -> Result<Self, Error> {
+        if ops.peer_oob_data().is_some() {
+            pairing_data.local_features.use_oob = UseOutOfBand::Present;
+        }
+        // Distributed keys are only useful if they are kept
+        if matches!(
+            pairing_data.local_features.security_properties.bond(),
+            BondingFlag::Bonding
+        ) {
+            if ops.local_identity().is_some() {
+                pairing_data
+                    .local_features
+                    .initiator_key_distribution
+                    .set_identity_key();
+            }
+            pairing_data.local_features.initiator_key_distribution.set_signing_key();
+            pairing_data
+                .local_features
+                .responder_key_distribution
+                .set_identity_key();
+            pairing_data.local_features.responder_key_distribution.set_signing_key();
+            #[cfg(feature = "legacy-pairing")]
+            pairing_data
+                .local_features
+                .responder_key_distribution
+                .set_encryption_key();
+        }
         let mut packet = prepare_packet::<P>(Command::PairingRequest)?;
 
         let payload = packet.payload_mut();
@@ -131,6 +177,8 @@ impl DHKeyEaSentTag {
 struct PairingData {
     local_address: Address,
     peer_address: Address,
+    // Identity address distributed by the peer, the link keeps using `peer_address`
+    peer_identity_address: Option<Address>,
     local_features: PairingFeatures,
     peer_features: PairingFeatures,
     pairing_method: PairingMethod,
@@ -145,6 +193,8 @@ struct PairingData {
     peer_nonce: Nonce,
     mac_key: Option<MacKey>,
     ltk: Option<LongTermKey>,
+    #[cfg(feature = "legacy-pairing")]
+    temporary_key: TemporaryKey,
     timeout_at: Instant,
     bond_information: Option<BondInformation>,
 }
@@ -154,6 +204,12 @@ impl PairingData {
         matches!(self.local_features.security_properties.bond(), BondingFlag::Bonding)
             && matches!(self.peer_features.security_properties.bond(), BondingFlag::Bonding)
     }
+
+    /// LE legacy pairing is used unless both devices support LE Secure Connections
+    fn legacy(&self) -> bool {
+        !(self.local_features.security_properties.secure_connection()
+            && self.peer_features.security_properties.secure_connection())
+    }
 }
 
 pub struct Pairing {
@@ -189,6 +245,7 @@ impl Pairing {
             pairing_method: PairingMethod::JustWorks,
             local_address,
             peer_address,
+            peer_identity_address: None,
             peer_public_key: None,
             local_public_key: None,
             local_secret_ra: 0,
@@ -204,6 +261,8 @@ impl Pairing {
             dh_key: None,
             confirm: Confirm(0),
             ltk: None,
+            #[cfg(feature = "legacy-pairing")]
+            temporary_key: TemporaryKey(0),
             private_key: None,
             timeout_at: Instant::now() + crate::security_manager::constants::TIMEOUT_DISABLE,
             bond_information: None,
@@ -219,12 +278,18 @@ impl Pairing {
         peer_address: Address,
         ops: &mut OPS,
         local_io: IoCapabilities,
+        use_bond: bool,
     ) -> Result<Pairing, Error> {
         let ret = Self::new_idle(local_address, peer_address, local_io);
         {
             let mut pairing_data = ret.pairing_data.borrow_mut();
             pairing_data.local_features.security_properties = AuthReq::new(ops.bonding_flag());
-            let next_step = if let Some(bond) = ops.try_enable_bonded_encryption()? {
+            let bond = if use_bond {
+                ops.try_enable_bonded_encryption()?
+            } else {
+                None
+            };
+            let next_step = if let Some(bond) = bond {
                 pairing_data.bond_information = Some(bond);
                 Step::WaitingBondedLinkEncryption
             } else {
@@ -236,6 +301,10 @@ impl Pairing {
         Ok(ret)
     }
 
+    pub fn is_finished(&self) -> bool {
+        matches!(self.current_step.borrow().deref(), Step::Success | Step::Error(_))
+    }
+
     pub fn peer_address(&self) -> Address {
         self.pairing_data.borrow().peer_address
     }
@@ -243,7 +312,12 @@ impl Pairing {
     pub fn security_level(&self) -> SecurityLevel {
         let step = self.current_step.borrow();
         match step.deref() {
-            Step::SendingKeys(_) | Step::ReceivingKeys(_) | Step::Success => self
+            Step::WaitingEncryptionInformation
+            | Step::WaitingCentralIdentification
+            | Step::WaitingIdentityInformation
+            | Step::WaitingIdentityAddressInformation
+            | Step::WaitingSigningInformation
+            | Step::Success => self
                 .pairing_data
                 .borrow()
                 .bond_information
@@ -282,8 +356,12 @@ impl Pairing {
             (Step::WaitingLinkEncrypted, Event::LinkEncryptedResult(res)) => {
                 if res {
                     info!("Link encrypted!");
-                    // TODO wait for keys
-                    Step::Success
+                    Self::next_key_step(
+                        self.pairing_data.borrow_mut().deref_mut(),
+                        Step::WaitingEncryptionInformation,
+                        ops,
+                        rng,
+                    )?
                 } else {
                     error!("Link encryption failed!");
                     Step::Error(Error::Security(Reason::KeyRejected))
@@ -315,6 +393,12 @@ impl Pairing {
                     rng,
                 )?)
             }
+            #[cfg(feature = "legacy-pairing")]
+            (Step::LegacyWaitingPassKeyInput, Event::PassKeyInput(input)) => {
+                let mut pairing_data = self.pairing_data.borrow_mut();
+                pairing_data.temporary_key = TemporaryKey(input as u128);
+                Self::send_legacy_confirm(ops, pairing_data.deref_mut(), rng)?
+            }
             (x, Event::PassKeyConfirm | Event::PassKeyCancel) => x,
             _ => Step::Error(Error::InvalidState),
         };
@@ -329,20 +413,7 @@ impl Pairing {
                 let is_success = matches!(x, Step::Success);
                 self.current_step.replace(x);
                 if is_success {
-                    let pairing_data = self.pairing_data.borrow();
-                    if let Some(bond) = pairing_data.bond_information.as_ref() {
-                        let pairing_bond = if pairing_data.want_bonding() {
-                            Some(bond.clone())
-                        } else {
-                            None
-                        };
-                        ops.try_send_connection_event(ConnectionEvent::PairingComplete {
-                            security_level: bond.security_level,
-                            bond: pairing_bond,
-                        })?;
-                    } else {
-                        error!("[smp] No bond information stored");
-                    }
+                    Self::pairing_complete(self.pairing_data.borrow().deref(), ops)?;
                 }
                 Ok(())
             }
@@ -376,14 +447,20 @@ impl Pairing {
                 }
                 (Step::WaitingPairingResponse(_), Command::PairingResponse) => {
                     Self::handle_pairing_response(command.payload, ops, pairing_data)?;
-                    Self::generate_private_public_key_pair(pairing_data, rng)?;
-                    Self::send_public_key(ops, pairing_data.local_public_key.as_ref().unwrap())?;
-                    Step::WaitingPublicKey
+                    Self::start_pairing(ops, pairing_data, rng)?
                 }
                 (Step::WaitingPublicKey, Command::PairingPublicKey) => {
                     Self::handle_public_key(command.payload, pairing_data)?;
                     match pairing_data.pairing_method {
-                        PairingMethod::OutOfBand => todo!("OOB not implemented"),
+                        PairingMethod::OutOfBand => {
+                            pairing_data.peer_secret_rb = check_peer_oob_data(
+                                ops.peer_oob_data(),
+                                pairing_data.peer_public_key.as_ref().ok_or(Error::InvalidValue)?,
+                            )?;
+                            pairing_data.local_nonce = Nonce::new(rng);
+                            Self::send_nonce(ops, &pairing_data.local_nonce)?;
+                            Step::WaitingOobRandom
+                        }
                         PairingMethod::PassKeyEntry { central, .. } => {
                             if central == PassKeyEntryAction::Display {
                                 pairing_data.local_secret_ra =
@@ -433,21 +510,302 @@ impl Pairing {
                         )?)
                     }
                 }
+                (Step::WaitingOobRandom, Command::PairingRandom) => {
+                    pairing_data.peer_nonce = Nonce(u128::from_le_bytes(
+                        command.payload.try_into().map_err(|_| Error::InvalidValue)?,
+                    ));
+                    Step::WaitingDHKeyEb(DHKeyEaSentTag::new(pairing_data, ops)?)
+                }
                 (Step::WaitingDHKeyEb(_), Command::PairingDhKeyCheck) => {
                     Self::handle_dhkey_eb(command.payload, ops, pairing_data)?;
                     Step::WaitingLinkEncrypted
                 }
+                #[cfg(feature = "legacy-pairing")]
+                (Step::LegacyWaitingConfirm, Command::PairingConfirm) => {
+                    Self::handle_pass_key_confirm(command.payload, pairing_data)?;
+                    Self::send_nonce(ops, &pairing_data.local_nonce)?;
+                    Step::LegacyWaitingRandom
+                }
+                #[cfg(feature = "legacy-pairing")]
+                (Step::LegacyWaitingRandom, Command::PairingRandom) => {
+                    Self::handle_legacy_random(command.payload, ops, pairing_data)?;
+                    Step::WaitingLinkEncrypted
+                }
                 (x, Command::KeypressNotification) => x,
 
+                (Step::WaitingEncryptionInformation, Command::EncryptionInformation) => {
+                    Self::handle_encryption_information(command.payload, pairing_data)?;
+                    Step::WaitingCentralIdentification
+                }
+                (Step::WaitingCentralIdentification, Command::CentralIdentification) => {
+                    Self::handle_central_identification(command.payload, pairing_data)?;
+                    Self::next_key_step(pairing_data, Step::WaitingIdentityInformation, ops, rng)?
+                }
+                (Step::WaitingIdentityInformation, Command::IdentityInformation) => {
+                    Self::handle_identity_information(command.payload, pairing_data)?;
+                    Step::WaitingIdentityAddressInformation
+                }
+                (Step::WaitingIdentityAddressInformation, Command::IdentityAddressInformation) => {
+                    Self::handle_identity_address_information(command.payload, pairing_data)?;
+                    Self::next_key_step(pairing_data, Step::WaitingSigningInformation, ops, rng)?
+                }
+                (Step::WaitingSigningInformation, Command::SigningInformation) => {
+                    Self::handle_signing_information(command.payload, pairing_data)?;
+                    Self::next_key_step(pairing_data, Step::Success, ops, rng)?
+                }
+
                 _ => return Err(Error::InvalidState),
             }
         };
 
+        let is_success = matches!(next_step, Step::Success);
         self.current_step.replace(next_step);
+        if is_success {
+            Self::pairing_complete(pairing_data, ops)?;
+        }
 
         Ok(())
     }
 
+    fn pairing_complete<P: PacketPool, OPS: PairingOps<P>>(
+        pairing_data: &PairingData,
+        ops: &mut OPS,
+    ) -> Result<(), Error> {
+        if let Some(bond) = pairing_data.bond_information.as_ref() {
+            let pairing_bond = if pairing_data.want_bonding() {
+                ops.try_update_bond_information(bond)?;
+                Some(bond.clone())
+            } else {
+                None
+            };
+            ops.try_send_connection_event(ConnectionEvent::PairingComplete {
+                security_level: bond.security_level,
+                bond: pairing_bond,
+            })?;
+        } else {
+            error!("[smp] No bond information stored");
+        }
+        Ok(())
+    }
+
+    /// The step waiting for the next key distributed by the responder, starting from `from`.
+    /// Once the responder is done, the initiator distributes its own keys ([Vol 3] Part H, Section 3.6.1).
+    fn next_key_step<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        pairing_data: &mut PairingData,
+        from: Step,
+        ops: &mut OPS,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        // Keys negotiated in the pairing response
+        let keys = pairing_data.peer_features.responder_key_distribution;
+        Ok(match from {
+            // The encryption key is only distributed by LE legacy pairing
+            Step::WaitingEncryptionInformation if keys.encryption_key() && pairing_data.legacy() => {
+                Step::WaitingEncryptionInformation
+            }
+            Step::WaitingEncryptionInformation | Step::WaitingIdentityInformation if keys.identity_key() => {
+                Step::WaitingIdentityInformation
+            }
+            Step::WaitingEncryptionInformation | Step::WaitingIdentityInformation | Step::WaitingSigningInformation
+                if keys.signing_key() =>
+            {
+                Step::WaitingSigningInformation
+            }
+            _ => {
+                Self::send_keys(pairing_data, ops, rng)?;
+                Step::Success
+            }
+        })
+    }
+
+    /// Distribute the keys agreed in the pairing response ([Vol 3] Part H, Section 3.6.1).
+    fn send_keys<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        pairing_data: &mut PairingData,
+        ops: &mut OPS,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let keys = pairing_data.peer_features.initiator_key_distribution;
+        if keys.identity_key() {
+            let (irk, address) = ops.local_identity().ok_or(Error::InvalidState)?;
+            ops.try_send_packet(make_identity_information_packet(&irk)?)?;
+            ops.try_send_packet(make_identity_address_information_packet(&address)?)?;
+        }
+        if keys.signing_key() {
+            let mut csrk = [0; 16];
+            rng.fill_bytes(&mut csrk);
+            let csrk = ConnectionSignatureResolvingKey::from_le_bytes(csrk);
+            ops.try_send_packet(make_signing_information_packet(&csrk)?)?;
+            if let Some(bond) = pairing_data.bond_information.as_mut() {
+                bond.local_csrk = Some(SigningKey::new(csrk));
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_encryption_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<(), Error> {
+        let ltk = LongTermKey::from_le_bytes(payload.try_into().map_err(|_| Error::InvalidValue)?);
+        if let Some(bond) = pairing_data.bond_information.as_mut() {
+            bond.ltk = ltk;
+        }
+
+        trace!("Encryption information: LTK: {:?}", ltk);
+        Ok(())
+    }
+
+    fn handle_central_identification(payload: &[u8], pairing_data: &mut PairingData) -> Result<(), Error> {
+        let ediv = u16::from_le_bytes(payload[0..2].try_into().map_err(|_| Error::InvalidValue)?);
+        let rand: [u8; 8] = payload[2..10].try_into().map_err(|_| Error::InvalidValue)?;
+        if let Some(bond) = pairing_data.bond_information.as_mut() {
+            bond.ediv = ediv;
+            bond.rand = rand;
+        }
+
+        trace!("Central identification: EDIV: {:?}, Rand: {:?}", ediv, rand);
+        Ok(())
+    }
+
+    fn handle_identity_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<(), Error> {
+        let irk = IdentityResolvingKey::from_le_bytes(payload.try_into().map_err(|_| Error::InvalidValue)?);
+        if let Some(bond) = pairing_data.bond_information.as_mut() {
+            bond.identity.irk = Some(irk);
+        }
+
+        trace!("Identity information: IRK: {:?}", irk);
+        Ok(())
+    }
+
+    fn handle_identity_address_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<(), Error> {
+        let address = parse_identity_address(payload)?;
+        pairing_data.peer_identity_address = Some(address);
+        if let Some(bond) = pairing_data.bond_information.as_mut() {
+            bond.identity.bd_addr = address.addr;
+            bond.identity_kind = address.kind;
+        }
+
+        trace!("Identity address information: {:?}", address);
+        Ok(())
+    }
+
+    fn handle_signing_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<(), Error> {
+        let csrk = ConnectionSignatureResolvingKey::from_le_bytes(payload.try_into().map_err(|_| Error::InvalidValue)?);
+        if let Some(bond) = pairing_data.bond_information.as_mut() {
+            bond.peer_csrk = Some(SigningKey::new(csrk));
+        }
+
+        trace!("Signing information: CSRK: {:?}", csrk);
+        Ok(())
+    }
+
+    fn start_pairing<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        #[cfg(feature = "legacy-pairing")]
+        if pairing_data.legacy() {
+            return Self::start_legacy_pairing(ops, pairing_data, rng);
+        }
+        Self::prepare_local_key_pair(ops, pairing_data, rng)?;
+        Self::send_public_key(ops, pairing_data.local_public_key.as_ref().unwrap())?;
+        Ok(Step::WaitingPublicKey)
+    }
+
+    /// Generate the local key pair, or take the one of the local out-of-band data if the peer has received it
+    /// ([Vol 3] Part H, Section 2.3.5.6.4).
+    fn prepare_local_key_pair<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        if pairing_data.pairing_method == PairingMethod::OutOfBand
+            && matches!(pairing_data.peer_features.use_oob, UseOutOfBand::Present)
+        {
+            let (secret_key, random) = ops.local_oob_data().ok_or(Error::Security(Reason::OobNotAvailable))?;
+            pairing_data.local_public_key = Some(secret_key.public_key());
+            pairing_data.private_key = Some(secret_key);
+            pairing_data.local_secret_ra = random.0;
+            Ok(())
+        } else {
+            Self::generate_private_public_key_pair(pairing_data, rng)
+        }
+    }
+
+    /// Choose the temporary key of LE legacy pairing ([Vol 3] Part H, Section 2.3.5.5).
+    #[cfg(feature = "legacy-pairing")]
+    fn start_legacy_pairing<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        match pairing_data.pairing_method {
+            PairingMethod::JustWorks => {
+                pairing_data.temporary_key = TemporaryKey(0);
+                Self::send_legacy_confirm(ops, pairing_data, rng)
+            }
+            PairingMethod::PassKeyEntry {
+                central: PassKeyEntryAction::Display,
+                ..
+            } => {
+                let pass_key: u32 = rng.sample(rand::distributions::Uniform::new_inclusive(0, 999999));
+                pairing_data.temporary_key = TemporaryKey(pass_key as u128);
+                ops.try_send_connection_event(ConnectionEvent::PassKeyDisplay(PassKey(pass_key)))?;
+                Self::send_legacy_confirm(ops, pairing_data, rng)
+            }
+            PairingMethod::PassKeyEntry { .. } => {
+                ops.try_send_connection_event(ConnectionEvent::PassKeyInput)?;
+                Ok(Step::LegacyWaitingPassKeyInput)
+            }
+            _ => Err(Error::Security(Reason::OobNotAvailable)),
+        }
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn send_legacy_confirm<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        pairing_data.local_nonce = Nonce::new(rng);
+        let confirm = Self::legacy_confirm(pairing_data, &pairing_data.local_nonce)?;
+        ops.try_send_packet(make_confirm_packet(&confirm)?)?;
+        Ok(Step::LegacyWaitingConfirm)
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn legacy_confirm(pairing_data: &PairingData, r: &Nonce) -> Result<Confirm, Error> {
+        let preq = pairing_features_command(Command::PairingRequest, &pairing_data.local_features)?;
+        let pres = pairing_features_command(Command::PairingResponse, &pairing_data.peer_features)?;
+        Ok(pairing_data
+            .temporary_key
+            .c1(r, &preq, &pres, pairing_data.local_address, pairing_data.peer_address))
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn handle_legacy_random<P: PacketPool, OPS: PairingOps<P>>(
+        payload: &[u8],
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+    ) -> Result<(), Error> {
+        let peer_nonce = Nonce(u128::from_le_bytes(
+            payload.try_into().map_err(|_| Error::InvalidValue)?,
+        ));
+        if Self::legacy_confirm(pairing_data, &peer_nonce)? != pairing_data.confirm {
+            return Err(Error::Security(Reason::ConfirmValueFailed));
+        }
+        pairing_data.peer_nonce = peer_nonce;
+
+        let stk = pairing_data
+            .temporary_key
+            .s1(&pairing_data.peer_nonce, &pairing_data.local_nonce);
+        let bond = ops.try_enable_encryption(
+            &stk,
+            pairing_data.pairing_method.security_level(),
+            pairing_data.want_bonding(),
+        )?;
+        pairing_data.bond_information = Some(bond);
+        Ok(())
+    }
+
     fn handle_pairing_response<P: PacketPool, OPS: PairingOps<P>>(
         payload: &[u8],
         ops: &mut OPS,
@@ -457,12 +815,17 @@ impl Pairing {
         if peer_features.maximum_encryption_key_size < ENCRYPTION_KEY_SIZE_128_BITS {
             return Err(Error::Security(Reason::EncryptionKeySize));
         }
-        if !peer_features.security_properties.secure_connection() {
+        if !peer_features.security_properties.secure_connection() && !cfg!(feature = "legacy-pairing") {
             return Err(Error::Security(Reason::UnspecifiedReason));
         }
 
         pairing_data.peer_features = peer_features;
         pairing_data.pairing_method = choose_pairing_method(pairing_data.local_features, pairing_data.peer_features);
+        #[cfg(feature = "legacy-pairing")]
+        if pairing_data.legacy() {
+            pairing_data.pairing_method =
+                choose_legacy_pairing_method(pairing_data.local_features, pairing_data.peer_features);
+        }
         info!("[smp] Pairing method {:?}", pairing_data.pairing_method);
 
         Ok(())
@@ -3,9 +3,10 @@ use embassy_time::Instant;
 use rand_core::{CryptoRng, RngCore};
 
 use crate::connection::{ConnectionEvent, SecurityLevel};
+use crate::security_manager::crypto::{Nonce, SecretKey};
 use crate::security_manager::types::{BondingFlag, Command};
-use crate::security_manager::TxPacket;
-use crate::{Address, BondInformation, Error, IoCapabilities, LongTermKey, PacketPool};
+use crate::security_manager::{OobData, TxPacket};
+use crate::{Address, BondInformation, Error, IdentityResolvingKey, IoCapabilities, LongTermKey, PacketPool};
 
 pub mod central;
 pub mod peripheral;
@@ -25,6 +26,12 @@ pub trait PairingOps<P: PacketPool> {
     fn connection_handle(&mut self) -> ConnHandle;
     fn try_send_connection_event(&mut self, event: ConnectionEvent) -> Result<(), Error>;
     fn bonding_flag(&self) -> BondingFlag;
+    /// Local identity resolving key and identity address, distributed when privacy is enabled
+    fn local_identity(&self) -> Option<(IdentityResolvingKey, Address)>;
+    /// Key pair and random value of the local out-of-band data, if generated
+    fn local_oob_data(&self) -> Option<(SecretKey, Nonce)>;
+    /// Out-of-band data received from the peer for this connection
+    fn peer_oob_data(&self) -> Option<OobData>;
 }
 
 pub enum Pairing {
@@ -61,6 +68,13 @@ impl Pairing {
         }
     }
 
+    pub(crate) fn is_finished(&self) -> bool {
+        match self {
+            Pairing::Central(c) => c.is_finished(),
+            Pairing::Peripheral(p) => p.is_finished(),
+        }
+    }
+
     pub(crate) fn security_level(&self) -> SecurityLevel {
         match self {
             Pairing::Central(c) => c.security_level(),
@@ -76,12 +90,14 @@ impl Pairing {
         peer_address: Address,
         ops: &mut OPS,
         local_io: IoCapabilities,
+        use_bond: bool,
     ) -> Result<Self, Error> {
         Ok(Pairing::Central(central::Pairing::initiate(
             local_address,
             peer_address,
             ops,
             local_io,
+            use_bond,
         )?))
     }
 
@@ -189,6 +205,9 @@ mod tests {
         pub(crate) connection_events: heapless::Vec<ConnectionEvent, 10>,
         pub(crate) bond_information: Option<BondInformation>,
         pub(crate) bondable: bool,
+        pub(crate) local_identity: Option<(IdentityResolvingKey, Address)>,
+        pub(crate) local_oob: Option<(SecretKey, Nonce)>,
+        pub(crate) peer_oob: Option<OobData>,
     }
 
     impl<const N: usize> PairingOps<HeaplessPool> for TestOps<N> {
@@ -203,12 +222,12 @@ mod tests {
             is_bonded: bool,
         ) -> Result<BondInformation, Error> {
             self.encryptions.push(ltk.clone()).unwrap();
-            Ok(BondInformation {
+            Ok(BondInformation::new(
+                Identity::default(),
+                ltk.clone(),
                 security_level,
-                identity: Identity::default(),
-                ltk: ltk.clone(),
                 is_bonded,
-            })
+            ))
         }
 
         fn try_enable_bonded_encryption(&mut self) -> Result<Option<BondInformation>, Error> {
@@ -240,6 +259,18 @@ mod tests {
                 BondingFlag::NoBonding
             }
         }
+
+        fn local_identity(&self) -> Option<(IdentityResolvingKey, Address)> {
+            self.local_identity
+        }
+
+        fn local_oob_data(&self) -> Option<(SecretKey, Nonce)> {
+            self.local_oob.clone()
+        }
+
+        fn peer_oob_data(&self) -> Option<OobData> {
+            self.peer_oob
+        }
     }
 
     #[test]
@@ -251,8 +282,14 @@ mod tests {
         let mut central_ops = TestOps::<10>::default();
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::NoInputNoOutput);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::NoInputNoOutput).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::NoInputNoOutput,
+            true,
+        )
+        .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -302,8 +339,14 @@ mod tests {
         let mut central_ops = TestOps::<10>::default();
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::DisplayYesNo);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::DisplayYesNo).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::DisplayYesNo,
+            true,
+        )
+        .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -388,8 +431,14 @@ mod tests {
         let mut central_ops = TestOps::<80>::default();
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::KeyboardOnly);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::KeyboardOnly).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::KeyboardOnly,
+            true,
+        )
+        .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -468,8 +517,14 @@ mod tests {
         let mut central_ops = TestOps::<80>::default();
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::DisplayOnly);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::KeyboardOnly).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::KeyboardOnly,
+            true,
+        )
+        .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -547,7 +602,8 @@ mod tests {
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::KeyboardOnly);
         let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::DisplayOnly).unwrap();
+            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::DisplayOnly, true)
+                .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -626,8 +682,14 @@ mod tests {
         central_ops.bondable = true;
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::NoInputNoOutput);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::NoInputNoOutput).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::NoInputNoOutput,
+            true,
+        )
+        .unwrap();
 
         let mut num_central_data_sent = 0;
         let mut num_peripheral_data_sent = 0;
@@ -649,7 +711,32 @@ mod tests {
         peripheral_pairing
             .handle_event(Event::LinkEncryptedResult(true), &mut peripheral_ops, &mut rng)
             .unwrap();
+        // The central distributes its signing key
+        transmit_packets(
+            &mut peripheral_ops,
+            &mut central_ops,
+            &mut rng,
+            &peripheral_pairing,
+            &central_pairing,
+            &mut num_central_data_sent,
+            &mut num_peripheral_data_sent,
+        );
 
+        let (
+            ConnectionEvent::PairingComplete {
+                bond: Some(central_bond),
+                ..
+            },
+            ConnectionEvent::PairingComplete {
+                bond: Some(peripheral_bond),
+                ..
+            },
+        ) = (&central_ops.connection_events[0], &peripheral_ops.connection_events[0])
+        else {
+            panic!("expected pairing to complete with a bond");
+        };
+        assert!(central_bond.local_csrk.is_some());
+        assert_eq!(central_bond.local_csrk, peripheral_bond.peer_csrk);
         assert!(matches!(
             central_ops.connection_events[0],
             ConnectionEvent::PairingComplete {
@@ -676,6 +763,90 @@ mod tests {
         assert_eq!(peripheral_pairing.security_level(), SecurityLevel::Encrypted);
     }
 
+    #[test]
+    fn bondable_key_distribution() {
+        let peripheral = Address::random([0xff, 1, 2, 3, 4, 5]);
+        let central = Address::random([0xff, 2, 2, 3, 4, 5]);
+        let peripheral_identity = (IdentityResolvingKey::new(1), Address::random([1, 1, 2, 3, 4, 0xc5]));
+        let central_identity = (IdentityResolvingKey::new(2), Address::random([2, 2, 2, 3, 4, 0xc5]));
+
+        let mut peripheral_ops = TestOps::<80> {
+            bondable: true,
+            local_identity: Some(peripheral_identity),
+            ..Default::default()
+        };
+        let mut central_ops = TestOps::<80> {
+            bondable: true,
+            local_identity: Some(central_identity),
+            ..Default::default()
+        };
+
+        let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::NoInputNoOutput);
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::NoInputNoOutput,
+            true,
+        )
+        .unwrap();
+
+        let mut num_central_data_sent = 0;
+        let mut num_peripheral_data_sent = 0;
+        let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
+        transmit_packets(
+            &mut peripheral_ops,
+            &mut central_ops,
+            &mut rng,
+            &peripheral_pairing,
+            &central_pairing,
+            &mut num_central_data_sent,
+            &mut num_peripheral_data_sent,
+        );
+
+        central_pairing
+            .handle_event(Event::LinkEncryptedResult(true), &mut central_ops, &mut rng)
+            .unwrap();
+        peripheral_pairing
+            .handle_event(Event::LinkEncryptedResult(true), &mut peripheral_ops, &mut rng)
+            .unwrap();
+        // The peripheral distributes its keys first, then the central
+        transmit_packets(
+            &mut peripheral_ops,
+            &mut central_ops,
+            &mut rng,
+            &peripheral_pairing,
+            &central_pairing,
+            &mut num_central_data_sent,
+            &mut num_peripheral_data_sent,
+        );
+
+        let (
+            ConnectionEvent::PairingComplete {
+                bond: Some(central_bond),
+                ..
+            },
+            ConnectionEvent::PairingComplete {
+                bond: Some(peripheral_bond),
+                ..
+            },
+        ) = (&central_ops.connection_events[0], &peripheral_ops.connection_events[0])
+        else {
+            panic!("expected pairing to complete with a bond");
+        };
+        assert_eq!(central_bond.identity.irk, Some(peripheral_identity.0));
+        assert_eq!(central_bond.identity.bd_addr, peripheral_identity.1.addr);
+        assert_eq!(peripheral_bond.identity.irk, Some(central_identity.0));
+        assert_eq!(peripheral_bond.identity.bd_addr, central_identity.1.addr);
+        assert!(central_bond.peer_csrk.is_some());
+        assert_eq!(central_bond.peer_csrk, peripheral_bond.local_csrk);
+        assert!(central_bond.local_csrk.is_some());
+        assert_eq!(central_bond.local_csrk, peripheral_bond.peer_csrk);
+        // The pairing stays bound to the connection addresses
+        assert_eq!(central_pairing.peer_address(), peripheral);
+        assert_eq!(peripheral_pairing.peer_address(), central);
+    }
+
     #[test]
     fn bonded_central_initiates() {
         let peripheral = Address::random([0xff, 1, 2, 3, 4, 5]);
@@ -691,6 +862,11 @@ mod tests {
                 irk: None,
                 bd_addr: peripheral.addr,
             },
+            identity_kind: peripheral.kind,
+            ediv: 0,
+            rand: [0; 8],
+            local_csrk: None,
+            peer_csrk: None,
         });
 
         peripheral_ops.bond_information = Some(BondInformation {
@@ -701,13 +877,24 @@ mod tests {
                 irk: None,
                 bd_addr: central.addr,
             },
+            identity_kind: central.kind,
+            ediv: 0,
+            rand: [0; 8],
+            local_csrk: None,
+            peer_csrk: None,
         });
 
         let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
 
         let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::NoInputNoOutput);
-        let central_pairing =
-            central::Pairing::initiate(central, peripheral, &mut central_ops, IoCapabilities::NoInputNoOutput).unwrap();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::NoInputNoOutput,
+            true,
+        )
+        .unwrap();
         assert_eq!(central_ops.sent_packets.len(), 0);
         assert_eq!(peripheral_ops.sent_packets.len(), 0);
         assert_eq!(central_ops.encryptions.len(), 1);
@@ -758,6 +945,11 @@ mod tests {
                 irk: None,
                 bd_addr: peripheral.addr,
             },
+            identity_kind: peripheral.kind,
+            ediv: 0,
+            rand: [0; 8],
+            local_csrk: None,
+            peer_csrk: None,
         });
 
         peripheral_ops.bond_information = Some(BondInformation {
@@ -768,6 +960,11 @@ mod tests {
                 irk: None,
                 bd_addr: central.addr,
             },
+            identity_kind: central.kind,
+            ediv: 0,
+            rand: [0; 8],
+            local_csrk: None,
+            peer_csrk: None,
         });
 
         let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
@@ -828,6 +1025,113 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "legacy-pairing")]
+    #[test]
+    fn legacy_pass_key_entry_central() {
+        use crate::security_manager::crypto::{Confirm, Nonce, TemporaryKey};
+
+        let peripheral = Address::random([0xff, 1, 2, 3, 4, 5]);
+        let central = Address::random([0xff, 2, 2, 3, 4, 5]);
+
+        let mut central_ops = TestOps::<10> {
+            bondable: true,
+            ..Default::default()
+        };
+        let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
+        let central_pairing = central::Pairing::initiate(
+            central,
+            peripheral,
+            &mut central_ops,
+            IoCapabilities::KeyboardOnly,
+            true,
+        )
+        .unwrap();
+        let mut preq = [0x01; 7];
+        preq[1..].copy_from_slice(central_ops.sent_packets[0].payload());
+        assert_eq!(preq[6] & 0x01, 0x01);
+
+        // Legacy peripheral with a display, distributing its encryption key
+        let response = [0x00, 0x00, 0x05, 0x10, 0x00, 0x01];
+        let mut pres = [0x02; 7];
+        pres[1..].copy_from_slice(&response);
+        central_pairing
+            .handle_l2cap_command(Command::PairingResponse, &response, &mut central_ops, &mut rng)
+            .unwrap();
+        assert!(matches!(
+            central_ops.connection_events[0],
+            ConnectionEvent::PassKeyInput
+        ));
+        central_pairing
+            .handle_event(Event::PassKeyInput(123456), &mut central_ops, &mut rng)
+            .unwrap();
+        assert_eq!(central_ops.sent_packets[1].command, Command::PairingConfirm);
+        let mconfirm = Confirm(u128::from_le_bytes(
+            central_ops.sent_packets[1].payload().try_into().unwrap(),
+        ));
+
+        let tk = TemporaryKey(123456);
+        let srand = Nonce(0x5783D52156AD6F0E6388274EC6702EE0);
+        let sconfirm = tk.c1(&srand, &preq, &pres, central, peripheral);
+        central_pairing
+            .handle_l2cap_command(
+                Command::PairingConfirm,
+                &sconfirm.0.to_le_bytes(),
+                &mut central_ops,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(central_ops.sent_packets[2].command, Command::PairingRandom);
+        let mrand = Nonce(u128::from_le_bytes(
+            central_ops.sent_packets[2].payload().try_into().unwrap(),
+        ));
+        assert_eq!(tk.c1(&mrand, &preq, &pres, central, peripheral), mconfirm);
+
+        central_pairing
+            .handle_l2cap_command(
+                Command::PairingRandom,
+                &srand.0.to_le_bytes(),
+                &mut central_ops,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(central_ops.encryptions[0], tk.s1(&srand, &mrand));
+
+        // The peripheral distributes LTK, EDIV and Rand for reconnections
+        central_pairing
+            .handle_event(Event::LinkEncryptedResult(true), &mut central_ops, &mut rng)
+            .unwrap();
+        central_pairing
+            .handle_l2cap_command(
+                Command::EncryptionInformation,
+                &0x1122_3344_5566_7788_99aa_bbcc_ddee_ff00u128.to_le_bytes(),
+                &mut central_ops,
+                &mut rng,
+            )
+            .unwrap();
+        central_pairing
+            .handle_l2cap_command(
+                Command::CentralIdentification,
+                &[0x34, 0x12, 1, 2, 3, 4, 5, 6, 7, 8],
+                &mut central_ops,
+                &mut rng,
+            )
+            .unwrap();
+
+        match &central_ops.connection_events[1] {
+            ConnectionEvent::PairingComplete {
+                security_level,
+                bond: Some(bond),
+            } => {
+                assert_eq!(*security_level, SecurityLevel::EncryptedAuthenticated);
+                assert_eq!(bond.ltk, LongTermKey(0x1122_3344_5566_7788_99aa_bbcc_ddee_ff00));
+                assert_eq!(bond.ediv, 0x1234);
+                assert_eq!(bond.rand, [1, 2, 3, 4, 5, 6, 7, 8]);
+            }
+            _ => panic!("Unexpected connection event"),
+        }
+        assert_eq!(central_pairing.security_level(), SecurityLevel::EncryptedAuthenticated);
+    }
+
     fn transmit_packets<const N: usize>(
         peripheral_ops: &mut TestOps<N>,
         central_ops: &mut TestOps<N>,
@@ -878,4 +1182,76 @@ mod tests {
             }
         }
     }
+
+    fn make_oob_data(rng: &mut ChaCha12Rng) -> ((SecretKey, Nonce), OobData) {
+        let secret_key = SecretKey::new(rng);
+        let random = Nonce::new(rng);
+        let public_key = secret_key.public_key();
+        let confirm = random.f4(public_key.x(), public_key.x(), 0);
+        let oob_data = OobData {
+            random: random.0,
+            confirm: confirm.0,
+        };
+        ((secret_key, random), oob_data)
+    }
+
+    #[test]
+    fn out_of_band() {
+        let peripheral = Address::random([0xff, 1, 2, 3, 4, 5]);
+        let central = Address::random([0xff, 2, 2, 3, 4, 5]);
+        let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
+
+        // Out-of-band data received by the central, the peripheral or both
+        for (central_has_data, peripheral_has_data) in [(true, false), (false, true), (true, true)] {
+            let mut peripheral_ops = TestOps::<10>::default();
+            let mut central_ops = TestOps::<10>::default();
+            if central_has_data {
+                let (local_oob, oob_data) = make_oob_data(&mut rng);
+                peripheral_ops.local_oob = Some(local_oob);
+                central_ops.peer_oob = Some(oob_data);
+            }
+            if peripheral_has_data {
+                let (local_oob, oob_data) = make_oob_data(&mut rng);
+                central_ops.local_oob = Some(local_oob);
+                peripheral_ops.peer_oob = Some(oob_data);
+            }
+
+            let peripheral_pairing = peripheral::Pairing::new(peripheral, central, IoCapabilities::NoInputNoOutput);
+            let central_pairing = central::Pairing::initiate(
+                central,
+                peripheral,
+                &mut central_ops,
+                IoCapabilities::NoInputNoOutput,
+                true,
+            )
+            .unwrap();
+
+            let mut num_central_data_sent = 0;
+            let mut num_peripheral_data_sent = 0;
+            transmit_packets(
+                &mut peripheral_ops,
+                &mut central_ops,
+                &mut rng,
+                &peripheral_pairing,
+                &central_pairing,
+                &mut num_central_data_sent,
+                &mut num_peripheral_data_sent,
+            );
+
+            assert_eq!(central_ops.encryptions.len(), 1);
+            assert_eq!(central_ops.encryptions[0], peripheral_ops.encryptions[0]);
+            central_pairing
+                .handle_event(Event::LinkEncryptedResult(true), &mut central_ops, &mut rng)
+                .unwrap();
+            peripheral_pairing
+                .handle_event(Event::LinkEncryptedResult(true), &mut peripheral_ops, &mut rng)
+                .unwrap();
+
+            assert_eq!(central_pairing.security_level(), SecurityLevel::EncryptedAuthenticated);
+            assert_eq!(
+                peripheral_pairing.security_level(),
+                SecurityLevel::EncryptedAuthenticated
+            );
+        }
+    }
 }
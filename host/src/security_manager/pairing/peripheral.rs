@@ -1,7 +1,6 @@
 use core::cell::RefCell;
 use core::ops::{Deref, DerefMut};
 
-use bt_hci::param::{AddrKind, BdAddr};
 use embassy_time::Instant;
 use rand::Rng;
 use rand_core::{CryptoRng, RngCore};
@@ -10,14 +9,22 @@ use crate::codec::{Decode, Encode};
 use crate::connection::SecurityLevel;
 use crate::prelude::ConnectionEvent;
 use crate::security_manager::constants::ENCRYPTION_KEY_SIZE_128_BITS;
-use crate::security_manager::crypto::{Confirm, DHKey, MacKey, Nonce, PublicKey, SecretKey};
+#[cfg(feature = "legacy-pairing")]
+use crate::security_manager::crypto::TemporaryKey;
+use crate::security_manager::crypto::{
+    Confirm, ConnectionSignatureResolvingKey, DHKey, MacKey, Nonce, PublicKey, SecretKey,
+};
 use crate::security_manager::pairing::util::{
-    choose_pairing_method, make_confirm_packet, make_dhkey_check_packet, make_pairing_random, make_public_key_packet,
-    prepare_packet, CommandAndPayload, PairingMethod, PassKeyEntryAction,
+    check_peer_oob_data, choose_pairing_method, make_central_identification_packet, make_confirm_packet,
+    make_dhkey_check_packet, make_encryption_information_packet, make_identity_address_information_packet,
+    make_identity_information_packet, make_pairing_random, make_public_key_packet, make_signing_information_packet,
+    parse_identity_address, prepare_packet, CommandAndPayload, PairingMethod, PassKeyEntryAction,
 };
+#[cfg(feature = "legacy-pairing")]
+use crate::security_manager::pairing::util::{choose_legacy_pairing_method, pairing_features_command};
 use crate::security_manager::pairing::{Event, PairingOps};
-use crate::security_manager::types::{AuthReq, BondingFlag, Command, PairingFeatures, PassKey};
-use crate::security_manager::Reason;
+use crate::security_manager::types::{AuthReq, BondingFlag, Command, PairingFeatures, PassKey, UseOutOfBand};
+use crate::security_manager::{Reason, SigningKey};
 use crate::{Address, BondInformation, Error, IdentityResolvingKey, IoCapabilities, LongTermKey, PacketPool};
 
 #[derive(Debug, Clone)]
@@ -32,15 +39,21 @@ enum Step {
     WaitingPassKeyInput(Option<[u8; size_of::<u128>()]>),
     WaitingPassKeyEntryConfirm(i32),
     WaitingPassKeyEntryRandom(i32),
-    // TODO add OOB
+    // Out of band
+    WaitingOobRandom,
     WaitingDHKeyEa,
+    // LE legacy pairing
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingPassKeyInput(Option<[u8; size_of::<u128>()]>),
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingConfirm,
+    #[cfg(feature = "legacy-pairing")]
+    LegacyWaitingRandom,
     WaitingLinkEncrypted,
-    // TODO: WaitingIdentitity is actually a subset of `ReceivingKeys(i32)`,
-    // they can be removed after implementing the full receiving keys procedure.
+    // Keys distributed by the initiator
     WaitingIdentitityInformation,
     WaitingIdentitityAddressInformation,
-    SendingKeys(i32),
-    ReceivingKeys(i32),
+    WaitingSigningInformation,
     Success,
     Error(Error),
 }
@@ -85,6 +98,8 @@ pub struct Pairing {
 struct PairingData {
     local_address: Address,
     peer_address: Address,
+    // Identity address distributed by the peer, the link keeps using `peer_address`
+    peer_identity_address: Option<Address>,
     peer_features: PairingFeatures,
     local_features: PairingFeatures,
     pairing_method: PairingMethod,
@@ -99,6 +114,8 @@ struct PairingData {
     peer_nonce: Nonce,
     mac_key: Option<MacKey>,
     long_term_key: LongTermKey,
+    #[cfg(feature = "legacy-pairing")]
+    temporary_key: TemporaryKey,
     timeout_at: Instant,
     bond_information: Option<BondInformation>,
 }
@@ -108,6 +125,12 @@ impl PairingData {
         matches!(self.local_features.security_properties.bond(), BondingFlag::Bonding)
             && matches!(self.peer_features.security_properties.bond(), BondingFlag::Bonding)
     }
+
+    /// LE legacy pairing is used unless both devices support LE Secure Connections
+    fn legacy(&self) -> bool {
+        !(self.local_features.security_properties.secure_connection()
+            && self.peer_features.security_properties.secure_connection())
+    }
 }
 
 impl Pairing {
@@ -132,6 +155,10 @@ impl Pairing {
         }
         *current_step = Step::Error(Error::Timeout);
     }
+    pub fn is_finished(&self) -> bool {
+        matches!(self.current_step.borrow().deref(), Step::Success | Step::Error(_))
+    }
+
     pub fn peer_address(&self) -> Address {
         self.pairing_data.borrow().peer_address
     }
@@ -141,6 +168,7 @@ impl Pairing {
             pairing_data: RefCell::new(PairingData {
                 local_address,
                 peer_address,
+                peer_identity_address: None,
                 local_features: PairingFeatures {
                     io_capabilities: local_io,
                     ..Default::default()
@@ -158,6 +186,8 @@ impl Pairing {
                 peer_nonce: Nonce(0),
                 mac_key: None,
                 long_term_key: LongTermKey(0),
+                #[cfg(feature = "legacy-pairing")]
+                temporary_key: TemporaryKey(0),
                 timeout_at: Instant::now() + crate::security_manager::constants::TIMEOUT,
                 bond_information: None,
             }),
@@ -209,23 +239,12 @@ impl Pairing {
                 if res {
                     info!("Link encrypted!");
                     if matches!(x.0, Step::WaitingLinkEncrypted) {
-                        // TODO send key data
+                        Self::send_keys(self.pairing_data.borrow_mut().deref_mut(), ops, rng)?;
                     } else {
                         self.pairing_data.borrow_mut().bond_information = ops.try_enable_bonded_encryption()?;
                     }
 
-                    if self
-                        .pairing_data
-                        .borrow()
-                        .peer_features
-                        .initiator_key_distribution
-                        .identity_key()
-                    {
-                        // Remote will share identity key
-                        Step::WaitingIdentitityInformation
-                    } else {
-                        Step::Success
-                    }
+                    Self::next_key_step(&self.pairing_data.borrow(), Step::WaitingIdentitityInformation)
                 } else {
                     error!("Failed to enable encryption!");
                     Step::Error(Error::Security(Reason::KeyRejected))
@@ -251,6 +270,15 @@ impl Pairing {
                     None => Step::WaitingPassKeyEntryConfirm(0),
                 }
             }
+            #[cfg(feature = "legacy-pairing")]
+            (Step::LegacyWaitingPassKeyInput(confirm), Event::PassKeyInput(input)) => {
+                let mut pairing_data = self.pairing_data.borrow_mut();
+                pairing_data.temporary_key = TemporaryKey(input as u128);
+                match confirm {
+                    Some(payload) => Self::handle_legacy_confirm(&payload, ops, pairing_data.deref_mut(), rng)?,
+                    None => Step::LegacyWaitingConfirm,
+                }
+            }
             (x, Event::PassKeyConfirm | Event::PassKeyCancel | Event::PassKeyInput(_)) => x,
             _ => Step::Error(Error::InvalidState),
         };
@@ -269,6 +297,7 @@ impl Pairing {
                     if let Some(bond) = pairing_data.bond_information.as_ref() {
                         debug!("bond info: {:?}", bond);
                         let pairing_bond = if pairing_data.want_bonding() {
+                            ops.try_update_bond_information(bond)?;
                             Some(bond.clone())
                         } else {
                             None
@@ -290,8 +319,7 @@ impl Pairing {
         match step.deref() {
             Step::WaitingIdentitityInformation
             | Step::WaitingIdentitityAddressInformation
-            | Step::SendingKeys(_)
-            | Step::ReceivingKeys(_)
+            | Step::WaitingSigningInformation
             | Step::Success => self
                 .pairing_data
                 .borrow()
@@ -318,14 +346,20 @@ impl Pairing {
                 (Step::WaitingPairingRequest, Command::PairingRequest) => {
                     Self::handle_pairing_request(command.payload, ops, pairing_data)?;
                     Self::send_pairing_response(ops, pairing_data)?;
-                    Step::WaitingPublicKey
+                    Self::start_pairing(ops, pairing_data, rng)?
                 }
                 (Step::WaitingPublicKey, Command::PairingPublicKey) => {
                     Self::handle_public_key(command.payload, pairing_data);
-                    Self::generate_private_public_key_pair(pairing_data, rng)?;
+                    Self::prepare_local_key_pair(ops, pairing_data, rng)?;
                     Self::send_public_key(ops, pairing_data.local_public_key.as_ref().unwrap())?;
                     match pairing_data.pairing_method {
-                        PairingMethod::OutOfBand => todo!("OOB not implemented"),
+                        PairingMethod::OutOfBand => {
+                            pairing_data.peer_secret_ra = check_peer_oob_data(
+                                ops.peer_oob_data(),
+                                pairing_data.peer_public_key.as_ref().ok_or(Error::InvalidValue)?,
+                            )?;
+                            Step::WaitingOobRandom
+                        }
                         PairingMethod::PassKeyEntry { peripheral, .. } => {
                             if peripheral == PassKeyEntryAction::Display {
                                 pairing_data.local_secret_rb =
@@ -370,10 +404,31 @@ impl Pairing {
                     Self::handle_pass_key_random(round, command.payload, ops, pairing_data)?
                 }
 
+                (Step::WaitingOobRandom, Command::PairingRandom) => {
+                    Self::handle_numeric_compare_random(command.payload, pairing_data)?;
+                    pairing_data.local_nonce = Nonce::new(rng);
+                    Self::send_nonce(ops, &pairing_data.local_nonce)?;
+                    Step::WaitingDHKeyEa
+                }
                 (Step::WaitingDHKeyEa, Command::PairingDhKeyCheck) => {
                     Self::handle_dhkey_ea(command.payload, ops, pairing_data)?
                 }
 
+                #[cfg(feature = "legacy-pairing")]
+                (Step::LegacyWaitingPassKeyInput(_), Command::PairingConfirm) => {
+                    let confirm: [u8; size_of::<u128>()] =
+                        command.payload.try_into().map_err(|_| Error::InvalidValue)?;
+                    Step::LegacyWaitingPassKeyInput(Some(confirm))
+                }
+                #[cfg(feature = "legacy-pairing")]
+                (Step::LegacyWaitingConfirm, Command::PairingConfirm) => {
+                    Self::handle_legacy_confirm(command.payload, ops, pairing_data, rng)?
+                }
+                #[cfg(feature = "legacy-pairing")]
+                (Step::LegacyWaitingRandom, Command::PairingRandom) => {
+                    Self::handle_legacy_random(command.payload, ops, pairing_data)?
+                }
+
                 (x, Command::KeypressNotification) => x,
 
                 (Step::WaitingIdentitityInformation, Command::IdentityInformation) => {
@@ -384,6 +439,10 @@ impl Pairing {
                     Self::handle_identity_address_information(command.payload, pairing_data)?
                 }
 
+                (Step::WaitingSigningInformation, Command::SigningInformation) => {
+                    Self::handle_signing_information(command.payload, pairing_data)?
+                }
+
                 _ => return Err(Error::InvalidState),
             }
         };
@@ -428,7 +487,7 @@ impl Pairing {
         if peer_features.maximum_encryption_key_size < ENCRYPTION_KEY_SIZE_128_BITS {
             return Err(Error::Security(Reason::EncryptionKeySize));
         }
-        if !peer_features.security_properties.secure_connection() {
+        if !peer_features.security_properties.secure_connection() && !cfg!(feature = "legacy-pairing") {
             return Err(Error::Security(Reason::UnspecifiedReason));
         }
 
@@ -438,14 +497,140 @@ impl Pairing {
                 .initiator_key_distribution
                 .set_identity_key();
         }
+        // A signing key is only useful if it is kept
+        if peer_features.initiator_key_distribution.signing_key() && matches!(ops.bonding_flag(), BondingFlag::Bonding)
+        {
+            pairing_data.local_features.initiator_key_distribution.set_signing_key();
+        }
+        if peer_features.responder_key_distribution.identity_key() && ops.local_identity().is_some() {
+            pairing_data
+                .local_features
+                .responder_key_distribution
+                .set_identity_key();
+        }
+        if peer_features.responder_key_distribution.signing_key() && matches!(ops.bonding_flag(), BondingFlag::Bonding)
+        {
+            pairing_data.local_features.responder_key_distribution.set_signing_key();
+        }
+
+        if ops.peer_oob_data().is_some() {
+            pairing_data.local_features.use_oob = UseOutOfBand::Present;
+        }
 
         pairing_data.peer_features = peer_features;
         pairing_data.local_features.security_properties = AuthReq::new(ops.bonding_flag());
         pairing_data.pairing_method = choose_pairing_method(pairing_data.peer_features, pairing_data.local_features);
+        #[cfg(feature = "legacy-pairing")]
+        if pairing_data.legacy() {
+            pairing_data.pairing_method =
+                choose_legacy_pairing_method(pairing_data.peer_features, pairing_data.local_features);
+            // The encryption key is only distributed by LE legacy pairing
+            if peer_features.responder_key_distribution.encryption_key()
+                && matches!(ops.bonding_flag(), BondingFlag::Bonding)
+            {
+                pairing_data
+                    .local_features
+                    .responder_key_distribution
+                    .set_encryption_key();
+            }
+        }
         info!("[smp] Pairing method {:?}", pairing_data.pairing_method);
         Ok(())
     }
 
+    fn start_pairing<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        #[cfg(feature = "legacy-pairing")]
+        if pairing_data.legacy() {
+            return Self::start_legacy_pairing(ops, pairing_data, rng);
+        }
+        Ok(Step::WaitingPublicKey)
+    }
+
+    /// Choose the temporary key of LE legacy pairing ([Vol 3] Part H, Section 2.3.5.5).
+    #[cfg(feature = "legacy-pairing")]
+    fn start_legacy_pairing<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        match pairing_data.pairing_method {
+            PairingMethod::JustWorks => {
+                pairing_data.temporary_key = TemporaryKey(0);
+                Ok(Step::LegacyWaitingConfirm)
+            }
+            PairingMethod::PassKeyEntry {
+                peripheral: PassKeyEntryAction::Display,
+                ..
+            } => {
+                let pass_key: u32 = rng.sample(rand::distributions::Uniform::new_inclusive(0, 999999));
+                pairing_data.temporary_key = TemporaryKey(pass_key as u128);
+                ops.try_send_connection_event(ConnectionEvent::PassKeyDisplay(PassKey(pass_key)))?;
+                Ok(Step::LegacyWaitingConfirm)
+            }
+            PairingMethod::PassKeyEntry { .. } => {
+                ops.try_send_connection_event(ConnectionEvent::PassKeyInput)?;
+                Ok(Step::LegacyWaitingPassKeyInput(None))
+            }
+            _ => Err(Error::Security(Reason::OobNotAvailable)),
+        }
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn legacy_confirm(pairing_data: &PairingData, r: &Nonce) -> Result<Confirm, Error> {
+        let preq = pairing_features_command(Command::PairingRequest, &pairing_data.peer_features)?;
+        let pres = pairing_features_command(Command::PairingResponse, &pairing_data.local_features)?;
+        Ok(pairing_data
+            .temporary_key
+            .c1(r, &preq, &pres, pairing_data.peer_address, pairing_data.local_address))
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn handle_legacy_confirm<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        payload: &[u8],
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+        rng: &mut RNG,
+    ) -> Result<Step, Error> {
+        pairing_data.confirm = Confirm(u128::from_le_bytes(
+            payload.try_into().map_err(|_| Error::InvalidValue)?,
+        ));
+        pairing_data.local_nonce = Nonce::new(rng);
+        let confirm = Self::legacy_confirm(pairing_data, &pairing_data.local_nonce)?;
+        ops.try_send_packet(make_confirm_packet(&confirm)?)?;
+        Ok(Step::LegacyWaitingRandom)
+    }
+
+    #[cfg(feature = "legacy-pairing")]
+    fn handle_legacy_random<P: PacketPool, OPS: PairingOps<P>>(
+        payload: &[u8],
+        ops: &mut OPS,
+        pairing_data: &mut PairingData,
+    ) -> Result<Step, Error> {
+        let peer_nonce = Nonce(u128::from_le_bytes(
+            payload.try_into().map_err(|_| Error::InvalidValue)?,
+        ));
+        if Self::legacy_confirm(pairing_data, &peer_nonce)? != pairing_data.confirm {
+            return Err(Error::Security(Reason::ConfirmValueFailed));
+        }
+        pairing_data.peer_nonce = peer_nonce;
+        Self::send_nonce(ops, &pairing_data.local_nonce)?;
+
+        pairing_data.long_term_key = pairing_data
+            .temporary_key
+            .s1(&pairing_data.local_nonce, &pairing_data.peer_nonce);
+        let bond = ops.try_enable_encryption(
+            &pairing_data.long_term_key,
+            pairing_data.pairing_method.security_level(),
+            pairing_data.want_bonding(),
+        )?;
+        pairing_data.bond_information = Some(bond);
+        Ok(Step::WaitingLinkEncrypted)
+    }
+
     fn send_pairing_response<P: PacketPool, OPS: PairingOps<P>>(
         ops: &mut OPS,
         pairing_data: &mut PairingData,
@@ -482,41 +667,104 @@ impl Pairing {
     }
 
     fn handle_identity_address_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<Step, Error> {
-        let addr_type = payload[0];
-        let kind = if addr_type == 0 {
-            AddrKind::PUBLIC
-        } else if addr_type == 1 {
-            AddrKind::RANDOM
-        } else {
-            // Impossible
-            error!("[security manager] Invalid address type: {:?}", addr_type);
-            return Err(Error::InvalidValue);
-        };
-        let addr = BdAddr::new(payload[1..7].try_into().map_err(|_| Error::InvalidValue)?);
-        pairing_data.peer_address = Address { kind, addr };
+        let address = parse_identity_address(payload)?;
+        pairing_data.peer_identity_address = Some(address);
 
         if let Some(ref mut bond) = &mut pairing_data.bond_information {
-            bond.identity.bd_addr = addr;
+            bond.identity.bd_addr = address.addr;
+            bond.identity_kind = address.kind;
         }
 
-        trace!(
-            "Identity address information: addr_type: {:?}, addr: {:?}",
-            addr_type,
-            addr
-        );
+        trace!("Identity address information: {:?}", address);
+        Ok(Self::next_key_step(pairing_data, Step::WaitingSigningInformation))
+    }
+
+    fn handle_signing_information(payload: &[u8], pairing_data: &mut PairingData) -> Result<Step, Error> {
+        let csrk = ConnectionSignatureResolvingKey::from_le_bytes(payload.try_into().map_err(|_| Error::InvalidValue)?);
+        if let Some(ref mut bond) = &mut pairing_data.bond_information {
+            bond.peer_csrk = Some(SigningKey::new(csrk));
+        }
+
+        trace!("Signing information: CSRK: {:?}", csrk);
         Ok(Step::Success)
     }
 
+    /// Distribute the keys agreed in the pairing response, ahead of the initiator ([Vol 3] Part H, Section 3.6.1).
+    fn send_keys<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        pairing_data: &mut PairingData,
+        ops: &mut OPS,
+        rng: &mut RNG,
+    ) -> Result<(), Error> {
+        let keys = pairing_data.local_features.responder_key_distribution;
+        if keys.encryption_key() {
+            let mut ltk = [0; 16];
+            rng.fill_bytes(&mut ltk);
+            let ltk = LongTermKey::from_le_bytes(ltk);
+            let ediv: u16 = rng.gen();
+            let mut rand = [0; 8];
+            rng.fill_bytes(&mut rand);
+            ops.try_send_packet(make_encryption_information_packet(&ltk)?)?;
+            ops.try_send_packet(make_central_identification_packet(ediv, &rand)?)?;
+            if let Some(bond) = pairing_data.bond_information.as_mut() {
+                bond.ltk = ltk;
+                bond.ediv = ediv;
+                bond.rand = rand;
+            }
+        }
+        if keys.identity_key() {
+            let (irk, address) = ops.local_identity().ok_or(Error::InvalidState)?;
+            ops.try_send_packet(make_identity_information_packet(&irk)?)?;
+            ops.try_send_packet(make_identity_address_information_packet(&address)?)?;
+        }
+        if keys.signing_key() {
+            let mut csrk = [0; 16];
+            rng.fill_bytes(&mut csrk);
+            let csrk = ConnectionSignatureResolvingKey::from_le_bytes(csrk);
+            ops.try_send_packet(make_signing_information_packet(&csrk)?)?;
+            if let Some(bond) = pairing_data.bond_information.as_mut() {
+                bond.local_csrk = Some(SigningKey::new(csrk));
+            }
+        }
+        Ok(())
+    }
+
+    /// The step waiting for the next key distributed by the initiator, starting from `from` ([Vol 3] Part H, Section 3.6.1).
+    fn next_key_step(pairing_data: &PairingData, from: Step) -> Step {
+        // Keys negotiated in the pairing response
+        let keys = pairing_data.local_features.initiator_key_distribution;
+        match from {
+            Step::WaitingIdentitityInformation if keys.identity_key() => Step::WaitingIdentitityInformation,
+            Step::WaitingIdentitityInformation | Step::WaitingSigningInformation if keys.signing_key() => {
+                Step::WaitingSigningInformation
+            }
+            _ => Step::Success,
+        }
+    }
+
     fn handle_public_key(payload: &[u8], pairing_data: &mut PairingData) {
         let peer_public_key = PublicKey::from_bytes(payload);
         pairing_data.peer_public_key = Some(peer_public_key);
     }
 
-    fn generate_private_public_key_pair<RNG: CryptoRng + RngCore>(
+    /// Generate the local key pair, or take the one of the local out-of-band data if the peer has received it
+    /// ([Vol 3] Part H, Section 2.3.5.6.4).
+    fn prepare_local_key_pair<P: PacketPool, OPS: PairingOps<P>, RNG: CryptoRng + RngCore>(
+        ops: &mut OPS,
         pairing_data: &mut PairingData,
         rng: &mut RNG,
     ) -> Result<(), Error> {
-        let secret_key = SecretKey::new(rng);
+        if pairing_data.pairing_method == PairingMethod::OutOfBand
+            && matches!(pairing_data.peer_features.use_oob, UseOutOfBand::Present)
+        {
+            let (secret_key, random) = ops.local_oob_data().ok_or(Error::Security(Reason::OobNotAvailable))?;
+            pairing_data.local_secret_rb = random.0;
+            Self::use_private_key(pairing_data, secret_key)
+        } else {
+            Self::use_private_key(pairing_data, SecretKey::new(rng))
+        }
+    }
+
+    fn use_private_key(pairing_data: &mut PairingData, secret_key: SecretKey) -> Result<(), Error> {
         let public_key = secret_key.public_key();
         let peer_public_key = pairing_data
             .peer_public_key
@@ -1003,11 +1251,9 @@ mod tests {
             assert_eq!(stored_irk.0, u128::from_le_bytes(irk_data));
 
             assert_eq!(bond.identity.bd_addr, BdAddr::new([0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC]));
-            assert_eq!(pairing_data.peer_address.kind, AddrKind::PUBLIC);
-            assert_eq!(
-                pairing_data.peer_address.addr,
-                BdAddr::new([0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC])
-            );
+            let identity_address = pairing_data.peer_identity_address.unwrap();
+            assert_eq!(identity_address.kind, AddrKind::PUBLIC);
+            assert_eq!(identity_address.addr, BdAddr::new([0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC]));
         }
 
         // Verify completeness
@@ -1023,4 +1269,86 @@ mod tests {
             _ => panic!("Unexpected connection event"),
         }
     }
+
+    #[cfg(feature = "legacy-pairing")]
+    #[test]
+    fn legacy_just_works_with_encryption_key_distribution() {
+        use crate::security_manager::crypto::{Confirm, TemporaryKey};
+
+        let mut pairing_ops: TestOps<10> = TestOps {
+            bondable: true,
+            ..Default::default()
+        };
+        let local = Address::random([1, 2, 3, 4, 5, 6]);
+        let peer = Address::random([7, 8, 9, 10, 11, 12]);
+        let pairing = Pairing::new(local, peer, IoCapabilities::NoInputNoOutput);
+        let mut rng: ChaCha12Rng = ChaCha12Core::seed_from_u64(1).into();
+
+        // Legacy central requests bonding and the peripheral encryption key, without Secure Connections
+        let request = [0x03, 0x00, 0x01, 0x10, 0x00, 0x01];
+        pairing
+            .handle_l2cap_command::<HeaplessPool, _, _>(Command::PairingRequest, &request, &mut pairing_ops, &mut rng)
+            .unwrap();
+        let mut preq = [0x01; 7];
+        preq[1..].copy_from_slice(&request);
+        let mut pres = [0x02; 7];
+        pres[1..].copy_from_slice(pairing_ops.sent_packets[0].payload());
+        assert_eq!(pres[6], 0x01);
+
+        // Central sends Mconfirm, expects Sconfirm
+        let tk = TemporaryKey(0);
+        let mrand = Nonce(0x5783D52156AD6F0E6388274EC6702EE0);
+        let mconfirm = tk.c1(&mrand, &preq, &pres, peer, local);
+        pairing
+            .handle_l2cap_command::<HeaplessPool, _, _>(
+                Command::PairingConfirm,
+                &mconfirm.0.to_le_bytes(),
+                &mut pairing_ops,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(pairing_ops.sent_packets[1].command, Command::PairingConfirm);
+
+        // Central sends Mrand, expects Srand matching Sconfirm and encryption with the STK
+        pairing
+            .handle_l2cap_command::<HeaplessPool, _, _>(
+                Command::PairingRandom,
+                &mrand.0.to_le_bytes(),
+                &mut pairing_ops,
+                &mut rng,
+            )
+            .unwrap();
+        assert_eq!(pairing_ops.sent_packets[2].command, Command::PairingRandom);
+        let srand = Nonce(u128::from_le_bytes(
+            pairing_ops.sent_packets[2].payload().try_into().unwrap(),
+        ));
+        let sconfirm = Confirm(u128::from_le_bytes(
+            pairing_ops.sent_packets[1].payload().try_into().unwrap(),
+        ));
+        assert_eq!(tk.c1(&srand, &preq, &pres, peer, local), sconfirm);
+        assert_eq!(pairing_ops.encryptions[0], tk.s1(&srand, &mrand));
+
+        // Peripheral distributes LTK, EDIV and Rand
+        pairing
+            .handle_event(Event::LinkEncryptedResult(true), &mut pairing_ops, &mut rng)
+            .unwrap();
+        assert_eq!(pairing_ops.sent_packets[3].command, Command::EncryptionInformation);
+        assert_eq!(pairing_ops.sent_packets[4].command, Command::CentralIdentification);
+        let ltk = LongTermKey::from_le_bytes(pairing_ops.sent_packets[3].payload().try_into().unwrap());
+        let identification = pairing_ops.sent_packets[4].payload();
+        assert!(matches!(pairing.current_step.borrow().deref(), Step::Success));
+
+        match &pairing_ops.connection_events[0] {
+            ConnectionEvent::PairingComplete {
+                security_level,
+                bond: Some(bond),
+            } => {
+                assert_eq!(*security_level, SecurityLevel::Encrypted);
+                assert_eq!(bond.ltk, ltk);
+                assert_eq!(bond.ediv.to_le_bytes(), identification[0..2]);
+                assert_eq!(bond.rand, identification[2..10]);
+            }
+            _ => panic!("Unexpected connection event"),
+        }
+    }
 }
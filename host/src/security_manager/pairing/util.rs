@@ -1,9 +1,15 @@
+use bt_hci::param::{AddrKind, BdAddr};
+
+#[cfg(feature = "legacy-pairing")]
+use crate::codec::Encode;
 use crate::pdu::Pdu;
 use crate::prelude::SecurityLevel;
-use crate::security_manager::crypto::{Check, Confirm, DHKey, MacKey, Nonce, PublicKey};
+use crate::security_manager::crypto::{
+    Check, Confirm, ConnectionSignatureResolvingKey, DHKey, MacKey, Nonce, PublicKey,
+};
 use crate::security_manager::types::{Command, PairingFeatures, UseOutOfBand};
-use crate::security_manager::{Reason, TxPacket};
-use crate::{Address, Error, IoCapabilities, LongTermKey, PacketPool};
+use crate::security_manager::{OobData, Reason, TxPacket};
+use crate::{Address, Error, IdentityResolvingKey, IoCapabilities, LongTermKey, PacketPool};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -33,11 +39,13 @@ impl PairingMethod {
     }
 }
 
+/// Choose the LE Secure Connections pairing method, where out-of-band data on either side takes precedence
+/// ([Vol 3] Part H, Section 2.3.5.1).
 pub fn choose_pairing_method(central: PairingFeatures, peripheral: PairingFeatures) -> PairingMethod {
-    if !central.security_properties.man_in_the_middle() && !peripheral.security_properties.man_in_the_middle() {
-        PairingMethod::JustWorks
-    } else if matches!(central.use_oob, UseOutOfBand::Present) || matches!(peripheral.use_oob, UseOutOfBand::Present) {
+    if matches!(central.use_oob, UseOutOfBand::Present) || matches!(peripheral.use_oob, UseOutOfBand::Present) {
         PairingMethod::OutOfBand
+    } else if !central.security_properties.man_in_the_middle() && !peripheral.security_properties.man_in_the_middle() {
+        PairingMethod::JustWorks
     } else if peripheral.io_capabilities == IoCapabilities::DisplayOnly {
         match central.io_capabilities {
             IoCapabilities::KeyboardOnly | IoCapabilities::KeyboardDisplay => PairingMethod::PassKeyEntry {
@@ -86,6 +94,53 @@ pub fn choose_pairing_method(central: PairingFeatures, peripheral: PairingFeatur
     }
 }
 
+/// LE legacy pairing has no numeric comparison, which falls back to Just Works or Passkey Entry
+/// ([Vol 3] Part H, Section 2.3.5.1). Out-of-band data is only supported by LE Secure Connections and is ignored.
+#[cfg(feature = "legacy-pairing")]
+pub fn choose_legacy_pairing_method(mut central: PairingFeatures, mut peripheral: PairingFeatures) -> PairingMethod {
+    central.use_oob = UseOutOfBand::NotPresent;
+    peripheral.use_oob = UseOutOfBand::NotPresent;
+    match choose_pairing_method(central, peripheral) {
+        PairingMethod::NumericComparison => match (central.io_capabilities, peripheral.io_capabilities) {
+            (IoCapabilities::KeyboardDisplay, IoCapabilities::DisplayYesNo)
+            | (IoCapabilities::KeyboardOnly, IoCapabilities::KeyboardDisplay) => PairingMethod::PassKeyEntry {
+                central: PassKeyEntryAction::Input,
+                peripheral: PassKeyEntryAction::Display,
+            },
+            (IoCapabilities::DisplayYesNo, IoCapabilities::KeyboardDisplay) => PairingMethod::PassKeyEntry {
+                central: PassKeyEntryAction::Display,
+                peripheral: PassKeyEntryAction::Input,
+            },
+            _ => PairingMethod::JustWorks,
+        },
+        method => method,
+    }
+}
+
+/// Pairing request or pairing response command, as used by the LE legacy pairing confirm value.
+#[cfg(feature = "legacy-pairing")]
+pub fn pairing_features_command(command: Command, features: &PairingFeatures) -> Result<[u8; 7], Error> {
+    let mut bytes = [0; 7];
+    bytes[0] = command.into();
+    features.encode(&mut bytes[1..]).map_err(|_| Error::InvalidValue)?;
+    Ok(bytes)
+}
+
+/// Check the out-of-band data received from the peer against the public key it sent, returning the peer random value
+/// used by the DHKey check. The random value is zero without out-of-band data ([Vol 3] Part H, Section 2.3.5.6.4).
+pub fn check_peer_oob_data(peer_oob: Option<OobData>, peer_public_key: &PublicKey) -> Result<u128, Error> {
+    match peer_oob {
+        Some(oob) => {
+            let confirm = Nonce(oob.random).f4(peer_public_key.x(), peer_public_key.x(), 0);
+            if confirm != Confirm(oob.confirm) {
+                return Err(Error::Security(Reason::ConfirmValueFailed));
+            }
+            Ok(oob.random)
+        }
+        None => Ok(0),
+    }
+}
+
 pub fn prepare_packet<P: PacketPool>(command: Command) -> Result<TxPacket<P>, Error> {
     let packet = P::allocate().ok_or(Error::OutOfMemory)?;
     TxPacket::new(packet, command)
@@ -98,6 +153,66 @@ pub fn make_pairing_random<P: PacketPool>(nonce: &Nonce) -> Result<TxPacket<P>,
     Ok(packet)
 }
 
+pub fn make_signing_information_packet<P: PacketPool>(
+    csrk: &ConnectionSignatureResolvingKey,
+) -> Result<TxPacket<P>, Error> {
+    let mut packet = prepare_packet::<P>(Command::SigningInformation)?;
+    let response = packet.payload_mut();
+    response.copy_from_slice(&csrk.to_le_bytes());
+    Ok(packet)
+}
+
+pub fn make_encryption_information_packet<P: PacketPool>(ltk: &LongTermKey) -> Result<TxPacket<P>, Error> {
+    let mut packet = prepare_packet::<P>(Command::EncryptionInformation)?;
+    let response = packet.payload_mut();
+    response.copy_from_slice(&ltk.to_le_bytes());
+    Ok(packet)
+}
+
+pub fn make_central_identification_packet<P: PacketPool>(ediv: u16, rand: &[u8; 8]) -> Result<TxPacket<P>, Error> {
+    let mut packet = prepare_packet::<P>(Command::CentralIdentification)?;
+    let response = packet.payload_mut();
+    response[0..2].copy_from_slice(&ediv.to_le_bytes());
+    response[2..10].copy_from_slice(rand);
+    Ok(packet)
+}
+
+pub fn make_identity_information_packet<P: PacketPool>(irk: &IdentityResolvingKey) -> Result<TxPacket<P>, Error> {
+    let mut packet = prepare_packet::<P>(Command::IdentityInformation)?;
+    let response = packet.payload_mut();
+    response.copy_from_slice(&irk.to_le_bytes());
+    Ok(packet)
+}
+
+pub fn make_identity_address_information_packet<P: PacketPool>(address: &Address) -> Result<TxPacket<P>, Error> {
+    let mut packet = prepare_packet::<P>(Command::IdentityAddressInformation)?;
+    let response = packet.payload_mut();
+    response[0] = if address.kind == AddrKind::PUBLIC { 0 } else { 1 };
+    response[1..7].copy_from_slice(address.addr.raw());
+    Ok(packet)
+}
+
+/// Parse the identity address distributed in an identity address information command.
+pub fn parse_identity_address(payload: &[u8]) -> Result<Address, Error> {
+    let addr_type = *payload.first().ok_or(Error::InvalidValue)?;
+    let kind = match addr_type {
+        0 => AddrKind::PUBLIC,
+        1 => AddrKind::RANDOM,
+        _ => {
+            error!("[security manager] Invalid address type: {:?}", addr_type);
+            return Err(Error::InvalidValue);
+        }
+    };
+    let addr = BdAddr::new(
+        payload
+            .get(1..7)
+            .ok_or(Error::InvalidValue)?
+            .try_into()
+            .map_err(|_| Error::InvalidValue)?,
+    );
+    Ok(Address { kind, addr })
+}
+
 pub fn make_public_key_packet<P: PacketPool>(public_key: &PublicKey) -> Result<TxPacket<P>, Error> {
     let mut x = [0u8; 32];
     let mut y = [0u8; 32];
@@ -181,8 +296,8 @@ mod tests {
 
     #[test]
     fn oob_used() {
-        for p_oob in 0..1 {
-            for c_oob in 0..1 {
+        for p_oob in 0..2 {
+            for c_oob in 0..2 {
                 let p_oob = if p_oob == 1 {
                     UseOutOfBand::Present
                 } else {
@@ -216,4 +331,22 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn peer_oob_data_checked() {
+        let mut rng: rand_chacha::ChaCha12Rng = rand_core::SeedableRng::seed_from_u64(1);
+        let public_key = crate::security_manager::crypto::SecretKey::new(&mut rng).public_key();
+        let random = Nonce::new(&mut rng);
+        let mut oob = OobData {
+            random: random.0,
+            confirm: random.f4(public_key.x(), public_key.x(), 0).0,
+        };
+        assert_eq!(check_peer_oob_data(None, &public_key), Ok(0));
+        assert_eq!(check_peer_oob_data(Some(oob), &public_key), Ok(random.0));
+        oob.confirm ^= 1;
+        assert_eq!(
+            check_peer_oob_data(Some(oob), &public_key),
+            Err(Error::Security(Reason::ConfirmValueFailed))
+        );
+    }
 }
@@ -1,4 +1,5 @@
-use bt_hci::{FixedSizeValue, WriteHci};
+use bt_hci::{FixedSizeValue, FromHciBytes, FromHciBytesError, WriteHci};
+use heapless::Vec;
 
 use crate::codec::Error;
 
@@ -32,12 +33,12 @@ pub struct L2capSignalHeader {
 
 unsafe impl FixedSizeValue for L2capSignalHeader {
     fn is_valid(data: &[u8]) -> bool {
-        true
+        L2capSignalCode::try_from(data[0]).is_ok()
     }
 }
 
 #[cfg(not(feature = "defmt"))]
-pub trait L2capSignal: WriteHci + FixedSizeValue + core::fmt::Debug {
+pub trait L2capSignal: WriteHci + core::fmt::Debug {
     fn channel() -> u16 {
         L2CAP_CID_LE_U_SIGNAL
     }
@@ -45,7 +46,7 @@ pub trait L2capSignal: WriteHci + FixedSizeValue + core::fmt::Debug {
 }
 
 #[cfg(feature = "defmt")]
-pub trait L2capSignal: WriteHci + FixedSizeValue + defmt::Format {
+pub trait L2capSignal: WriteHci + defmt::Format {
     fn channel() -> u16 {
         L2CAP_CID_LE_U_SIGNAL
     }
@@ -54,6 +55,7 @@ pub trait L2capSignal: WriteHci + FixedSizeValue + defmt::Format {
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
 pub enum L2capSignalCode {
     CommandRejectRes = 0x01,
     ConnectionReq = 0x02,
@@ -106,6 +108,41 @@ impl TryFrom<u8> for L2capSignalCode {
     }
 }
 
+impl L2capSignalCode {
+    /// Whether the signal is a response to a request, which must never be rejected.
+    pub fn is_response(&self) -> bool {
+        matches!(
+            self,
+            Self::CommandRejectRes
+                | Self::ConnectionRes
+                | Self::ConfigurationRes
+                | Self::DisconnectionRes
+                | Self::EchoRes
+                | Self::InformationRes
+                | Self::ConnParamUpdateRes
+                | Self::LeCreditConnRes
+                | Self::CreditConnRes
+                | Self::CreditConnReconfigRes
+        )
+    }
+
+    /// The request answered by this response, if any.
+    pub fn request(&self) -> Option<Self> {
+        Some(match self {
+            Self::ConnectionRes => Self::ConnectionReq,
+            Self::ConfigurationRes => Self::ConfigurationReq,
+            Self::DisconnectionRes => Self::DisconnectionReq,
+            Self::EchoRes => Self::EchoReq,
+            Self::InformationRes => Self::InformationReq,
+            Self::ConnParamUpdateRes => Self::ConnParamUpdateReq,
+            Self::LeCreditConnRes => Self::LeCreditConnReq,
+            Self::CreditConnRes => Self::CreditConnReq,
+            Self::CreditConnReconfigRes => Self::CreditConnReconfigReq,
+            _ => return None,
+        })
+    }
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -130,7 +167,7 @@ impl L2capSignal for LeCreditConnReq {
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[repr(u16)]
 pub enum LeCreditConnResultCode {
     Success = 0x0000,
@@ -143,6 +180,27 @@ pub enum LeCreditConnResultCode {
     InvalidSourceId = 0x0009,
     ScidAlreadyAllocated = 0x000A,
     UnacceptableParameters = 0x000B,
+    InvalidParameters = 0x000C,
+}
+
+impl TryFrom<u16> for LeCreditConnResultCode {
+    type Error = Error;
+    fn try_from(val: u16) -> Result<Self, Error> {
+        Ok(match val {
+            0x0000 => Self::Success,
+            0x0002 => Self::SpsmNotSupported,
+            0x0004 => Self::NoResources,
+            0x0005 => Self::InsufficientAuthentication,
+            0x0006 => Self::InsufficientAuthorization,
+            0x0007 => Self::EncryptionKeyTooShort,
+            0x0008 => Self::InsufficientEncryption,
+            0x0009 => Self::InvalidSourceId,
+            0x000A => Self::ScidAlreadyAllocated,
+            0x000B => Self::UnacceptableParameters,
+            0x000C => Self::InvalidParameters,
+            _ => return Err(Error::InvalidValue),
+        })
+    }
 }
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -188,17 +246,270 @@ impl L2capSignal for LeCreditFlowInd {
     }
 }
 
+/// Maximum number of channels that can be opened or reconfigured by a single enhanced credit based signal.
+pub(crate) const L2CAP_ECFC_MAX_CHANNELS: usize = 5;
+
+fn write_u16s<W: embedded_io::Write>(mut writer: W, values: &[u16]) -> Result<(), W::Error> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+async fn write_u16s_async<W: embedded_io_async::Write>(mut writer: W, values: &[u16]) -> Result<(), W::Error> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes()).await?;
+    }
+    Ok(())
+}
+
+fn read_u16s<const N: usize>(data: &[u8]) -> Result<Vec<u16, N>, FromHciBytesError> {
+    if !data.len().is_multiple_of(2) {
+        return Err(FromHciBytesError::InvalidSize);
+    }
+    let mut values = Vec::new();
+    for c in data.chunks_exact(2) {
+        values
+            .push(u16::from_le_bytes([c[0], c[1]]))
+            .map_err(|_| FromHciBytesError::InvalidSize)?;
+    }
+    Ok(values)
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct CreditConnReq {
+    pub spsm: u16,
+    pub mtu: u16,
+    pub mps: u16,
+    pub credits: u16,
+    pub scids: Vec<u16, L2CAP_ECFC_MAX_CHANNELS>,
+}
+
+impl WriteHci for CreditConnReq {
+    fn size(&self) -> usize {
+        8 + 2 * self.scids.len()
+    }
+
+    fn write_hci<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s(&mut writer, &[self.spsm, self.mtu, self.mps, self.credits])?;
+        write_u16s(&mut writer, &self.scids)
+    }
+
+    async fn write_hci_async<W: embedded_io_async::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s_async(&mut writer, &[self.spsm, self.mtu, self.mps, self.credits]).await?;
+        write_u16s_async(&mut writer, &self.scids).await
+    }
+}
+
+impl<'de> FromHciBytes<'de> for CreditConnReq {
+    fn from_hci_bytes(data: &'de [u8]) -> Result<(Self, &'de [u8]), FromHciBytesError> {
+        if data.len() < 10 {
+            return Err(FromHciBytesError::InvalidSize);
+        }
+        let (fixed, scids) = data.split_at(8);
+        let fixed = read_u16s::<4>(fixed)?;
+        Ok((
+            Self {
+                spsm: fixed[0],
+                mtu: fixed[1],
+                mps: fixed[2],
+                credits: fixed[3],
+                scids: read_u16s(scids)?,
+            },
+            &[],
+        ))
+    }
+}
+
+impl L2capSignal for CreditConnReq {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReq
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct CreditConnRes {
+    pub mtu: u16,
+    pub mps: u16,
+    pub credits: u16,
+    pub result: LeCreditConnResultCode,
+    pub dcids: Vec<u16, L2CAP_ECFC_MAX_CHANNELS>,
+}
+
+impl WriteHci for CreditConnRes {
+    fn size(&self) -> usize {
+        8 + 2 * self.dcids.len()
+    }
+
+    fn write_hci<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s(&mut writer, &[self.mtu, self.mps, self.credits, self.result as u16])?;
+        write_u16s(&mut writer, &self.dcids)
+    }
+
+    async fn write_hci_async<W: embedded_io_async::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s_async(&mut writer, &[self.mtu, self.mps, self.credits, self.result as u16]).await?;
+        write_u16s_async(&mut writer, &self.dcids).await
+    }
+}
+
+impl<'de> FromHciBytes<'de> for CreditConnRes {
+    fn from_hci_bytes(data: &'de [u8]) -> Result<(Self, &'de [u8]), FromHciBytesError> {
+        if data.len() < 10 {
+            return Err(FromHciBytesError::InvalidSize);
+        }
+        let (fixed, dcids) = data.split_at(8);
+        let fixed = read_u16s::<4>(fixed)?;
+        Ok((
+            Self {
+                mtu: fixed[0],
+                mps: fixed[1],
+                credits: fixed[2],
+                result: LeCreditConnResultCode::try_from(fixed[3]).map_err(|_| FromHciBytesError::InvalidValue)?,
+                dcids: read_u16s(dcids)?,
+            },
+            &[],
+        ))
+    }
+}
+
+impl L2capSignal for CreditConnRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnRes
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
+pub struct CreditConnReconfigReq {
+    pub mtu: u16,
+    pub mps: u16,
+    pub dcids: Vec<u16, L2CAP_ECFC_MAX_CHANNELS>,
+}
+
+impl WriteHci for CreditConnReconfigReq {
+    fn size(&self) -> usize {
+        4 + 2 * self.dcids.len()
+    }
+
+    fn write_hci<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s(&mut writer, &[self.mtu, self.mps])?;
+        write_u16s(&mut writer, &self.dcids)
+    }
+
+    async fn write_hci_async<W: embedded_io_async::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s_async(&mut writer, &[self.mtu, self.mps]).await?;
+        write_u16s_async(&mut writer, &self.dcids).await
+    }
+}
+
+impl<'de> FromHciBytes<'de> for CreditConnReconfigReq {
+    fn from_hci_bytes(data: &'de [u8]) -> Result<(Self, &'de [u8]), FromHciBytesError> {
+        if data.len() < 6 {
+            return Err(FromHciBytesError::InvalidSize);
+        }
+        let (fixed, dcids) = data.split_at(4);
+        let fixed = read_u16s::<2>(fixed)?;
+        Ok((
+            Self {
+                mtu: fixed[0],
+                mps: fixed[1],
+                dcids: read_u16s(dcids)?,
+            },
+            &[],
+        ))
+    }
+}
+
+impl L2capSignal for CreditConnReconfigReq {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReconfigReq
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum CreditConnReconfigResultCode {
+    Success = 0x0000,
+    MtuReductionNotAllowed = 0x0001,
+    MpsReductionNotAllowed = 0x0002,
+    InvalidDestinationCid = 0x0003,
+    UnacceptableParameters = 0x0004,
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
+pub struct CreditConnReconfigRes {
+    pub result: CreditConnReconfigResultCode,
+}
+
+unsafe impl FixedSizeValue for CreditConnReconfigRes {
+    fn is_valid(data: &[u8]) -> bool {
+        u16::from_le_bytes([data[0], data[1]]) <= CreditConnReconfigResultCode::UnacceptableParameters as u16
+    }
+}
+
+impl L2capSignal for CreditConnReconfigRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CreditConnReconfigRes
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum CommandRejectReason {
+    CommandNotUnderstood = 0x0000,
+    SignalingMtuExceeded = 0x0001,
+    InvalidCid = 0x0002,
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone)]
 pub struct CommandRejectRes {
     pub reason: u16,
-    // TODO: Optional fields pub data: u16,
+    /// Reason specific data: the actual signaling MTU, or the local and remote CID of the request.
+    pub data: Vec<u16, 2>,
 }
 
-unsafe impl FixedSizeValue for CommandRejectRes {
-    fn is_valid(data: &[u8]) -> bool {
-        true
+impl WriteHci for CommandRejectRes {
+    fn size(&self) -> usize {
+        2 + 2 * self.data.len()
+    }
+
+    fn write_hci<W: embedded_io::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s(&mut writer, &[self.reason])?;
+        write_u16s(&mut writer, &self.data)
+    }
+
+    async fn write_hci_async<W: embedded_io_async::Write>(&self, mut writer: W) -> Result<(), W::Error> {
+        write_u16s_async(&mut writer, &[self.reason]).await?;
+        write_u16s_async(&mut writer, &self.data).await
+    }
+}
+
+impl<'de> FromHciBytes<'de> for CommandRejectRes {
+    fn from_hci_bytes(data: &'de [u8]) -> Result<(Self, &'de [u8]), FromHciBytesError> {
+        if data.len() < 2 {
+            return Err(FromHciBytesError::InvalidSize);
+        }
+        let (reason, data) = data.split_at(2);
+        Ok((
+            Self {
+                reason: u16::from_le_bytes([reason[0], reason[1]]),
+                data: read_u16s(data)?,
+            },
+            &[],
+        ))
+    }
+}
+
+impl L2capSignal for CommandRejectRes {
+    fn code() -> L2capSignalCode {
+        L2capSignalCode::CommandRejectRes
     }
 }
 
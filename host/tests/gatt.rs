@@ -47,7 +47,7 @@ async fn gatt_client_server() {
         let mut storage: [u8; 1] = [0; 1];
         let mut expected = value.wrapping_add(1);
 
-        let mut table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
+        let table: AttributeTable<'_, NoopRawMutex, 10> = AttributeTable::new();
         let mut svc = table.add_service(Service::new(0x1800u16));
         let _ = svc.add_characteristic_ro(0x2a00u16, id);
         let _ = svc.add_characteristic_ro(0x2a01u16, &appearance);
@@ -175,6 +175,13 @@ async fn gatt_client_server() {
                         println!("[central] service discovered successfully");
                         let c: Characteristic<u8> = client.characteristic_by_uuid(&service, &VALUE_UUID).await.unwrap();
 
+                        println!("[central] discovering all services");
+                        let discovered = client.discover::<4, 2>().await.unwrap();
+                        assert_eq!(discovered.len(), 3);
+                        let custom = discovered.iter().find(|s| s.service.uuid() == &SERVICE_UUID).unwrap();
+                        let value = custom.characteristics.iter().find(|c| c.info.uuid == VALUE_UUID).unwrap();
+                        assert_eq!(value.characteristic::<u8>(), c);
+
                         let mut data = [0; 1];
                         client.read_characteristic(&c, &mut data[..]).await.unwrap();
                         println!("[central] read value: {}", data[0]);
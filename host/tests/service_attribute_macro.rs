@@ -11,12 +11,15 @@ struct CustomService {
     long_uuid: f32,
     #[characteristic(uuid = "2a38", read, notify)]
     notify: [u8; 8],
+    #[descriptor(uuid = "2901", read(encrypted), value = "Secured")]
+    #[characteristic(uuid = "2a39", read(encrypted), write(authenticated, authorized))]
+    secured: u8,
     non_characteristic_field: u8,
 }
 
 #[tokio::test]
 async fn gatt_service_derive() {
-    let mut table: AttributeTable<NoopRawMutex, 10> = AttributeTable::new();
+    let mut table: AttributeTable<NoopRawMutex, 16> = AttributeTable::new();
     let service = CustomService::new(&mut table);
 
     // Check all fields of service have been generated and are accessible
@@ -25,4 +28,72 @@ async fn gatt_service_derive() {
     let _characteristic_short_uuid = service.short_uuid;
     let _characteristic_long_uuid = service.long_uuid;
     let _notify = service.notify;
+    let _secured = service.secured;
+}
+
+#[gatt_service(uuid = "180f", secondary)]
+struct SecondaryService {
+    #[characteristic(uuid = "2a19", read)]
+    level: u8,
+}
+
+#[gatt_service(uuid = "1812")]
+struct IncludingService {
+    #[characteristic(uuid = "2a4d", read, notify)]
+    report: [u8; 8],
+}
+
+#[gatt_server(mutex_type = NoopRawMutex)]
+struct IncludingServer {
+    secondary: SecondaryService,
+    #[include(secondary)]
+    including: IncludingService,
+}
+
+#[tokio::test]
+async fn gatt_server_include() {
+    let server = IncludingServer::new_default("include").unwrap();
+
+    // The included service is added first, the include declaration follows the including service declaration
+    assert!(server.secondary.handle() < server.including.handle());
+    assert_eq!(server.secondary.level.handle, server.secondary.handle() + 2);
+    assert_eq!(server.including.report.handle, server.including.handle() + 3);
+}
+
+#[gatt_service(uuid = "181a")]
+struct DescribedService {
+    #[descriptor(uuid = descriptors::VALID_RANGE, name = "range", read, write(encrypted), capacity = 4, value = [0u8, 100])]
+    #[characteristic(
+        uuid = "2a6e",
+        read,
+        write,
+        reliable_write,
+        writable_auxiliaries,
+        description = "Temperature",
+        format = PresentationFormat::new(PresentationFormat::SINT16, -2, units::CELSIUS_TEMPERATURE_DEGREE_CELSIUS)
+    )]
+    temperature: i16,
+}
+
+#[tokio::test]
+async fn gatt_service_standard_descriptors() {
+    let table: AttributeTable<NoopRawMutex, 8> = AttributeTable::new();
+    let service = DescribedService::new(&table);
+    assert_eq!(DescribedService::ATTRIBUTE_COUNT, 7);
+
+    // Extended properties, user description and presentation format come before the descriptors declared with
+    // `#[descriptor]`
+    let value = service.temperature.handle;
+    assert_eq!(service.temperature_range_descriptor.handle(), value + 4);
+
+    // With writable auxiliaries, the user description can be written up to the length of the initial one
+    struct RawHandle(u16);
+    impl AttributeHandle for RawHandle {
+        type Value = [u8; 4];
+
+        fn handle(&self) -> u16 {
+            self.0
+        }
+    }
+    assert!(table.set(&RawHandle(value + 2), b"Temp").is_ok());
 }